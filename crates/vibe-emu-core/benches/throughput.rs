@@ -0,0 +1,54 @@
+//! Emulation throughput, run with `cargo bench -p vibe-emu-core`.
+//!
+//! Steps a synthetic cartridge that keeps the CPU busy with the LCD and APU
+//! running, at each accuracy level, and prints how many times real-time
+//! speed the core manages.
+
+use vibe_emu_core::{
+    cartridge::Cartridge,
+    gameboy::{AccuracyLevel, GameBoy},
+};
+
+/// Emulated seconds per measurement.
+const SECONDS: u64 = 10;
+const DOT_CLOCK_HZ: u64 = 4_194_304;
+
+fn busy_cart() -> Cartridge {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x40] = 0xD9; // RETI
+    rom[0x100..0x114].copy_from_slice(&[
+        0x3E, 0x01, // LD A,$01
+        0xE0, 0xFF, // LDH (IE),A
+        0xFB, // EI
+        0x21, 0x00, 0xC0, // LD HL,$C000
+        0x06, 0x00, // LD B,$00
+        0x22, // LD (HL+),A
+        0x3C, // INC A
+        0xCB, 0x37, // SWAP A
+        0x05, // DEC B
+        0x20, 0xF9, // JR NZ,-7
+        0x18, 0xF2, // JR -14
+        0x00,
+    ]);
+    Cartridge::load(rom)
+}
+
+fn main() {
+    for (name, cgb) in [("DMG", false), ("CGB", true)] {
+        for level in [AccuracyLevel::CycleAccurate, AccuracyLevel::Relaxed] {
+            let mut gb = Box::new(GameBoy::new_with_mode(cgb));
+            gb.mmu.load_cart(busy_cart());
+            gb.set_accuracy(level);
+            // Warm up caches and branch predictors.
+            gb.bench_run(DOT_CLOCK_HZ / 10);
+
+            let result = gb.bench_run(SECONDS * DOT_CLOCK_HZ);
+            println!(
+                "{name} {level:?}: {:.1} MHz, {:.1}x real time ({:.2?})",
+                result.cycles_per_second() / 1e6,
+                result.speed(),
+                result.wall,
+            );
+        }
+    }
+}
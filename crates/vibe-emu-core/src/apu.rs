@@ -14,11 +14,15 @@
 //! - Sweep calculation and overflow check timing
 //! - Various hardware quirk emulation for different CGB revisions
 
-use std::cell::Cell;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::cell::Cell;
 
-use crate::audio_queue::{AudioConsumer, AudioProducer, audio_queue};
+use crate::audio_queue::{AudioConsumer, AudioProducer, audio_queue_with_limit};
 
 use crate::hardware::{CgbRevision, DmgRevision};
+use crate::math;
+use crate::savestate::{Record, StateError, state_enum, state_fields, state_record};
 
 /// State machine for skipping DIV-APU events when APU powers on with DIV bit already set.
 ///
@@ -56,6 +60,12 @@ const CPU_CLOCK_HZ: u32 = 4_194_304;
 const FRAME_SEQUENCER_PERIOD: u32 = 8192;
 const VOLUME_FACTOR: i16 = 64;
 pub const AUDIO_LATENCY_MS: u32 = 40;
+/// Range accepted by [`Apu::set_latency_ms`].
+pub const MIN_AUDIO_LATENCY_MS: u32 = 10;
+pub const MAX_AUDIO_LATENCY_MS: u32 = 500;
+/// Default window for [`Apu::is_silent`], in frames (about half a second).
+pub const DEFAULT_SILENCE_WINDOW_FRAMES: u32 = 30;
+const FRAME_CYCLES: u64 = 456 * 154;
 // Audio sample pipeline delay is computed dynamically when a channel is
 // triggered.  See `trigger_square` for details.
 
@@ -65,6 +75,18 @@ const POWER_ON_REGS: [u8; 0x30] = [
     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
 ];
 
+/// Wave RAM contents at power-on on DMG hardware. The exact bytes vary
+/// between units; these are the commonly documented ones, which some music
+/// drivers rely on when they play CH3 before loading a waveform.
+pub const DMG_WAVE_RAM_POWER_ON: [u8; 0x10] = [
+    0x84, 0x40, 0x43, 0xAA, 0x2D, 0x78, 0x92, 0x3C, 0x60, 0x59, 0x59, 0xB0, 0x34, 0xB8, 0x2E, 0xDA,
+];
+
+/// Wave RAM contents at power-on on CGB hardware.
+pub const CGB_WAVE_RAM_POWER_ON: [u8; 0x10] = [
+    0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF,
+];
+
 // Duty table for pulse channels (CH1, CH2). Each entry is an 8-step
 // waveform. Index (0..3) corresponds to duty selector in NRx1:
 // 0 -> 00000001 (12.5%)
@@ -742,6 +764,7 @@ impl NoiseChannel {
     }
 }
 
+#[derive(Default)]
 struct FrameSequencer {
     step: u8,
 }
@@ -771,6 +794,7 @@ pub struct Apu {
     sample_rate: u32,
     sample_timer_accum: u64,
     audio_out: Option<AudioProducer>,
+    latency_ms: u32,
     pcm_samples: [u8; 4],
     pcm_active: [bool; 4],
     pcm_mask: [u8; 2],
@@ -780,6 +804,10 @@ pub struct Apu {
     hp_prev_output_left: f32,
     hp_prev_input_right: f32,
     hp_prev_output_right: f32,
+    /// Whether samples are mixed once per step; see [`Apu::set_fast_mix`].
+    fast_mix: bool,
+    /// Stereo width applied to mixed samples; see [`Apu::set_stereo_width`].
+    stereo_width: f32,
     pcm12: u8,
     pcm34: u8,
     regs: [u8; 0x30],
@@ -837,6 +865,28 @@ pub struct Apu {
     ch1_restart_hold_skip: bool,
     /// True if a negate calculation has been used since last trigger
     sweep_neg_used: bool,
+
+    /// Cycles since a channel was last active, for `is_silent`.
+    quiet_cycles: u64,
+    silence_window: u64,
+    /// Samples generated since the last `take_frame_samples`, when capture
+    /// is on. Holds at most one second's worth; older samples are dropped.
+    captured: Option<VecDeque<(i16, i16)>>,
+    /// Register writes since the last `take_reg_log`, when logging is on.
+    reg_log: Option<Vec<RegWrite>>,
+    /// Dot cycles since `start_reg_log`, for timestamping `reg_log`.
+    reg_log_cycles: u64,
+}
+
+/// One APU register write recorded by [`Apu::start_reg_log`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegWrite {
+    /// Dot cycles (4.19 MHz in both speed modes) since logging started.
+    pub cycle: u64,
+    /// Register address in $FF10-$FF3F.
+    pub addr: u16,
+    /// The byte the CPU wrote, before any masking by the APU.
+    pub value: u8,
 }
 
 /// Lightweight snapshot of APU state for test diagnostics.
@@ -893,13 +943,64 @@ impl Apu {
         }
     }
 
-    // Keep <= AUDIO_LATENCY_MS of stereo frames in the queue
-    fn max_frames_for_rate(rate: u32) -> usize {
-        ((rate as usize * AUDIO_LATENCY_MS as usize) / 1000).max(1)
+    // Keep <= latency_ms of stereo frames in the queue
+    fn max_frames_for_rate(rate: u32, latency_ms: u32) -> usize {
+        ((rate as usize * latency_ms as usize) / 1000).max(1)
     }
 
     fn calc_hp_coef(rate: u32) -> f32 {
-        0.999_958_f32.powf(4_194_304.0 / rate as f32)
+        math::powf(0.999_958, 4_194_304.0 / rate as f32)
+    }
+
+    /// Returns the CGB revision used for revision-specific quirks.
+    pub fn cgb_revision(&self) -> CgbRevision {
+        self.cgb_revision
+    }
+
+    /// Returns the DMG revision used for revision-specific quirks.
+    pub fn dmg_revision(&self) -> DmgRevision {
+        self.dmg_revision
+    }
+
+    /// Reconfigures the hardware revisions used by revision-specific quirks.
+    ///
+    /// Channel state is left untouched; in-flight glitch timing may not match
+    /// either revision until the machine is reset.
+    pub fn set_revisions(&mut self, dmg_revision: DmgRevision, cgb_revision: CgbRevision) {
+        self.dmg_revision = dmg_revision;
+        self.cgb_revision = cgb_revision;
+    }
+
+    /// Returns true when the CGB-D/E quirk paths (noise alignment, sweep and
+    /// envelope timing) are active rather than the pre-D/DMG ones.
+    pub fn uses_de_quirks(&self) -> bool {
+        !self.is_pre_de_revision()
+    }
+
+    /// Returns true once no channel has been active (all NR52 status bits
+    /// clear, or the APU powered off) for the silence window. A trigger makes
+    /// this false straight away.
+    ///
+    /// Frontends can use this to pause their audio stream during quiet
+    /// sections.
+    pub fn is_silent(&self) -> bool {
+        !self.any_channel_active() && self.quiet_cycles >= self.silence_window
+    }
+
+    /// Sets how long the channels must stay inactive before
+    /// [`Self::is_silent`] reports silence.
+    pub fn set_silence_window_frames(&mut self, frames: u32) {
+        self.silence_window = frames as u64 * FRAME_CYCLES;
+    }
+
+    /// Returns the window set with [`Self::set_silence_window_frames`].
+    pub fn silence_window_frames(&self) -> u32 {
+        (self.silence_window / FRAME_CYCLES) as u32
+    }
+
+    fn any_channel_active(&self) -> bool {
+        self.nr52 & 0x80 != 0
+            && (self.ch1.enabled || self.ch2.enabled || self.ch3.enabled || self.ch4.enabled)
     }
 
     pub fn set_speed(&mut self, speed: f32) {
@@ -925,18 +1026,138 @@ impl Apu {
     /// drained by the audio backend.
     pub fn enable_output(&mut self, sample_rate: u32) -> AudioConsumer {
         self.set_sample_rate(sample_rate);
-        let capacity_frames = Self::max_frames_for_rate(sample_rate);
-        let (producer, consumer) = audio_queue(capacity_frames);
+        // Allocate for the largest latency so it can be changed later without
+        // handing the backend a new consumer.
+        let (producer, consumer) = audio_queue_with_limit(
+            Self::max_frames_for_rate(sample_rate, MAX_AUDIO_LATENCY_MS),
+            Self::max_frames_for_rate(sample_rate, self.latency_ms),
+        );
         self.audio_out = Some(producer);
         consumer
     }
 
+    pub fn latency_ms(&self) -> u32 {
+        self.latency_ms
+    }
+
+    /// Sets how much audio may be queued ahead of the backend, clamped to
+    /// [`MIN_AUDIO_LATENCY_MS`]..=[`MAX_AUDIO_LATENCY_MS`].
+    ///
+    /// Takes effect immediately on an enabled output. The queue is resized in
+    /// place: when lowering the latency, frames already queued still play out
+    /// rather than being cut, so there is no discontinuity in the stream.
+    pub fn set_latency_ms(&mut self, latency_ms: u32) {
+        self.latency_ms = latency_ms.clamp(MIN_AUDIO_LATENCY_MS, MAX_AUDIO_LATENCY_MS);
+        if let Some(out) = &self.audio_out {
+            out.set_capacity_frames(Self::max_frames_for_rate(self.sample_rate, self.latency_ms));
+        }
+    }
+
     /// Disable audio output.
     pub fn disable_output(&mut self) {
         self.audio_out = None;
     }
 
+    /// Turns sample capture on or off. While on, every sample the APU
+    /// generates is kept for [`Self::take_frame_samples`], independent of the
+    /// output queue and its latency limit. If samples aren't taken for more
+    /// than a second, the oldest are dropped. Turning it off drops anything
+    /// not yet taken.
+    pub fn set_sample_capture(&mut self, enabled: bool) {
+        if enabled {
+            self.captured.get_or_insert_with(VecDeque::new);
+        } else {
+            self.captured = None;
+        }
+    }
+
+    /// Returns whether sample capture is on; see [`Self::set_sample_capture`].
+    pub fn sample_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    /// Returns every sample generated since the last call, for headless
+    /// tests that need the exact audio stream. Empty unless capture is on
+    /// (see [`Self::set_sample_capture`]).
+    pub fn take_frame_samples(&mut self) -> Vec<(i16, i16)> {
+        self.captured
+            .as_mut()
+            .map(|captured| Vec::from(core::mem::take(captured)))
+            .unwrap_or_default()
+    }
+
+    /// Appends every sample generated since the last take to `out`, like
+    /// [`Self::take_frame_samples`] but reusing both buffers' allocations.
+    pub fn drain_frame_samples_into(&mut self, out: &mut Vec<(i16, i16)>) {
+        if let Some(captured) = &mut self.captured {
+            out.extend(captured.drain(..));
+        }
+    }
+
+    /// Starts recording every CPU write to $FF10-$FF3F, including writes the
+    /// APU ignores while powered off, for exporting a game's music to
+    /// VGM-style formats. Timestamps count from this call. Restarting drops
+    /// anything not yet taken.
+    pub fn start_reg_log(&mut self) {
+        self.reg_log = Some(Vec::new());
+        self.reg_log_cycles = 0;
+    }
+
+    /// Stops recording register writes and drops anything not yet taken.
+    pub fn stop_reg_log(&mut self) {
+        self.reg_log = None;
+    }
+
+    /// Returns the writes recorded since the last call, oldest first. Empty
+    /// unless logging is on (see [`Self::start_reg_log`]).
+    pub fn take_reg_log(&mut self) -> Vec<RegWrite> {
+        self.reg_log
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Emits the samples due in each step in one go instead of counting
+    /// cycles one at a time.
+    ///
+    /// Channel emulation is unaffected, so register reads, PCM12/PCM34 and
+    /// timing stay exact, and the output still goes through the high-pass
+    /// filter. Samples due within one step share the same mix.
+    pub fn set_fast_mix(&mut self, enabled: bool) {
+        self.fast_mix = enabled;
+    }
+
+    /// Returns the setting from [`Self::set_fast_mix`].
+    pub fn fast_mix(&self) -> bool {
+        self.fast_mix
+    }
+
+    /// Widens or narrows the stereo image of the output by scaling the
+    /// difference between the left and right samples: 1.0 (the default)
+    /// leaves the mix untouched, values between 0.0 and 1.0 narrow it down to
+    /// mono at 0.0, and values above 1.0 push panned channels further apart.
+    /// Negative values count as 0.0.
+    ///
+    /// This is a cosmetic effect on the mixed samples only, so channel
+    /// emulation, register reads, PCM12/PCM34 and timing are unaffected.
+    /// Games only have a stereo image to widen if they pan channels with
+    /// NR51.
+    pub fn set_stereo_width(&mut self, width: f32) {
+        self.stereo_width = width.max(0.0);
+    }
+
+    /// Returns the setting from [`Self::set_stereo_width`].
+    pub fn stereo_width(&self) -> f32 {
+        self.stereo_width
+    }
+
     pub fn push_samples(&mut self, left: i16, right: i16) {
+        if let Some(captured) = &mut self.captured {
+            if captured.len() >= self.sample_rate as usize {
+                captured.pop_front();
+            }
+            captured.push_back((left, right));
+        }
         if !self.tracking_audio() {
             return;
         }
@@ -1134,8 +1355,13 @@ impl Apu {
         self.ch3.wave_shadow[index]
     }
 
+    /// Clears the channels and registers as NR52 bit 7 going low does.
+    ///
+    /// Length counters survive on DMG (where NRx1 stays writable while off)
+    /// but are cleared on CGB. Host-side state (the output queue, speed and
+    /// filter settings) is left alone so playback continues through the
+    /// silence instead of starving the audio backend.
     fn power_off(&mut self) {
-        // On DMG, length counters survive power off/on cycles
         let ch1_len = self.ch1.length;
         let ch2_len = self.ch2.length;
         let ch3_len = self.ch3.length;
@@ -1153,13 +1379,9 @@ impl Apu {
         }
         self.nr50 = 0;
         self.nr51 = 0;
-        self.disable_output();
-        self.sample_timer_accum = 0;
         self.pcm_samples = [0; 4];
         self.pcm_active = [false; 4];
         self.pcm_mask = [0xFF; 2];
-        self.speed_factor = 1.0;
-        self.hp_coef = Apu::calc_hp_coef(self.sample_rate);
         self.hp_prev_input_left = 0.0;
         self.hp_prev_output_left = 0.0;
         self.hp_prev_input_right = 0.0;
@@ -1336,6 +1558,7 @@ impl Apu {
             sample_rate: 44_100,
             sample_timer_accum: 0,
             audio_out: None,
+            latency_ms: AUDIO_LATENCY_MS,
             pcm_samples: [0; 4],
             pcm_active: [false; 4],
             pcm_mask: [0xFF; 2],
@@ -1345,6 +1568,8 @@ impl Apu {
             hp_prev_output_left: 0.0,
             hp_prev_input_right: 0.0,
             hp_prev_output_right: 0.0,
+            fast_mix: false,
+            stereo_width: 1.0,
             pcm12: 0,
             pcm34: 0,
             cpu_cycles: 0,
@@ -1377,6 +1602,11 @@ impl Apu {
             ch1_restart_hold: 0,
             ch1_restart_hold_skip: false,
             sweep_neg_used: false,
+            quiet_cycles: 0,
+            silence_window: DEFAULT_SILENCE_WINDOW_FRAMES as u64 * FRAME_CYCLES,
+            captured: None,
+            reg_log: None,
+            reg_log_cycles: 0,
         };
 
         // Apply power-on register defaults (boot ROM may be skipped).
@@ -1420,6 +1650,11 @@ impl Apu {
         apu.cgb_mode = cgb;
         apu.cgb_revision = revision;
         apu.dmg_revision = dmg_revision;
+        apu.wave_ram = if cgb {
+            CGB_WAVE_RAM_POWER_ON
+        } else {
+            DMG_WAVE_RAM_POWER_ON
+        };
         apu.hp_coef = Apu::calc_hp_coef(apu.sample_rate);
         apu
     }
@@ -1437,6 +1672,34 @@ impl Apu {
                 self.sweep_instant_calc_done = false;
             }
 
+            return self.peek_reg(addr);
+        }
+
+        if (0xFF30..=0xFF3F).contains(&addr) {
+            let index = (addr - 0xFF30) as usize;
+            // DMG needs prestep to align the wave_form_just_read timing window
+            // with an advance-before-read ordering. CGB always redirects
+            // reads to the current position, so the extra advance would put us
+            // 2 ticks ahead of the correct phase.
+            if !self.cgb_mode {
+                self.prestep_wave();
+            }
+            return self.wave_cpu_read(index);
+        }
+
+        self.peek_reg(addr)
+    }
+
+    /// Reads a sound register or wave RAM byte (0xFF10-0xFF3F) without any
+    /// side effects. Wave RAM reads give the stored byte, not what the CPU
+    /// would see while channel 3 plays. Other addresses read as 0xFF.
+    pub fn peek_reg(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF30..=0xFF3F => return self.wave_ram[(addr - 0xFF30) as usize],
+            0xFF10..=0xFF2F => {}
+            _ => return 0xFF,
+        }
+        if addr == 0xFF26 {
             let mut val = self.regs[(addr - 0xFF10) as usize] & 0x7F;
             val |= self.nr52 & 0x80;
             if self.ch1.enabled {
@@ -1454,18 +1717,6 @@ impl Apu {
             return val | Apu::read_mask(addr);
         }
 
-        if (0xFF30..=0xFF3F).contains(&addr) {
-            let index = (addr - 0xFF30) as usize;
-            // DMG needs prestep to align the wave_form_just_read timing window
-            // with an advance-before-read ordering. CGB always redirects
-            // reads to the current position, so the extra advance would put us
-            // 2 ticks ahead of the correct phase.
-            if !self.cgb_mode {
-                self.prestep_wave();
-            }
-            return self.wave_cpu_read(index);
-        }
-
         let idx = (addr - 0xFF10) as usize;
         self.regs[idx] | Apu::read_mask(addr)
     }
@@ -1601,6 +1852,13 @@ impl Apu {
     }
 
     pub fn write_reg(&mut self, addr: u16, mut val: u8) {
+        if let Some(log) = &mut self.reg_log {
+            log.push(RegWrite {
+                cycle: self.reg_log_cycles,
+                addr,
+                value: val,
+            });
+        }
         if self.nr52 & 0x80 == 0 && addr != 0xFF26 && !(0xFF30..=0xFF3F).contains(&addr) {
             // On DMG, NR11/NR21/NR31/NR41 length writes are allowed even when APU is off
             if !self.cgb_mode && matches!(addr, 0xFF11 | 0xFF16 | 0xFF1B | 0xFF20) {
@@ -1910,10 +2168,14 @@ impl Apu {
             0xFF25 => self.nr51 = val,
             0xFF26 => {
                 if val & 0x80 == 0 {
+                    if self.nr52 & 0x80 != 0 {
+                        core_event!(DEBUG, "power off");
+                    }
                     self.nr52 &= !0x80;
                     self.power_off();
                 } else {
                     if self.nr52 & 0x80 == 0 {
+                        core_event!(DEBUG, "power on");
                         // On 0->1 transition, reset internal timing/pipelines to match hardware startup state.
                         self.lf_div = 1;
                         self.ch1.out_latched = 0;
@@ -2287,6 +2549,7 @@ impl Apu {
         }
 
         let step = self.sequencer.advance();
+        core_event!(TRACE, step, "frame sequencer");
         self.clock_frame_sequencer(step);
 
         if !was_skipped {
@@ -3084,6 +3347,7 @@ impl Apu {
     }
 
     pub fn step(&mut self, cycles: u16) {
+        self.reg_log_cycles += u64::from(cycles);
         let rate = self.sample_rate as u64;
         let sample_period = CPU_CLOCK_HZ as u64;
         // Advance square channels at 2 MHz: 1 tick per 2 CPU cycles (accumulated)
@@ -3121,6 +3385,24 @@ impl Apu {
                 self.refresh_pcm_regs();
             }
         }
+        if self.any_channel_active() {
+            self.quiet_cycles = 0;
+        } else {
+            self.quiet_cycles = self.quiet_cycles.saturating_add(cycles as u64);
+        }
+        if self.fast_mix {
+            self.cpu_cycles = self.cpu_cycles.wrapping_add(cycles as u64);
+            self.sample_timer_accum += rate * cycles as u64;
+            if self.sample_timer_accum >= sample_period {
+                let due = self.sample_timer_accum / sample_period;
+                self.sample_timer_accum %= sample_period;
+                let (left, right) = self.mix_output();
+                for _ in 0..due {
+                    self.push_samples(left, right);
+                }
+            }
+            return;
+        }
         for _ in 0..cycles {
             self.cpu_cycles = self.cpu_cycles.wrapping_add(1);
             #[cfg(feature = "apu-trace")]
@@ -3191,10 +3473,22 @@ impl Apu {
             self.hp_prev_output_right = 0.0;
             (0, 0)
         } else {
-            self.dc_block(left_sample, right_sample)
+            let (left, right) = self.dc_block(left_sample, right_sample);
+            self.widen(left, right)
         }
     }
 
+    /// Applies [`Self::set_stereo_width`] as a mid/side gain.
+    fn widen(&self, left: i16, right: i16) -> (i16, i16) {
+        if self.stereo_width == 1.0 {
+            return (left, right);
+        }
+        let mid = (left as f32 + right as f32) * 0.5;
+        let side = (left as f32 - right as f32) * 0.5 * self.stereo_width;
+        let clamp = |v: f32| math::round(v).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        (clamp(mid + side), clamp(mid - side))
+    }
+
     fn dc_block(&mut self, left: i16, right: i16) -> (i16, i16) {
         let r = self.hp_coef;
         let left_in = left as f32;
@@ -3205,7 +3499,7 @@ impl Apu {
         self.hp_prev_output_left = left_out;
         self.hp_prev_input_right = right_in;
         self.hp_prev_output_right = right_out;
-        (left_out.round() as i16, right_out.round() as i16)
+        (math::round(left_out) as i16, math::round(right_out) as i16)
     }
 
     pub fn ch1_frequency(&self) -> u16 {
@@ -3244,10 +3538,30 @@ impl Apu {
         // Queue sizing is handled by `enable_output()`.
     }
 
+    /// Old name for [`Self::frame_sequencer_step`].
+    #[deprecated(note = "use frame_sequencer_step")]
     pub fn sequencer_step(&self) -> u8 {
         self.sequencer.step
     }
 
+    /// Frame-sequencer step (0-7) that the next DIV-APU event will run.
+    ///
+    /// Length counters are clocked when an even step runs. Powering the APU on
+    /// via NR52 resets this to 0.
+    pub fn frame_sequencer_step(&self) -> u8 {
+        self.sequencer.step
+    }
+
+    /// Number of counted DIV-APU events modulo 8.
+    ///
+    /// Sweep (phase 3/7) and envelope (phase 7) countdowns are keyed off this
+    /// counter. It matches [`Self::frame_sequencer_step`] except right after
+    /// the APU is powered on while the DIV-APU bit is set, where it starts at 1
+    /// and the first DIV-APU event is swallowed.
+    pub fn div_apu_phase(&self) -> u8 {
+        (self.div_divider & 7) as u8
+    }
+
     pub fn ch1_timer(&self) -> i32 {
         self.ch1.timer
     }
@@ -3423,6 +3737,205 @@ impl Apu {
     }
 }
 
+state_enum!(SkipDivEvent {
+    SkipDivEvent::Inactive,
+    SkipDivEvent::Skip,
+    SkipDivEvent::Skipped,
+});
+
+state_record!(EnvelopeClock {
+    clock,
+    locked,
+    should_lock,
+});
+state_record!(Envelope {
+    initial,
+    period,
+    add,
+    volume,
+    timer,
+});
+state_record!(Sweep {
+    period,
+    negate,
+    shift,
+    timer,
+    shadow,
+    enabled,
+    neg_used,
+});
+state_record!(SquareChannel {
+    enabled,
+    dac_enabled,
+    active,
+    length,
+    length_enable,
+    duty,
+    duty_next,
+    duty_pos,
+    pending_reset,
+    frequency,
+    timer,
+    envelope,
+    sweep,
+    sample_length,
+    sample_countdown,
+    delay,
+    sample_surpressed,
+    just_reloaded,
+    did_tick,
+    out_latched,
+    out_stage1,
+    out_stage2,
+});
+state_record!(WaveChannel {
+    enabled,
+    dac_enabled,
+    length,
+    length_enable,
+    frequency,
+    timer,
+    shift,
+    sample_length,
+    sample_countdown,
+    delay,
+    pending_reset,
+    did_tick,
+    current_sample_index,
+    current_sample_byte,
+    wave_position,
+    wave_sample_buffer,
+    wave_ram_access_index,
+    wave_ram_locked,
+    wave_form_just_read,
+    sample_suppressed,
+    bugged_read_countdown,
+    bugged_read_index,
+    wave_shadow,
+    wave_ram_state,
+    tick_count,
+    out_latched,
+    out_stage1,
+    out_stage2,
+});
+state_record!(NoiseChannel {
+    enabled,
+    dac_enabled,
+    length,
+    length_enable,
+    envelope,
+    clock_shift,
+    divisor,
+    narrow,
+    lfsr,
+    timer,
+    alignment,
+    current_lfsr_sample,
+    counter,
+    reload_counter,
+    counter_countdown,
+    delta,
+    countdown_reloaded,
+    dmg_delayed_start,
+    pending_disable,
+    pending_reset,
+    sample_suppressed,
+    volume_countdown,
+    current_volume,
+    envelope_clock,
+    out_latched,
+    out_stage1,
+    out_stage2,
+});
+state_record!(FrameSequencer { step });
+
+// Audio output plumbing (queue, sample rate, speed, DC filter, fast mixing)
+// belongs to the host, and the hardware mode/revisions are configuration;
+// neither is saved, and nor are the sample capture and register log buffers.
+state_fields!(Apu {
+    ch1,
+    ch2,
+    ch3,
+    ch4,
+    wave_ram,
+    nr50,
+    nr51,
+    nr52,
+    sequencer,
+    pcm_samples,
+    pcm_active,
+    pcm_mask,
+    pcm12,
+    pcm34,
+    regs,
+    cpu_cycles,
+    lf_div_counter,
+    ch1_env_clock,
+    ch2_env_clock,
+    ch4_env_clock,
+    div_divider,
+    ch1_env_countdown,
+    ch2_env_countdown,
+    lf_div,
+    double_speed,
+    ch1_last_env_write_cycle,
+    apu_enable_tick,
+    mhz2_residual,
+    wave_prestep_deficit,
+    skip_div_event,
+    sweep_countdown,
+    sweep_calc_countdown,
+    sweep_calc_reload_timer,
+    sweep_shadow_freq,
+    sweep_addend,
+    sweep_completed_addend,
+    sweep_unshifted,
+    sweep_instant_calc_done,
+    ch1_restart_hold,
+    ch1_restart_hold_skip,
+    sweep_neg_used,
+});
+
+impl Apu {
+    pub(crate) fn save_state(&self) -> Record {
+        let mut rec = Record::new();
+        self.save_state_fields(&mut rec);
+        rec
+    }
+
+    pub(crate) fn load_state(&mut self, rec: &Record) -> Result<(), StateError> {
+        self.load_state_fields(rec)
+    }
+
+    /// Moves the host audio output (queue, sample rate, speed, stereo width
+    /// and filter state) from `other` into this APU.
+    pub(crate) fn adopt_output(&mut self, other: &mut Apu) {
+        core::mem::swap(&mut self.audio_out, &mut other.audio_out);
+        self.latency_ms = other.latency_ms;
+        self.sample_rate = other.sample_rate;
+        self.sample_timer_accum = other.sample_timer_accum;
+        self.speed_factor = other.speed_factor;
+        self.stereo_width = other.stereo_width;
+        self.hp_coef = other.hp_coef;
+        self.hp_prev_input_left = other.hp_prev_input_left;
+        self.hp_prev_output_left = other.hp_prev_output_left;
+        self.hp_prev_input_right = other.hp_prev_input_right;
+        self.hp_prev_output_right = other.hp_prev_output_right;
+    }
+
+    /// Takes over host-side settings from `other`: stereo width, latency,
+    /// whether samples are captured, the silence window and any register log
+    /// in progress, which keeps recording.
+    pub(crate) fn adopt_host_settings(&mut self, other: &mut Apu) {
+        self.stereo_width = other.stereo_width;
+        self.latency_ms = other.latency_ms;
+        self.set_sample_capture(other.sample_capture());
+        self.silence_window = other.silence_window;
+        self.reg_log = other.reg_log.take();
+        self.reg_log_cycles = other.reg_log_cycles;
+    }
+}
+
 impl Default for Apu {
     fn default() -> Self {
         Self::new()
@@ -1,14 +1,17 @@
-use std::cell::UnsafeCell;
-use std::mem::MaybeUninit;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use alloc::sync::Arc;
+use alloc::{boxed::Box, vec::Vec};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 /// Single-producer / single-consumer ring buffer of stereo i16 frames.
 ///
 /// Intended for the emulator thread (producer) feeding an audio callback thread
 /// (consumer) without locks.
 ///
-/// This queue is *lossy* when full: new pushes are dropped.
+/// This queue is *lossy* when full: new pushes are dropped. "Full" is a soft
+/// limit that the producer can lower or raise (up to the allocated size) while
+/// the consumer keeps running.
 #[derive(Clone)]
 pub struct AudioConsumer {
     inner: Arc<Inner>,
@@ -23,6 +26,8 @@ struct Inner {
     // One extra slot so head==tail is unambiguously empty.
     buf: Box<[UnsafeCell<MaybeUninit<[i16; 2]>>]>,
     cap: usize,
+    /// Frames the producer may queue; at most `cap - 1`.
+    limit: AtomicUsize,
     head: AtomicUsize,
     tail: AtomicUsize,
 }
@@ -45,7 +50,7 @@ impl Inner {
     }
 
     fn capacity_frames(&self) -> usize {
-        self.cap.saturating_sub(1)
+        self.limit.load(Ordering::Relaxed)
     }
 
     #[inline]
@@ -56,7 +61,13 @@ impl Inner {
 }
 
 pub fn audio_queue(capacity_frames: usize) -> (AudioProducer, AudioConsumer) {
-    let cap = capacity_frames.saturating_add(1).max(2);
+    audio_queue_with_limit(capacity_frames, capacity_frames)
+}
+
+/// Allocates room for `max_frames` but initially only lets the producer queue
+/// `limit` frames; see [`AudioProducer::set_capacity_frames`].
+pub fn audio_queue_with_limit(max_frames: usize, limit: usize) -> (AudioProducer, AudioConsumer) {
+    let cap = max_frames.saturating_add(1).max(2);
     let mut v: Vec<UnsafeCell<MaybeUninit<[i16; 2]>>> = Vec::with_capacity(cap);
     for _ in 0..cap {
         v.push(UnsafeCell::new(MaybeUninit::uninit()));
@@ -65,6 +76,7 @@ pub fn audio_queue(capacity_frames: usize) -> (AudioProducer, AudioConsumer) {
     let inner = Arc::new(Inner {
         buf: v.into_boxed_slice(),
         cap,
+        limit: AtomicUsize::new(limit.clamp(1, cap - 1)),
         head: AtomicUsize::new(0),
         tail: AtomicUsize::new(0),
     });
@@ -83,7 +95,7 @@ impl AudioProducer {
         let head = self.inner.head.load(Ordering::Relaxed);
         let next = self.inner.next_index(head);
         let tail = self.inner.tail.load(Ordering::Acquire);
-        if next == tail {
+        if next == tail || self.inner.len() >= self.inner.capacity_frames() {
             // Full: drop newest.
             return false;
         }
@@ -109,6 +121,22 @@ impl AudioProducer {
     pub fn capacity_frames(&self) -> usize {
         self.inner.capacity_frames()
     }
+
+    /// Largest value [`Self::set_capacity_frames`] accepts.
+    #[inline]
+    pub fn max_capacity_frames(&self) -> usize {
+        self.inner.cap - 1
+    }
+
+    /// Changes how many frames may be queued without reallocating.
+    ///
+    /// Frames already queued beyond a lowered limit are not discarded; the
+    /// consumer plays them out and new pushes are dropped until the queue
+    /// falls below the limit.
+    pub fn set_capacity_frames(&self, frames: usize) {
+        let frames = frames.clamp(1, self.max_capacity_frames());
+        self.inner.limit.store(frames, Ordering::Relaxed);
+    }
 }
 
 impl AudioConsumer {
@@ -0,0 +1,159 @@
+//! C-compatible embedding interface.
+//!
+//! The machine is exposed as an opaque [`VibeGb`] handle created by
+//! [`vibe_gb_new`] and released with [`vibe_gb_free`]. Every entry point
+//! catches panics, so unwinding never crosses the FFI boundary; a call that
+//! panics reports failure through its return value instead.
+//!
+//! A typical frontend loop:
+//!
+//! ```c
+//! VibeGb *gb = vibe_gb_new();
+//! vibe_gb_load_rom(gb, rom, rom_len);
+//! for (;;) {
+//!     vibe_gb_set_buttons(gb, poll_buttons());
+//!     vibe_gb_run_frame(gb);
+//!     present(vibe_gb_framebuffer(gb)); /* 160x144 0x00RRGGBB pixels */
+//! }
+//! vibe_gb_free(gb);
+//! ```
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr;
+
+use crate::{cartridge::Cartridge, gameboy::GameBoy, input::Buttons};
+
+/// Upper bound on the cycles [`vibe_gb_run_frame`] runs while waiting for a
+/// frame (two frames' worth of dots).
+const RUN_FRAME_LIMIT: u64 = 2 * 456 * 154;
+
+/// Opaque machine handle.
+pub struct VibeGb {
+    gb: Box<GameBoy>,
+}
+
+fn guard<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+/// Creates a DMG machine with no cartridge inserted.
+///
+/// Returns null on failure. The handle must be released with
+/// [`vibe_gb_free`].
+#[unsafe(no_mangle)]
+pub extern "C" fn vibe_gb_new() -> *mut VibeGb {
+    guard(ptr::null_mut(), || {
+        Box::into_raw(Box::new(VibeGb {
+            gb: Box::new(GameBoy::new()),
+        }))
+    })
+}
+
+/// Inserts a ROM image and restarts the machine in the post-boot state.
+///
+/// CGB mode is selected from the cartridge header. The ROM bytes are copied,
+/// so the caller keeps ownership of `data`. Returns `false` if `gb` or `data`
+/// is null, `len` is zero, or the image could not be loaded.
+///
+/// # Safety
+///
+/// `gb` must be null or a live handle from [`vibe_gb_new`], and `data` must
+/// be null or point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vibe_gb_load_rom(gb: *mut VibeGb, data: *const u8, len: usize) -> bool {
+    if gb.is_null() || data.is_null() || len == 0 {
+        return false;
+    }
+    // SAFETY: both pointers were checked for null and the caller guarantees
+    // they are valid.
+    let (handle, rom) = unsafe { (&mut *gb, std::slice::from_raw_parts(data, len)) };
+    guard(false, || {
+        let cart = Cartridge::load(rom.to_vec());
+        let mut next = Box::new(GameBoy::new_with_mode(cart.cgb));
+        next.mmu.load_cart(cart);
+        handle.gb = next;
+        true
+    })
+}
+
+/// Runs until the PPU finishes the next frame.
+///
+/// Returns `false` if no frame completed within two frames' worth of cycles
+/// (e.g. the game turned the LCD off), or if `gb` is null.
+///
+/// # Safety
+///
+/// `gb` must be null or a live handle from [`vibe_gb_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vibe_gb_run_frame(gb: *mut VibeGb) -> bool {
+    if gb.is_null() {
+        return false;
+    }
+    // SAFETY: checked for null; the caller guarantees the handle is live.
+    let gb = unsafe { &mut *(*gb).gb };
+    guard(false, || {
+        gb.mmu.ppu.clear_frame_flag();
+        let deadline = gb.cpu.cycles + RUN_FRAME_LIMIT;
+        while !gb.mmu.ppu.frame_ready() {
+            if gb.cpu.cycles >= deadline {
+                return false;
+            }
+            gb.cpu.step(&mut gb.mmu);
+        }
+        true
+    })
+}
+
+/// Returns the 160x144 framebuffer as `0x00RRGGBB` pixels, row by row.
+///
+/// The pointer stays valid until the next call that takes `gb` mutably.
+/// Returns null if `gb` is null.
+///
+/// # Safety
+///
+/// `gb` must be null or a live handle from [`vibe_gb_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vibe_gb_framebuffer(gb: *const VibeGb) -> *const u32 {
+    if gb.is_null() {
+        return ptr::null();
+    }
+    // SAFETY: checked for null; the caller guarantees the handle is live.
+    let gb = unsafe { &*(*gb).gb };
+    gb.mmu.ppu.framebuffer().as_ptr()
+}
+
+/// Sets the held buttons. A set bit means pressed: bits 0-3 are
+/// Right/Left/Up/Down and bits 4-7 are A/B/Select/Start (see
+/// [`Buttons`]).
+///
+/// # Safety
+///
+/// `gb` must be null or a live handle from [`vibe_gb_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vibe_gb_set_buttons(gb: *mut VibeGb, buttons: u8) {
+    if gb.is_null() {
+        return;
+    }
+    // SAFETY: checked for null; the caller guarantees the handle is live.
+    let gb = unsafe { &mut *(*gb).gb };
+    guard((), || {
+        let mmu = &mut gb.mmu;
+        mmu.input
+            .set_buttons(Buttons::from_bits(buttons), &mut mmu.if_reg);
+    })
+}
+
+/// Releases a handle from [`vibe_gb_new`]. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `gb` must be null or a live handle that is not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vibe_gb_free(gb: *mut VibeGb) {
+    if gb.is_null() {
+        return;
+    }
+    // SAFETY: the caller hands back ownership of a handle from `vibe_gb_new`.
+    let handle = unsafe { Box::from_raw(gb) };
+    guard((), || drop(handle));
+}
@@ -1,8 +1,16 @@
-use std::cell::Cell;
+use crate::savestate::{Record, StateError, StateValue, state_record};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{cell::Cell, fmt, time::Duration};
+#[cfg(feature = "std")]
 use std::{
     fs, io,
     path::{Path, PathBuf},
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -16,6 +24,145 @@ pub enum MbcType {
     Unknown(u8),
 }
 
+/// The mapper hardware on a cartridge, from [`Cartridge::mapper_kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapperKind {
+    /// No mapper: 32 KiB of ROM, optionally with up to 8 KiB of RAM.
+    None,
+    Mbc1,
+    /// MBC1 wired for multicarts (MBC1M), with a 4-bit ROM bank register.
+    Mbc1Multicart,
+    Mbc2,
+    Mbc3 {
+        rtc: bool,
+    },
+    /// MBC3 variant with 8 MiB ROM and 64 KiB RAM.
+    Mbc30 {
+        rtc: bool,
+    },
+    Mbc5 {
+        rumble: bool,
+    },
+    /// A cartridge type byte that isn't emulated; runs as ROM only.
+    Unknown(u8),
+}
+
+impl fmt::Display for MapperKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("ROM only"),
+            Self::Mbc1 => f.write_str("MBC1"),
+            Self::Mbc1Multicart => f.write_str("MBC1M"),
+            Self::Mbc2 => f.write_str("MBC2"),
+            Self::Mbc3 { rtc: false } => f.write_str("MBC3"),
+            Self::Mbc3 { rtc: true } => f.write_str("MBC3+RTC"),
+            Self::Mbc30 { rtc: false } => f.write_str("MBC30"),
+            Self::Mbc30 { rtc: true } => f.write_str("MBC30+RTC"),
+            Self::Mbc5 { rumble: false } => f.write_str("MBC5"),
+            Self::Mbc5 { rumble: true } => f.write_str("MBC5+RUMBLE"),
+            Self::Unknown(kind) => write!(f, "unknown mapper ${kind:02X}"),
+        }
+    }
+}
+
+/// Color support declared by the CGB flag at $0143.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgbSupport {
+    /// Monochrome game; a CGB runs it in DMG compatibility mode.
+    DmgOnly,
+    /// Runs on both, with color on a CGB ($80).
+    Enhanced,
+    /// Requires a CGB ($C0); on a DMG it typically shows a warning screen or
+    /// hangs.
+    CgbOnly,
+}
+
+/// A cartridge and hardware mode that won't run as the game intends, from
+/// [`Cartridge::check_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeMismatch {
+    /// A CGB-only game running in DMG mode.
+    CgbOnlyOnDmg,
+}
+
+impl fmt::Display for ModeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModeMismatch::CgbOnlyOnDmg => f.write_str(
+                "cartridge requires a Game Boy Color but is running in DMG mode; \
+                 expect a warning screen or a hang",
+            ),
+        }
+    }
+}
+
+/// A way in which a ROM image disagrees with its own header, as reported by
+/// [`Header::anomalies`]. Cartridges still load; these flag dumps (often
+/// homebrew) where banking may not behave as the author expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderAnomaly {
+    /// The file length is not a power of two.
+    NonPowerOfTwoSize { len: usize },
+    /// The file is longer than the header's ROM size (padding, a trainer, or
+    /// an understated size byte).
+    TrailingData { declared: usize, len: usize },
+    /// The file is shorter than the header's ROM size.
+    Truncated { declared: usize, len: usize },
+    /// The ROM size byte at $0148 is not a known value.
+    UnknownRomSize(u8),
+}
+
+impl fmt::Display for HeaderAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderAnomaly::NonPowerOfTwoSize { len } => {
+                write!(f, "ROM size {len:#X} is not a power of two")
+            }
+            HeaderAnomaly::TrailingData { declared, len } => write!(
+                f,
+                "ROM is {len:#X} bytes but the header declares {declared:#X}; trailing data ignored by banking"
+            ),
+            HeaderAnomaly::Truncated { declared, len } => write!(
+                f,
+                "ROM is {len:#X} bytes but the header declares {declared:#X}; missing banks"
+            ),
+            HeaderAnomaly::UnknownRomSize(byte) => {
+                write!(f, "unknown header ROM size byte {byte:#04X}")
+            }
+        }
+    }
+}
+
+/// Which bank a [`BankEvent`] selects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BankKind {
+    /// The ROM bank mapped at $4000-$7FFF.
+    Rom,
+    /// The RAM bank (or MBC3 RTC register) mapped at $A000-$BFFF. On MBC1
+    /// this register also holds the upper ROM bank bits.
+    Ram,
+}
+
+/// A write to a mapper bank register, reported by
+/// [`Cartridge::set_bank_callback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BankEvent {
+    /// The address the CPU wrote to.
+    pub addr: u16,
+    pub kind: BankKind,
+    /// The bank selected after the write, as returned by
+    /// [`Cartridge::current_rom_bank`] or [`Cartridge::current_ram_bank`].
+    pub bank: u16,
+}
+
+struct BankCallback(Box<dyn FnMut(BankEvent) + Send>);
+
+impl fmt::Debug for BankCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BankCallback")
+    }
+}
+
 #[derive(Debug)]
 pub struct Cartridge {
     pub rom: Vec<u8>,
@@ -24,10 +171,17 @@ pub struct Cartridge {
     pub cgb: bool,
     pub title: String,
     cart_type: u8,
+    /// SHA-1 of `rom` as loaded; see [`Cartridge::rom_hash`].
+    rom_hash: [u8; 20],
+    /// Set by every write to A000-BFFF; see [`Cartridge::ram_dirty`].
+    ram_dirty: bool,
+    #[cfg(feature = "std")]
     save_path: Option<PathBuf>,
+    #[cfg(feature = "std")]
     rtc_path: Option<PathBuf>,
     mbc_state: MbcState,
     cart_bus: Cell<u8>,
+    bank_callback: Option<BankCallback>,
 }
 
 #[derive(Debug)]
@@ -64,31 +218,67 @@ enum MbcState {
     Unknown,
 }
 
-#[derive(Debug, Clone, Copy, Default)]
-struct RtcRegisters {
-    seconds: u8,
-    minutes: u8,
-    hours: u8,
-    days: u16,
-    halt: bool,
-    carry: bool,
+/// MBC3 real-time clock registers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RtcState {
+    /// 0-59 (6 bits).
+    pub seconds: u8,
+    /// 0-59 (6 bits).
+    pub minutes: u8,
+    /// 0-23 (5 bits).
+    pub hours: u8,
+    /// Day counter, 0-511 (9 bits).
+    pub days: u16,
+    /// Clock stopped.
+    pub halt: bool,
+    /// Day counter overflowed past 511.
+    pub carry: bool,
 }
 
 #[derive(Debug, Clone)]
 struct Mbc3Rtc {
-    regs: RtcRegisters,
-    latched: RtcRegisters,
+    regs: RtcState,
+    latched: RtcState,
     latched_active: bool,
-    last_update: SystemTime,
+    /// Wall-clock time of the last sync, since the Unix epoch.
+    last_update: Duration,
     subsecond_cycles: u32,
 }
 
 const RTC_CYCLES_PER_SECOND: u32 = 4_194_304;
 
+/// Current wall-clock time since the Unix epoch. Without `std` there is no
+/// clock, so the RTC only advances with emulated time.
+#[cfg(feature = "std")]
+fn wall_clock_now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "std"))]
+fn wall_clock_now() -> Duration {
+    Duration::ZERO
+}
+
 const RTC_FILE_MAGIC: &[u8; 4] = b"RTC1";
 const RTC_FILE_VERSION: u8 = 1;
 
-impl RtcRegisters {
+/// Highest battery save slot number; slots are numbered from 1.
+pub const MAX_RAM_SLOT: u8 = 9;
+
+impl RtcState {
+    /// Clamps each field to its register width.
+    fn masked(self) -> Self {
+        Self {
+            seconds: self.seconds & 0x3F,
+            minutes: self.minutes & 0x3F,
+            hours: self.hours & 0x1F,
+            days: self.days & 0x01FF,
+            ..self
+        }
+    }
+
     fn control_byte(&self) -> u8 {
         let mut out = ((self.days >> 8) as u8) & 0x01;
         if self.halt {
@@ -102,8 +292,8 @@ impl RtcRegisters {
 }
 
 impl Mbc3Rtc {
-    fn new(now: SystemTime) -> Self {
-        let regs = RtcRegisters::default();
+    fn new(now: Duration) -> Self {
+        let regs = RtcState::default();
         Self {
             regs,
             latched: regs,
@@ -118,6 +308,14 @@ impl Mbc3Rtc {
         self.latched_active = true;
     }
 
+    /// Overwrites the clock. The latched copy follows so the game reads the
+    /// new time right away, and the current second restarts.
+    fn set(&mut self, state: RtcState) {
+        self.regs = state.masked();
+        self.latched = self.regs;
+        self.subsecond_cycles = 0;
+    }
+
     fn read_register(&self, reg: u8) -> u8 {
         let regs = if self.latched_active {
             &self.latched
@@ -171,8 +369,8 @@ impl Mbc3Rtc {
         self.add_cycles(cpu_cycles);
     }
 
-    fn sync_wall(&mut self, now: SystemTime) {
-        let elapsed = now.duration_since(self.last_update).unwrap_or_default();
+    fn sync_wall(&mut self, now: Duration) {
+        let elapsed = now.saturating_sub(self.last_update);
         self.last_update = now;
         if self.regs.halt {
             return;
@@ -187,7 +385,7 @@ impl Mbc3Rtc {
         self.add_cycles(elapsed_cycles.min(u64::MAX as u128) as u64);
     }
 
-    fn mark_persisted(&mut self, now: SystemTime) {
+    fn mark_persisted(&mut self, now: Duration) {
         self.last_update = now;
     }
 
@@ -264,11 +462,7 @@ impl Mbc3Rtc {
         data.extend_from_slice(RTC_FILE_MAGIC);
         data.push(RTC_FILE_VERSION);
 
-        let saved_time = self
-            .last_update
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        let saved_time = self.last_update.as_secs();
         data.extend_from_slice(&saved_time.to_le_bytes());
 
         let subsecond_nanos = ((self.subsecond_cycles as u128).saturating_mul(1_000_000_000u128)
@@ -300,7 +494,7 @@ impl Mbc3Rtc {
         let secs = u64::from_le_bytes(data[5..13].try_into().unwrap());
         let nanos = u32::from_le_bytes(data[13..17].try_into().unwrap()).min(999_999_999);
 
-        self.last_update = UNIX_EPOCH + Duration::from_secs(secs);
+        self.last_update = Duration::from_secs(secs);
         self.subsecond_cycles = ((nanos as u128).saturating_mul(RTC_CYCLES_PER_SECOND as u128)
             / 1_000_000_000u128)
             .min((RTC_CYCLES_PER_SECOND - 1) as u128) as u32;
@@ -363,6 +557,15 @@ impl Cartridge {
         }
     }
 
+    /// Returns the byte of the currently selected external RAM bank at
+    /// `addr` ($A000-$BFFF), whether or not RAM is enabled. RTC registers
+    /// are never returned.
+    ///
+    /// This is intended for UI/debugger tooling.
+    pub fn peek_ram(&self, addr: u16) -> u8 {
+        self.read_ram_wrapped(self.ram_index(addr))
+    }
+
     /// Returns whether external RAM / RTC is currently enabled for the $A000-$BFFF window.
     ///
     /// This is intended for UI/debugger tooling.
@@ -378,6 +581,87 @@ impl Cartridge {
         }
     }
 
+    /// Calls `callback` after every write to a ROM or RAM bank register, for
+    /// debuggers logging bank switches. Writes that select the bank already
+    /// mapped are reported too. The callback belongs to this cartridge and is
+    /// dropped with it.
+    pub fn set_bank_callback(&mut self, callback: Box<dyn FnMut(BankEvent) + Send>) {
+        self.bank_callback = Some(BankCallback(callback));
+    }
+
+    /// Removes the callback set by [`Self::set_bank_callback`].
+    pub fn clear_bank_callback(&mut self) {
+        self.bank_callback = None;
+    }
+
+    /// Which bank register, if any, a write to `addr` lands in.
+    fn bank_register(&self, addr: u16) -> Option<BankKind> {
+        match (&self.mbc_state, addr) {
+            (MbcState::Mbc2 { .. }, 0x0000..=0x3FFF) if addr & 0x0100 != 0 => Some(BankKind::Rom),
+            (
+                MbcState::Mbc1 { .. }
+                | MbcState::Mbc3 { .. }
+                | MbcState::Mbc30 { .. }
+                | MbcState::Mbc5 { .. },
+                0x2000..=0x3FFF,
+            ) => Some(BankKind::Rom),
+            (
+                MbcState::Mbc1 { .. }
+                | MbcState::Mbc3 { .. }
+                | MbcState::Mbc30 { .. }
+                | MbcState::Mbc5 { .. },
+                0x4000..=0x5FFF,
+            ) => Some(BankKind::Ram),
+            _ => None,
+        }
+    }
+
+    /// The mapper chip, as declared by the cartridge type byte at $0147,
+    /// plus the extras that change how it behaves.
+    pub fn mapper_kind(&self) -> MapperKind {
+        match self.mbc {
+            MbcType::NoMbc => MapperKind::None,
+            MbcType::Mbc1 => match self.mbc_state {
+                MbcState::Mbc1 {
+                    multicart: true, ..
+                } => MapperKind::Mbc1Multicart,
+                _ => MapperKind::Mbc1,
+            },
+            MbcType::Mbc2 => MapperKind::Mbc2,
+            MbcType::Mbc3 => MapperKind::Mbc3 {
+                rtc: self.has_rtc(),
+            },
+            MbcType::Mbc30 => MapperKind::Mbc30 {
+                rtc: self.has_rtc(),
+            },
+            MbcType::Mbc5 => MapperKind::Mbc5 {
+                rumble: matches!(self.cart_type, 0x1C..=0x1E),
+            },
+            MbcType::Unknown(kind) => MapperKind::Unknown(kind),
+        }
+    }
+
+    /// Size of the loaded ROM image. Bank numbers wrap at this size, so it is
+    /// the image rather than the header's size byte that counts.
+    pub fn rom_size_bytes(&self) -> usize {
+        self.rom.len()
+    }
+
+    /// Number of 16 KiB ROM banks, at least 1.
+    pub fn rom_bank_count(&self) -> usize {
+        (self.rom.len() / 0x4000).max(1)
+    }
+
+    /// Size of the cartridge RAM, including MBC2's built-in 512 half-bytes.
+    pub fn ram_size_bytes(&self) -> usize {
+        self.ram.len()
+    }
+
+    /// Number of 8 KiB RAM banks; 2 KiB and MBC2 RAM count as one bank.
+    pub fn ram_bank_count(&self) -> usize {
+        self.ram.len().div_ceil(0x2000)
+    }
+
     pub fn step_rtc(&mut self, cpu_cycles: u16) {
         if let Some(rtc) = self.rtc_mut() {
             rtc.step(cpu_cycles as u64);
@@ -390,6 +674,7 @@ impl Cartridge {
         c
     }
 
+    #[cfg(feature = "std")]
     pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let data = fs::read(&path)?;
         let mut cart = Self::load(data);
@@ -419,8 +704,7 @@ impl Cartridge {
                         rtc_path.display()
                     );
                 }
-                let now = SystemTime::now();
-                rtc.sync_wall(now);
+                rtc.sync_wall(wall_clock_now());
                 rtc.latch();
             }
         }
@@ -437,6 +721,9 @@ impl Cartridge {
 
     pub fn load(data: Vec<u8>) -> Self {
         let header = Header::parse(&data);
+        for anomaly in header.anomalies() {
+            core_warn!(target: "vibe_emu_core::cartridge", "{anomaly}");
+        }
         let ram_size = header.ram_size();
 
         let cart_type = header.cart_type();
@@ -444,7 +731,7 @@ impl Cartridge {
         let mbc = header.mbc_type();
         let cgb = header.cgb_supported();
         let title = header.title();
-        let now = SystemTime::now();
+        let now = wall_clock_now();
 
         let mbc_state = match mbc {
             MbcType::NoMbc => MbcState::NoMbc,
@@ -482,6 +769,7 @@ impl Cartridge {
         };
 
         Self {
+            rom_hash: crate::sha1::sha1(&data),
             rom: data,
             // Many titles assume powered-on cart RAM reads as $FF.
             ram: vec![0xFF; ram_size],
@@ -489,22 +777,62 @@ impl Cartridge {
             cgb,
             title,
             cart_type,
+            ram_dirty: false,
+            #[cfg(feature = "std")]
             save_path: None,
+            #[cfg(feature = "std")]
             rtc_path: None,
             mbc_state,
             cart_bus: Cell::new(0xFF),
+            bank_callback: None,
         }
     }
 
+    /// SHA-1 of the ROM image, for keying saves, states and cheats to a game
+    /// rather than to a file path.
+    ///
+    /// Computed once by [`Self::load`]; later changes to [`Self::rom`] are
+    /// not reflected.
+    pub fn rom_hash(&self) -> [u8; 20] {
+        self.rom_hash
+    }
+
+    /// Returns the color support declared in the header.
+    pub fn cgb_support(&self) -> CgbSupport {
+        Header::parse(&self.rom).cgb_support()
+    }
+
+    /// Checks whether this cartridge can run as intended on hardware in CGB
+    /// (`cgb == true`) or DMG mode. DMG games on a CGB are fine: the CGB
+    /// runs them in compatibility mode.
+    pub fn check_mode(&self, cgb: bool) -> Option<ModeMismatch> {
+        (!cgb && self.cgb_support() == CgbSupport::CgbOnly).then_some(ModeMismatch::CgbOnlyOnDmg)
+    }
+
+    /// Mismatches between the ROM image and its header. See
+    /// [`Header::anomalies`].
+    pub fn header_anomalies(&self) -> Vec<HeaderAnomaly> {
+        Header::parse(&self.rom).anomalies()
+    }
+
     pub fn read(&mut self, addr: u16) -> u8 {
         let open_bus = Self::open_bus(&self.cart_bus);
         self.read_with_open_bus(addr, open_bus)
     }
 
-    pub fn read_with_open_bus(&mut self, addr: u16, open_bus: u8) -> u8 {
+    /// Reads `addr` through the mapper without disturbing the cartridge data
+    /// bus, for debugger views and state dumps.
+    pub fn peek(&self, addr: u16) -> u8 {
+        let bus = self.cart_bus.get();
+        let value = self.read_with_open_bus(addr, bus);
+        self.cart_bus.set(bus);
+        value
+    }
+
+    pub fn read_with_open_bus(&self, addr: u16, open_bus: u8) -> u8 {
         let rom_bank_count = (self.rom.len() / 0x4000).max(1);
         let cart_bus = &self.cart_bus;
-        match (&mut self.mbc_state, addr) {
+        match (&self.mbc_state, addr) {
             (MbcState::NoMbc, 0x0000..=0x7FFF) => Self::bus_read(
                 cart_bus,
                 self.rom.get(addr as usize).copied().unwrap_or(0xFF),
@@ -601,8 +929,9 @@ impl Cartridge {
                     0xFF
                 } else {
                     // MBC2 has 512x4-bit internal RAM, mirrored across 0xA000-0xBFFF.
-                    let idx = (addr as usize - 0xA000) & 0x01FF;
-                    let nibble = self.ram.get(idx).copied().unwrap_or(0x0F) & 0x0F;
+                    // Only the low nibble is stored; the upper data lines float
+                    // high.
+                    let nibble = self.read_ram_wrapped(self.ram_index(addr)) & 0x0F;
                     Self::bus_read(cart_bus, 0xF0 | nibble)
                 }
             }
@@ -692,6 +1021,24 @@ impl Cartridge {
     }
 
     pub fn write(&mut self, addr: u16, val: u8) {
+        if matches!(addr, 0xA000..=0xBFFF) {
+            self.ram_dirty = true;
+        }
+        self.write_mapper(addr, val);
+        if self.bank_callback.is_some()
+            && let Some(kind) = self.bank_register(addr)
+        {
+            let bank = match kind {
+                BankKind::Rom => self.current_rom_bank(),
+                BankKind::Ram => self.current_ram_bank().into(),
+            };
+            if let Some(BankCallback(callback)) = &mut self.bank_callback {
+                callback(BankEvent { addr, kind, bank });
+            }
+        }
+    }
+
+    fn write_mapper(&mut self, addr: u16, val: u8) {
         let cart_bus = &self.cart_bus;
         // CPU drives the cart data bus on writes too.
         if matches!(addr, 0x0000..=0x7FFF | 0xA000..=0xBFFF) {
@@ -726,7 +1073,7 @@ impl Cartridge {
             }
             (MbcState::Mbc2 { ram_enable, .. }, 0xA000..=0xBFFF) => {
                 if *ram_enable {
-                    let idx = (addr as usize - 0xA000) & 0x01FF;
+                    let idx = self.ram_index(addr);
                     if let Some(b) = self.ram.get_mut(idx) {
                         *b = val & 0x0F;
                     }
@@ -938,8 +1285,48 @@ impl Cartridge {
         )
     }
 
-    fn has_rtc(&self) -> bool {
-        matches!(self.cart_type, 0x0F | 0x10 | 0x13)
+    /// Whether the header says the cartridge has the MBC3 clock crystal
+    /// (types 0x0F and 0x10). Only these carts get RTC registers, RTC save
+    /// state and an `.rtc` file next to the save.
+    pub fn has_rtc(&self) -> bool {
+        matches!(self.cart_type, 0x0F | 0x10)
+    }
+
+    fn rtc_ref(&self) -> Option<&Mbc3Rtc> {
+        match &self.mbc_state {
+            MbcState::Mbc3 { rtc: Some(rtc), .. } | MbcState::Mbc30 { rtc: Some(rtc), .. } => {
+                Some(rtc)
+            }
+            _ => None,
+        }
+    }
+
+    /// Current (running) RTC registers, or `None` if the cartridge has no
+    /// clock.
+    pub fn rtc(&self) -> Option<RtcState> {
+        self.rtc_ref().map(|rtc| rtc.regs)
+    }
+
+    /// RTC registers as captured by the last latch (0x00 then 0x01 written
+    /// to 0x6000-0x7FFF), or `None` if the cartridge has no clock.
+    pub fn rtc_latched(&self) -> Option<RtcState> {
+        self.rtc_ref().map(|rtc| rtc.latched)
+    }
+
+    /// Sets the clock, e.g. from a frontend's clock editor. Fields are masked
+    /// to their register widths.
+    ///
+    /// Both the running and latched registers take the new value, so a game
+    /// reading the latched registers sees it before its next latch. Returns
+    /// `false` if the cartridge has no clock.
+    pub fn set_rtc(&mut self, state: RtcState) -> bool {
+        match self.rtc_mut() {
+            Some(rtc) => {
+                rtc.set(state);
+                true
+            }
+            None => false,
+        }
     }
 
     fn rtc_mut(&mut self) -> Option<&mut Mbc3Rtc> {
@@ -951,22 +1338,292 @@ impl Cartridge {
         }
     }
 
+    /// Path of battery save slot `slot` (1-9): `<rom>.slotN.sav` next to the
+    /// default `.sav`. `None` for cartridges without a battery-backed save
+    /// file or for out-of-range slots.
+    #[cfg(feature = "std")]
+    pub fn ram_slot_path(&self, slot: u8) -> Option<PathBuf> {
+        if !(1..=MAX_RAM_SLOT).contains(&slot) || !self.has_battery() || self.ram.is_empty() {
+            return None;
+        }
+        let path = self.save_path.as_ref()?;
+        Some(path.with_extension(format!("slot{slot}.sav")))
+    }
+
+    /// Writes cartridge RAM to save slot `slot` without touching the default
+    /// `.sav`. The RTC is not part of slots; it keeps tracking wall time.
+    #[cfg(feature = "std")]
+    pub fn save_ram_slot(&self, slot: u8) -> io::Result<()> {
+        let path = self
+            .ram_slot_path(slot)
+            .ok_or_else(|| Self::no_slot_error(slot))?;
+        fs::write(path, &self.ram)
+    }
+
+    /// Replaces cartridge RAM with the contents of save slot `slot`.
+    #[cfg(feature = "std")]
+    pub fn load_ram_slot(&mut self, slot: u8) -> io::Result<()> {
+        let path = self
+            .ram_slot_path(slot)
+            .ok_or_else(|| Self::no_slot_error(slot))?;
+        let bytes = fs::read(path)?;
+        self.ram.fill(0);
+        for (d, s) in self.ram.iter_mut().zip(bytes.iter()) {
+            *d = *s;
+        }
+        self.ram_dirty = true;
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn no_slot_error(slot: u8) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no battery save slot {slot} for this cartridge"),
+        )
+    }
+
+    /// Whether cartridge RAM may have changed since it was loaded or last
+    /// written by [`Self::save_ram`].
+    ///
+    /// Any CPU write to A000-BFFF sets this, even one the mapper ignores
+    /// because RAM is disabled or the address selects an RTC register.
+    /// Loading a RAM slot or a save state sets it too.
+    pub fn ram_dirty(&self) -> bool {
+        self.ram_dirty
+    }
+
+    #[cfg(feature = "std")]
     pub fn save_ram(&mut self) -> io::Result<()> {
         if let (true, Some(path)) = (self.has_battery(), &self.save_path)
             && !self.ram.is_empty()
         {
             fs::write(path, &self.ram)?;
         }
+        self.ram_dirty = false;
 
         let rtc_path = self.rtc_path.clone();
         if let (Some(path), Some(rtc)) = (rtc_path, self.rtc_mut()) {
-            rtc.mark_persisted(SystemTime::now());
+            rtc.mark_persisted(wall_clock_now());
             fs::write(path, rtc.serialize())?;
         }
         Ok(())
     }
 }
 
+state_record!(RtcState {
+    seconds,
+    minutes,
+    hours,
+    days,
+    halt,
+    carry,
+});
+
+impl StateValue for Mbc3Rtc {
+    fn write(&self, out: &mut Vec<u8>) {
+        let since_epoch = self.last_update;
+        let mut rec = Record::new();
+        rec.put("regs", &self.regs);
+        rec.put("latched", &self.latched);
+        rec.put("latched_active", &self.latched_active);
+        rec.put("last_update_secs", &since_epoch.as_secs());
+        rec.put("last_update_nanos", &since_epoch.subsec_nanos());
+        rec.put("subsecond_cycles", &self.subsecond_cycles);
+        rec.write(out);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        let rec = Record::read(input)?;
+        let mut rtc = Mbc3Rtc::new(Duration::ZERO);
+        rec.load("regs", &mut rtc.regs)?;
+        rec.load("latched", &mut rtc.latched)?;
+        rec.load("latched_active", &mut rtc.latched_active)?;
+        let secs: u64 = rec.get("last_update_secs")?.unwrap_or(0);
+        let nanos: u32 = rec.get("last_update_nanos")?.unwrap_or(0);
+        rtc.last_update = Duration::new(secs, nanos.min(999_999_999));
+        rec.load("subsecond_cycles", &mut rtc.subsecond_cycles)?;
+        rtc.subsecond_cycles = rtc.subsecond_cycles.min(RTC_CYCLES_PER_SECOND - 1);
+        Ok(rtc)
+    }
+}
+
+impl StateValue for MbcState {
+    fn write(&self, out: &mut Vec<u8>) {
+        let mut rec = Record::new();
+        match self {
+            MbcState::NoMbc => rec.put("kind", &0u8),
+            MbcState::Mbc1 {
+                rom_bank,
+                ram_bank,
+                mode,
+                ram_enable,
+                multicart,
+            } => {
+                rec.put("kind", &1u8);
+                rec.put("rom_bank", rom_bank);
+                rec.put("ram_bank", ram_bank);
+                rec.put("mode", mode);
+                rec.put("ram_enable", ram_enable);
+                rec.put("multicart", multicart);
+            }
+            MbcState::Mbc2 {
+                rom_bank,
+                ram_enable,
+            } => {
+                rec.put("kind", &2u8);
+                rec.put("rom_bank", rom_bank);
+                rec.put("ram_enable", ram_enable);
+            }
+            MbcState::Mbc3 {
+                rom_bank,
+                ram_bank,
+                ram_enable,
+                rtc,
+            }
+            | MbcState::Mbc30 {
+                rom_bank,
+                ram_bank,
+                ram_enable,
+                rtc,
+            } => {
+                let kind = if matches!(self, MbcState::Mbc3 { .. }) {
+                    3u8
+                } else {
+                    4u8
+                };
+                rec.put("kind", &kind);
+                rec.put("rom_bank", rom_bank);
+                rec.put("ram_bank", ram_bank);
+                rec.put("ram_enable", ram_enable);
+                rec.put("rtc", rtc);
+            }
+            MbcState::Mbc5 {
+                rom_bank,
+                ram_bank,
+                ram_enable,
+            } => {
+                rec.put("kind", &5u8);
+                rec.put("rom_bank", rom_bank);
+                rec.put("ram_bank", ram_bank);
+                rec.put("ram_enable", ram_enable);
+            }
+            MbcState::Unknown => rec.put("kind", &6u8),
+        }
+        rec.write(out);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        let rec = Record::read(input)?;
+        let rom_bank = || -> Result<u8, StateError> { Ok(rec.get("rom_bank")?.unwrap_or(1)) };
+        let ram_bank = || -> Result<u8, StateError> { Ok(rec.get("ram_bank")?.unwrap_or(0)) };
+        let ram_enable =
+            || -> Result<bool, StateError> { Ok(rec.get("ram_enable")?.unwrap_or(false)) };
+        Ok(match rec.require::<u8>("kind")? {
+            0 => MbcState::NoMbc,
+            1 => MbcState::Mbc1 {
+                rom_bank: rom_bank()?,
+                ram_bank: ram_bank()?,
+                mode: rec.get("mode")?.unwrap_or(0),
+                ram_enable: ram_enable()?,
+                multicart: rec.get("multicart")?.unwrap_or(false),
+            },
+            2 => MbcState::Mbc2 {
+                rom_bank: rom_bank()?,
+                ram_enable: ram_enable()?,
+            },
+            3 => MbcState::Mbc3 {
+                rom_bank: rom_bank()?,
+                ram_bank: ram_bank()?,
+                ram_enable: ram_enable()?,
+                rtc: rec.get("rtc")?.flatten(),
+            },
+            4 => MbcState::Mbc30 {
+                rom_bank: rom_bank()?,
+                ram_bank: ram_bank()?,
+                ram_enable: ram_enable()?,
+                rtc: rec.get("rtc")?.flatten(),
+            },
+            5 => MbcState::Mbc5 {
+                rom_bank: rec.get("rom_bank")?.unwrap_or(1),
+                ram_bank: ram_bank()?,
+                ram_enable: ram_enable()?,
+            },
+            6 => MbcState::Unknown,
+            _ => return Err(StateError::InvalidField("kind".into())),
+        })
+    }
+}
+
+impl Cartridge {
+    /// Header global checksum, used to tell cartridges with the same title
+    /// apart when matching save states.
+    fn global_checksum(&self) -> u16 {
+        match self.rom.get(0x014E..0x0150) {
+            Some(b) => u16::from_be_bytes([b[0], b[1]]),
+            None => 0,
+        }
+    }
+
+    pub(crate) fn save_state(&self) -> Record {
+        let mut rec = Record::new();
+        rec.put("title", &self.title.as_bytes().to_vec());
+        rec.put("global_checksum", &self.global_checksum());
+        rec.put("rom_len", &self.rom.len());
+        rec.put("ram", &self.ram);
+        rec.put("mbc_state", &self.mbc_state);
+        rec.put("cart_bus", &self.cart_bus);
+        rec
+    }
+
+    /// Restores RAM and mapper state. Nothing is modified unless the whole
+    /// record is valid for this cartridge.
+    pub(crate) fn load_state(&mut self, rec: &Record) -> Result<(), StateError> {
+        let title: Vec<u8> = rec.require("title")?;
+        let checksum: u16 = rec.require("global_checksum")?;
+        let rom_len: usize = rec.require("rom_len")?;
+        if title != self.title.as_bytes()
+            || checksum != self.global_checksum()
+            || rom_len != self.rom.len()
+        {
+            return Err(StateError::CartridgeMismatch);
+        }
+
+        let ram: Option<Vec<u8>> = rec.get("ram")?;
+        if ram.as_ref().is_some_and(|ram| ram.len() != self.ram.len()) {
+            return Err(StateError::InvalidField("ram".into()));
+        }
+        let mbc_state: Option<MbcState> = rec.get("mbc_state")?;
+        if mbc_state.as_ref().is_some_and(|state| {
+            core::mem::discriminant(state) != core::mem::discriminant(&self.mbc_state)
+        }) {
+            return Err(StateError::CartridgeMismatch);
+        }
+        let cart_bus: Option<u8> = rec.get("cart_bus")?;
+
+        // Copied in place: frontends such as libretro hold a pointer to the
+        // RAM buffer across state loads.
+        if let Some(ram) = ram {
+            self.ram.copy_from_slice(&ram);
+            self.ram_dirty = true;
+        }
+        if let Some(mut state) = mbc_state {
+            // The record may come from a cart with a clock; only 0x0F/0x10
+            // carts get one.
+            if let MbcState::Mbc3 { rtc, .. } | MbcState::Mbc30 { rtc, .. } = &mut state
+                && !self.has_rtc()
+            {
+                *rtc = None;
+            }
+            self.mbc_state = state;
+        }
+        if let Some(value) = cart_bus {
+            self.cart_bus.set(value);
+        }
+        Ok(())
+    }
+}
+
 fn detect_mbc1_multicart(rom: &[u8]) -> bool {
     // Mooneye's MBC1 multicart test targets the common 8 Mbit (64 bank) wiring.
     // This hardware variant can't be reliably detected from the header alone,
@@ -994,15 +1651,58 @@ fn detect_mbc1_multicart(rom: &[u8]) -> bool {
     true
 }
 
-struct Header<'a> {
+/// Read-only view of a ROM image's cartridge header.
+pub struct Header<'a> {
     data: &'a [u8],
 }
 
 impl<'a> Header<'a> {
-    fn parse(data: &'a [u8]) -> Self {
+    pub fn parse(data: &'a [u8]) -> Self {
         Self { data }
     }
 
+    /// ROM size declared by the byte at $0148, or `None` if the image has no
+    /// header or the byte is unknown.
+    pub fn rom_size(&self) -> Option<usize> {
+        if self.data.len() < 0x150 {
+            return None;
+        }
+        match self.data[0x0148] {
+            n @ 0x00..=0x08 => Some(0x8000 << n),
+            // Unofficial sizes listed in some documentation.
+            0x52 => Some(72 * 0x4000),
+            0x53 => Some(80 * 0x4000),
+            0x54 => Some(96 * 0x4000),
+            _ => None,
+        }
+    }
+
+    /// Checks the image length against itself and the header's ROM size
+    /// byte. Images too short to hold a header report nothing.
+    pub fn anomalies(&self) -> Vec<HeaderAnomaly> {
+        let len = self.data.len();
+        let mut out = Vec::new();
+        if len < 0x150 {
+            return out;
+        }
+
+        let declared = self.rom_size();
+        if !len.is_power_of_two() && declared != Some(len) {
+            out.push(HeaderAnomaly::NonPowerOfTwoSize { len });
+        }
+        match declared {
+            Some(declared) if len > declared => {
+                out.push(HeaderAnomaly::TrailingData { declared, len })
+            }
+            Some(declared) if len < declared => {
+                out.push(HeaderAnomaly::Truncated { declared, len })
+            }
+            Some(_) => {}
+            None => out.push(HeaderAnomaly::UnknownRomSize(self.data[0x0148])),
+        }
+        out
+    }
+
     fn title(&self) -> String {
         let end = 0x0143.min(self.data.len());
         let mut slice = &self.data[0x0134.min(self.data.len())..end];
@@ -1016,6 +1716,14 @@ impl<'a> Header<'a> {
         self.data.get(0x0143).copied().unwrap_or(0) & 0x80 != 0
     }
 
+    fn cgb_support(&self) -> CgbSupport {
+        match self.data.get(0x0143).copied().unwrap_or(0) {
+            flag if flag & 0xC0 == 0xC0 => CgbSupport::CgbOnly,
+            flag if flag & 0x80 != 0 => CgbSupport::Enhanced,
+            _ => CgbSupport::DmgOnly,
+        }
+    }
+
     fn mbc_type(&self) -> MbcType {
         if self.data.len() < 0x150 {
             return MbcType::NoMbc;
@@ -1046,7 +1754,7 @@ impl<'a> Header<'a> {
     }
 
     fn has_rtc(&self) -> bool {
-        matches!(self.cart_type(), 0x0F | 0x10 | 0x13)
+        matches!(self.cart_type(), 0x0F | 0x10)
     }
 
     fn ram_size(&self) -> usize {
@@ -1081,7 +1789,7 @@ mod tests {
 
     #[test]
     fn rtc_ticks_through_invalid_values() {
-        let now = SystemTime::UNIX_EPOCH;
+        let now = Duration::ZERO;
         let mut rtc = Mbc3Rtc::new(now);
 
         rtc.regs.seconds = 59;
@@ -1107,7 +1815,7 @@ mod tests {
 
     #[test]
     fn rtc_halt_preserves_subseconds() {
-        let start = SystemTime::UNIX_EPOCH;
+        let start = Duration::ZERO;
         let mut rtc = Mbc3Rtc::new(start);
         rtc.subsecond_cycles = RTC_CYCLES_PER_SECOND - 10_000;
 
@@ -1124,7 +1832,7 @@ mod tests {
 
     #[test]
     fn rtc_seconds_write_resets_phase() {
-        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10);
+        let now = Duration::from_secs(10);
         let mut rtc = Mbc3Rtc::new(now);
         rtc.subsecond_cycles = ms_to_cycles(750);
 
@@ -1139,7 +1847,7 @@ mod tests {
 
     #[test]
     fn rtc_day_overflow_sets_carry() {
-        let mut rtc = Mbc3Rtc::new(SystemTime::UNIX_EPOCH);
+        let mut rtc = Mbc3Rtc::new(Duration::ZERO);
         rtc.regs.seconds = 59;
         rtc.regs.minutes = 59;
         rtc.regs.hours = 23;
@@ -0,0 +1,122 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::gameboy::GameBoy;
+
+/// First address searched: cartridge RAM at A000-BFFF, then WRAM at
+/// C000-DFFF.
+const SEARCH_BASE: u16 = 0xA000;
+const SEARCH_LEN: usize = 0x4000;
+
+/// How a byte must have changed between the last two snapshots to stay a
+/// candidate in [`RamSearch::filter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    fn matches(self, old: u8, new: u8) -> bool {
+        match self {
+            Comparison::Changed => new != old,
+            Comparison::Unchanged => new == old,
+            Comparison::Increased => new > old,
+            Comparison::Decreased => new < old,
+        }
+    }
+}
+
+/// Narrows WRAM and cartridge RAM down to the addresses holding a value of
+/// interest, such as a lives counter, by comparing snapshots taken between
+/// frames.
+///
+/// Take a snapshot, let the value change in the game (or not), take another
+/// and [`filter`](Self::filter) by how it changed; repeat until few
+/// candidates remain. Addresses are as the CPU sees them: C000-DFFF with the
+/// current SVBK bank and A000-BFFF with the current cartridge RAM bank, read
+/// even while cartridge RAM is disabled. Snapshots only read memory, so
+/// searching never affects emulation.
+pub struct RamSearch {
+    previous: Box<[u8; SEARCH_LEN]>,
+    current: Box<[u8; SEARCH_LEN]>,
+    snapshots: u32,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    pub fn new() -> Self {
+        Self {
+            previous: Box::new([0; SEARCH_LEN]),
+            current: Box::new([0; SEARCH_LEN]),
+            snapshots: 0,
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Records the current contents of WRAM and cartridge RAM. The first
+    /// snapshot after [`Self::new`] or [`Self::reset`] makes every address
+    /// a candidate; cartridge RAM is only searched if the cartridge has any.
+    pub fn snapshot(&mut self, gb: &GameBoy) {
+        core::mem::swap(&mut self.previous, &mut self.current);
+        let mmu = &gb.mmu;
+        let sram_len = mmu
+            .cart
+            .as_ref()
+            .map_or(0, |cart| cart.ram_size_bytes().min(0x2000));
+        for (addr, value) in (SEARCH_BASE..).zip(self.current.iter_mut()) {
+            *value = match addr {
+                0xA000..=0xBFFF => match &mmu.cart {
+                    Some(cart) if usize::from(addr - 0xA000) < sram_len => cart.peek_ram(addr),
+                    _ => 0xFF,
+                },
+                _ => mmu.peek_byte(addr),
+            };
+        }
+
+        if self.snapshots == 0 {
+            let sram_end = SEARCH_BASE + sram_len as u16;
+            self.candidates = (SEARCH_BASE..sram_end).chain(0xC000..=0xDFFF).collect();
+        }
+        self.snapshots = self.snapshots.saturating_add(1);
+    }
+
+    /// Keeps only the candidates whose value changed as `comparison` says
+    /// between the last two snapshots, and returns how many remain. Does
+    /// nothing until two snapshots have been taken.
+    pub fn filter(&mut self, comparison: Comparison) -> usize {
+        if self.snapshots >= 2 {
+            let (previous, current) = (&self.previous, &self.current);
+            self.candidates.retain(|&addr| {
+                let i = usize::from(addr - SEARCH_BASE);
+                comparison.matches(previous[i], current[i])
+            });
+        }
+        self.candidates.len()
+    }
+
+    /// Addresses still matching every filter so far, in ascending order.
+    pub fn candidates(&self) -> Vec<u16> {
+        self.candidates.clone()
+    }
+
+    /// Value of `addr` in the latest snapshot, if `addr` is searched.
+    pub fn value(&self, addr: u16) -> Option<u8> {
+        let i = usize::from(addr.checked_sub(SEARCH_BASE)?);
+        self.current.get(i).copied()
+    }
+
+    /// Forgets all snapshots and candidates to start a new search.
+    pub fn reset(&mut self) {
+        self.snapshots = 0;
+        self.candidates.clear();
+    }
+}
+
+impl Default for RamSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,224 @@
+//! Colour palettes for DMG cartridges running on CGB hardware.
+//!
+//! The CGB boot ROM colours a DMG game by looking up a checksum of its
+//! header title, using the 4th title letter to tell apart titles with the
+//! same checksum. Only games with Nintendo as licensee are looked up; the
+//! rest get [`DEFAULT_PALETTES`].
+//!
+//! [`TITLE_PALETTES`] holds the boot ROM's assignments and [`lookup`]
+//! searches it; [`lookup_in`] takes a frontend's own table instead.
+//! [`Mmu::load_cart`](crate::mmu::Mmu::load_cart) applies [`lookup`] when a
+//! DMG cartridge is inserted in CGB mode; a frontend can override the result
+//! with [`Ppu::apply_dmg_colorization`](crate::ppu::Ppu::apply_dmg_colorization).
+
+/// BG and OBJ colours for a DMG game, as `0x00RRGGBB` for shades 0-3.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompatPalettes {
+    pub bg: [u32; 4],
+    pub obj0: [u32; 4],
+    pub obj1: [u32; 4],
+}
+
+/// The palettes the boot ROM loads for games it has no entry for.
+pub const DEFAULT_PALETTES: CompatPalettes = CompatPalettes {
+    bg: [0xFFFFFF, 0x7BFF31, 0x0063C6, 0x000000],
+    obj0: [0xFFFFFF, 0xFF8484, 0x943939, 0x000000],
+    obj1: [0xFFFFFF, 0xFF8484, 0x943939, 0x000000],
+};
+
+/// One row of a title palette table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TitlePalette {
+    /// Sum of the 16 title bytes at $0134-$0143, modulo 256.
+    pub checksum: u8,
+    /// Title byte at $0137, for checksums shared by several games. `None`
+    /// matches any letter.
+    pub fourth_letter: Option<u8>,
+    pub palettes: CompatPalettes,
+}
+
+/// The boot ROM's title checksum for `rom`, or `None` if the header is
+/// missing or the licensee is not Nintendo (old licensee $01, or $33 with
+/// new licensee "01").
+pub fn title_checksum(rom: &[u8]) -> Option<u8> {
+    let header = rom.get(0x0134..0x0150)?;
+    let nintendo = match header[0x014B - 0x0134] {
+        0x01 => true,
+        0x33 => &header[0x0144 - 0x0134..0x0146 - 0x0134] == b"01",
+        _ => false,
+    };
+    nintendo.then(|| {
+        header[..0x10]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+    })
+}
+
+/// The palettes [`TITLE_PALETTES`] assigns to `rom`, as the CGB boot ROM
+/// would pick them.
+pub fn lookup(rom: &[u8]) -> CompatPalettes {
+    lookup_in(rom, TITLE_PALETTES)
+}
+
+/// The palettes `table` assigns to `rom`, or [`DEFAULT_PALETTES`] if no row
+/// matches. Among rows with the same checksum, the first whose 4th letter
+/// matches wins.
+pub fn lookup_in(rom: &[u8], table: &[TitlePalette]) -> CompatPalettes {
+    let Some(checksum) = title_checksum(rom) else {
+        return DEFAULT_PALETTES;
+    };
+    let letter = rom[0x0137];
+    table
+        .iter()
+        .find(|row| row.checksum == checksum && row.fourth_letter.is_none_or(|l| l == letter))
+        .map_or(DEFAULT_PALETTES, |row| row.palettes)
+}
+
+const WHITE: u32 = 0xFFFFFF;
+const BLACK: u32 = 0x000000;
+
+const RED: [u32; 4] = [WHITE, 0xFF8484, 0x943939, BLACK];
+const GREEN: [u32; 4] = [WHITE, 0x7BFF31, 0x008400, BLACK];
+const BLUE: [u32; 4] = [WHITE, 0x63A5FF, 0x0000FF, BLACK];
+const BROWN: [u32; 4] = [WHITE, 0xFFAD63, 0x843100, BLACK];
+const ORANGE: [u32; 4] = [WHITE, 0xFF7300, 0x944200, BLACK];
+const OLIVE: [u32; 4] = [WHITE, 0xADAD84, 0x42737B, BLACK];
+const PURPLE: [u32; 4] = [WHITE, 0x8C8CDE, 0x52528C, BLACK];
+const GREEN_BLUE: [u32; 4] = [WHITE, 0x7BFF31, 0x0063C6, BLACK];
+const LIME_RED: [u32; 4] = [WHITE, 0x52FF00, 0xFF4200, BLACK];
+const AMBER_RED: [u32; 4] = [WHITE, 0xFF9C00, 0xFF0000, BLACK];
+const YELLOW_RED: [u32; 4] = [WHITE, 0xFFFF00, 0xFF0000, BLACK];
+const LEAF: [u32; 4] = [WHITE, 0x7BFF00, 0xB57300, BLACK];
+const GRAY: [u32; 4] = [WHITE, 0xA5A5A5, 0x525252, BLACK];
+const MULTI: [u32; 4] = [WHITE, 0x5ABDFF, 0xFF0000, 0x0000FF];
+const LAVENDER: [u32; 4] = [0xA59CFF, 0xFFFF00, 0x006300, BLACK];
+const CRIMSON: [u32; 4] = [0xFF6352, 0xD60000, 0x630000, BLACK];
+const GOLD: [u32; 4] = [0xFFC542, 0xFFD600, 0x943A00, 0x4A0000];
+const INVERTED: [u32; 4] = [BLACK, 0x008484, 0xFFDE00, WHITE];
+const SEA: [u32; 4] = [0xFFFF9C, 0x94B5FF, 0x639473, 0x003A3A];
+const PITCH: [u32; 4] = [0x6BFF00, WHITE, 0xFF524A, BLACK];
+const WHITE_BLUE: [u32; 4] = [WHITE, WHITE, 0x63A5FF, 0x0000FF];
+const NIGHT: [u32; 4] = [0x0000FF, WHITE, 0xFFFF7B, 0x0084FF];
+const SAND: [u32; 4] = [0xFFFFCE, 0x63EFEF, 0x9C8431, 0x5A5A5A];
+const SURF: [u32; 4] = [WHITE, 0xFFFF7B, 0x0084FF, 0xFF0000];
+const FIELD: [u32; 4] = [0x52DE00, 0xFF8400, 0xFFFF00, WHITE];
+const FOREST: [u32; 4] = [WHITE, 0x00FF00, 0x318400, 0x004A00];
+const MUSTARD: [u32; 4] = [WHITE, 0xFFCE00, 0x9C6300, BLACK];
+const EMBER: [u32; 4] = [0xFFFF00, 0xFF0000, 0x630000, BLACK];
+const SKY: [u32; 4] = [0xB5B5FF, 0xFFFF94, 0xAD5A42, BLACK];
+const SHADOW: [u32; 4] = [BLACK, WHITE, 0xFF8484, 0x943939];
+
+const fn row(
+    checksum: u8,
+    fourth_letter: Option<u8>,
+    bg: [u32; 4],
+    obj0: [u32; 4],
+    obj1: [u32; 4],
+) -> TitlePalette {
+    TitlePalette {
+        checksum,
+        fourth_letter,
+        palettes: CompatPalettes { bg, obj0, obj1 },
+    }
+}
+
+/// The CGB boot ROM's per-title palettes in its own order. Rows whose
+/// checksum is shared by several titles carry the 4th letter; a shared
+/// checksum with any other letter gets [`DEFAULT_PALETTES`].
+#[rustfmt::skip]
+pub const TITLE_PALETTES: &[TitlePalette] = &[
+    row(0x88, None, LAVENDER, LAVENDER, LAVENDER),        // ALLEY WAY
+    row(0x16, None, BROWN, BROWN, BROWN),                 // YAKUMAN
+    row(0x36, None, FIELD, WHITE_BLUE, RED),              // BASEBALL
+    row(0xD1, None, PITCH, WHITE_BLUE, BROWN),            // TENNIS
+    row(0xDB, None, YELLOW_RED, YELLOW_RED, YELLOW_RED),  // TETRIS
+    row(0xF2, None, YELLOW_RED, YELLOW_RED, MULTI),       // QIX
+    row(0x3C, None, BLUE, BLUE, RED),                     // DR.MARIO
+    row(0x8C, None, OLIVE, ORANGE, OLIVE),                // RADARMISSION
+    row(0x92, None, BROWN, BROWN, BROWN),                 // F1RACE
+    row(0x3D, None, LIME_RED, RED, RED),                  // YOSSY NO TAMAGO
+    row(0x5C, None, LAVENDER, CRIMSON, NIGHT),            // HOSHINOKA-BI
+    row(0x58, None, GRAY, GRAY, GRAY),                    // X
+    row(0xC9, None, SAND, ORANGE, BLUE),                  // MARIOLAND2
+    row(0x3E, None, AMBER_RED, AMBER_RED, MULTI),         // YOSSY NO COOKIE
+    row(0x70, None, FOREST, RED, BLUE),                   // ZELDA
+    row(0x1D, None, LAVENDER, CRIMSON, CRIMSON),          // KIRBY'S PINBALL
+    row(0x59, None, OLIVE, ORANGE, MULTI),                // SUPERMARIOLAND3
+    row(0x69, None, YELLOW_RED, YELLOW_RED, MULTI),       // TETRIS FLASH
+    row(0x19, None, AMBER_RED, RED, RED),                 // DONKEY KONG
+    row(0x35, None, BROWN, BROWN, BROWN),                 // MARIO'S PICROSS
+    row(0xA8, None, SEA, GOLD, RED),                      // SUPER DONKEYKONG
+    row(0x14, None, RED, GREEN, RED),                     // POKEMON RED
+    row(0xAA, None, GREEN_BLUE, RED, GREEN_BLUE),         // POKEMON GREEN
+    row(0x75, None, BROWN, BROWN, BROWN),                 // PICROSS 2
+    row(0x95, None, LIME_RED, LIME_RED, MULTI),           // YOSSY NO PANEPON
+    row(0x99, None, BROWN, BROWN, BROWN),                 // KIRAKIRA KIDS
+    row(0x34, None, LEAF, RED, RED),                      // GAMEBOY GALLERY
+    row(0x6F, None, MUSTARD, MUSTARD, MUSTARD),           // POCKETCAMERA
+    row(0x15, None, YELLOW_RED, YELLOW_RED, YELLOW_RED),  // POKEMON YELLOW
+    row(0xFF, None, AMBER_RED, AMBER_RED, AMBER_RED),     // BALLOON KID
+    row(0x97, None, BROWN, BLUE, BLUE),                   // KINGOFTHEZOO
+    row(0x4B, None, GREEN, RED, RED),                     // DMG FOOTBALL
+    row(0x90, None, GREEN, RED, RED),                     // WORLD CUP
+    row(0x17, None, GREEN, RED, BLUE),                    // OTHELLO
+    row(0x10, None, BROWN, BLUE, GREEN),                  // SUPER RC PRO-AM
+    row(0x39, None, BROWN, BLUE, BLUE),                   // DYNABLASTER
+    row(0xF7, None, BROWN, GREEN, BLUE),                  // BOY AND BLOB GB2
+    row(0xF6, None, BROWN, BLUE, GREEN),                  // MEGAMAN
+    row(0xA2, None, BROWN, GREEN, BLUE),                  // STAR WARS-NOA
+    row(0x49, None, LAVENDER, CRIMSON, NIGHT),            // KIRBY DREAM LAND
+    row(0x4E, None, SURF, RED, BLUE),                     // WAVERACE
+    row(0x43, None, BROWN, BLUE, BLUE),                   // THE CHESSMASTER
+    row(0x68, None, BROWN, BLUE, GREEN),                  // LOLO2
+    row(0xE0, None, AMBER_RED, AMBER_RED, MULTI),         // YOSHI'S COOKIE
+    row(0x8B, None, GREEN, RED, BLUE),                    // MYSTIC QUEST
+    row(0xF0, None, PITCH, WHITE_BLUE, BROWN),            // TOPRANKTENNIS
+    row(0xCE, None, PITCH, WHITE_BLUE, BROWN),            // TOPRANKINGTENNIS
+    row(0x0C, None, BROWN, BROWN, BROWN),                 // MANSELL
+    row(0x29, None, BROWN, BLUE, GREEN),                  // MEGAMAN3
+    row(0xE8, None, INVERTED, INVERTED, INVERTED),        // SPACE INVADERS
+    row(0xB7, None, BROWN, BROWN, BROWN),                 // GAME&WATCH
+    row(0x86, None, SEA, GOLD, RED),                      // DONKEYKONGLAND95
+    row(0x9A, None, GREEN, RED, RED),                     // ASTEROIDS/MISCMD
+    row(0x52, None, BROWN, BLUE, GREEN),                  // STREET FIGHTER 2
+    row(0x01, None, BROWN, BLUE, GREEN),                  // DEFENDER/JOUST
+    row(0x9D, None, PURPLE, RED, BROWN),                  // KILLERINSTINCT95
+    row(0x71, None, AMBER_RED, AMBER_RED, AMBER_RED),     // TETRIS BLAST
+    row(0x9C, None, PURPLE, PURPLE, GOLD),                // PINOCCHIO
+    row(0xBD, None, GREEN, RED, RED),                     // TOY STORY
+    row(0x5D, None, BROWN, BLUE, GREEN),                  // BA.TOSHINDEN
+    row(0x6D, None, BROWN, BLUE, GREEN),                  // NETTOU KOF 95
+    row(0x67, None, BROWN, BROWN, BROWN),                 // STAR STACKER
+    row(0x3F, None, GREEN_BLUE, RED, RED),                // TETRIS PLUS
+    row(0x6B, None, PURPLE, GOLD, MULTI),                 // DONKEYKONGLAND 3
+    // Checksums shared by several titles.
+    row(0xB3, Some(b'B'), LAVENDER, CRIMSON, NIGHT),      // KIRBY2
+    row(0xB3, Some(b'U'), OLIVE, ORANGE, ORANGE),         // MOGURANYA
+    row(0xB3, Some(b'R'), LIME_RED, LIME_RED, MULTI),     // TETRIS ATTACK
+    row(0x46, Some(b'E'), SKY, SHADOW, SHADOW),           // SUPER MARIOLAND
+    row(0x46, Some(b'R'), BLUE, EMBER, GREEN),            // METROID2
+    row(0x28, Some(b'F'), GREEN, RED, RED),               // GOLF
+    row(0x28, Some(b'A'), INVERTED, INVERTED, INVERTED),  // GALAXIAN
+    row(0xA5, Some(b'A'), INVERTED, INVERTED, INVERTED),  // SOLARSTRIKER
+    row(0xA5, Some(b'R'), BROWN, GREEN, GREEN),           // BT2RAGNAROKWORLD
+    row(0xC6, Some(b'A'), OLIVE, ORANGE, MULTI),          // GBWARS
+    row(0xC6, Some(b' '), GREEN_BLUE, RED, RED),          // KEN GRIFFEY JR
+    row(0xD3, Some(b'R'), PURPLE, RED, PURPLE),           // KAERUNOTAMENI
+    row(0xD3, Some(b'I'), OLIVE, BROWN, BLUE),            // WARIOLAND2
+    row(0x27, Some(b'B'), LAVENDER, CRIMSON, NIGHT),      // KIRBY BLOCKBALL
+    row(0x27, Some(b'N'), GREEN, RED, BLUE),              // MAGNETIC SOCCER
+    row(0x61, Some(b'E'), BLUE, RED, BLUE),               // POKEMON BLUE
+    row(0x61, Some(b'A'), GREEN, RED, BLUE),              // VEGAS STAKES
+    row(0x18, Some(b'K'), PURPLE, GOLD, MULTI),           // DONKEYKONGLAND
+    row(0x18, Some(b'I'), GREEN_BLUE, RED, RED),          // WARIO BLAST
+    row(0x66, Some(b'E'), LEAF, RED, RED),                // GAMEBOY GALLERY2
+    row(0x66, Some(b'L'), GREEN_BLUE, RED, RED),          // MILLI/CENTI/PEDE
+    row(0x6A, Some(b'K'), PURPLE, GOLD, MULTI),           // DONKEYKONGLAND 2
+    row(0x6A, Some(b'I'), LIME_RED, RED, RED),            // MARIO & YOSHI
+    row(0xBF, Some(b' '), PURPLE, RED, RED),              // KID ICARUS
+    row(0xBF, Some(b'C'), PITCH, WHITE_BLUE, BROWN),      // SOCCER
+    row(0x0D, Some(b'R'), YELLOW_RED, YELLOW_RED, MULTI), // TETRIS2
+    row(0x0D, Some(b'E'), PURPLE, GOLD, GOLD),            // POKEBOM
+    row(0xF4, Some(b'-'), GREEN_BLUE, BLUE, RED),         // PAC-IN-TIME
+    row(0xF4, Some(b' '), LEAF, RED, RED),                // G&W GALLERY
+];
@@ -1,5 +1,8 @@
-use crate::hardware::DmgRevision;
+use crate::disasm::{InstructionPreview, PreviewRegs, preview_sm83};
+use crate::hardware::{DmgRevision, Model};
 use crate::ppu::OamBugAccess;
+use crate::savestate::{Record, StateError, state_fields};
+use alloc::{format, string::String};
 
 // CPU flag bits as documented in gbdev.io/pandocs/The_CPU_Flags.html
 const FLAG_Z: u8 = 0x80; // Zero
@@ -45,6 +48,15 @@ const CGB_BOOT_E: u8 = 0x08;
 const CGB_BOOT_H: u8 = 0x00;
 const CGB_BOOT_L: u8 = 0x7C;
 
+// The CGB_BOOT_* values above are what the CGB boot ROM leaves when it hands
+// a DMG cartridge over in compatibility mode. A CGB cartridge gets these
+// instead; the AGB boot ROM also sets B bit 0 and clears Z.
+const CGB_NATIVE_BOOT_D: u8 = 0xFF;
+const CGB_NATIVE_BOOT_E: u8 = 0x56;
+const CGB_NATIVE_BOOT_L: u8 = 0x0D;
+const AGB_BOOT_F: u8 = 0x00;
+const AGB_BOOT_B: u8 = 0x01;
+
 // Clock ratios per machine cycle
 const CYCLES_PER_M_CYCLE: u16 = 4; // normal speed
 const CYCLES_PER_M_CYCLE_DOUBLE: u16 = 2; // double-speed mode
@@ -65,9 +77,13 @@ pub struct Cpu {
     pub pc: u16,
     pub sp: u16,
     pub cycles: u64,
+    instructions_retired: u64,
     pub ime: bool,
     pub halted: bool,
     pub stopped: bool,
+    /// Address of the illegal opcode that locked up the CPU; see
+    /// [`Cpu::is_locked`].
+    locked_at: Option<u16>,
     stop_vram_blocked: bool,
     pub double_speed: bool,
     halt_bug: bool,
@@ -75,6 +91,11 @@ pub struct Cpu {
     halt_pc: Option<u16>,
     halt_pending: u8,
     dma_conflict_active: bool,
+    /// M-cycles ticked since the start of the current `step`.
+    step_m_cycles: u32,
+    last_instruction_cycles: u32,
+    /// xorshift64 state for [`Cpu::set_timing_fuzz`]; `None` when off.
+    timing_fuzz: Option<u64>,
 }
 
 impl Cpu {
@@ -104,9 +125,11 @@ impl Cpu {
             pc: 0x0000,
             sp: 0x0000,
             cycles: 0,
+            instructions_retired: 0,
             ime: false,
             halted: false,
             stopped: false,
+            locked_at: None,
             stop_vram_blocked: false,
             double_speed: false,
             halt_bug: false,
@@ -114,6 +137,9 @@ impl Cpu {
             halt_pc: None,
             halt_pending: 0,
             dma_conflict_active: false,
+            step_m_cycles: 0,
+            last_instruction_cycles: 0,
+            timing_fuzz: None,
         }
     }
 
@@ -143,9 +169,11 @@ impl Cpu {
                 pc: BOOT_PC,
                 sp: BOOT_SP,
                 cycles: 0,
+                instructions_retired: 0,
                 ime: false,
                 halted: false,
                 stopped: false,
+                locked_at: None,
                 stop_vram_blocked: false,
                 double_speed: false,
                 halt_bug: false,
@@ -153,6 +181,9 @@ impl Cpu {
                 halt_pc: None,
                 halt_pending: 0,
                 dma_conflict_active: false,
+                step_m_cycles: 0,
+                last_instruction_cycles: 0,
+                timing_fuzz: None,
             }
         } else {
             let (a, f, b, c, d, e, h, l) = match dmg_revision {
@@ -189,9 +220,11 @@ impl Cpu {
                 pc: BOOT_PC,
                 sp: BOOT_SP,
                 cycles: 0,
+                instructions_retired: 0,
                 ime: false,
                 halted: false,
                 stopped: false,
+                locked_at: None,
                 stop_vram_blocked: false,
                 double_speed: false,
                 halt_bug: false,
@@ -199,10 +232,38 @@ impl Cpu {
                 halt_pc: None,
                 halt_pending: 0,
                 dma_conflict_active: false,
+                step_m_cycles: 0,
+                last_instruction_cycles: 0,
+                timing_fuzz: None,
             }
         }
     }
 
+    /// The model-identification value the boot ROM leaves in A: 0x01 on DMG,
+    /// 0x11 on CGB and AGB. The AGB additionally sets bit 0 of B.
+    pub fn boot_model_byte(cgb: bool) -> u8 {
+        if cgb { CGB_BOOT_A } else { DMG_ABC_BOOT_A }
+    }
+
+    /// Create a CPU in the state the boot ROM of `model` leaves it in when it
+    /// jumps to a cartridge, as documented in Pan Docs (Power Up Sequence).
+    ///
+    /// CGB and AGB assume a CGB-enhanced cartridge. `dmg_revision` only
+    /// matters for [`Model::Dmg`].
+    pub fn new_post_boot(model: Model, dmg_revision: DmgRevision) -> Self {
+        let mut cpu = Self::new_with_mode_and_revision(model.is_cgb(), dmg_revision);
+        if model.is_cgb() {
+            cpu.d = CGB_NATIVE_BOOT_D;
+            cpu.e = CGB_NATIVE_BOOT_E;
+            cpu.l = CGB_NATIVE_BOOT_L;
+        }
+        if model == Model::Agb {
+            cpu.f = AGB_BOOT_F;
+            cpu.b = AGB_BOOT_B;
+        }
+        cpu
+    }
+
     fn get_bc(&self) -> u16 {
         ((self.b as u16) << 8) | self.c as u16
     }
@@ -268,6 +329,7 @@ impl Cpu {
         let cpu_cycles = CYCLES_PER_M_CYCLE * m_cycles as u16;
 
         self.cycles += dot_cycles as u64;
+        self.step_m_cycles = self.step_m_cycles.wrapping_add(m_cycles as u32);
 
         let prev_dot_div = mmu.dot_div;
         mmu.dot_div = mmu.dot_div.wrapping_add(dot_cycles);
@@ -394,6 +456,9 @@ impl Cpu {
     #[inline(always)]
     fn write8(&mut self, mmu: &mut crate::mmu::Mmu, addr: u16, val: u8) {
         mmu.last_cpu_pc = Some(self.pc);
+        if addr == 0xFF46 {
+            mmu.oam_dma_write_cycle = self.cycles;
+        }
         if !(self.dma_conflict_active
             && ((0xA000..=0xBFFF).contains(&addr) || (0xC000..=0xFDFF).contains(&addr)))
         {
@@ -408,6 +473,69 @@ impl Cpu {
         self.write8(mmu, addr.wrapping_add(1), (val >> 8) as u8);
     }
 
+    /// M-cycles taken by the instruction executed by the last [`Self::step`].
+    ///
+    /// Interrupt dispatch is not included. When the last step did not execute
+    /// an instruction (HALT, STOP, or a General DMA stall), this is the number
+    /// of M-cycles that step idled for.
+    pub fn last_instruction_cycles(&self) -> u32 {
+        self.last_instruction_cycles
+    }
+
+    /// Number of instructions executed since power-on or reset.
+    ///
+    /// A CB-prefixed instruction counts once. Interrupt dispatch and the
+    /// M-cycles spent halted, stopped or stalled by General DMA do not count,
+    /// but the HALT and STOP instructions themselves do.
+    pub fn instructions_retired(&self) -> u64 {
+        self.instructions_retired
+    }
+
+    /// Whether the CPU has locked up by executing one of the undefined
+    /// opcodes D3, DB, DD, E3, E4, EB, EC, ED, F4, FC and FD.
+    ///
+    /// As on hardware, a locked CPU never executes another instruction and
+    /// ignores interrupts, HALT wake-ups and joypad presses; only a reset
+    /// recovers it. The rest of the machine keeps running, so the PPU, APU
+    /// and timers are still clocked. [`GameBoy::run`](crate::gameboy::GameBoy::run)
+    /// stops with [`RunOutcome::Locked`](crate::gameboy::RunOutcome::Locked)
+    /// when it happens.
+    pub fn is_locked(&self) -> bool {
+        self.locked_at.is_some()
+    }
+
+    /// Address of the undefined opcode that locked up the CPU, if any.
+    pub fn locked_at(&self) -> Option<u16> {
+        self.locked_at
+    }
+
+    /// Decodes the instruction at PC and reports what it would do, without
+    /// executing it or touching any hardware: jump, call and return targets
+    /// with the current flags, the address a CALL returns to, and the
+    /// registers, flags and memory it would change.
+    ///
+    /// Meant for debuggers, e.g. to step over a CALL by running to
+    /// [`InstructionPreview::return_address`]. The preview ignores
+    /// interrupts that would be dispatched first and the HALT bug.
+    pub fn preview_next(&self, mmu: &crate::mmu::Mmu) -> InstructionPreview {
+        let bytes: [u8; 3] =
+            core::array::from_fn(|i| mmu.peek_byte(self.pc.wrapping_add(i as u16)));
+        let stack_top = u16::from_le_bytes([
+            mmu.peek_byte(self.sp),
+            mmu.peek_byte(self.sp.wrapping_add(1)),
+        ]);
+        let regs = PreviewRegs {
+            f: self.f,
+            c: self.c,
+            bc: u16::from_be_bytes([self.b, self.c]),
+            de: u16::from_be_bytes([self.d, self.e]),
+            hl: self.get_hl(),
+            sp: self.sp,
+            stack_top,
+        };
+        preview_sm83(&bytes, self.pc, &regs)
+    }
+
     /// Formatted CPU state string for debugging.
     pub fn debug_state(&self) -> String {
         format!(
@@ -577,6 +705,31 @@ impl Cpu {
         }
     }
 
+    /// Delays each interrupt dispatch by zero or one M-cycle, chosen by a
+    /// PRNG seeded with `seed`; `None` turns it off. See
+    /// [`GameBoy::set_timing_fuzz`](crate::gameboy::GameBoy::set_timing_fuzz).
+    pub fn set_timing_fuzz(&mut self, seed: Option<u64>) {
+        // SplitMix64 finalizer, so nearby seeds diverge at once and seed 0
+        // doesn't lock xorshift up.
+        self.timing_fuzz = seed.map(|seed| {
+            let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            (z ^ (z >> 31)).max(1)
+        });
+    }
+
+    /// Whether timing fuzz holds back the interrupt about to be dispatched.
+    fn fuzz_dispatch_delay(&mut self) -> bool {
+        let Some(state) = self.timing_fuzz.as_mut() else {
+            return false;
+        };
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state >> 63 != 0
+    }
+
     fn handle_interrupts(&mut self, mmu: &mut crate::mmu::Mmu) {
         let pending = (mmu.if_reg & mmu.ie_reg) & 0x1F;
         if pending == 0 {
@@ -584,6 +737,9 @@ impl Cpu {
         }
 
         if self.ime {
+            if self.fuzz_dispatch_delay() {
+                self.tick(mmu, 1);
+            }
             let (initial_bit, _) = Self::next_interrupt(pending);
             let mut return_pc = self.pc;
 
@@ -605,16 +761,25 @@ impl Cpu {
 
             // Interrupt entry pushes the return address onto the stack.
             // If the upper-byte push targets IE ($FFFF), the write can change
-            // which interrupt is dispatched (or cancel dispatch entirely).
-            // Re-check IE/IF after the upper-byte push but before the lower-byte
-            // push to match hardware (mooneye "interrupts/ie_push").
+            // which interrupt is dispatched (or cancel dispatch entirely), so IE
+            // is sampled after the upper-byte push (mooneye "interrupts/ie_push").
+            // IF is sampled one cycle later, after the lower-byte push: requests
+            // raised during that cycle, or a lower-byte push that lands on IF
+            // itself, still affect the vector.
 
             // Upper byte push.
             Self::dmg_oam_bug_idu_if_needed(mmu, self.sp, OamBugAccess::Write);
             self.sp = self.sp.wrapping_sub(1);
             self.write8(mmu, self.sp, (return_pc >> 8) as u8);
 
-            let queue = (mmu.ie_reg & mmu.if_reg) & 0x1F;
+            let ie = mmu.ie_reg;
+
+            // Lower byte push. This still occurs even if the dispatch is cancelled.
+            Self::dmg_oam_bug_idu_if_needed(mmu, self.sp, OamBugAccess::Write);
+            self.sp = self.sp.wrapping_sub(1);
+            self.write8(mmu, self.sp, return_pc as u8);
+
+            let queue = (ie & mmu.if_reg) & 0x1F;
 
             #[cfg(feature = "cpu-trace")]
             {
@@ -633,18 +798,12 @@ impl Cpu {
                 }
             }
 
-            // The low-byte push still occurs even if the dispatch is cancelled.
             let (bit, vector) = if queue == 0 {
                 (0, 0)
             } else {
                 Self::next_interrupt(queue)
             };
 
-            // Lower byte push.
-            Self::dmg_oam_bug_idu_if_needed(mmu, self.sp, OamBugAccess::Write);
-            self.sp = self.sp.wrapping_sub(1);
-            self.write8(mmu, self.sp, return_pc as u8);
-
             if bit != 0 {
                 mmu.if_reg &= !bit;
                 if (self.halt_pending & bit) != 0 {
@@ -683,7 +842,18 @@ impl Cpu {
 
         // DMA bus-conflict handling is assessed per instruction.
         self.dma_conflict_active = false;
+        self.step_m_cycles = 0;
 
+        // STOP ends as soon as a selected joypad line is low, whether or not
+        // the joypad interrupt is enabled.
+        if self.stopped && mmu.input.read() & 0x0F != 0x0F {
+            self.stopped = false;
+        }
+        if self.locked_at.is_some() {
+            self.tick(mmu, 1);
+            self.last_instruction_cycles = self.step_m_cycles;
+            return;
+        }
         if self.stopped {
             // In CGB mode, STOP keeps the PPU running, but it cannot access VRAM.
             // Force VRAM reads during rendering to return 0x00 so the output
@@ -691,17 +861,26 @@ impl Cpu {
             if mmu.is_cgb() {
                 mmu.ppu.set_render_vram_blocked(self.stop_vram_blocked);
                 self.tick(mmu, 1);
+            } else {
+                // A stopped DMG clocks nothing, but time still passes and the
+                // LCD shows a dark screen.
+                self.cycles += CYCLES_PER_M_CYCLE as u64;
+                self.step_m_cycles = 1;
+                mmu.ppu.step_dmg_stopped(CYCLES_PER_M_CYCLE);
             }
+            self.last_instruction_cycles = self.step_m_cycles;
             return;
         }
         if mmu.gdma_active() {
             mmu.gdma_step(GDMA_STEP_CYCLES.into());
             self.tick(mmu, 1);
+            self.last_instruction_cycles = self.step_m_cycles;
             return;
         }
 
         if self.halted {
             self.tick(mmu, 1);
+            self.last_instruction_cycles = self.step_m_cycles;
             self.handle_interrupts(mmu);
             return;
         }
@@ -714,6 +893,9 @@ impl Cpu {
         } else {
             self.fetch8(mmu)
         };
+        self.instructions_retired += 1;
+        #[cfg(feature = "tracing-instructions")]
+        let _span = tracing::trace_span!("instruction", pc = opcode_pc, opcode).entered();
 
         // DMG: when executing from ROM during a ROM-sourced OAM DMA transfer,
         // some stack/RAM accesses observe the DMA bus instead of actual RAM.
@@ -1772,14 +1954,20 @@ impl Cpu {
                     }
                     | if self.a < val { FLAG_C } else { 0 };
             }
-            _ => {
-                panic!(
-                    "unhandled opcode {opcode:02X} at PC={:04X}",
-                    self.pc.wrapping_sub(1)
+            0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
+                core_warn!(
+                    target: "vibe_emu_core::cpu",
+                    "illegal opcode {opcode:02X} at {opcode_pc:04X}; CPU locked"
                 );
+                self.locked_at = Some(opcode_pc);
+                self.last_instruction_cycles = self.step_m_cycles;
+                return;
             }
+            _ => unreachable!("opcode {opcode:02X} is decoded above"),
         }
 
+        self.last_instruction_cycles = self.step_m_cycles;
+
         if enable_after && self.ime_enable_delay > 0 {
             self.ime = true;
         }
@@ -1790,6 +1978,45 @@ impl Cpu {
     }
 }
 
+state_fields!(Cpu {
+    a,
+    f,
+    b,
+    c,
+    d,
+    e,
+    h,
+    l,
+    pc,
+    sp,
+    cycles,
+    instructions_retired,
+    ime,
+    halted,
+    stopped,
+    locked_at,
+    stop_vram_blocked,
+    double_speed,
+    halt_bug,
+    ime_enable_delay,
+    halt_pc,
+    halt_pending,
+    dma_conflict_active,
+    last_instruction_cycles,
+});
+
+impl Cpu {
+    pub(crate) fn save_state(&self) -> Record {
+        let mut rec = Record::new();
+        self.save_state_fields(&mut rec);
+        rec
+    }
+
+    pub(crate) fn load_state(&mut self, rec: &Record) -> Result<(), StateError> {
+        self.load_state_fields(rec)
+    }
+}
+
 impl Default for Cpu {
     fn default() -> Self {
         Self::new()
@@ -1,3 +1,9 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::ops::{BitOr, BitOrAssign};
+
 /// Decode an SM83 instruction from the given memory slice.
 /// `mem` should be a slice starting at the instruction to decode.
 /// `addr` is the absolute address (used for relative jump target display).
@@ -317,3 +323,274 @@ pub fn format_bytes(mem: &[u8], addr: u16, len: u16) -> String {
     }
     s
 }
+
+/// CPU registers an instruction writes, as reported by
+/// [`InstructionPreview::registers_written`]. F is reported separately as
+/// [`InstructionPreview::flags_affected`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RegisterSet(u8);
+
+impl RegisterSet {
+    pub const A: Self = Self(0x01);
+    pub const B: Self = Self(0x02);
+    pub const C: Self = Self(0x04);
+    pub const D: Self = Self(0x08);
+    pub const E: Self = Self(0x10);
+    pub const H: Self = Self(0x20);
+    pub const L: Self = Self(0x40);
+    pub const SP: Self = Self(0x80);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for RegisterSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for RegisterSet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// How an instruction moves PC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlFlow {
+    /// Continues with the next instruction.
+    Sequential,
+    /// JP or JR.
+    Jump,
+    /// CALL or RST, which push the return address.
+    Call,
+    /// RET or RETI.
+    Return,
+}
+
+/// What the instruction at PC would do, from
+/// [`Cpu::preview_next`](crate::cpu::Cpu::preview_next).
+///
+/// Addresses that depend on registers or the stack are resolved with the
+/// CPU state at the time of the preview.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstructionPreview {
+    pub pc: u16,
+    pub mnemonic: String,
+    /// Length in bytes, including the CB prefix.
+    pub len: u16,
+    pub flow: ControlFlow,
+    /// Whether the jump, call or return tests a flag.
+    pub conditional: bool,
+    /// Whether the jump, call or return would be taken with the current
+    /// flags. Always `true` for unconditional ones and `false` for
+    /// [`ControlFlow::Sequential`].
+    pub taken: bool,
+    /// PC if the jump, call or return is taken: its target, RST vector or
+    /// the address on top of the stack.
+    pub branch_target: Option<u16>,
+    /// PC if it is not taken, i.e. the following instruction; `None` for
+    /// unconditional jumps, calls and returns.
+    pub fallthrough: Option<u16>,
+    /// Address CALL and RST push, where step-over should stop.
+    pub return_address: Option<u16>,
+    /// Stack address that would receive the low byte of a CALL, RST or
+    /// PUSH, i.e. SP - 2.
+    pub push_address: Option<u16>,
+    /// Memory operand read or written, e.g. (HL), (a16) or an LDH address.
+    /// Stack accesses are not included.
+    pub memory_address: Option<u16>,
+    pub registers_written: RegisterSet,
+    /// Mask of the F bits (Z = 0x80, N = 0x40, H = 0x20, C = 0x10) the
+    /// instruction may change.
+    pub flags_affected: u8,
+}
+
+impl InstructionPreview {
+    /// Every PC the CPU may continue at, taken branch first.
+    pub fn next_pcs(&self) -> impl Iterator<Item = u16> {
+        self.branch_target.into_iter().chain(self.fallthrough)
+    }
+}
+
+/// Where an instruction's operands point, so [`preview_sm83`] can resolve
+/// register-relative addresses.
+pub(crate) struct PreviewRegs {
+    pub f: u8,
+    pub c: u8,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    /// Address on top of the stack, for returns.
+    pub stack_top: u16,
+}
+
+/// Decodes the instruction in `mem` at `addr` and works out its effects.
+pub(crate) fn preview_sm83(mem: &[u8], addr: u16, regs: &PreviewRegs) -> InstructionPreview {
+    let (mnemonic, len, target) = decode_sm83(mem, addr);
+    let op = mem.first().copied().unwrap_or(0);
+    let next = addr.wrapping_add(len);
+    let (x, y, z) = (op >> 6, (op >> 3) & 0x07, op & 0x07);
+    let condition = match y & 0x03 {
+        0 => regs.f & 0x80 == 0,
+        1 => regs.f & 0x80 != 0,
+        2 => regs.f & 0x10 == 0,
+        _ => regs.f & 0x10 != 0,
+    };
+
+    let (flow, conditional, branch_target) = match op {
+        0x18 | 0xC3 => (ControlFlow::Jump, false, target),
+        0xE9 => (ControlFlow::Jump, false, Some(regs.hl)),
+        0x20 | 0x28 | 0x30 | 0x38 | 0xC2 | 0xCA | 0xD2 | 0xDA => (ControlFlow::Jump, true, target),
+        0xCD => (ControlFlow::Call, false, target),
+        0xC4 | 0xCC | 0xD4 | 0xDC => (ControlFlow::Call, true, target),
+        _ if x == 3 && z == 7 => (ControlFlow::Call, false, Some(u16::from(y) * 8)),
+        0xC9 | 0xD9 => (ControlFlow::Return, false, Some(regs.stack_top)),
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => (ControlFlow::Return, true, Some(regs.stack_top)),
+        _ => (ControlFlow::Sequential, false, None),
+    };
+    let taken = flow != ControlFlow::Sequential && (!conditional || condition);
+    let fallthrough = (flow == ControlFlow::Sequential || conditional).then_some(next);
+    let pushes = flow == ControlFlow::Call || (x == 3 && z == 5 && y & 1 == 0);
+
+    let (registers_written, flags_affected) = if op == 0xCB {
+        cb_effects(mem.get(1).copied().unwrap_or(0))
+    } else {
+        base_effects(op)
+    };
+
+    let hl_operand = match op {
+        0xCB => mem.get(1).is_some_and(|cb| cb & 0x07 == 6),
+        0x76 => false,
+        _ => match x {
+            0 => (matches!(z, 4..=6) && y == 6) || (z == 2 && y >= 4),
+            1 => y == 6 || z == 6,
+            2 => z == 6,
+            _ => false,
+        },
+    };
+    let memory_address = match op {
+        0x02 | 0x0A => Some(regs.bc),
+        0x12 | 0x1A => Some(regs.de),
+        0x08 | 0xEA | 0xFA => {
+            let byte = |i| mem.get(i).copied().unwrap_or(0);
+            Some(u16::from_le_bytes([byte(1), byte(2)]))
+        }
+        0xE0 | 0xF0 => target,
+        0xE2 | 0xF2 => Some(0xFF00 | u16::from(regs.c)),
+        _ => hl_operand.then_some(regs.hl),
+    };
+
+    InstructionPreview {
+        pc: addr,
+        mnemonic,
+        len,
+        flow,
+        conditional,
+        taken,
+        branch_target,
+        fallthrough,
+        return_address: (flow == ControlFlow::Call).then_some(next),
+        push_address: pushes.then(|| regs.sp.wrapping_sub(2)),
+        memory_address,
+        registers_written,
+        flags_affected,
+    }
+}
+
+/// Register `idx` in the `r` operand encoding; empty for (HL).
+fn r_reg(idx: u8) -> RegisterSet {
+    match idx {
+        0 => RegisterSet::B,
+        1 => RegisterSet::C,
+        2 => RegisterSet::D,
+        3 => RegisterSet::E,
+        4 => RegisterSet::H,
+        5 => RegisterSet::L,
+        7 => RegisterSet::A,
+        _ => RegisterSet::empty(),
+    }
+}
+
+/// Register pair `idx` in the `rp` encoding; `af` selects the PUSH/POP
+/// variant, where 3 is AF instead of SP.
+fn rp_regs(idx: u8, af: bool) -> RegisterSet {
+    match idx {
+        0 => RegisterSet::B | RegisterSet::C,
+        1 => RegisterSet::D | RegisterSet::E,
+        2 => RegisterSet::H | RegisterSet::L,
+        _ if af => RegisterSet::A,
+        _ => RegisterSet::SP,
+    }
+}
+
+fn base_effects(op: u8) -> (RegisterSet, u8) {
+    const ALL: u8 = 0xF0;
+    let (x, y, z) = (op >> 6, (op >> 3) & 0x07, op & 0x07);
+    let (p, q) = (y >> 1, y & 1);
+    let hl = RegisterSet::H | RegisterSet::L;
+    let sp = RegisterSet::SP;
+    let none = RegisterSet::empty();
+    match (x, z) {
+        (0, 1) if q == 0 => (rp_regs(p, false), 0),
+        (0, 1) => (hl, 0x70),
+        (0, 2) => {
+            let hl_step = if p >= 2 { hl } else { none };
+            let a = if q == 1 { RegisterSet::A } else { none };
+            (hl_step | a, 0)
+        }
+        (0, 3) => (rp_regs(p, false), 0),
+        (0, 4) | (0, 5) => (r_reg(y), 0xE0),
+        (0, 6) => (r_reg(y), 0),
+        (0, 7) => match y {
+            0..=3 => (RegisterSet::A, ALL),
+            4 => (RegisterSet::A, 0xB0),
+            5 => (RegisterSet::A, 0x60),
+            _ => (none, 0x70),
+        },
+        (1, _) if op == 0x76 => (none, 0),
+        (1, _) => (r_reg(y), 0),
+        (2, _) | (3, 6) if y == 7 => (none, ALL),
+        (2, _) | (3, 6) => (RegisterSet::A, ALL),
+        (3, 0) => match y {
+            0..=3 => (sp, 0),
+            5 => (sp, ALL),
+            6 => (RegisterSet::A, 0),
+            7 => (hl, ALL),
+            _ => (none, 0),
+        },
+        (3, 1) if q == 0 => (rp_regs(p, true) | sp, if p == 3 { ALL } else { 0 }),
+        (3, 1) if p == 2 => (none, 0),
+        (3, 1) => (sp, 0),
+        (3, 2) if y == 6 || y == 7 => (RegisterSet::A, 0),
+        (3, 4) if y < 4 => (sp, 0),
+        (3, 5) if q == 0 || p == 0 => (sp, 0),
+        (3, 7) => (sp, 0),
+        _ => (none, 0),
+    }
+}
+
+fn cb_effects(op: u8) -> (RegisterSet, u8) {
+    match op >> 6 {
+        0 => (r_reg(op & 0x07), 0xF0),
+        1 => (RegisterSet::empty(), 0xE0),
+        _ => (r_reg(op & 0x07), 0),
+    }
+}
@@ -0,0 +1,54 @@
+//! Environment-variable tuning knobs and debug trace switches.
+//!
+//! With `std` each knob is read once per process. Without it there is no
+//! environment, so every knob keeps its default and every trace stays off.
+
+use alloc::string::String;
+
+#[cfg(feature = "std")]
+pub(crate) use std::sync::OnceLock;
+
+/// The value of `key`, if it is set and valid Unicode.
+#[cfg(feature = "std")]
+pub(crate) fn var(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn var(_key: &str) -> Option<String> {
+    None
+}
+
+/// The value of `key` if it is set, with invalid Unicode replaced.
+#[cfg(feature = "std")]
+pub(crate) fn var_lossy(key: &str) -> Option<String> {
+    std::env::var_os(key).map(|v| v.to_string_lossy().into_owned())
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn var_lossy(_key: &str) -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) use once::OnceLock;
+
+/// Stand-in for `std::sync::OnceLock`, covering what the knobs use. Threads
+/// racing on the first call may each run the initializer; one result wins.
+#[cfg(not(feature = "std"))]
+mod once {
+    use alloc::boxed::Box;
+    use once_cell::race::OnceBox;
+
+    pub(crate) struct OnceLock<T>(OnceBox<T>);
+
+    impl<T> OnceLock<T> {
+        pub(crate) const fn new() -> Self {
+            Self(OnceBox::new())
+        }
+
+        pub(crate) fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+            self.0.get_or_init(|| Box::new(f()))
+        }
+    }
+}
@@ -1,8 +1,130 @@
+#[cfg(feature = "std")]
+use crate::savestate::SlotLoadError;
 use crate::{
     cpu::Cpu,
-    hardware::{CgbRevision, DmgRevision},
+    hardware::{CgbRevision, DmgRevision, HardwareRevision, Model},
+    input::Buttons,
     mmu::Mmu,
+    savestate::{self, Record, StateError},
+    serial::LinkPort,
 };
+use alloc::{boxed::Box, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{fs, io, path::Path, time::Duration};
+
+/// Dots in one full LCD frame (154 lines of 456 dots).
+const FRAME_DOTS: u64 = 456 * 154;
+
+/// Dot clock rate; one dot per cycle of the 4.19 MHz base oscillator.
+const DOT_CLOCK_HZ: u64 = 4_194_304;
+
+/// How long [`GameBoy::skip_boot_animation`] waits for the boot ROM to hand
+/// off. The official boot ROMs take under three seconds; ones that lock up
+/// on a bad logo never finish.
+const BOOT_HANDOFF_TIMEOUT: u64 = 5 * DOT_CLOCK_HZ;
+
+/// Limit for [`GameBoy::run`].
+///
+/// Every variant is bounded; open-ended stops carry a cycle cap so a ROM that
+/// never reaches them can't hang the caller. Cycles are dot cycles as counted
+/// by [`GameBoy::cycles`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunBudget<'a> {
+    /// Run at least this many cycles.
+    Cycles(u64),
+    /// Run until this many frames have completed.
+    Frames(u32),
+    /// Run until the CPU is about to execute one of `addrs`, or `max_cycles`
+    /// have elapsed.
+    Breakpoint { addrs: &'a [u16], max_cycles: u64 },
+    /// Run until a serial transfer completes, or `max_cycles` have elapsed.
+    SerialOutput { max_cycles: u64 },
+}
+
+/// Why [`GameBoy::run`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The CPU is about to execute a breakpoint address; it has not run yet.
+    Breakpoint(u16),
+    /// The frame budget was used up.
+    FramesElapsed(u32),
+    /// The cycle budget (or cap) was used up; holds the cycles actually run,
+    /// which may overshoot by part of an instruction.
+    CyclesElapsed(u64),
+    /// A serial transfer completed, sending this byte.
+    SerialOutput(u8),
+    /// The CPU can no longer make progress: it jumped to itself with no
+    /// interrupt able to fire, or halted with none enabled. Holds the PC.
+    HaltLoop(u16),
+    /// The CPU executed an undefined opcode and locked up; holds its
+    /// address. See [`Cpu::is_locked`].
+    Locked(u16),
+}
+
+/// Throughput measured by [`GameBoy::bench_run`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    /// Dot cycles emulated, which may overshoot the request by part of an
+    /// instruction.
+    pub cycles: u64,
+    /// Host time the run took.
+    pub wall: std::time::Duration,
+}
+
+#[cfg(feature = "std")]
+impl BenchResult {
+    /// Emulated dot cycles per second of host time.
+    pub fn cycles_per_second(&self) -> f64 {
+        self.cycles as f64 / self.wall.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    /// How many times faster than a real Game Boy the run went.
+    pub fn speed(&self) -> f64 {
+        self.cycles_per_second() / DOT_CLOCK_HZ as f64
+    }
+}
+
+/// Speed/accuracy trade-off chosen with [`GameBoy::set_accuracy`].
+///
+/// CPU, timer, DMA, interrupt and serial emulation is the same at every
+/// level; only how the PPU draws and how the APU mixes changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccuracyLevel {
+    /// Models the PPU pixel FIFO dot by dot and filters audio like the
+    /// hardware's output stage.
+    #[default]
+    CycleAccurate,
+    /// Latches OBJ attributes once per line instead of as each object is
+    /// fetched, and mixes audio once per CPU step.
+    ///
+    /// Mode lengths, STAT and interrupt timing are unchanged, and mid-line
+    /// palette, scroll, window and LCDC writes still land on the right
+    /// pixel. What is given up:
+    /// - OBJ attributes and rows are read from OAM at the end of mode 3,
+    ///   so OAM DMA or writes racing mode 3 show different tiles.
+    /// - Audio samples due within one CPU step share the same mix.
+    ///
+    /// On the `throughput` bench DMG mode runs about 1.5x faster. CGB mode
+    /// does not get faster, since the PPU still draws every dot.
+    ///
+    /// See [`Ppu::set_latch_objects_per_line`](crate::ppu::Ppu::set_latch_objects_per_line)
+    /// and [`Apu::set_fast_mix`](crate::apu::Apu::set_fast_mix).
+    Relaxed,
+}
+
+/// CPU power state reported by [`GameBoy::power_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerState {
+    /// Executing instructions.
+    Running,
+    /// Waiting in HALT for an enabled interrupt to be requested.
+    Halted,
+    /// Stopped by STOP until a joypad press.
+    Stopped,
+    /// Locked up by an undefined opcode until reset; see [`Cpu::is_locked`].
+    Locked,
+}
 
 /// High-level emulator facade representing a single Game Boy / Game Boy Color.
 ///
@@ -19,6 +141,23 @@ pub struct GameBoy {
     pub dmg_revision: DmgRevision,
     /// CGB revision used for revision-specific quirks.
     pub cgb_revision: CgbRevision,
+    /// Set when a revision was switched in place and the machine has not been
+    /// reset since.
+    revision_reset_pending: bool,
+    /// Register preset from [`Self::new_post_boot`], re-applied on reset.
+    post_boot_model: Option<Model>,
+    /// Level from [`Self::set_accuracy`], re-applied on reset and load.
+    accuracy: AccuracyLevel,
+    /// Interval from [`Self::set_autosave_interval`], in dots.
+    #[cfg(feature = "std")]
+    autosave_dots: Option<u64>,
+    /// [`Self::cycles`] when the last autosave period started.
+    #[cfg(feature = "std")]
+    last_autosave: u64,
+    /// State from [`Self::set_restart_point`].
+    restart_point: Option<Vec<u8>>,
+    /// Seed from [`Self::set_timing_fuzz`], re-applied on reset and load.
+    timing_fuzz: Option<u64>,
 }
 
 impl GameBoy {
@@ -51,9 +190,39 @@ impl GameBoy {
             cgb,
             dmg_revision,
             cgb_revision,
+            revision_reset_pending: false,
+            post_boot_model: None,
+            accuracy: AccuracyLevel::default(),
+            #[cfg(feature = "std")]
+            autosave_dots: None,
+            #[cfg(feature = "std")]
+            last_autosave: 0,
+            restart_point: None,
+            timing_fuzz: None,
         }
     }
 
+    /// Creates a machine in the state `model`'s boot ROM leaves behind, for
+    /// running a cartridge without a boot ROM.
+    ///
+    /// CPU registers, SP and PC follow the Pan Docs power-up table; CGB and
+    /// AGB assume a CGB-enhanced cartridge. I/O registers (LCDC, STAT, DIV,
+    /// the APU and so on) get their post-boot values. `revision` picks the
+    /// board revision for its family, and the other family keeps its default.
+    ///
+    /// Unlike [`Self::new_with_revisions`], whose CGB preset matches a DMG
+    /// cartridge in compatibility mode, the preset survives [`Self::reset`].
+    pub fn new_post_boot(model: Model, revision: HardwareRevision) -> Self {
+        let (dmg_revision, cgb_revision) = match revision {
+            HardwareRevision::Dmg(rev) => (rev, CgbRevision::default()),
+            HardwareRevision::Cgb(rev) => (DmgRevision::default(), rev),
+        };
+        let mut gb = Self::new_with_revisions(model.is_cgb(), dmg_revision, cgb_revision);
+        gb.cpu = Cpu::new_post_boot(model, dmg_revision);
+        gb.post_boot_model = Some(model);
+        gb
+    }
+
     /// Creates a machine initialized to an approximate power-on state.
     ///
     /// This is intended for executing a boot ROM. If you are skipping the boot
@@ -69,6 +238,15 @@ impl GameBoy {
             cgb,
             dmg_revision,
             cgb_revision,
+            revision_reset_pending: false,
+            post_boot_model: None,
+            accuracy: AccuracyLevel::default(),
+            #[cfg(feature = "std")]
+            autosave_dots: None,
+            #[cfg(feature = "std")]
+            last_autosave: 0,
+            restart_point: None,
+            timing_fuzz: None,
         }
     }
 
@@ -77,34 +255,948 @@ impl GameBoy {
         Self::new_power_on_with_revisions(cgb, DmgRevision::default(), revision)
     }
 
-    /// Resets to the post-boot state, preserving cartridge and boot ROM.
-    pub fn reset(&mut self) {
-        let cart = self.mmu.cart.take();
-        let boot = self.mmu.boot_rom.take();
-        self.cpu = Cpu::new_with_mode_and_revision(self.cgb, self.dmg_revision);
-        self.mmu = Mmu::new_with_revisions(self.cgb, self.dmg_revision, self.cgb_revision);
-        if let Some(c) = cart {
-            self.mmu.load_cart(c);
+    /// Switches the CGB revision used for revision-specific quirks in place.
+    ///
+    /// The PPU/APU pick up the new revision immediately and keep their current
+    /// state, but state already derived from the old revision (boot registers,
+    /// in-flight glitch timers) is not recomputed. Call [`Self::reset`] or
+    /// [`Self::reset_power_on`] before relying on the result;
+    /// [`Self::revision_reset_pending`] reports whether that is still needed.
+    pub fn set_cgb_revision(&mut self, revision: CgbRevision) {
+        if self.cgb_revision == revision {
+            return;
         }
-        if let Some(b) = boot {
-            self.mmu.load_boot_rom(b);
+        self.cgb_revision = revision;
+        self.mmu.set_revisions(self.dmg_revision, revision);
+        self.revision_reset_pending = true;
+    }
+
+    /// Switches the DMG revision used for revision-specific quirks in place.
+    ///
+    /// See [`Self::set_cgb_revision`] for the reset requirement.
+    pub fn set_dmg_revision(&mut self, revision: DmgRevision) {
+        if self.dmg_revision == revision {
+            return;
+        }
+        self.dmg_revision = revision;
+        self.mmu.set_revisions(revision, self.cgb_revision);
+        self.revision_reset_pending = true;
+    }
+
+    /// Returns true if a revision was switched since the last reset.
+    pub fn revision_reset_pending(&self) -> bool {
+        self.revision_reset_pending
+    }
+
+    /// Emulated dot cycles since power-on.
+    ///
+    /// These tick at a fixed 4.19 MHz regardless of CGB double speed, so they
+    /// measure emulated wall time rather than instructions executed.
+    pub fn cycles(&self) -> u64 {
+        self.cpu.cycles
+    }
+
+    /// Emulated time since power-on in nanoseconds, derived from
+    /// [`Self::cycles`]. Unaffected by how fast the host runs the machine.
+    pub fn elapsed_ns(&self) -> u64 {
+        (self.cpu.cycles as u128 * 1_000_000_000 / DOT_CLOCK_HZ as u128) as u64
+    }
+
+    /// The byte the boot ROM hands over in A for this machine, which games
+    /// and sound drivers check to detect the model: 0x01 on DMG, 0x11 on CGB
+    /// and AGB (the AGB is told apart by bit 0 of B).
+    ///
+    /// Reflects the emulated hardware, not the live register, so it stays
+    /// valid after the game has overwritten A.
+    pub fn detected_model_byte(&self) -> u8 {
+        Cpu::boot_model_byte(self.cgb)
+    }
+
+    /// Whether KEY1 bit 0 is set, so the next STOP switches between normal
+    /// and double speed instead of stopping the CPU. Always `false` outside
+    /// CGB mode, where KEY1 cannot be written.
+    pub fn speed_switch_armed(&self) -> bool {
+        self.mmu.key1 & 0x01 != 0
+    }
+
+    /// Whether the CPU is running, halted, stopped or locked up.
+    ///
+    /// While halted or stopped a frontend can throttle host work; see
+    /// [`Self::cycles_until_next_interrupt`] for how long nothing can change.
+    pub fn power_state(&self) -> PowerState {
+        if self.cpu.is_locked() {
+            PowerState::Locked
+        } else if self.cpu.stopped {
+            PowerState::Stopped
+        } else if self.cpu.halted {
+            PowerState::Halted
+        } else {
+            PowerState::Running
+        }
+    }
+
+    /// Lower bound, in dot cycles (see [`Self::cycles`]), on when the next
+    /// enabled interrupt can be requested: VBlank, STAT, timer overflow or an
+    /// internally clocked serial transfer, assuming no register writes in the
+    /// meantime. `Some(0)` if one is already pending.
+    ///
+    /// `None` if only untimed sources (joypad, externally clocked serial) are
+    /// enabled, so only host input can wake the CPU. The same applies while
+    /// stopped, since STOP halts the timer and LCD.
+    pub fn cycles_until_next_interrupt(&self) -> Option<u64> {
+        let mmu = &self.mmu;
+        let enabled = mmu.ie_reg & 0x1F;
+        if mmu.if_reg & enabled != 0 {
+            return Some(0);
+        }
+        if self.cpu.stopped {
+            return None;
+        }
+        let double_speed = self.cpu.double_speed;
+        let mut next: Option<u32> = None;
+        let mut consider = |dots: Option<u32>| {
+            if let Some(dots) = dots {
+                next = Some(next.map_or(dots, |n| n.min(dots)));
+            }
+        };
+        if enabled & 0x01 != 0 {
+            consider(mmu.ppu.dots_until_vblank());
+        }
+        if enabled & 0x02 != 0 {
+            consider(mmu.ppu.dots_until_mode_change());
+        }
+        if enabled & 0x04 != 0 {
+            // The timer runs at the CPU clock, twice the dot rate in double
+            // speed.
+            consider(
+                mmu.timer
+                    .cycles_until_overflow()
+                    .map(|c| if double_speed { c / 2 } else { c }),
+            );
+        }
+        if enabled & 0x08 != 0 {
+            consider(mmu.serial.dots_until_transfer_done(double_speed));
+        }
+        next.map(u64::from)
+    }
+
+    /// Cycles until the PPU next enters VBlank, for frontends that size a
+    /// [`RunBudget::Cycles`] run to end on a frame boundary.
+    ///
+    /// Counted in the same units as [`Self::cycles`], which tick at the dot
+    /// rate in both speed modes, so double speed needs no adjustment. With the
+    /// LCD off there is no VBlank to wait for, so this returns one frame's
+    /// worth of cycles.
+    pub fn cycles_until_vblank(&self) -> u32 {
+        self.mmu
+            .ppu
+            .dots_until_vblank()
+            .unwrap_or(FRAME_DOTS as u32)
+    }
+
+    /// Executes instructions until the PPU moves on to a different scanline.
+    ///
+    /// The CPU and PPU only advance together, so this stops after the
+    /// instruction during which LY changed; the PPU may already be a few dots
+    /// into the new line. Returns `false` if LY did not change within two
+    /// frames (e.g. the LCD is off).
+    pub fn step_to_next_scanline(&mut self) -> bool {
+        let start_ly = self.mmu.ppu.ly();
+        self.step_until(|gb| gb.mmu.ppu.ly() != start_ly)
+    }
+
+    /// Executes instructions until LY next becomes `target`.
+    ///
+    /// If LY already equals `target`, this runs until the line comes around
+    /// again on the following frame. Like [`Self::step_to_next_scanline`], it
+    /// stops on an instruction boundary. Returns `false` if `target` was not
+    /// reached within two frames.
+    pub fn step_to_ly(&mut self, target: u8) -> bool {
+        let mut left_target = self.mmu.ppu.ly() != target;
+        self.step_until(|gb| {
+            let ly = gb.mmu.ppu.ly();
+            if ly != target {
+                left_target = true;
+                false
+            } else {
+                left_target
+            }
+        })
+    }
+
+    /// Composes only every `(n + 1)`th frame while keeping timing exact, so a
+    /// slow host can keep audio smooth by drawing fewer frames. See
+    /// [`Ppu::set_frame_skip`](crate::ppu::Ppu::set_frame_skip).
+    ///
+    /// [`Self::run_frame`] still returns once per frame; check
+    /// [`Ppu::frame_rendered`](crate::ppu::Ppu::frame_rendered) before
+    /// presenting the framebuffer.
+    pub fn set_frame_skip(&mut self, n: u8) {
+        self.mmu.ppu.set_frame_skip(n);
+    }
+
+    /// Trades emulation accuracy for speed; see [`AccuracyLevel`] for what
+    /// each level gives up. The level persists across [`Self::reset`] and
+    /// [`Self::load_state`], and can be changed at any point.
+    pub fn set_accuracy(&mut self, level: AccuracyLevel) {
+        self.accuracy = level;
+        let relaxed = level == AccuracyLevel::Relaxed;
+        self.mmu.ppu.set_latch_objects_per_line(relaxed);
+        self.mmu.apu.set_fast_mix(relaxed);
+    }
+
+    /// Returns the level set with [`Self::set_accuracy`].
+    pub fn accuracy(&self) -> AccuracyLevel {
+        self.accuracy
+    }
+
+    /// Perturbs interrupt timing to expose emulation paths that only work
+    /// with one exact timing. For testing the emulator, not for play.
+    ///
+    /// With `Some(seed)`, every interrupt dispatch is held back by either
+    /// zero or one M-cycle (4 dots, 2 in double speed) before its 5-cycle
+    /// entry sequence, chosen by a PRNG seeded from `seed`. The delay never
+    /// exceeds one M-cycle per dispatch, in line with how late an interrupt
+    /// can be noticed relative to an instruction boundary; nothing else
+    /// changes. Runs with the same seed are identical, and `None`, the
+    /// default, restores the exact timing.
+    ///
+    /// Comparing framebuffer hashes across several seeds points at code
+    /// that depends on the emulator's interrupt timing being cycle-exact.
+    /// The seed is re-applied on [`Self::reset`] and [`Self::load_state`]
+    /// and is not saved in save states.
+    pub fn set_timing_fuzz(&mut self, seed: Option<u64>) {
+        self.timing_fuzz = seed;
+        self.cpu.set_timing_fuzz(seed);
+    }
+
+    /// Returns the seed set with [`Self::set_timing_fuzz`].
+    pub fn timing_fuzz(&self) -> Option<u64> {
+        self.timing_fuzz
+    }
+
+    /// Executes instructions until the PPU completes a frame.
+    ///
+    /// Returns `false` if no frame completed within two frames' worth of
+    /// cycles (e.g. the LCD is off).
+    pub fn run_frame(&mut self) -> bool {
+        core_span!(INFO, "frame", frame = self.mmu.ppu.frame_count());
+        self.mmu.ppu.clear_frame_flag();
+        let completed = self.step_until(|gb| gb.mmu.ppu.frame_ready());
+        #[cfg(feature = "std")]
+        self.autosave_if_due();
+        completed
+    }
+
+    /// Holds `buttons` and runs one frame, as with [`Self::run_frame`].
+    pub fn run_frame_with_buttons(&mut self, buttons: Buttons) -> bool {
+        let mmu = &mut self.mmu;
+        mmu.input.set_buttons(buttons, &mut mmu.if_reg);
+        self.run_frame()
+    }
+
+    /// Runs one frame, as with [`Self::run_frame`], and returns the finished
+    /// framebuffer together with the audio generated along the way, for
+    /// frontends that drive video and audio from a single callback.
+    ///
+    /// Sample capture (see
+    /// [`Apu::set_sample_capture`](crate::apu::Apu::set_sample_capture)) is
+    /// on for the frame and back as it was afterwards. Leave it on to have
+    /// the next call also return samples from frames run by other means, so
+    /// the audio stream has no gaps.
+    pub fn run_frame_av(&mut self) -> (&[u32], Vec<(i16, i16)>) {
+        let capturing = self.mmu.apu.sample_capture();
+        self.mmu.apu.set_sample_capture(true);
+        self.run_frame();
+        let samples = self.mmu.apu.take_frame_samples();
+        self.mmu.apu.set_sample_capture(capturing);
+        (self.mmu.ppu.framebuffer(), samples)
+    }
+
+    /// Runs one frame and writes its output into caller-owned buffers: the
+    /// framebuffer is copied into `video` and the frame's audio is appended
+    /// to `audio`. Everything happens on the calling thread, so a browser
+    /// frontend can call this once per `requestAnimationFrame`.
+    ///
+    /// Sample capture works as in [`Self::run_frame_av`]. Returns `false` if
+    /// the frame overran, as with [`Self::run_frame`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `video` is not exactly 160 × 144 pixels long.
+    pub fn step_frame_into(&mut self, video: &mut [u32], audio: &mut Vec<(i16, i16)>) -> bool {
+        let capturing = self.mmu.apu.sample_capture();
+        self.mmu.apu.set_sample_capture(true);
+        let completed = self.run_frame();
+        video.copy_from_slice(self.mmu.ppu.framebuffer());
+        self.mmu.apu.drain_frame_samples_into(audio);
+        self.mmu.apu.set_sample_capture(capturing);
+        completed
+    }
+
+    /// Returns the frame the PPU is drawing right now, for showing exactly
+    /// where a paused machine stopped: lines already drawn this frame and
+    /// blank lines below them. See
+    /// [`Ppu::partial_frame`](crate::ppu::Ppu::partial_frame).
+    ///
+    /// Nothing is rendered ahead; the machine and its framebuffer are left
+    /// untouched.
+    pub fn render_current_frame_so_far(&self) -> Vec<u32> {
+        self.mmu.ppu.partial_frame()
+    }
+
+    /// Flushes battery-backed cartridge RAM to its `.sav` every `interval`
+    /// of emulated time while it is dirty (see
+    /// [`Cartridge::ram_dirty`](crate::cartridge::Cartridge::ram_dirty)), so
+    /// a crash loses at most one interval of progress. `None`, the default,
+    /// leaves saving to explicit [`Mmu::save_cart_ram`] calls.
+    ///
+    /// The check runs once per frame in [`Self::run_frame`] and
+    /// [`Self::run`]; frontends stepping the CPU themselves call
+    /// [`Self::autosave_if_due`] after each frame. Being emulated time,
+    /// fast-forward saves more often per real second and a paused machine
+    /// never saves. Not saved in save states.
+    #[cfg(feature = "std")]
+    pub fn set_autosave_interval(&mut self, interval: Option<Duration>) {
+        self.autosave_dots = interval.map(|interval| {
+            let dots = interval.as_nanos() * u128::from(DOT_CLOCK_HZ) / 1_000_000_000;
+            u64::try_from(dots).unwrap_or(u64::MAX).max(1)
+        });
+        self.last_autosave = self.cpu.cycles;
+    }
+
+    /// The interval set by [`Self::set_autosave_interval`], rounded to whole
+    /// dots.
+    #[cfg(feature = "std")]
+    pub fn autosave_interval(&self) -> Option<Duration> {
+        self.autosave_dots.map(|dots| {
+            let nanos = u128::from(dots) * 1_000_000_000 / u128::from(DOT_CLOCK_HZ);
+            Duration::from_nanos(nanos as u64)
+        })
+    }
+
+    /// Saves cartridge RAM if an autosave interval is set, at least that
+    /// much emulated time has passed since the last check that started a
+    /// new period, and the RAM is dirty. Returns whether it was written.
+    ///
+    /// A period that ends with clean RAM starts the next one without
+    /// touching the file. Rewinding [`Self::cycles`] through a reset or a
+    /// loaded state restarts the period.
+    #[cfg(feature = "std")]
+    pub fn autosave_if_due(&mut self) -> bool {
+        let Some(interval) = self.autosave_dots else {
+            return false;
+        };
+        let now = self.cpu.cycles;
+        if now < self.last_autosave {
+            self.last_autosave = now;
         }
+        if now - self.last_autosave < interval {
+            return false;
+        }
+        self.last_autosave = now;
+        if !self.mmu.cart.as_ref().is_some_and(|c| c.ram_dirty()) {
+            return false;
+        }
+        self.mmu.save_cart_ram();
+        true
+    }
+
+    /// Writes everything that must outlive the session and unplugs the link
+    /// port peripheral, for a frontend to call once before exiting.
+    ///
+    /// Battery-backed cartridge RAM and the RTC go to their save files as
+    /// with [`Cartridge::save_ram`](crate::cartridge::Cartridge::save_ram),
+    /// and the peripheral is dropped so it can close its connection or
+    /// thread. Unlike [`Mmu::save_cart_ram`](crate::mmu::Mmu::save_cart_ram)
+    /// a failed write is returned rather than logged. Calling it again is
+    /// harmless: the saves are rewritten and nothing is left to unplug.
+    #[cfg(feature = "std")]
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        drop(self.detach_serial());
+        match &mut self.mmu.cart {
+            Some(cart) => cart.save_ram(),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs until `n` more frames have completed, e.g. to get a ROM past its
+    /// boot logo to a stable point before taking a screenshot.
+    ///
+    /// Returns `false` if any single frame took longer than two frames'
+    /// worth of cycles, as with [`Self::run_frame`].
+    pub fn run_to_vblank_count(&mut self, n: u32) -> bool {
+        (0..n).all(|_| self.run_frame())
+    }
+
+    /// Runs until `marker` appears in serial output sent from now on, or
+    /// `max_cycles` dot cycles pass. Returns whether the marker was seen.
+    ///
+    /// Stops after the instruction that completed the marker's last byte.
+    pub fn run_until_serial(&mut self, marker: &[u8], max_cycles: u64) -> bool {
+        if marker.is_empty() {
+            return true;
+        }
+        let start = self.mmu.serial.peek_output().len();
+        let mut searched = start;
+        let deadline = self.cpu.cycles.saturating_add(max_cycles);
+        while self.cpu.cycles < deadline {
+            self.cpu.step(&mut self.mmu);
+            let out = self.mmu.serial.peek_output();
+            if out.len() > searched {
+                let from = searched.saturating_sub(marker.len() - 1).max(start);
+                if out[from..].windows(marker.len()).any(|w| w == marker) {
+                    return true;
+                }
+                searched = out.len();
+            }
+        }
+        false
+    }
+
+    /// Runs the mapped boot ROM until it writes 0xFF50 to unmap itself, so
+    /// the game starts with the state the boot ROM really leaves behind
+    /// (palettes, registers, VRAM) without showing its animation live.
+    ///
+    /// Stops right after the write, with PC at the game's entry point.
+    /// Returns `false` if the boot ROM had not handed off after five
+    /// emulated seconds, e.g. because it locked up on a logo mismatch;
+    /// `true` straight away if no boot ROM is mapped.
+    pub fn skip_boot_animation(&mut self) -> bool {
+        let deadline = self.cpu.cycles + BOOT_HANDOFF_TIMEOUT;
+        while self.mmu.boot_mapped {
+            if self.cpu.cycles >= deadline {
+                return false;
+            }
+            self.cpu.step(&mut self.mmu);
+        }
+        true
+    }
+
+    /// Queues `byte` to be received by the next serial transfer, for
+    /// homebrew that reads commands over the link port. See
+    /// [`Serial::inject_byte`](crate::serial::Serial::inject_byte).
+    pub fn inject_serial_byte(&mut self, byte: u8) {
+        self.mmu.serial.inject_byte(byte);
+    }
+
+    /// Plugs `port` into the link port in place of whatever was attached. It
+    /// stays attached across [`Self::load_state`].
+    pub fn attach_serial(&mut self, port: Box<dyn LinkPort + Send>) {
+        self.mmu.serial.connect(port);
+    }
+
+    /// Unplugs the link port peripheral, leaving nothing attached, and
+    /// returns it.
+    ///
+    /// A transfer in progress is not aborted: bits the old peripheral had
+    /// not yet supplied shift in as 1s, as with a pulled cable, and the
+    /// serial interrupt fires as usual. An externally clocked transfer waits
+    /// for a clock that no longer comes, until the game gives up on it.
+    pub fn detach_serial(&mut self) -> Box<dyn LinkPort + Send> {
+        self.mmu.serial.disconnect()
+    }
+
+    /// Rolls the machine back to `from_state` and replays one frame per
+    /// entry of `inputs`, each a [`Buttons`] bit set.
+    ///
+    /// This is the engine half of rollback netplay: given the same state and
+    /// inputs, the machine ends up in the same place every time. The
+    /// cartridge RTC follows the host clock and is the one exception.
+    pub fn resimulate(&mut self, from_state: &[u8], inputs: &[u8]) -> Result<(), StateError> {
+        self.load_state(from_state)?;
+        for &input in inputs {
+            self.run_frame_with_buttons(Buttons::from_bits(input));
+        }
+        Ok(())
+    }
+
+    /// Runs until `budget` is used up or the machine stops somewhere
+    /// interesting, and reports which.
+    ///
+    /// A breakpoint is checked before each instruction, so running again from
+    /// a [`RunOutcome::Breakpoint`] stops immediately; step the CPU once
+    /// first. Halt loops are detected under every budget.
+    pub fn run(&mut self, budget: RunBudget<'_>) -> RunOutcome {
+        self.run_with_hook(budget, |_| false)
+    }
+
+    /// Like [`Self::run`], calling `before_step` before each instruction.
+    /// Returning `true` from it stops there, before the instruction runs,
+    /// with [`RunOutcome::Breakpoint`].
+    ///
+    /// This is for breakpoints keyed on more than the address, such as the
+    /// ROM bank, and for host work that has to keep pace with the CPU, such
+    /// as clocking a link cable.
+    pub fn run_with_hook(
+        &mut self,
+        budget: RunBudget<'_>,
+        mut before_step: impl FnMut(&mut Self) -> bool,
+    ) -> RunOutcome {
+        let start = self.cpu.cycles;
+        let max_cycles = match budget {
+            RunBudget::Cycles(cycles) => cycles,
+            RunBudget::Frames(_) => u64::MAX,
+            RunBudget::Breakpoint { max_cycles, .. } => max_cycles,
+            RunBudget::SerialOutput { max_cycles } => max_cycles,
+        };
+        let mut frames = 0u32;
+        let serial_len = self.mmu.serial.peek_output().len();
+        self.mmu.ppu.clear_frame_flag();
+
+        loop {
+            let pc = self.cpu.pc;
+            match budget {
+                RunBudget::Frames(target) if frames >= target => {
+                    return RunOutcome::FramesElapsed(frames);
+                }
+                RunBudget::Breakpoint { addrs, .. } if addrs.contains(&pc) => {
+                    return RunOutcome::Breakpoint(pc);
+                }
+                _ => {}
+            }
+            let elapsed = self.cpu.cycles - start;
+            if elapsed >= max_cycles {
+                return RunOutcome::CyclesElapsed(elapsed);
+            }
+            if before_step(self) {
+                return RunOutcome::Breakpoint(self.cpu.pc);
+            }
+
+            let retired = self.cpu.instructions_retired();
+            self.cpu.step(&mut self.mmu);
+
+            if let Some(addr) = self.cpu.locked_at() {
+                return RunOutcome::Locked(addr);
+            }
+            let jumped_to_self = self.cpu.instructions_retired() != retired && self.cpu.pc == pc;
+            if self.stuck(jumped_to_self) {
+                return RunOutcome::HaltLoop(self.cpu.pc);
+            }
+            if self.mmu.ppu.frame_ready() {
+                self.mmu.ppu.clear_frame_flag();
+                frames += 1;
+                #[cfg(feature = "std")]
+                self.autosave_if_due();
+            }
+            if let RunBudget::SerialOutput { .. } = budget
+                && let Some(&byte) = self.mmu.serial.peek_output().get(serial_len)
+            {
+                return RunOutcome::SerialOutput(byte);
+            }
+        }
+    }
+
+    /// Steps the machine for at least `cycles` dot cycles as fast as the host
+    /// allows and reports how long that took, for tracking emulation speed.
+    ///
+    /// This is the bare CPU loop, with the PPU, APU, timer and DMA clocked as
+    /// usual: no frame pacing, no breakpoint or halt-loop checks, and no
+    /// autosave. It allocates nothing itself. Frames are still rendered into
+    /// the framebuffer, and audio goes wherever the APU is set up to send it;
+    /// leave sample capture off to keep allocation out of the measurement.
+    #[cfg(feature = "std")]
+    pub fn bench_run(&mut self, cycles: u64) -> BenchResult {
+        let start_cycles = self.cpu.cycles;
+        let target = start_cycles.saturating_add(cycles);
+        let start = std::time::Instant::now();
+        while self.cpu.cycles < target {
+            self.cpu.step(&mut self.mmu);
+        }
+        BenchResult {
+            cycles: self.cpu.cycles - start_cycles,
+            wall: start.elapsed(),
+        }
+    }
+
+    /// Runs the next instruction, running through the whole subroutine if it
+    /// is a CALL or RST, like a debugger's step over. Returns `false` if
+    /// that takes more than `max_cycles` dot cycles or the CPU gets stuck.
+    ///
+    /// A CALL is run to its return address with the stack back at its
+    /// current level, so recursive calls to the same subroutine do not stop
+    /// early. An interrupt dispatched after the instruction has its handler
+    /// run up to its return as well. Stepping a halted CPU waits for it to
+    /// wake up. Breakpoints set elsewhere are not checked.
+    pub fn step_over(&mut self, max_cycles: u64) -> bool {
+        let deadline = self.cpu.cycles.saturating_add(max_cycles);
+        let preview = self.cpu.preview_next(&self.mmu);
+        let halted = self.cpu.halted;
+        if !halted && let Some(ret) = preview.return_address {
+            return self.run_to_return(ret, self.cpu.sp, deadline);
+        }
+
+        let next = if halted {
+            [Some(self.cpu.pc), None]
+        } else {
+            [preview.branch_target, preview.fallthrough]
+        };
+        let retired = self.cpu.instructions_retired();
+        loop {
+            if self.cpu.cycles >= deadline {
+                return false;
+            }
+            self.cpu.step(&mut self.mmu);
+            let done = if halted {
+                !self.cpu.halted
+            } else {
+                self.cpu.instructions_retired() != retired
+            };
+            if done {
+                break;
+            }
+        }
+        if next.contains(&Some(self.cpu.pc)) {
+            return true;
+        }
+        // An interrupt was dispatched right after the instruction, pushing
+        // the address the instruction would have continued at.
+        let ret = u16::from_le_bytes([
+            self.mmu.peek_byte(self.cpu.sp),
+            self.mmu.peek_byte(self.cpu.sp.wrapping_add(1)),
+        ]);
+        self.run_to_return(ret, self.cpu.sp.wrapping_add(2), deadline)
+    }
+
+    /// Runs until the current subroutine returns to its caller, i.e. until a
+    /// RET or RETI lifts SP above its current level, like a debugger's step
+    /// out. Returns `false` if that takes more than `max_cycles` dot cycles.
+    ///
+    /// Interrupt handlers that run meanwhile return below that level and
+    /// are run through.
+    pub fn step_out(&mut self, max_cycles: u64) -> bool {
+        let deadline = self.cpu.cycles.saturating_add(max_cycles);
+        let entry_sp = self.cpu.sp;
+        while self.cpu.cycles < deadline {
+            let returning = !self.cpu.halted
+                && matches!(
+                    self.mmu.peek_byte(self.cpu.pc),
+                    0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9
+                );
+            self.cpu.step(&mut self.mmu);
+            if returning && self.cpu.sp > entry_sp {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Runs until PC reaches `addr` with SP at or above `sp`.
+    fn run_to_return(&mut self, addr: u16, sp: u16, deadline: u64) -> bool {
+        while self.cpu.cycles < deadline {
+            let budget = RunBudget::Breakpoint {
+                addrs: &[addr],
+                max_cycles: deadline - self.cpu.cycles,
+            };
+            match self.run(budget) {
+                RunOutcome::Breakpoint(_) if self.cpu.sp >= sp => return true,
+                // A deeper call to the same subroutine returning.
+                RunOutcome::Breakpoint(_) => self.cpu.step(&mut self.mmu),
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// True if the CPU can't move on without a reset: halted with no
+    /// interrupt enabled, or spinning on a jump to itself with no interrupt
+    /// able to pull it out.
+    fn stuck(&self, jumped_to_self: bool) -> bool {
+        let enabled = self.mmu.ie_reg & 0x1F;
+        if self.cpu.halted {
+            enabled == 0
+        } else {
+            jumped_to_self && (!self.cpu.ime || enabled == 0)
+        }
+    }
+
+    fn step_until(&mut self, mut done: impl FnMut(&Self) -> bool) -> bool {
+        let deadline = self.cpu.cycles + 2 * FRAME_DOTS;
+        while self.cpu.cycles < deadline {
+            self.cpu.step(&mut self.mmu);
+            if done(self) {
+                return true;
+            }
+        }
+        false
     }
 
-    /// Resets to the power-on state, preserving cartridge and boot ROM.
+    /// Human-readable dump of the CPU, interrupt, LCD, timer and APU registers,
+    /// the current banks and the instruction at PC, for bug reports.
+    ///
+    /// Reads are side-effect free. The layout is one labelled line per unit
+    /// and is kept stable so dumps can be diffed.
+    pub fn dump_state_text(&self) -> String {
+        use core::fmt::Write;
+
+        let cpu = &self.cpu;
+        let mmu = &self.mmu;
+        let flag = |mask: u8, c: char| if cpu.f & mask != 0 { c } else { '-' };
+        let mut out = String::with_capacity(640);
+
+        let _ = writeln!(
+            out,
+            "CPU   AF={:02X}{:02X} BC={:02X}{:02X} DE={:02X}{:02X} HL={:02X}{:02X} SP={:04X} PC={:04X}",
+            cpu.a, cpu.f, cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l, cpu.sp, cpu.pc
+        );
+        let _ = writeln!(
+            out,
+            "      F={}{}{}{} IME={} HALT={} STOP={} SPEED={} CYCLES={}",
+            flag(0x80, 'Z'),
+            flag(0x40, 'N'),
+            flag(0x20, 'H'),
+            flag(0x10, 'C'),
+            cpu.ime as u8,
+            cpu.halted as u8,
+            cpu.stopped as u8,
+            if cpu.double_speed { 2 } else { 1 },
+            cpu.cycles
+        );
+        let _ = writeln!(out, "INT   IE={:02X} IF={:02X}", mmu.ie_reg, mmu.if_reg);
+
+        let lcd = |addr: u16| mmu.ppu.peek_reg(addr);
+        let _ = writeln!(
+            out,
+            "LCD   LCDC={:02X} STAT={:02X} LY={:02X} LYC={:02X} SCY={:02X} SCX={:02X} WY={:02X} WX={:02X} BGP={:02X} OBP0={:02X} OBP1={:02X} MODE={} FRAME={}",
+            lcd(0xFF40),
+            lcd(0xFF41),
+            lcd(0xFF44),
+            lcd(0xFF45),
+            lcd(0xFF42),
+            lcd(0xFF43),
+            lcd(0xFF4A),
+            lcd(0xFF4B),
+            lcd(0xFF47),
+            lcd(0xFF48),
+            lcd(0xFF49),
+            mmu.ppu.mode(),
+            mmu.ppu.frame_count()
+        );
+        let _ = writeln!(
+            out,
+            "TIMER DIV={:02X} TIMA={:02X} TMA={:02X} TAC={:02X}",
+            mmu.timer.read(0xFF04),
+            mmu.timer.read(0xFF05),
+            mmu.timer.read(0xFF06),
+            mmu.timer.read(0xFF07)
+        );
+
+        const APU_REGS: [(&str, u16); 21] = [
+            ("NR10", 0xFF10),
+            ("NR11", 0xFF11),
+            ("NR12", 0xFF12),
+            ("NR13", 0xFF13),
+            ("NR14", 0xFF14),
+            ("NR21", 0xFF16),
+            ("NR22", 0xFF17),
+            ("NR23", 0xFF18),
+            ("NR24", 0xFF19),
+            ("NR30", 0xFF1A),
+            ("NR31", 0xFF1B),
+            ("NR32", 0xFF1C),
+            ("NR33", 0xFF1D),
+            ("NR34", 0xFF1E),
+            ("NR41", 0xFF20),
+            ("NR42", 0xFF21),
+            ("NR43", 0xFF22),
+            ("NR44", 0xFF23),
+            ("NR50", 0xFF24),
+            ("NR51", 0xFF25),
+            ("NR52", 0xFF26),
+        ];
+        out.push_str("APU  ");
+        for (name, addr) in APU_REGS {
+            let _ = write!(out, " {name}={:02X}", mmu.apu.peek_reg(addr));
+        }
+        out.push('\n');
+
+        let (rom_bank, ram_bank) = mmu
+            .cart
+            .as_ref()
+            .map(|c| (c.current_rom_bank(), c.current_ram_bank()))
+            .unwrap_or((0, 0));
+        let _ = writeln!(
+            out,
+            "BANK  ROM={:03X} SRAM={:02X} WRAM={} VRAM={} BOOT={}",
+            rom_bank, ram_bank, mmu.wram_bank, mmu.ppu.vram_bank, mmu.boot_mapped as u8
+        );
+
+        let bytes: [u8; 3] = core::array::from_fn(|i| mmu.peek_byte(cpu.pc.wrapping_add(i as u16)));
+        let (mnemonic, len, _) = crate::disasm::decode_sm83(&bytes, cpu.pc);
+        let _ = write!(out, "PC    {:04X}:", cpu.pc);
+        for b in &bytes[..len.clamp(1, 3) as usize] {
+            let _ = write!(out, " {b:02X}");
+        }
+        let _ = writeln!(out, "  {mnemonic}");
+        out
+    }
+
+    /// Serializes the machine into a versioned save state.
+    ///
+    /// The cartridge ROM and boot ROM are not included; the state can only be
+    /// loaded back with the same cartridge inserted.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut machine = Record::new();
+        machine.put("cgb", &self.cgb);
+        machine.put("dmg_revision", &self.dmg_revision);
+        machine.put("cgb_revision", &self.cgb_revision);
+
+        let mut root = Record::new();
+        root.put_record("machine", &machine);
+        root.put_record("cpu", &self.cpu.save_state());
+        root.put_record("mmu", &self.mmu.save_state());
+        savestate::encode_state(&root)
+    }
+
+    /// Restores a save state produced by [`Self::save_state`], migrating it
+    /// from older format versions if needed.
+    ///
+    /// Fields missing from older states keep their post-boot values. On error
+    /// the machine is left unchanged.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let root = savestate::decode_state(data)?;
+        let machine = root
+            .record("machine")?
+            .ok_or_else(|| StateError::MissingField("machine".into()))?;
+        let cgb = machine.require("cgb").map_err(|e| e.in_field("machine"))?;
+        let dmg_revision = machine
+            .require("dmg_revision")
+            .map_err(|e| e.in_field("machine"))?;
+        let cgb_revision = machine
+            .require("cgb_revision")
+            .map_err(|e| e.in_field("machine"))?;
+        let cpu = root
+            .record("cpu")?
+            .ok_or_else(|| StateError::MissingField("cpu".into()))?;
+        let mmu = root
+            .record("mmu")?
+            .ok_or_else(|| StateError::MissingField("mmu".into()))?;
+
+        // Boxed: the machine is large enough to matter on small thread stacks.
+        let mut next = Box::new(Self::new_with_revisions(cgb, dmg_revision, cgb_revision));
+        next.mmu.cart = self.mmu.cart.take();
+        let result = next
+            .cpu
+            .load_state(&cpu)
+            .map_err(|e| e.in_field("cpu"))
+            .and_then(|()| next.mmu.load_state(&mmu).map_err(|e| e.in_field("mmu")));
+        if let Err(e) = result {
+            self.mmu.cart = next.mmu.cart.take();
+            return Err(e);
+        }
+
+        next.mmu.adopt_host_state(&mut self.mmu);
+        next.set_accuracy(self.accuracy);
+        next.post_boot_model = self.post_boot_model;
+        #[cfg(feature = "std")]
+        {
+            next.autosave_dots = self.autosave_dots;
+            next.last_autosave = self.last_autosave;
+        }
+        next.restart_point = self.restart_point.take();
+        next.set_timing_fuzz(self.timing_fuzz);
+        *self = *next;
+        Ok(())
+    }
+
+    /// Marks the current state as the point [`Self::restart`] returns to,
+    /// replacing any earlier one. Call between frames, as with
+    /// [`Self::save_state`].
+    ///
+    /// The restart point is kept in memory only. It survives [`Self::reset`]
+    /// and loading states, so practice runs can be repeated from it.
+    pub fn set_restart_point(&mut self) {
+        self.restart_point = Some(self.save_state());
+    }
+
+    /// Forgets the restart point.
+    pub fn clear_restart_point(&mut self) {
+        self.restart_point = None;
+    }
+
+    pub fn has_restart_point(&self) -> bool {
+        self.restart_point.is_some()
+    }
+
+    /// Loads the state saved by [`Self::set_restart_point`]. Returns
+    /// `Ok(false)` without touching the machine if none is set.
+    ///
+    /// The point is kept, so it can be restarted from again. One that no
+    /// longer matches the inserted cartridge is discarded and
+    /// [`StateError::CartridgeMismatch`] is returned.
+    pub fn restart(&mut self) -> Result<bool, StateError> {
+        let Some(state) = self.restart_point.take() else {
+            return Ok(false);
+        };
+        let result = self.load_state(&state);
+        match result {
+            Err(StateError::CartridgeMismatch) => {}
+            _ => self.restart_point = Some(state),
+        }
+        result.map(|()| true)
+    }
+
+    /// Writes [`Self::save_state`] to quick-save slot `slot` of the ROM at
+    /// `rom` (see [`savestate::slot_path`]). Call between frames.
+    #[cfg(feature = "std")]
+    pub fn save_state_slot(&self, rom: &Path, slot: u8) -> io::Result<()> {
+        fs::write(savestate::slot_path(rom, slot), self.save_state())
+    }
+
+    /// Loads quick-save slot `slot` of the ROM at `rom`. The machine is left
+    /// untouched if the slot is empty or can't be loaded.
+    #[cfg(feature = "std")]
+    pub fn load_state_slot(&mut self, rom: &Path, slot: u8) -> Result<(), SlotLoadError> {
+        let data = match fs::read(savestate::slot_path(rom, slot)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(SlotLoadError::Empty(slot));
+            }
+            Err(e) => return Err(SlotLoadError::Io(e)),
+        };
+        self.load_state(&data).map_err(SlotLoadError::State)
+    }
+
+    /// Resets to the post-boot state, preserving cartridge, boot ROM and the
+    /// PPU and APU settings a frontend applied (palette, frame skip, ...).
+    pub fn reset(&mut self) {
+        self.cpu = match self.post_boot_model {
+            Some(model) => Cpu::new_post_boot(model, self.dmg_revision),
+            None => Cpu::new_with_mode_and_revision(self.cgb, self.dmg_revision),
+        };
+        let mmu = Mmu::new_with_revisions(self.cgb, self.dmg_revision, self.cgb_revision);
+        self.reset_mmu(mmu);
+    }
+
+    /// Resets to the power-on state, preserving cartridge, boot ROM and host
+    /// settings as [`Self::reset`] does.
     ///
     /// This is useful when you want to re-run the boot ROM sequence.
     pub fn reset_power_on(&mut self) {
-        let cart = self.mmu.cart.take();
-        let boot = self.mmu.boot_rom.take();
         self.cpu = Cpu::new_power_on_with_revision(self.cgb, self.dmg_revision);
-        self.mmu = Mmu::new_power_on_with_revisions(self.cgb, self.dmg_revision, self.cgb_revision);
-        if let Some(c) = cart {
+        let mmu = Mmu::new_power_on_reusing(
+            self.cgb,
+            self.dmg_revision,
+            self.cgb_revision,
+            self.mmu.power_on_wram.take(),
+        );
+        self.reset_mmu(mmu);
+    }
+
+    /// Swaps in the freshly reset `mmu`, moving the cartridge, boot ROM and
+    /// host settings over from the old one.
+    fn reset_mmu(&mut self, mmu: Mmu) {
+        let mut old = core::mem::replace(&mut self.mmu, mmu);
+        self.mmu.adopt_host_settings(&mut old);
+        self.revision_reset_pending = false;
+        if let Some(c) = old.cart.take() {
             self.mmu.load_cart(c);
         }
-        if let Some(b) = boot {
+        if let Some(b) = old.boot_rom.take() {
             self.mmu.load_boot_rom(b);
         }
+        self.set_accuracy(self.accuracy);
+        self.set_timing_fuzz(self.timing_fuzz);
     }
 }
 
@@ -1,3 +1,6 @@
+use alloc::vec::Vec;
+use core::fmt;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 /// DMG hardware revision.
 ///
@@ -26,7 +29,65 @@ pub enum CgbRevision {
     RevE,
 }
 
+impl DmgRevision {
+    /// All DMG revisions, oldest first.
+    pub const ALL: [DmgRevision; 4] = [
+        DmgRevision::Rev0,
+        DmgRevision::RevA,
+        DmgRevision::RevB,
+        DmgRevision::RevC,
+    ];
+
+    /// Returns every DMG revision, oldest first.
+    pub fn all() -> &'static [DmgRevision] {
+        &Self::ALL
+    }
+
+    /// Returns the conventional board name, e.g. `"DMG-C"`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            DmgRevision::Rev0 => "DMG-0",
+            DmgRevision::RevA => "DMG-A",
+            DmgRevision::RevB => "DMG-B",
+            DmgRevision::RevC => "DMG-C",
+        }
+    }
+}
+
+impl fmt::Display for DmgRevision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 impl CgbRevision {
+    /// All CGB revisions, oldest first.
+    pub const ALL: [CgbRevision; 6] = [
+        CgbRevision::Rev0,
+        CgbRevision::RevA,
+        CgbRevision::RevB,
+        CgbRevision::RevC,
+        CgbRevision::RevD,
+        CgbRevision::RevE,
+    ];
+
+    /// Returns every CGB revision, oldest first.
+    pub fn all() -> &'static [CgbRevision] {
+        &Self::ALL
+    }
+
+    /// Returns the conventional board name, e.g. `"CGB-E"`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            CgbRevision::Rev0 => "CGB-0",
+            CgbRevision::RevA => "CGB-A",
+            CgbRevision::RevB => "CGB-B",
+            CgbRevision::RevC => "CGB-C",
+            CgbRevision::RevD => "CGB-D",
+            CgbRevision::RevE => "CGB-E",
+        }
+    }
+
     #[inline]
     /// Returns whether this revision supports the DE window behavior.
     pub const fn supports_de_window(self) -> bool {
@@ -42,3 +103,78 @@ impl CgbRevision {
         )
     }
 }
+
+impl fmt::Display for CgbRevision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A DMG or CGB board revision, for UIs that pick one revision per mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HardwareRevision {
+    Dmg(DmgRevision),
+    Cgb(CgbRevision),
+}
+
+impl HardwareRevision {
+    /// Returns the revision to emulate when the user has not picked one.
+    ///
+    /// This is the most common retail board for the mode: DMG-C or CGB-E.
+    pub fn default_for_mode(cgb: bool) -> Self {
+        if cgb {
+            HardwareRevision::Cgb(CgbRevision::default())
+        } else {
+            HardwareRevision::Dmg(DmgRevision::default())
+        }
+    }
+
+    /// Returns every revision selectable in the given mode, oldest first.
+    pub fn all_for_mode(cgb: bool) -> Vec<Self> {
+        if cgb {
+            CgbRevision::ALL
+                .iter()
+                .copied()
+                .map(HardwareRevision::Cgb)
+                .collect()
+        } else {
+            DmgRevision::ALL
+                .iter()
+                .copied()
+                .map(HardwareRevision::Dmg)
+                .collect()
+        }
+    }
+
+    /// Returns the conventional board name, e.g. `"CGB-E"`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            HardwareRevision::Dmg(rev) => rev.name(),
+            HardwareRevision::Cgb(rev) => rev.name(),
+        }
+    }
+}
+
+impl fmt::Display for HardwareRevision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Console model, for choosing a post-boot register preset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Model {
+    #[default]
+    Dmg,
+    Cgb,
+    /// Game Boy Advance running a CGB cartridge. Emulated as a CGB apart from
+    /// the registers its boot ROM leaves behind.
+    Agb,
+}
+
+impl Model {
+    /// Returns whether the model runs in CGB mode.
+    pub const fn is_cgb(self) -> bool {
+        matches!(self, Model::Cgb | Model::Agb)
+    }
+}
@@ -1,6 +1,65 @@
+use core::ops::{BitOr, BitOrAssign};
+
+use crate::savestate::state_record;
+
+/// Set of pressed buttons. Unlike the raw joypad state, a set bit means the
+/// button is held.
+///
+/// The low nibble holds the D-pad and the high nibble the action buttons,
+/// matching the order in which P1 reports them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Buttons(u8);
+
+impl Buttons {
+    pub const RIGHT: Self = Self(0x01);
+    pub const LEFT: Self = Self(0x02);
+    pub const UP: Self = Self(0x04);
+    pub const DOWN: Self = Self(0x08);
+    pub const A: Self = Self(0x10);
+    pub const B: Self = Self(0x20);
+    pub const SELECT: Self = Self(0x40);
+    pub const START: Self = Self(0x80);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Buttons {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Buttons {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
 pub struct Input {
     p1: u8,
     state: u8,
+    /// Buttons whose press raised the most recent joypad interrupt.
+    last_cause: Option<Buttons>,
 }
 
 impl Input {
@@ -8,23 +67,21 @@ impl Input {
         Self {
             p1: 0xCF,
             state: 0xFF,
+            last_cause: None,
         }
     }
 
     pub fn read(&self) -> u8 {
-        let mut res = self.p1 & 0xF0;
-        if self.p1 & 0x10 == 0 {
-            res |= self.state & 0x0F;
-        } else if self.p1 & 0x20 == 0 {
-            res |= (self.state >> 4) & 0x0F;
-        } else {
-            res |= 0x0F;
-        }
-        res
+        (self.p1 & 0xF0) | self.lines()
     }
 
-    pub fn write(&mut self, val: u8) {
+    /// Writes the P1 select bits. Selecting a group with a button already
+    /// held pulls its line low, which requests the joypad interrupt just
+    /// like a press does.
+    pub fn write(&mut self, val: u8, if_reg: &mut u8) {
+        let old_lines = self.lines();
         self.p1 = (self.p1 & 0xCF) | (val & 0x30);
+        self.raise_on_falling_lines(old_lines, if_reg);
     }
 
     pub fn set_state(&mut self, state: u8) {
@@ -32,14 +89,62 @@ impl Input {
     }
 
     /// Update the input state and set the joypad interrupt flag if any
-    /// button transitioned from released to pressed.
+    /// P10-P13 input line went from high to low.
+    ///
+    /// A line only follows the buttons of the groups selected in P1, so a
+    /// press in a group the game isn't listening to raises nothing.
     pub fn update_state(&mut self, state: u8, if_reg: &mut u8) {
-        // Bits are active-low: 0 = pressed
-        let newly_pressed = self.state & !state;
-        if newly_pressed != 0 {
-            *if_reg |= 0x10; // Joypad interrupt
-        }
+        let old_lines = self.lines();
         self.state = state;
+        self.raise_on_falling_lines(old_lines, if_reg);
+    }
+
+    /// Like [`Self::update_state`], but takes the set of held buttons
+    /// instead of an active-low mask.
+    pub fn set_buttons(&mut self, buttons: Buttons, if_reg: &mut u8) {
+        self.update_state(!buttons.bits(), if_reg);
+    }
+
+    /// Buttons currently held.
+    pub fn buttons(&self) -> Buttons {
+        Buttons(!self.state)
+    }
+
+    /// The held buttons whose lines went low to raise the most recent joypad
+    /// interrupt, or `None` if none has been raised yet. Debug aid only: the
+    /// game can't see which button it was.
+    pub fn last_interrupt_cause(&self) -> Option<Buttons> {
+        self.last_cause
+    }
+
+    /// P10-P13, low nibble, active-low. With both groups selected each line
+    /// is low if either of its buttons is held.
+    fn lines(&self) -> u8 {
+        let mut lines = 0x0F;
+        if self.p1 & 0x10 == 0 {
+            lines &= self.state;
+        }
+        if self.p1 & 0x20 == 0 {
+            lines &= self.state >> 4;
+        }
+        lines
+    }
+
+    fn raise_on_falling_lines(&mut self, old_lines: u8, if_reg: &mut u8) {
+        let falling = old_lines & !self.lines();
+        if falling == 0 {
+            return;
+        }
+        *if_reg |= 0x10; // Joypad interrupt
+        let held = !self.state;
+        let mut cause = 0;
+        if self.p1 & 0x10 == 0 {
+            cause |= held & falling;
+        }
+        if self.p1 & 0x20 == 0 {
+            cause |= held & (falling << 4);
+        }
+        self.last_cause = Some(Buttons(cause));
     }
 }
 
@@ -48,3 +153,5 @@ impl Default for Input {
         Self::new()
     }
 }
+
+state_record!(Input { p1, state });
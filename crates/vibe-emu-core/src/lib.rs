@@ -4,35 +4,85 @@
 //! Frontends (desktop UI, mobile) live in separate crates and drive the core via
 //! the [`gameboy`] facade.
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(non_snake_case)]
 #![allow(dead_code)]
 
+extern crate alloc;
+
+/// Log sink shared with frontends and companion crates.
+#[cfg(feature = "std")]
 pub mod diagnostics;
 
+/// Without `std` the environment-gated debug traces can never be switched on,
+/// so their messages are only type-checked.
+#[cfg(not(feature = "std"))]
+#[allow(unused_macros)]
+macro_rules! eprintln {
+	($($arg:tt)*) => {{
+		let _ = format_args!($($arg)*);
+	}};
+}
+
 #[allow(unused_macros)]
 macro_rules! core_trace {
 	(target: $target:expr, $($arg:tt)*) => {{
+		#[cfg(feature = "std")]
 		if crate::diagnostics::has_log_sink() {
 			crate::diagnostics::emit(crate::diagnostics::Level::Trace, $target, format_args!($($arg)*));
 		}
+		#[cfg(not(feature = "std"))]
+		let _ = format_args!($($arg)*);
+		#[cfg(feature = "tracing")]
+		tracing::trace!(target: $target, $($arg)*);
 	}};
 }
 
 #[allow(unused_macros)]
 macro_rules! core_info {
 	(target: $target:expr, $($arg:tt)*) => {{
+		#[cfg(feature = "std")]
 		if crate::diagnostics::has_log_sink() {
 			crate::diagnostics::emit(crate::diagnostics::Level::Info, $target, format_args!($($arg)*));
 		}
+		#[cfg(not(feature = "std"))]
+		let _ = format_args!($($arg)*);
+		#[cfg(feature = "tracing")]
+		tracing::info!(target: $target, $($arg)*);
 	}};
 }
 
 #[allow(unused_macros)]
 macro_rules! core_warn {
 	(target: $target:expr, $($arg:tt)*) => {{
+		#[cfg(feature = "std")]
 		if crate::diagnostics::has_log_sink() {
 			crate::diagnostics::emit(crate::diagnostics::Level::Warn, $target, format_args!($($arg)*));
 		}
+		#[cfg(not(feature = "std"))]
+		let _ = format_args!($($arg)*);
+		#[cfg(feature = "tracing")]
+		tracing::warn!(target: $target, $($arg)*);
+	}};
+}
+
+/// Enters a `tracing` span at `$level` until the end of the enclosing scope.
+/// Expands to nothing without the `tracing` feature.
+#[allow(unused_macros)]
+macro_rules! core_span {
+	($level:ident, $name:expr $(, $($field:tt)*)?) => {
+		#[cfg(feature = "tracing")]
+		let _span = tracing::span!(tracing::Level::$level, $name $(, $($field)*)?).entered();
+	};
+}
+
+/// Emits a structured `tracing` event at `$level`. Expands to nothing without
+/// the `tracing` feature.
+#[allow(unused_macros)]
+macro_rules! core_event {
+	($level:ident, $($arg:tt)*) => {{
+		#[cfg(feature = "tracing")]
+		tracing::event!(tracing::Level::$level, $($arg)*);
 	}};
 }
 
@@ -42,12 +92,31 @@ pub mod apu;
 /// Lock-free-ish audio ring buffer used by the APU.
 pub mod audio_queue;
 
+mod env;
+
+mod math;
+
+mod sha1;
+
 /// Cartridge mappers (MBC) and ROM/RAM/RTC handling.
 pub mod cartridge;
 
+/// Colour palettes for DMG cartridges on CGB hardware.
+pub mod colorization;
+
+/// RAM search for finding cheat addresses.
+pub mod cheats;
+
+/// C-compatible embedding interface.
+#[cfg(feature = "capi")]
+pub mod capi;
+
 /// LR35902 CPU core.
 pub mod cpu;
 
+/// SM83 instruction decoder used by debugger views and state dumps.
+pub mod disasm;
+
 /// High-level facade that wires the CPU and MMU into a single machine.
 pub mod gameboy;
 
@@ -66,8 +135,17 @@ pub mod watchpoints;
 /// Pixel Processing Unit (PPU) emulation.
 pub mod ppu;
 
+/// Versioned save-state format and migrations.
+pub mod savestate;
+
 /// Serial unit and link cable plumbing.
 pub mod serial;
 
+/// State snapshots for tools reading the machine from another thread.
+pub mod snapshot;
+
 /// Divider/timer unit.
 pub mod timer;
+
+/// Instruction traces and divergence checks against reference emulators.
+pub mod trace;
@@ -0,0 +1,104 @@
+//! Float helpers that `core` lacks.
+//!
+//! With `std` these forward to the standard library. Without it they use the
+//! portable versions below, which agree closely enough for the filter and
+//! blending coefficients they compute but are not bit-identical.
+
+/// Rounds half away from zero, like `f32::round`.
+#[cfg(feature = "std")]
+pub(crate) fn round(x: f32) -> f32 {
+    x.round()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn round(x: f32) -> f32 {
+    portable_round(x)
+}
+
+/// `base` raised to `exp`, like `f32::powf`. `base` must be positive.
+#[cfg(feature = "std")]
+pub(crate) fn powf(base: f32, exp: f32) -> f32 {
+    base.powf(exp)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn powf(base: f32, exp: f32) -> f32 {
+    portable_powf(base, exp)
+}
+
+fn portable_round(x: f32) -> f32 {
+    // Every f32 of at least 2^23 is already a whole number; NaN and the
+    // infinities pass through too.
+    if x.is_nan() || x.abs() >= 8_388_608.0 {
+        return x;
+    }
+    let whole = x as i32 as f32;
+    if (x - whole).abs() >= 0.5 {
+        whole + 1.0f32.copysign(x)
+    } else {
+        whole
+    }
+}
+
+fn portable_powf(base: f32, exp: f32) -> f32 {
+    portable_exp(f64::from(exp) * portable_ln(f64::from(base))) as f32
+}
+
+/// Natural log of a positive, finite `x`.
+fn portable_ln(x: f64) -> f64 {
+    // x = m * 2^e with m in [1, 2), then ln m = 2 atanh((m - 1) / (m + 1)),
+    // whose series converges quickly since the argument is at most 1/3.
+    let bits = x.to_bits();
+    let e = ((bits >> 52) & 0x7FF) as i64 - 1023;
+    let m = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+    let z = (m - 1.0) / (m + 1.0);
+    let z2 = z * z;
+    let mut term = z;
+    let mut sum = 0.0;
+    for k in 0..30 {
+        sum += term / (2 * k + 1) as f64;
+        term *= z2;
+    }
+    2.0 * sum + e as f64 * core::f64::consts::LN_2
+}
+
+/// e^y for `y` in roughly -700..700.
+fn portable_exp(y: f64) -> f64 {
+    // e^y = 2^k * e^r with |r| <= ln 2 / 2.
+    let k = (y / core::f64::consts::LN_2 + 0.5f64.copysign(y)) as i64;
+    let r = y - k as f64 * core::f64::consts::LN_2;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for n in 1..20 {
+        term *= r / n as f64;
+        sum += term;
+    }
+    sum * f64::from_bits(((k + 1023) as u64) << 52)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portable_round_matches_std() {
+        for x in [
+            0.0f32, 0.4, 0.5, 0.49999997, 1.5, 2.5, -0.5, -1.5, -2.4, 1234.5, -32768.6, 1e9,
+        ] {
+            assert_eq!(portable_round(x), x.round(), "{x}");
+        }
+    }
+
+    #[test]
+    fn portable_powf_matches_std() {
+        for rate in [8_000.0f32, 22_050.0, 44_100.0, 48_000.0, 96_000.0] {
+            let exp = 4_194_304.0 / rate;
+            let portable = portable_powf(0.999_958, exp);
+            let std = 0.999_958_f32.powf(exp);
+            assert!(
+                (portable - std).abs() <= 1e-6,
+                "{rate}: {portable} vs {std}"
+            );
+        }
+    }
+}
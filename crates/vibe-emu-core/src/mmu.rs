@@ -1,52 +1,87 @@
 use crate::{
     apu::Apu,
     cartridge::Cartridge,
+    colorization,
     hardware::{CgbRevision, DmgRevision},
     input::Input,
     ppu::Ppu,
+    savestate::{Record, StateError, state_enum, state_fields},
     serial::Serial,
     timer::Timer,
 };
+use alloc::{boxed::Box, format, string::ToString, vec, vec::Vec};
+use core::ops::RangeInclusive;
 
 use crate::ppu::OamBugAccess;
+#[cfg(feature = "std")]
+use std::io;
 
 fn env_flag_enabled(var: &str) -> bool {
-    use std::sync::OnceLock;
+    use crate::env::OnceLock;
 
-    static CACHE: OnceLock<std::collections::HashMap<&'static str, bool>> = OnceLock::new();
     // Cache a small fixed set to avoid repeated env parsing.
+    const KEYS: [&str; 3] = [
+        "VIBEEMU_TRACE_OAMBUG",
+        "VIBEEMU_TRACE_LCDC",
+        "VIBEEMU_DMG_MODE3_LCDC_DELAY",
+    ];
+    static CACHE: OnceLock<[bool; KEYS.len()]> = OnceLock::new();
     let cache = CACHE.get_or_init(|| {
-        let mut map = std::collections::HashMap::new();
-        for key in [
-            "VIBEEMU_TRACE_OAMBUG",
-            "VIBEEMU_TRACE_LCDC",
-            "VIBEEMU_DMG_MODE3_LCDC_DELAY",
-        ] {
-            let enabled = std::env::var_os(key)
-                .map(|v| {
-                    let s = v.to_string_lossy();
-                    !(s.is_empty() || s == "0" || s.eq_ignore_ascii_case("false"))
-                })
-                .unwrap_or(false);
-            map.insert(key, enabled);
-        }
-        map
+        KEYS.map(|key| {
+            crate::env::var_lossy(key)
+                .is_some_and(|s| !(s.is_empty() || s == "0" || s.eq_ignore_ascii_case("false")))
+        })
     });
-    cache.get(var).copied().unwrap_or(false)
+    KEYS.iter()
+        .position(|&key| key == var)
+        .is_some_and(|i| cache[i])
 }
 
 fn dmg_mode3_lcdc_delay_dots() -> u8 {
-    use std::sync::OnceLock;
+    use crate::env::OnceLock;
     static DELAY: OnceLock<u8> = OnceLock::new();
     *DELAY.get_or_init(|| {
-        std::env::var("VIBEEMU_DMG_MODE3_LCDC_DELAY")
-            .ok()
+        crate::env::var("VIBEEMU_DMG_MODE3_LCDC_DELAY")
             .and_then(|v| v.trim().parse::<i16>().ok())
             .map(|v| v.clamp(0, 8) as u8)
             .unwrap_or(1)
     })
 }
 
+/// Bits of the IO register at `addr` ($FF00-$FF7F) that always read as 1:
+/// unused bits, and every bit of unmapped or write-only registers. The APU
+/// registers ($FF10-$FF3F) apply their own mask.
+///
+/// CGB-only registers are unmapped on DMG.
+fn io_read_mask(addr: u16, cgb: bool) -> u8 {
+    let cgb_only = |mask: u8| if cgb { mask } else { 0xFF };
+    match addr {
+        0xFF00 => 0xC0,                    // P1
+        0xFF01 => 0x00,                    // SB
+        0xFF02 => cgb_only(0x7C) & 0x7E,   // SC: bit 1 is CGB-only
+        0xFF04..=0xFF06 => 0x00,           // DIV, TIMA, TMA
+        0xFF07 => 0xF8,                    // TAC
+        0xFF0F => 0xE0,                    // IF
+        0xFF10..=0xFF3F => 0x00,           // APU
+        0xFF40 => 0x00,                    // LCDC
+        0xFF41 => 0x80,                    // STAT
+        0xFF42..=0xFF4B => 0x00,           // SCY-WX, including DMA
+        0xFF4D => cgb_only(0x7E),          // KEY1
+        0xFF4F => cgb_only(0xFE),          // VBK
+        0xFF55 => cgb_only(0x00),          // HDMA5
+        0xFF56 => cgb_only(0x3C),          // RP
+        0xFF68 | 0xFF6A => cgb_only(0x40), // BCPS, OCPS
+        0xFF69 | 0xFF6B => cgb_only(0x00), // BCPD, OCPD
+        0xFF6C => cgb_only(0xFE),          // OPRI
+        0xFF70 => cgb_only(0xF8),          // SVBK
+        0xFF72..=0xFF74 => cgb_only(0x00),
+        0xFF75 => cgb_only(0x8F),
+        0xFF76 | 0xFF77 => cgb_only(0x00), // PCM12, PCM34
+        // Unmapped, KEY0, BANK and HDMA1-HDMA4.
+        _ => 0xFF,
+    }
+}
+
 const WRAM_BANK_SIZE: usize = 0x1000;
 
 fn power_on_wram_seed(cgb: bool, dmg_revision: DmgRevision, cgb_revision: CgbRevision) -> u32 {
@@ -61,26 +96,62 @@ fn power_on_wram_seed(cgb: bool, dmg_revision: DmgRevision, cgb_revision: CgbRev
     if seed == 0 { 0xA5A5_5A5A } else { seed }
 }
 
-fn init_power_on_wram(seed: u32) -> [[u8; WRAM_BANK_SIZE]; 8] {
-    let mut wram = [[0u8; WRAM_BANK_SIZE]; 8];
-    let mut state = seed;
-
-    for bank_wram in &mut wram {
-        for byte in bank_wram.iter_mut() {
-            // xorshift32
-            state ^= state << 13;
-            state ^= state >> 17;
-            state ^= state << 5;
-            let mut v = state as u8;
-            // Ensure we don't accidentally end up with all $00/$FF.
-            if v == 0x00 || v == 0xFF {
-                v ^= 0xA5;
+/// The power-on WRAM contents for one seed. Generating them is far slower
+/// than copying, so the MMU keeps them for later power-on resets.
+pub(crate) struct PowerOnWram {
+    seed: u32,
+    banks: Box<[[u8; WRAM_BANK_SIZE]; 8]>,
+}
+
+impl PowerOnWram {
+    fn generate(seed: u32) -> Self {
+        let mut banks: Box<[[u8; WRAM_BANK_SIZE]; 8]> = vec![[0u8; WRAM_BANK_SIZE]; 8]
+            .into_boxed_slice()
+            .try_into()
+            .unwrap_or_else(|_| unreachable!());
+        let mut state = seed;
+
+        for bank_wram in banks.iter_mut() {
+            for byte in bank_wram.iter_mut() {
+                // xorshift32
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let mut v = state as u8;
+                // Ensure we don't accidentally end up with all $00/$FF.
+                if v == 0x00 || v == 0xFF {
+                    v ^= 0xA5;
+                }
+                *byte = v;
             }
-            *byte = v;
         }
+
+        Self { seed, banks }
     }
+}
 
-    wram
+/// Per-address CPU access counters collected while profiling is enabled.
+struct AccessProfile {
+    reads: Box<[u32]>,
+    writes: Box<[u32]>,
+    /// Reads + writes, kept alongside so the heatmap can be borrowed directly.
+    total: Box<[u32]>,
+}
+
+impl AccessProfile {
+    fn new() -> Self {
+        Self {
+            reads: vec![0; 0x10000].into_boxed_slice(),
+            writes: vec![0; 0x10000].into_boxed_slice(),
+            total: vec![0; 0x10000].into_boxed_slice(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.reads.fill(0);
+        self.writes.fill(0);
+        self.total.fill(0);
+    }
 }
 
 /// Transfer mode for CGB DMA operations.
@@ -148,6 +219,11 @@ pub struct Mmu {
     /// operation. This is set by the `Cpu` helpers before calling into the
     /// MMU so logs can attribute blocked accesses to the originating PC.
     pub last_cpu_pc: Option<u16>,
+    /// [`Cpu::cycles`](crate::cpu::Cpu::cycles) at the start of the CPU's
+    /// last FF46 write, set by the `Cpu` helpers just before the write.
+    pub(crate) oam_dma_write_cycle: u64,
+    /// Source and cycle of the last FF46 write; see [`Mmu::last_oam_dma`].
+    last_oam_dma: Option<(u16, u64)>,
 
     /// Last value driven on the CPU data bus by a CPU-visible read/write.
     /// Used to model open-bus behaviour for certain invalid/unused accesses.
@@ -168,6 +244,13 @@ pub struct Mmu {
     pub(crate) oam_bug_next_access: Option<OamBugAccess>,
 
     pub watchpoints: crate::watchpoints::WatchpointEngine,
+
+    /// CPU access counters; `None` unless profiling was enabled.
+    profile: Option<Box<AccessProfile>>,
+
+    /// Pattern WRAM was filled with at power-on, reused by the next
+    /// power-on reset; `None` for post-boot MMUs.
+    pub(crate) power_on_wram: Option<PowerOnWram>,
 }
 
 impl Mmu {
@@ -175,6 +258,24 @@ impl Mmu {
         self.cgb_mode
     }
 
+    pub fn cgb_revision(&self) -> CgbRevision {
+        self.cgb_revision
+    }
+
+    pub fn dmg_revision(&self) -> DmgRevision {
+        self.dmg_revision
+    }
+
+    /// Switches the hardware revisions used by the PPU/APU/serial quirk paths
+    /// in place, without rebuilding any device state.
+    pub fn set_revisions(&mut self, dmg_revision: DmgRevision, cgb_revision: CgbRevision) {
+        self.dmg_revision = dmg_revision;
+        self.cgb_revision = cgb_revision;
+        self.ppu.set_revisions(dmg_revision, cgb_revision);
+        self.apu.set_revisions(dmg_revision, cgb_revision);
+        self.serial.set_dmg_revision(dmg_revision);
+    }
+
     pub fn new_with_mode(cgb: bool) -> Self {
         Self::new_with_revisions(cgb, DmgRevision::default(), CgbRevision::default())
     }
@@ -257,9 +358,13 @@ impl Mmu {
             dmg_revision,
             oam_bug_next_access: None,
             last_cpu_pc: None,
+            oam_dma_write_cycle: 0,
+            last_oam_dma: None,
             data_bus: 0xFF,
             main_bus: 0xFF,
             watchpoints: crate::watchpoints::WatchpointEngine::default(),
+            profile: None,
+            power_on_wram: None,
         }
     }
 
@@ -272,6 +377,17 @@ impl Mmu {
         cgb: bool,
         dmg_revision: DmgRevision,
         cgb_revision: CgbRevision,
+    ) -> Self {
+        Self::new_power_on_reusing(cgb, dmg_revision, cgb_revision, None)
+    }
+
+    /// [`Self::new_power_on_with_revisions`], copying WRAM from `pattern`
+    /// when it was generated for the same seed instead of generating it.
+    pub(crate) fn new_power_on_reusing(
+        cgb: bool,
+        dmg_revision: DmgRevision,
+        cgb_revision: CgbRevision,
+        pattern: Option<PowerOnWram>,
     ) -> Self {
         let mut timer = Timer::new();
 
@@ -294,10 +410,13 @@ impl Mmu {
 
         let ppu = Ppu::new_with_revisions(cgb, dmg_revision, cgb_revision);
 
-        let wram = init_power_on_wram(power_on_wram_seed(cgb, dmg_revision, cgb_revision));
+        let seed = power_on_wram_seed(cgb, dmg_revision, cgb_revision);
+        let pattern = pattern
+            .filter(|p| p.seed == seed)
+            .unwrap_or_else(|| PowerOnWram::generate(seed));
 
-        Self {
-            wram,
+        let mut mmu = Self {
+            wram: [[0; WRAM_BANK_SIZE]; 8],
             wram_bank: 1,
             hram: [0; 0x7F],
             cart: None,
@@ -336,10 +455,17 @@ impl Mmu {
             dmg_revision,
             oam_bug_next_access: None,
             last_cpu_pc: None,
+            oam_dma_write_cycle: 0,
+            last_oam_dma: None,
             data_bus: 0xFF,
             main_bus: 0xFF,
             watchpoints: crate::watchpoints::WatchpointEngine::default(),
-        }
+            profile: None,
+            power_on_wram: None,
+        };
+        mmu.wram.copy_from_slice(&pattern.banks[..]);
+        mmu.power_on_wram = Some(pattern);
+        mmu
     }
 
     fn updates_main_bus(addr: u16) -> bool {
@@ -351,17 +477,24 @@ impl Mmu {
     }
 
     pub fn load_cart(&mut self, cart: Cartridge) {
+        if let Some(mismatch) = cart.check_mode(self.cgb_mode) {
+            core_warn!(target: "vibe_emu_core::cartridge", "{}: {mismatch}", cart.title);
+        }
         let is_dmg = !cart.cgb;
         if self.post_boot_state && is_dmg {
             let logo = cart.rom.get(0x0104..0x0134).unwrap_or(&[]);
             self.ppu.apply_dmg_post_boot_vram(logo);
         }
-        self.cart = Some(cart);
         if self.cgb_mode && is_dmg {
+            // The boot ROM colours DMG games by header title.
             self.ppu.apply_dmg_compatibility_palettes();
+            let pal = colorization::lookup(&cart.rom);
+            self.ppu.apply_dmg_colorization(pal.bg, pal.obj0, pal.obj1);
         }
+        self.cart = Some(cart);
     }
 
+    #[cfg(feature = "std")]
     pub fn save_cart_ram(&mut self) {
         if let Some(cart) = &mut self.cart
             && let Err(e) = cart.save_ram()
@@ -370,6 +503,30 @@ impl Mmu {
         }
     }
 
+    /// [`Cartridge::save_ram_slot`] for the inserted cartridge.
+    #[cfg(feature = "std")]
+    pub fn save_cart_ram_slot(&self, slot: u8) -> io::Result<()> {
+        match &self.cart {
+            Some(cart) => cart.save_ram_slot(slot),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no cartridge inserted",
+            )),
+        }
+    }
+
+    /// [`Cartridge::load_ram_slot`] for the inserted cartridge.
+    #[cfg(feature = "std")]
+    pub fn load_cart_ram_slot(&mut self, slot: u8) -> io::Result<()> {
+        match &mut self.cart {
+            Some(cart) => cart.load_ram_slot(slot),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no cartridge inserted",
+            )),
+        }
+    }
+
     pub fn load_boot_rom(&mut self, data: Vec<u8>) {
         self.boot_rom = Some(data);
         self.boot_mapped = true;
@@ -498,7 +655,7 @@ impl Mmu {
                     }
                     val
                 } else {
-                    if !self.cgb_mode {
+                    if self.ppu.oam_bug_enabled() {
                         let access = self
                             .oam_bug_next_access
                             .take()
@@ -533,7 +690,7 @@ impl Mmu {
                 }
             }
             0xFEA0..=0xFEFF => {
-                if !self.cgb_mode && !self.ppu.oam_read_accessible() {
+                if self.ppu.oam_bug_enabled() && !self.ppu.oam_read_accessible() {
                     let access = self
                         .oam_bug_next_access
                         .take()
@@ -544,42 +701,24 @@ impl Mmu {
                 }
                 0xFF
             }
+            0xFF00..=0xFF7F => self.read_io(addr) | io_read_mask(addr, self.cgb_mode),
+            0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize],
+            0xFFFF => self.ie_reg,
+        }
+    }
+
+    /// Reads an IO register in $FF00-$FF7F. Bits that always read as 1 are
+    /// added by [`io_read_mask`], not here.
+    fn read_io(&mut self, addr: u16) -> u8 {
+        match addr {
             0xFF00 => self.input.read(),
             0xFF01 | 0xFF02 => self.serial.read(addr),
             0xFF04..=0xFF07 => self.timer.read(addr),
-            // IF: upper 3 bits are unused and read back as 1 on hardware.
-            0xFF0F => self.if_reg | 0xE0,
+            0xFF0F => self.if_reg,
             0xFF10..=0xFF3F => self.apu.read_reg(addr),
-            0xFF40..=0xFF45 | 0xFF47..=0xFF4B | 0xFF68..=0xFF6B => self.ppu.read_reg(addr),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B | 0xFF68..=0xFF6C => self.ppu.read_reg(addr),
             0xFF46 => self.ppu.dma,
-            0xFF51 => {
-                if self.cgb_mode {
-                    (self.hdma.src >> 8) as u8
-                } else {
-                    0xFF
-                }
-            }
-            0xFF52 => {
-                if self.cgb_mode {
-                    (self.hdma.src & 0x00F0) as u8
-                } else {
-                    0xFF
-                }
-            }
-            0xFF53 => {
-                if self.cgb_mode {
-                    ((self.hdma.dst & 0x1F00) >> 8) as u8
-                } else {
-                    0xFF
-                }
-            }
-            0xFF54 => {
-                if self.cgb_mode {
-                    (self.hdma.dst & 0x00F0) as u8
-                } else {
-                    0xFF
-                }
-            }
+            // HDMA1-HDMA4 ($FF51-$FF54) are write-only and read as $FF.
             0xFF55 => {
                 if !self.cgb_mode {
                     0xFF
@@ -596,14 +735,16 @@ impl Mmu {
             }
             0xFF4D => {
                 if self.cgb_mode {
-                    (self.key1 & 0x81) | 0x7E
+                    self.key1 & 0x81
                 } else {
                     0xFF
                 }
             }
             0xFF56 => {
                 if self.cgb_mode {
-                    self.rp | 0xC0
+                    // Bit 1 reads 1 while no IR signal is received; there
+                    // is no IR peer, so it always does.
+                    (self.rp & 0xC1) | 0x02
                 } else {
                     0xFF
                 }
@@ -637,17 +778,16 @@ impl Mmu {
                 }
             }
             0xFF74 => {
-                if self.cgb_mode {
+                if self.cgb_mode && !self.ppu.is_cgb_dmg_compat_mode() {
                     self.undoc_ff74
                 } else {
-                    // DMG: read-only, locked to $FF.
+                    // DMG and CGB compatibility mode: read-only, locked to $FF.
                     0xFF
                 }
             }
             0xFF75 => {
                 if self.cgb_mode {
-                    // Only bits 4-6 are readable/writable; other bits read high.
-                    (self.undoc_ff75 & 0x70) | 0x8F
+                    self.undoc_ff75 & 0x70
                 } else {
                     0xFF
                 }
@@ -659,8 +799,6 @@ impl Mmu {
                     0xFF
                 }
             }
-            0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize],
-            0xFFFF => self.ie_reg,
             _ => 0xFF,
         }
     }
@@ -672,9 +810,146 @@ impl Mmu {
             self.main_bus = value;
         }
         self.watchpoints.note_read(self.last_cpu_pc, addr, value);
+        if let Some(profile) = self.profile.as_deref_mut() {
+            let i = addr as usize;
+            profile.reads[i] = profile.reads[i].saturating_add(1);
+            profile.total[i] = profile.total[i].saturating_add(1);
+        }
         value
     }
 
+    /// Reads memory without side effects, ignoring PPU/DMA access blocking.
+    ///
+    /// I/O registers read as 0xFF; HRAM and IE are returned as-is.
+    pub fn peek_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x00FF if self.boot_mapped => self
+                .boot_rom
+                .as_ref()
+                .and_then(|b| b.get(addr as usize).copied())
+                .unwrap_or(0xFF),
+            0x0200..=0x08FF if self.boot_mapped && self.cgb_mode => self
+                .boot_rom
+                .as_ref()
+                .and_then(|b| b.get(addr as usize).copied())
+                .unwrap_or(0xFF),
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => {
+                self.cart.as_ref().map(|c| c.peek(addr)).unwrap_or(0xFF)
+            }
+            0x8000..=0x9FFF => self.ppu.vram[self.ppu.vram_bank][(addr - 0x8000) as usize],
+            0xC000..=0xCFFF => self.wram[0][(addr - 0xC000) as usize],
+            0xD000..=0xDFFF => self.wram[self.wram_bank][(addr - 0xD000) as usize],
+            0xE000..=0xEFFF => self.wram[0][(addr - 0xE000) as usize],
+            0xF000..=0xFDFF => self.wram[self.wram_bank][(addr - 0xF000) as usize],
+            0xFE00..=0xFE9F => self.ppu.oam[(addr - 0xFE00) as usize],
+            0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize],
+            0xFFFF => self.ie_reg,
+            _ => 0xFF,
+        }
+    }
+
+    /// The 8 KiB of VRAM bank `bank` (0 or 1), as stored.
+    ///
+    /// This is the backing memory itself: VBK and the PPU's mode 3 lock are
+    /// ignored. Bank 1 is only used on CGB. Panics if `bank` is above 1.
+    pub fn vram(&self, bank: usize) -> &[u8] {
+        &self.ppu.vram[bank]
+    }
+
+    /// The 160 bytes of OAM, as stored, even while the PPU or OAM DMA has it
+    /// locked.
+    pub fn oam(&self) -> &[u8] {
+        &self.ppu.oam
+    }
+
+    /// The 4 KiB of WRAM bank `bank` (0-7), as stored, regardless of SVBK.
+    /// Banks 2-7 are only used on CGB. Panics if `bank` is above 7.
+    pub fn wram(&self, bank: usize) -> &[u8] {
+        &self.wram[bank]
+    }
+
+    /// Sets every byte of RAM mapped in `range` to `value` with bulk fills,
+    /// for tools that wipe memory.
+    ///
+    /// Covers VRAM, WRAM and its echo, OAM and HRAM as the CPU currently
+    /// sees them (VBK and SVBK pick the banks) but bypasses the PPU and DMA
+    /// locks, like [`Self::peek_byte`]. ROM, cartridge RAM, the unusable
+    /// area, I/O registers and IE are skipped. Cleared VRAM tiles are marked
+    /// in [`Ppu::take_dirty_tiles`](crate::ppu::Ppu::take_dirty_tiles).
+    pub fn clear_region(&mut self, range: RangeInclusive<u16>, value: u8) {
+        let (start, end) = (usize::from(*range.start()), usize::from(*range.end()));
+        if start > end {
+            return;
+        }
+        let clamp = |base: usize, len: usize| {
+            let lo = start.max(base);
+            let hi = end.min(base + len - 1);
+            (lo <= hi).then(|| lo - base..hi - base + 1)
+        };
+
+        if let Some(span) = clamp(0x8000, 0x2000) {
+            self.ppu.fill_vram(self.ppu.vram_bank, span, value);
+        }
+        let bank = self.wram_bank;
+        for (base, len, wram_bank) in [
+            (0xC000, 0x1000, 0),
+            (0xD000, 0x1000, bank),
+            (0xE000, 0x1000, 0),
+            (0xF000, 0x0E00, bank),
+        ] {
+            if let Some(span) = clamp(base, len) {
+                self.wram[wram_bank][span].fill(value);
+            }
+        }
+        if let Some(span) = clamp(0xFE00, 0xA0) {
+            self.ppu.oam[span].fill(value);
+        }
+        if let Some(span) = clamp(0xFF80, 0x7F) {
+            self.hram[span].fill(value);
+        }
+    }
+
+    /// Enables or disables per-address CPU access counting.
+    ///
+    /// Counters are allocated when profiling is enabled and dropped when it is
+    /// disabled. While disabled the only cost is one branch per access.
+    pub fn enable_profiling(&mut self, enabled: bool) {
+        if enabled {
+            if self.profile.is_none() {
+                self.profile = Some(Box::new(AccessProfile::new()));
+            }
+        } else {
+            self.profile = None;
+        }
+    }
+
+    pub fn profiling_enabled(&self) -> bool {
+        self.profile.is_some()
+    }
+
+    /// Combined read + write counts for all 64 KiB of the CPU address space,
+    /// indexed by address. Empty when profiling is disabled.
+    pub fn access_heatmap(&self) -> &[u32] {
+        self.profile.as_deref().map_or(&[], |p| &p.total)
+    }
+
+    /// Read counts indexed by address. Empty when profiling is disabled.
+    pub fn read_heatmap(&self) -> &[u32] {
+        self.profile.as_deref().map_or(&[], |p| &p.reads)
+    }
+
+    /// Write counts indexed by address. Empty when profiling is disabled.
+    pub fn write_heatmap(&self) -> &[u32] {
+        self.profile.as_deref().map_or(&[], |p| &p.writes)
+    }
+
+    /// Zeroes all access counters without disabling profiling.
+    pub fn clear_heatmap(&mut self) {
+        if let Some(profile) = self.profile.as_deref_mut() {
+            profile.clear();
+        }
+    }
+
     fn dma_read_byte(&mut self, addr: u16) -> u8 {
         let addr = if !self.cgb_mode && (0xFE00..=0xFF9F).contains(&addr) {
             addr.wrapping_sub(0x2000)
@@ -718,6 +993,11 @@ impl Mmu {
             self.main_bus = val;
         }
         self.watchpoints.note_write(self.last_cpu_pc, addr, val);
+        if let Some(profile) = self.profile.as_deref_mut() {
+            let i = addr as usize;
+            profile.writes[i] = profile.writes[i].saturating_add(1);
+            profile.total[i] = profile.total[i].saturating_add(1);
+        }
         if self.dma_cycles > 0 {
             match addr {
                 0x0000..=0x7FFF | 0xC000..=0xFDFF | 0xFF00..=0xFFFF => {}
@@ -751,7 +1031,8 @@ impl Mmu {
                 }
 
                 if allow {
-                    self.ppu.vram[self.ppu.vram_bank][(addr - 0x8000) as usize] = val;
+                    self.ppu
+                        .write_vram(self.ppu.vram_bank, (addr - 0x8000) as usize, val);
                 } else {
                     #[cfg(feature = "ppu-trace")]
                     {
@@ -804,7 +1085,7 @@ impl Mmu {
                 if allow {
                     self.oam_bug_next_access = None;
                     self.ppu.oam[(addr - 0xFE00) as usize] = val;
-                } else if !self.cgb_mode {
+                } else if self.ppu.oam_bug_enabled() {
                     let access = self
                         .oam_bug_next_access
                         .take()
@@ -841,7 +1122,7 @@ impl Mmu {
                 // Unusable region: ignore writes, but the CPU still drives the address bus.
                 // On DMG, blocked CPU accesses in $FE00-$FEFF during mode 2 can trigger
                 // the OAM corruption bug even if the address is in the unusable subrange.
-                if !self.cgb_mode && !self.ppu.oam_write_accessible() {
+                if self.ppu.oam_bug_enabled() && !self.ppu.oam_write_accessible() {
                     let access = self
                         .oam_bug_next_access
                         .take()
@@ -853,7 +1134,7 @@ impl Mmu {
                     self.oam_bug_next_access = None;
                 }
             }
-            0xFF00 => self.input.write(val),
+            0xFF00 => self.input.write(val, &mut self.if_reg),
             0xFF01 | 0xFF02 => self.serial.write(addr, val),
             0xFF04 => {
                 self.reset_div();
@@ -980,6 +1261,7 @@ impl Mmu {
                 self.ppu.dma = val;
                 let src = (val as u16) << 8;
                 self.pending_dma = Some(src);
+                self.last_oam_dma = Some((src, self.oam_dma_write_cycle));
                 // DMA starts after two M-cycles. `pending_delay` is tracked
                 // in T-cycles (dots). In double-speed mode an M-cycle is 2
                 // T-cycles instead of 4, so halve the delay there.
@@ -1009,6 +1291,12 @@ impl Mmu {
                 }
             }
             0xFF50 => self.boot_mapped = false,
+            // OPRI is latched by the boot ROM; later writes have no effect.
+            0xFF6C => {
+                if self.boot_mapped {
+                    self.ppu.write_reg(addr, val);
+                }
+            }
             0xFF70 => {
                 if self.cgb_mode {
                     let bank = (val & 0x07) as usize;
@@ -1026,7 +1314,7 @@ impl Mmu {
                 }
             }
             0xFF74 => {
-                if self.cgb_mode {
+                if self.cgb_mode && !self.ppu.is_cgb_dmg_compat_mode() {
                     self.undoc_ff74 = val;
                 }
             }
@@ -1043,7 +1331,8 @@ impl Mmu {
 
     /// Write to VRAM bypassing mode checks (used by DMA transfers)
     fn vram_dma_write(&mut self, addr: u16, val: u8) {
-        self.ppu.vram[self.ppu.vram_bank][(addr - 0x8000) as usize] = val;
+        self.ppu
+            .write_vram(self.ppu.vram_bank, (addr - 0x8000) as usize, val);
     }
 
     pub fn take_serial(&mut self) -> Vec<u8> {
@@ -1121,6 +1410,18 @@ impl Mmu {
         }
     }
 
+    /// Source address and start cycle of the most recent OAM DMA, or `None`
+    /// if FF46 has not been written since power-on.
+    ///
+    /// The cycle is [`Cpu::cycles`](crate::cpu::Cpu::cycles) when the CPU
+    /// wrote FF46; the copy itself begins two M-cycles later and takes 160
+    /// more. Restarting a transfer overwrites the record. Writes made
+    /// directly through [`Self::write_byte`] rather than by the CPU reuse the
+    /// cycle of the CPU's last FF46 write. Not saved in save states.
+    pub fn last_oam_dma(&self) -> Option<(u16, u64)> {
+        self.last_oam_dma
+    }
+
     /// Whether a DMA transfer is in progress.
     pub fn dma_active(&self) -> bool {
         self.dma_cycles > 0 || self.pending_delay > 0
@@ -1253,6 +1554,113 @@ impl Mmu {
     }
 }
 
+state_enum!(DmaMode {
+    DmaMode::Gdma,
+    DmaMode::Hdma,
+});
+
+state_fields!(HdmaState {
+    src,
+    dst,
+    blocks,
+    mode,
+    active,
+    cancelled,
+});
+
+// Cartridge ROM, boot ROM contents, hardware mode/revisions and debugger
+// state are not part of a save state.
+state_fields!(Mmu {
+    wram,
+    wram_bank,
+    hram,
+    boot_mapped,
+    if_reg,
+    ie_reg,
+    timer,
+    dot_div,
+    input,
+    key1,
+    rp,
+    undoc_ff72,
+    undoc_ff73,
+    undoc_ff74,
+    undoc_ff75,
+    dma_cycles,
+    dma_source,
+    pending_dma,
+    pending_delay,
+    gdma_cycles,
+    post_boot_state,
+    last_cpu_pc,
+    data_bus,
+    main_bus,
+    oam_bug_next_access,
+});
+
+impl Mmu {
+    pub(crate) fn save_state(&self) -> Record {
+        let mut rec = Record::new();
+        self.save_state_fields(&mut rec);
+        let mut hdma = Record::new();
+        self.hdma.save_state_fields(&mut hdma);
+        rec.put_record("hdma", &hdma);
+        rec.put_record("serial", &self.serial.save_state());
+        rec.put_record("ppu", &self.ppu.save_state());
+        rec.put_record("apu", &self.apu.save_state());
+        if let Some(cart) = &self.cart {
+            rec.put_record("cart", &cart.save_state());
+        }
+        rec
+    }
+
+    /// Restores device state. The cartridge is restored last and only if
+    /// everything else loaded, so a failed load leaves it untouched.
+    pub(crate) fn load_state(&mut self, rec: &Record) -> Result<(), StateError> {
+        self.load_state_fields(rec)?;
+        if self.wram_bank >= self.wram.len() {
+            return Err(StateError::InvalidField("wram_bank".into()));
+        }
+        if let Some(hdma) = rec.record("hdma")? {
+            self.hdma.load_state_fields(&hdma)?;
+        }
+        if let Some(serial) = rec.record("serial")? {
+            self.serial.load_state(&serial)?;
+        }
+        let ppu = rec
+            .record("ppu")?
+            .ok_or_else(|| StateError::MissingField("ppu".into()))?;
+        self.ppu.load_state(&ppu).map_err(|e| e.in_field("ppu"))?;
+        if let Some(apu) = rec.record("apu")? {
+            self.apu.load_state(&apu).map_err(|e| e.in_field("apu"))?;
+        }
+        match (rec.record("cart")?, self.cart.as_mut()) {
+            (Some(state), Some(cart)) => cart.load_state(&state),
+            (None, None) => Ok(()),
+            _ => Err(StateError::CartridgeMismatch),
+        }
+    }
+
+    /// Moves host-side attachments (boot ROM, link port, audio output and
+    /// debugger state) and settings (see [`Self::adopt_host_settings`]) from
+    /// `other` into this MMU.
+    pub(crate) fn adopt_host_state(&mut self, other: &mut Mmu) {
+        self.boot_rom = other.boot_rom.take();
+        self.adopt_host_settings(other);
+        self.serial.adopt_port(&mut other.serial);
+        self.apu.adopt_output(&mut other.apu);
+        core::mem::swap(&mut self.watchpoints, &mut other.watchpoints);
+        core::mem::swap(&mut self.profile, &mut other.profile);
+    }
+
+    /// Takes over the PPU and APU settings a frontend applies, such as the
+    /// DMG palette, frame skip or silence window, from `other`.
+    pub(crate) fn adopt_host_settings(&mut self, other: &mut Mmu) {
+        self.ppu.adopt_host_settings(&other.ppu);
+        self.apu.adopt_host_settings(&mut other.apu);
+    }
+}
+
 impl Default for Mmu {
     fn default() -> Self {
         Self::new()
@@ -1,4 +1,8 @@
 use crate::hardware::{CgbRevision, DmgRevision};
+use crate::math;
+use crate::savestate::{Record, StateError, state_enum, state_fields, state_record};
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+use core::ops::Range;
 
 #[inline]
 fn env_flag_from_str(value: &str) -> bool {
@@ -7,43 +11,37 @@ fn env_flag_from_str(value: &str) -> bool {
 
 #[inline]
 fn env_i16_or(key: &str, default: i16) -> i16 {
-    std::env::var(key)
-        .ok()
+    crate::env::var(key)
         .and_then(|v| v.trim().parse::<i16>().ok())
         .unwrap_or(default)
 }
 
 #[inline]
 fn env_u16_or(key: &str, default: u16) -> u16 {
-    std::env::var(key)
-        .ok()
+    crate::env::var(key)
         .and_then(|v| v.trim().parse::<u16>().ok())
         .unwrap_or(default)
 }
 
 #[inline]
 fn env_bool_or_false(key: &str) -> bool {
-    std::env::var(key)
-        .ok()
-        .is_some_and(|v| env_flag_from_str(v.trim()))
+    crate::env::var(key).is_some_and(|v| env_flag_from_str(v.trim()))
 }
 
 #[inline]
 fn env_os_bool_or_false(key: &str) -> bool {
-    std::env::var_os(key).is_some_and(|v| env_flag_from_str(v.to_string_lossy().as_ref()))
+    crate::env::var_lossy(key).is_some_and(|v| env_flag_from_str(&v))
 }
 
 #[inline]
 fn env_bool_or_true(key: &str) -> bool {
-    std::env::var(key)
-        .ok()
-        .is_none_or(|v| env_flag_from_str(v.trim()))
+    crate::env::var(key).is_none_or(|v| env_flag_from_str(v.trim()))
 }
 
 macro_rules! define_env_i16 {
     ($func:ident, $key:literal, $default:expr) => {
         fn $func() -> i16 {
-            use std::sync::OnceLock;
+            use crate::env::OnceLock;
             static VALUE: OnceLock<i16> = OnceLock::new();
             *VALUE.get_or_init(|| env_i16_or($key, $default))
         }
@@ -53,7 +51,7 @@ macro_rules! define_env_i16 {
 macro_rules! define_env_u16 {
     ($func:ident, $key:literal, $default:expr) => {
         fn $func() -> u16 {
-            use std::sync::OnceLock;
+            use crate::env::OnceLock;
             static VALUE: OnceLock<u16> = OnceLock::new();
             *VALUE.get_or_init(|| env_u16_or($key, $default))
         }
@@ -63,7 +61,7 @@ macro_rules! define_env_u16 {
 macro_rules! define_env_bool_false {
     ($func:ident, $key:literal) => {
         fn $func() -> bool {
-            use std::sync::OnceLock;
+            use crate::env::OnceLock;
             static VALUE: OnceLock<bool> = OnceLock::new();
             *VALUE.get_or_init(|| env_bool_or_false($key))
         }
@@ -73,7 +71,7 @@ macro_rules! define_env_bool_false {
 macro_rules! define_env_os_bool_false {
     ($func:ident, $key:literal) => {
         fn $func() -> bool {
-            use std::sync::OnceLock;
+            use crate::env::OnceLock;
             static VALUE: OnceLock<bool> = OnceLock::new();
             *VALUE.get_or_init(|| env_os_bool_or_false($key))
         }
@@ -83,7 +81,7 @@ macro_rules! define_env_os_bool_false {
 macro_rules! define_env_bool_true {
     ($func:ident, $key:literal) => {
         fn $func() -> bool {
-            use std::sync::OnceLock;
+            use crate::env::OnceLock;
             static VALUE: OnceLock<bool> = OnceLock::new();
             *VALUE.get_or_init(|| env_bool_or_true($key))
         }
@@ -269,6 +267,12 @@ const LINE_CYCLES: u16 = 456;
 
 // Number of lines spent in VBlank
 const VBLANK_LINES: u8 = 10;
+/// Rows in the buffer from [`Ppu::extended_framebuffer`]: every line of a
+/// frame, VBlank included.
+pub const CAPTURE_LINES: usize = SCREEN_HEIGHT + VBLANK_LINES as usize;
+
+/// `(SCX, SCY, WX, WY)` for one line of [`Ppu::scroll_log`].
+type ScrollLogEntry = (u8, u8, u8, u8);
 
 // Sprite limits
 const MAX_SPRITES_PER_LINE: usize = 10;
@@ -302,6 +306,68 @@ const MODE3_OBJ_FETCH_STAGE_LOW_0: u8 = 3;
 const MODE3_OBJ_FETCH_STAGE_LOW_1: u8 = 4;
 const MODE3_OBJ_FETCH_STAGE_HIGH: u8 = 5;
 
+/// How overlapping objects are ordered, selected by OPRI ($FF6C) on CGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectPriorityMode {
+    /// Lower OAM index wins (CGB default).
+    OamIndex,
+    /// Lower X coordinate wins, ties broken by OAM index (DMG behavior).
+    Coordinate,
+}
+
+/// Tiles in each VRAM bank ($8000-$97FF holds 384 tiles of 16 bytes).
+pub const TILES_PER_BANK: usize = 384;
+
+/// Which VRAM tiles changed, one bit per tile in each bank. Returned by
+/// [`Ppu::take_dirty_tiles`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileDirtyMask([u64; 2 * TILES_PER_BANK / 64]);
+
+impl TileDirtyMask {
+    /// No tiles marked.
+    pub const fn empty() -> Self {
+        Self([0; 2 * TILES_PER_BANK / 64])
+    }
+
+    /// Every tile in both banks marked.
+    pub const fn all() -> Self {
+        Self([u64::MAX; 2 * TILES_PER_BANK / 64])
+    }
+
+    /// Whether tile `tile` (0-383) of VRAM bank `bank` (0-1) is marked.
+    pub fn is_dirty(&self, bank: usize, tile: usize) -> bool {
+        let bit = bank * TILES_PER_BANK + tile;
+        self.0[bit / 64] & (1 << (bit % 64)) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    /// Number of marked tiles.
+    pub fn count(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Marked tiles as `(bank, tile)` pairs, bank 0 first.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..2 * TILES_PER_BANK)
+            .filter(|&bit| self.0[bit / 64] & (1 << (bit % 64)) != 0)
+            .map(|bit| (bit / TILES_PER_BANK, bit % TILES_PER_BANK))
+    }
+
+    fn mark(&mut self, bank: usize, tile: usize) {
+        let bit = bank * TILES_PER_BANK + tile;
+        self.0[bit / 64] |= 1 << (bit % 64);
+    }
+}
+
+impl Default for TileDirtyMask {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
 /// DMG OAM corruption bug access classification.
 ///
 /// On DMG hardware, OAM can become corrupted during PPU mode 2 (OAM scan) when
@@ -395,6 +461,9 @@ pub struct Ppu {
     boot_hold_cycles: u16,
 
     pub framebuffer: [u32; SCREEN_WIDTH * SCREEN_HEIGHT],
+    /// Palette index of each pixel in `framebuffer`; see
+    /// [`Ppu::framebuffer_indices`].
+    framebuffer_indices: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
     line_priority: [bool; SCREEN_WIDTH],
     line_color_zero: [bool; SCREEN_WIDTH],
     cgb_line_obj_enabled: [bool; SCREEN_WIDTH],
@@ -425,6 +494,8 @@ pub struct Ppu {
     pub(crate) oam_dma_current_dest: u8,
     /// Indicates a completed frame is available in `framebuffer`
     frame_ready: bool,
+    /// Dots counted towards the next dark frame while a DMG is in STOP.
+    stop_dots: u32,
     stat_irq_line: bool,
     // One-shot pulse used for the mode-2-on-entering-VBlank STAT quirk.
     // Used on DMG and on CGB when running in DMG-compat mode.
@@ -432,6 +503,8 @@ pub struct Ppu {
     /// CGB: tracks whether we've triggered the early LY=0 comparison during line 153
     cgb_line153_ly0_triggered: bool,
     frame_counter: u64,
+    /// Completed frames (VBlank entries) since reset.
+    frame_count: u64,
     dmg_startup_cycle: Option<u16>,
     dmg_startup_stage: Option<usize>,
     dmg_post_startup_line2: bool,
@@ -441,6 +514,33 @@ pub struct Ppu {
     debug_prev_mode: u8,
     /// Runtime DMG palette (allows choosing alternate non-green palettes)
     dmg_palette: [u32; 4],
+    /// LCD persistence: how much of the previous frame (0-256) is blended
+    /// into each finished one. 0 disables it.
+    ghost_weight: u32,
+    /// Last finished (blended) frame, kept while ghosting is enabled.
+    ghost_prev: Option<Box<[u32; SCREEN_WIDTH * SCREEN_HEIGHT]>>,
+    /// Frames skipped after each rendered one; see [`Ppu::set_frame_skip`].
+    frame_skip: u8,
+    /// Position within the current skip cycle; 0 is the rendered frame.
+    frame_skip_phase: u8,
+    /// Whether pixel composition is skipped for the frame in progress.
+    skip_render: bool,
+    /// Whether the most recently completed frame was drawn.
+    frame_rendered: bool,
+    /// Tiles written since the last [`Ppu::take_dirty_tiles`].
+    dirty_tiles: TileDirtyMask,
+    /// Whether OBJ attributes are latched once at the end of mode 3; see
+    /// [`Ppu::set_latch_objects_per_line`].
+    latch_objects_per_line: bool,
+    /// The last frame plus the VBlank lines after it, kept while
+    /// [`Ppu::set_capture_vblank_lines`] is on.
+    vblank_capture: Option<Box<[u32; SCREEN_WIDTH * CAPTURE_LINES]>>,
+    /// SCX, SCY, WX and WY as latched for each visible line, kept while
+    /// [`Ppu::set_scroll_logging`] is on.
+    scroll_log: Option<Box<[ScrollLogEntry; SCREEN_HEIGHT]>>,
+    /// Whether 16-bit inc/dec and accesses into OAM during mode 2 corrupt OAM.
+    /// Defaults to on for DMG and off for CGB.
+    oam_bug_enabled: bool,
 
     // --- DMG timing quirks ---
     //
@@ -966,11 +1066,10 @@ struct DmgObjSizeTuning {
 }
 
 fn dmg_mode3_obj_fetch_sim_dots() -> u16 {
-    use std::sync::OnceLock;
+    use crate::env::OnceLock;
     static DOTS: OnceLock<u16> = OnceLock::new();
     *DOTS.get_or_init(|| {
-        std::env::var("VIBEEMU_DMG_MODE3_OBJ_FETCH_SIM_DOTS")
-            .ok()
+        crate::env::var("VIBEEMU_DMG_MODE3_OBJ_FETCH_SIM_DOTS")
             .and_then(|v| v.trim().parse::<i16>().ok())
             .map(|v| v.clamp(0, 16) as u16)
             .unwrap_or(10)
@@ -978,7 +1077,7 @@ fn dmg_mode3_obj_fetch_sim_dots() -> u16 {
 }
 
 fn dmg_obj_size_tuning() -> &'static DmgObjSizeTuning {
-    use std::sync::OnceLock;
+    use crate::env::OnceLock;
     static TUNING: OnceLock<DmgObjSizeTuning> = OnceLock::new();
     TUNING.get_or_init(|| DmgObjSizeTuning {
         capture_bias: env_i16_or(
@@ -1069,7 +1168,7 @@ fn parse_trace_line_set(spec: &str) -> [bool; SCREEN_HEIGHT] {
                 continue;
             };
             if s > e {
-                std::mem::swap(&mut s, &mut e);
+                core::mem::swap(&mut s, &mut e);
             }
             let lo = s.max(0) as usize;
             let hi = e.min((SCREEN_HEIGHT - 1) as i16) as usize;
@@ -1089,11 +1188,10 @@ fn trace_obj_debug_line_enabled(ly: u8) -> bool {
     if !env_bool_or_false("VIBEEMU_TRACE_OBJ_DEBUG") {
         return false;
     }
-    use std::sync::OnceLock;
+    use crate::env::OnceLock;
     static LINES: OnceLock<[bool; SCREEN_HEIGHT]> = OnceLock::new();
     let set = LINES.get_or_init(|| {
-        std::env::var("VIBEEMU_TRACE_OBJ_DEBUG_LINES")
-            .ok()
+        crate::env::var("VIBEEMU_TRACE_OBJ_DEBUG_LINES")
             .map(|v| parse_trace_line_set(&v))
             .unwrap_or([true; SCREEN_HEIGHT])
     });
@@ -1101,9 +1199,7 @@ fn trace_obj_debug_line_enabled(ly: u8) -> bool {
 }
 
 fn read_trace_u64_env(key: &str) -> Option<u64> {
-    std::env::var(key)
-        .ok()
-        .and_then(|v| v.trim().parse::<u64>().ok())
+    crate::env::var(key).and_then(|v| v.trim().parse::<u64>().ok())
 }
 
 define_env_bool_false!(trace_scx_writes_enabled, "VIBEEMU_TRACE_SCX_WRITES_ALL");
@@ -1118,16 +1214,15 @@ macro_rules! define_trace_line_filter {
             if ly as usize >= SCREEN_HEIGHT {
                 return false;
             }
-            use std::sync::OnceLock;
+            use crate::env::OnceLock;
             static LINES: OnceLock<[bool; SCREEN_HEIGHT]> = OnceLock::new();
             static HAS_FILTER: OnceLock<bool> = OnceLock::new();
-            let has_filter = *HAS_FILTER.get_or_init(|| std::env::var_os($key).is_some());
+            let has_filter = *HAS_FILTER.get_or_init(|| crate::env::var_lossy($key).is_some());
             if !has_filter {
                 return true;
             }
             let set = LINES.get_or_init(|| {
-                std::env::var($key)
-                    .ok()
+                crate::env::var($key)
                     .map(|v| parse_trace_line_set(&v))
                     .unwrap_or([true; SCREEN_HEIGHT])
             });
@@ -1148,7 +1243,7 @@ define_trace_line_filter!(
 macro_rules! define_trace_frame_filter {
     ($func:ident, $min_key:literal, $max_key:literal) => {
         fn $func(frame: u64) -> bool {
-            use std::sync::OnceLock;
+            use crate::env::OnceLock;
             static FRAME_MIN: OnceLock<Option<u64>> = OnceLock::new();
             static FRAME_MAX: OnceLock<Option<u64>> = OnceLock::new();
 
@@ -1292,6 +1387,7 @@ impl Ppu {
             mode0_target_cycles: MODE0_CYCLES,
             boot_hold_cycles: 0,
             framebuffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            framebuffer_indices: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
             line_priority: [false; SCREEN_WIDTH],
             line_color_zero: [false; SCREEN_WIDTH],
             cgb_line_obj_enabled: [true; SCREEN_WIDTH],
@@ -1320,14 +1416,27 @@ impl Ppu {
             mode3_same_x_toggle: false,
             oam_dma_current_dest: 0xA1,
             frame_ready: false,
+            stop_dots: 0,
             stat_irq_line: false,
             dmg_mode2_vblank_irq_pending: false,
             cgb_line153_ly0_triggered: false,
             frame_counter: 0,
+            frame_count: 0,
             dmg_startup_cycle: None,
             dmg_startup_stage: None,
             dmg_post_startup_line2: false,
             dmg_palette: DMG_PALETTE,
+            ghost_weight: 0,
+            ghost_prev: None,
+            frame_skip: 0,
+            frame_skip_phase: 0,
+            skip_render: false,
+            frame_rendered: true,
+            dirty_tiles: TileDirtyMask::all(),
+            latch_objects_per_line: false,
+            vblank_capture: None,
+            scroll_log: None,
+            oam_bug_enabled: !cgb,
 
             dmg_line_bgp_base: 0,
             dmg_line_bgp_at_pixel: [0; SCREEN_WIDTH],
@@ -1372,6 +1481,15 @@ impl Ppu {
     fn set_mode(&mut self, new_mode: u8) {
         let old_mode = self.mode;
         self.mode = new_mode;
+        if old_mode != new_mode {
+            core_event!(
+                TRACE,
+                ly = self.ly,
+                from = old_mode,
+                to = new_mode,
+                "mode change"
+            );
+        }
 
         if new_mode == MODE_OAM {
             self.sprite_count = 0;
@@ -1459,6 +1577,11 @@ impl Ppu {
         self.mode3_wx_event_count = 0;
         self.mode3_wy_base = self.wy;
         self.mode3_wy_event_count = 0;
+        if let Some(log) = self.scroll_log.as_deref_mut()
+            && let Some(entry) = log.get_mut(usize::from(self.ly))
+        {
+            *entry = (self.scx, self.scy, self.wx, self.wy);
+        }
         self.mode3_obj_fetch_base = 0;
         self.mode3_obj_fetch_event_count = 0;
         self.mode3_pop_event_count = 0;
@@ -1495,7 +1618,7 @@ impl Ppu {
     }
 
     #[inline]
-    fn is_cgb_dmg_compat_mode(&self) -> bool {
+    pub(crate) fn is_cgb_dmg_compat_mode(&self) -> bool {
         self.cgb && self.dmg_compat
     }
 
@@ -3245,6 +3368,255 @@ impl Ppu {
         self.dmg_palette = pal;
     }
 
+    pub(crate) fn dmg_palette(&self) -> [u32; 4] {
+        self.dmg_palette
+    }
+
+    /// Simulates the slow response of the DMG LCD by blending `factor` of the
+    /// previous frame into each finished frame, so sprites flickered on
+    /// alternate frames look semi-transparent. Applies to DMG and CGB output.
+    ///
+    /// `factor` is clamped to `0.0..=1.0`; 0 (the default) disables it. At
+    /// most 255/256 of the previous frame is kept, so even at 1.0 the image
+    /// keeps following the game. The setting survives resets and loaded
+    /// states, but the first frame after either is shown unblended rather
+    /// than mixed with the old image.
+    pub fn set_ghosting(&mut self, factor: f32) {
+        let factor = if factor.is_nan() {
+            0.0
+        } else {
+            factor.clamp(0.0, 1.0)
+        };
+        self.ghost_weight = (math::round(factor * 256.0) as u32).min(255);
+        if self.ghost_weight == 0 {
+            self.ghost_prev = None;
+        }
+    }
+
+    /// Returns the ghosting factor set with [`Self::set_ghosting`].
+    pub fn ghosting(&self) -> f32 {
+        self.ghost_weight as f32 / 256.0
+    }
+
+    /// Skips the per-dot OBJ attribute latching of mode 3 and instead
+    /// latches every selected object's attributes and row data from OAM
+    /// once, at the end of mode 3, for hosts too slow for full accuracy.
+    ///
+    /// Mode 3 keeps its hardware length, and palette, scroll, window and
+    /// LCDC writes made during mode 3 are still replayed at the pixel they
+    /// hit. Only OBJ timing changes: objects see OAM as it is at the end
+    /// of the line rather than when each one is fetched, so OAM DMA or
+    /// writes racing mode 3 show different tiles. Pixels are still drawn
+    /// dot by dot, so only DMG mode, where OBJ latching dominates, runs
+    /// faster.
+    pub fn set_latch_objects_per_line(&mut self, enabled: bool) {
+        self.latch_objects_per_line = enabled;
+    }
+
+    /// Takes over host-side settings from `other`: the DMG display palette,
+    /// ghosting, frame skip, the OAM bug toggle and whether VBlank lines and
+    /// scroll registers are captured. Captured data and the previous frame
+    /// used for ghosting are not carried; they start afresh.
+    pub(crate) fn adopt_host_settings(&mut self, other: &Ppu) {
+        self.set_dmg_palette(other.dmg_palette);
+        self.set_ghosting(other.ghosting());
+        self.set_frame_skip(other.frame_skip);
+        self.oam_bug_enabled = other.oam_bug_enabled;
+        self.set_capture_vblank_lines(other.capture_vblank_lines());
+        self.set_scroll_logging(other.scroll_logging());
+    }
+
+    /// Returns the setting from [`Self::set_latch_objects_per_line`].
+    pub fn latch_objects_per_line(&self) -> bool {
+        self.latch_objects_per_line
+    }
+
+    /// Diagnostic mode that also records the 10 VBlank lines, for looking at
+    /// what a program does to the display after line 143. Off by default.
+    ///
+    /// While on, [`Self::extended_framebuffer`] holds 160 × 154 pixels: the
+    /// last frame, then one row per VBlank line. The PPU fetches no tiles or
+    /// objects during VBlank, so each of those rows is filled with the
+    /// backdrop it would show, background color 0 under the palette in
+    /// effect when the line starts. Palette writes made during VBlank thus
+    /// show up line by line.
+    pub fn set_capture_vblank_lines(&mut self, enabled: bool) {
+        if enabled != self.vblank_capture.is_some() {
+            self.vblank_capture = enabled.then(|| Box::new([0; SCREEN_WIDTH * CAPTURE_LINES]));
+        }
+    }
+
+    /// Returns the setting from [`Self::set_capture_vblank_lines`].
+    pub fn capture_vblank_lines(&self) -> bool {
+        self.vblank_capture.is_some()
+    }
+
+    /// The 160 × [`CAPTURE_LINES`] capture described at
+    /// [`Self::set_capture_vblank_lines`], or `None` while that is off.
+    ///
+    /// The first 144 rows are copied from [`Self::framebuffer`] as each
+    /// frame completes; row `144 + n` is rewritten when VBlank line
+    /// `144 + n` begins.
+    pub fn extended_framebuffer(&self) -> Option<&[u32; SCREEN_WIDTH * CAPTURE_LINES]> {
+        self.vblank_capture.as_deref()
+    }
+
+    /// Diagnostic mode that records SCX, SCY, WX and WY as the PPU latches
+    /// them at the start of mode 3 on each visible line, for debugging
+    /// raster effects such as parallax scrolling. Off by default.
+    pub fn set_scroll_logging(&mut self, enabled: bool) {
+        if enabled != self.scroll_log.is_some() {
+            self.scroll_log = enabled.then(|| Box::new([(0, 0, 0, 0); SCREEN_HEIGHT]));
+        }
+    }
+
+    /// Returns the setting from [`Self::set_scroll_logging`].
+    pub fn scroll_logging(&self) -> bool {
+        self.scroll_log.is_some()
+    }
+
+    /// `(SCX, SCY, WX, WY)` for each of the 144 visible lines, or an empty
+    /// slice while [`Self::set_scroll_logging`] is off.
+    ///
+    /// Entry `n` is rewritten when line `n` enters mode 3, so during a frame
+    /// lines not reached yet still hold the previous frame's values. Writes
+    /// made later in mode 3 are not reflected.
+    pub fn scroll_log(&self) -> &[ScrollLogEntry] {
+        self.scroll_log.as_deref().map_or(&[], |log| &log[..])
+    }
+
+    fn capture_vblank_line(&mut self, line: u8) {
+        let backdrop = if self.is_cgb_native_mode() {
+            self.cgb_bg_color_from_color_id(0, 0)
+        } else {
+            let shade = Self::dmg_shade(self.bgp, 0);
+            if self.dmg_compat {
+                self.cgb_bg_color_from_color_id(0, shade)
+            } else {
+                self.dmg_palette[shade as usize]
+            }
+        };
+        let Some(capture) = self.vblank_capture.as_deref_mut() else {
+            return;
+        };
+        if line as usize == SCREEN_HEIGHT {
+            capture[..self.framebuffer.len()].copy_from_slice(&self.framebuffer);
+        }
+        let row = line as usize * SCREEN_WIDTH;
+        capture[row..row + SCREEN_WIDTH].fill(backdrop);
+    }
+
+    /// Current scanline, 0-153. Unlike [`Self::ly`], this is still 153 for
+    /// the whole of the last line, where LY already reads 0 on CGB.
+    pub fn line(&self) -> u8 {
+        if self.cgb_line153_ly0_triggered {
+            153
+        } else {
+            self.ly
+        }
+    }
+
+    /// Draws only every `(n + 1)`th frame, for hosts too slow to compose
+    /// every one. Timing, interrupts and STAT are unaffected: skipped frames
+    /// still run every mode and set [`Self::frame_ready`], but leave the
+    /// framebuffer holding the last drawn frame. Check
+    /// [`Self::frame_rendered`] to avoid presenting the same frame twice.
+    ///
+    /// 0 (the default) draws every frame. The change applies from the next
+    /// frame, which is always drawn.
+    pub fn set_frame_skip(&mut self, n: u8) {
+        self.frame_skip = n;
+        self.frame_skip_phase = 0;
+        self.skip_render = false;
+    }
+
+    /// Returns the frame skip set with [`Self::set_frame_skip`].
+    pub fn frame_skip(&self) -> u8 {
+        self.frame_skip
+    }
+
+    /// Returns the tiles whose data changed since the last call and clears
+    /// the mask, so a renderer caching decoded tiles only re-uploads those.
+    ///
+    /// Every tile is marked on the first call and after a save state is
+    /// loaded. Writes made directly to [`Self::vram`] are not tracked.
+    pub fn take_dirty_tiles(&mut self) -> TileDirtyMask {
+        core::mem::take(&mut self.dirty_tiles)
+    }
+
+    /// Writes `val` to offset `offset` of VRAM bank `bank`, marking the tile
+    /// dirty if its data changed.
+    pub(crate) fn write_vram(&mut self, bank: usize, offset: usize, val: u8) {
+        let byte = &mut self.vram[bank][offset];
+        if *byte == val {
+            return;
+        }
+        *byte = val;
+        if offset < TILES_PER_BANK * 16 {
+            self.dirty_tiles.mark(bank, offset / 16);
+        }
+    }
+
+    /// Fills `span` of VRAM bank `bank` with `val`, marking every tile it
+    /// touches dirty.
+    pub(crate) fn fill_vram(&mut self, bank: usize, span: Range<usize>, val: u8) {
+        self.vram[bank][span.clone()].fill(val);
+        let tiles = span.start / 16..span.end.div_ceil(16).min(TILES_PER_BANK);
+        for tile in tiles {
+            self.dirty_tiles.mark(bank, tile);
+        }
+    }
+
+    /// Whether the most recently completed frame was drawn, as opposed to
+    /// skipped by [`Self::set_frame_skip`].
+    pub fn frame_rendered(&self) -> bool {
+        self.frame_rendered
+    }
+
+    /// Enables or disables OAM corruption from CPU accesses and 16-bit
+    /// inc/dec into 0xFE00-0xFEFF while the PPU scans OAM. Real CGB hardware
+    /// is unaffected, so it defaults to off there.
+    pub fn set_oam_bug_enabled(&mut self, enabled: bool) {
+        self.oam_bug_enabled = enabled;
+    }
+
+    pub fn oam_bug_enabled(&self) -> bool {
+        self.oam_bug_enabled
+    }
+
+    /// Records whether the frame that just completed was drawn and decides
+    /// whether to draw the next one.
+    fn advance_frame_skip(&mut self) {
+        self.frame_rendered = !self.skip_render;
+        self.frame_skip_phase = if self.frame_skip_phase >= self.frame_skip {
+            0
+        } else {
+            self.frame_skip_phase + 1
+        };
+        self.skip_render = self.frame_skip_phase != 0;
+    }
+
+    fn apply_ghosting(&mut self) {
+        if self.ghost_weight == 0 {
+            return;
+        }
+        let Some(prev) = self.ghost_prev.as_deref_mut() else {
+            self.ghost_prev = Some(Box::new(self.framebuffer));
+            return;
+        };
+        let w = self.ghost_weight;
+        for (cur, old) in self.framebuffer.iter_mut().zip(prev.iter_mut()) {
+            let mut out = 0;
+            for shift in [0, 8, 16] {
+                let c = (*cur >> shift) & 0xFF;
+                let p = (*old >> shift) & 0xFF;
+                out |= ((c * (256 - w) + p * w + 128) >> 8) << shift;
+            }
+            *cur = out;
+            *old = out;
+        }
+    }
+
     pub fn queue_reg_write(&mut self, addr: u16, value: u8, delay_dots: u8) {
         let delay = delay_dots.max(1);
         if self.pending_reg_write_count >= PENDING_REG_WRITES_MAX {
@@ -3277,6 +3649,41 @@ impl Ppu {
         self.mode3_position_in_line
     }
 
+    /// Latches the OAM attributes and row data of selected objects whose
+    /// fetch did not complete in mode 3, for objects at screen X in `xs`.
+    ///
+    /// Objects at OAM X 161-167 are matched in the last few dots of mode 3,
+    /// and the mode 3 length calibrated against hardware can end before the
+    /// modelled fetch completes. Their visible columns are still drawn, so
+    /// their rows are latched here rather than dropping the objects. With
+    /// [`Self::set_latch_objects_per_line`] nothing is fetched during
+    /// mode 3, so every object is latched here.
+    fn latch_unfetched_object_rows(&mut self, xs: core::ops::Range<i16>) {
+        if self.lcdc & 0x02 == 0 {
+            return;
+        }
+        for idx in 0..self.sprite_count {
+            let sprite = self.line_sprites[idx];
+            if sprite.obj_data_valid || !xs.contains(&sprite.x) {
+                continue;
+            }
+            let base = sprite.oam_index * 4;
+            let tile = self.oam_read_for_ppu(base + 2);
+            let flags = self.oam_read_for_ppu(base + 3);
+            let addr = self.compute_obj_row_addr_from_lcdc(sprite.y, tile, flags, self.lcdc);
+            let obj_lo = self.vram_read_for_render(0, addr);
+            let obj_hi = self.vram_read_for_render(0, addr + 1);
+            let s = &mut self.line_sprites[idx];
+            s.tile = tile;
+            s.flags = flags;
+            s.obj_row_addr = addr as u16;
+            s.obj_row_valid = true;
+            s.obj_lo = obj_lo;
+            s.obj_hi = obj_hi;
+            s.obj_data_valid = true;
+        }
+    }
+
     fn dmg_abort_mode3_object_fetch(&mut self) {
         let idx = self.mode3_obj_fetch_sprite_index;
         let stage = self.mode3_obj_fetch_stage;
@@ -3788,7 +4195,7 @@ impl Ppu {
     }
 
     fn oam_scan_finalize(&mut self) {
-        if self.is_cgb_native_mode() && self.opri & 0x01 == 0 {
+        if self.object_priority_mode() == ObjectPriorityMode::OamIndex {
             self.line_sprites[..self.sprite_count].sort_by_key(|s| s.oam_index);
         } else {
             self.line_sprites[..self.sprite_count].sort_by_key(|s| (s.x, s.oam_index));
@@ -4242,6 +4649,23 @@ impl Ppu {
         self.mode_clock
     }
 
+    /// Length in dots of mode 3 on the current line, or on the last line
+    /// that reached mode 3 while in HBlank, VBlank or OAM scan.
+    ///
+    /// At least 172 and fixed when mode 3 starts. HBlank takes the rest of
+    /// the 456-dot line.
+    ///
+    /// The SCX, object and window penalties are modelled for DMG (and CGB
+    /// hardware running DMG games) only: SCX % 8 is rounded up to whole
+    /// M-cycles, each object found by the OAM scan stalls the fetcher as
+    /// measured by mooneye's `intr_2_mode3_timing` tests, and the window
+    /// adds its fetch when it can start on the line. CGB native mode only
+    /// adds 6 dots when an object starts at or left of the screen edge (OAM
+    /// X 0-8); SCX, other objects and the window don't change its length.
+    pub fn current_mode3_length(&self) -> u16 {
+        self.mode3_target_cycles
+    }
+
     pub fn hblank_target_cycles(&self) -> u16 {
         self.mode0_target_cycles
     }
@@ -4250,6 +4674,57 @@ impl Ppu {
         self.mode
     }
 
+    /// Dots until the PPU next changes mode, the earliest point a STAT or
+    /// VBlank interrupt can be requested. `None` while the LCD is off; 0
+    /// while the DMG startup sequence is running.
+    pub fn dots_until_mode_change(&self) -> Option<u32> {
+        if !self.lcd_enabled() {
+            return None;
+        }
+        if self.dmg_startup_cycle.is_some() {
+            return Some(0);
+        }
+        let target = match self.mode {
+            MODE_HBLANK => self.mode0_target_cycles,
+            MODE_VBLANK => MODE1_CYCLES,
+            MODE_OAM => MODE2_CYCLES,
+            _ => self.mode3_target_cycles,
+        };
+        let hold = u32::from(self.boot_hold_cycles);
+        Some(hold + u32::from(target.saturating_sub(self.mode_clock)))
+    }
+
+    /// Dots until the PPU next enters VBlank and requests the VBlank
+    /// interrupt. `None` while the LCD is off.
+    pub fn dots_until_vblank(&self) -> Option<u32> {
+        let to_mode_change = self.dots_until_mode_change()?;
+        let line = u32::from(LINE_CYCLES);
+        let visible = SCREEN_HEIGHT as u32;
+        if let Some(cycle) = self.dmg_startup_cycle {
+            // The startup sequence covers lines 0 and 1 and ends in line 2's
+            // OAM scan.
+            let to_startup_end = u32::from(DMG_STARTUP_STAGE5_END.saturating_sub(cycle));
+            return Some(to_startup_end + (visible - 2) * line);
+        }
+        if self.mode == MODE_VBLANK {
+            // LY may already read 0 during line 153.
+            let ly = if self.cgb_line153_ly0_triggered {
+                153
+            } else {
+                u32::from(self.ly)
+            };
+            return Some(to_mode_change + (153 - ly.min(153)) * line + visible * line);
+        }
+        // Dots left in this visible line, then the rest of the visible lines.
+        let to_line_end = match self.mode {
+            MODE_HBLANK => to_mode_change,
+            MODE_OAM => to_mode_change + u32::from(LINE_CYCLES - MODE2_CYCLES),
+            _ => to_mode_change + u32::from(self.mode0_target_cycles),
+        };
+        let lines_after = (visible - 1).saturating_sub(u32::from(self.ly));
+        Some(to_line_end + lines_after * line)
+    }
+
     pub fn bgp(&self) -> u8 {
         self.bgp
     }
@@ -4326,14 +4801,14 @@ impl Ppu {
             let src = logo.get(i).copied().unwrap_or(0);
             for nibble in [src >> 4, src & 0x0F] {
                 let expanded = Self::dmg_boot_expand_nibble(nibble);
-                self.vram[0][addr] = expanded;
-                self.vram[0][addr + 2] = expanded;
+                self.write_vram(0, addr, expanded);
+                self.write_vram(0, addr + 2, expanded);
                 addr += 4;
             }
         }
 
         for (i, &b) in DMG_BOOT_TRADEMARK_BYTES.iter().enumerate() {
-            self.vram[0][DMG_BOOT_TRADEMARK_VRAM_BASE + i * 2] = b;
+            self.write_vram(0, DMG_BOOT_TRADEMARK_VRAM_BASE + i * 2, b);
         }
 
         // The DMG boot ROM explicitly primes map entries around $9910/$992F.
@@ -4394,9 +4869,46 @@ impl Ppu {
         self.obp0 = 0xD0;
         self.obp1 = 0xE0;
 
+        // The CGB boot ROM selects DMG-style object priority for DMG carts.
+        self.opri = 0x01;
         self.dmg_compat = true;
     }
 
+    /// Replaces the colours of a DMG game in CGB compatibility mode: BG
+    /// palette 0 and OBJ palettes 0 and 1, each given as `0x00RRGGBB` for
+    /// shades 0-3 and stored at the CGB's 15-bit precision. Lets a frontend
+    /// pick per-game colours instead of the boot ROM default; see
+    /// [`crate::colorization`].
+    ///
+    /// Does nothing on DMG hardware, which has no colour palettes.
+    pub fn apply_dmg_colorization(&mut self, bg: [u32; 4], obj0: [u32; 4], obj1: [u32; 4]) {
+        if !self.cgb {
+            return;
+        }
+        let rgb555 = |pal: [u32; 4]| {
+            pal.map(|c| {
+                let [_, r, g, b] = c.to_be_bytes();
+                u16::from(r >> 3) | u16::from(g >> 3) << 5 | u16::from(b >> 3) << 10
+            })
+        };
+        Self::write_palette(&mut self.bgpd[..8], rgb555(bg));
+        Self::write_palette(&mut self.obpd[..8], rgb555(obj0));
+        Self::write_palette(&mut self.obpd[8..16], rgb555(obj1));
+    }
+
+    /// Returns the object priority mode in effect. DMG hardware always
+    /// orders by X coordinate; CGB follows OPRI bit 0.
+    ///
+    /// Only the boot ROM can change OPRI; writes after it is unmapped are
+    /// ignored by the MMU.
+    pub fn object_priority_mode(&self) -> ObjectPriorityMode {
+        if self.cgb && self.opri & 0x01 == 0 {
+            ObjectPriorityMode::OamIndex
+        } else {
+            ObjectPriorityMode::Coordinate
+        }
+    }
+
     fn write_palette(slice: &mut [u8], pal: [u16; 4]) {
         for (i, &c) in pal.iter().enumerate() {
             slice[i * 2] = (c & 0xFF) as u8;
@@ -4404,6 +4916,24 @@ impl Ppu {
         }
     }
 
+    /// Advances the display while a DMG is in STOP. The PPU is frozen, but
+    /// with the LCD on the panel goes dark, so a frame of the darkest shade
+    /// is presented every frame's worth of dots.
+    pub(crate) fn step_dmg_stopped(&mut self, dots: u16) {
+        if !self.lcd_enabled() {
+            return;
+        }
+        self.stop_dots += u32::from(dots);
+        let frame = u32::from(LINE_CYCLES) * 154;
+        if self.stop_dots >= frame {
+            self.stop_dots -= frame;
+            self.framebuffer.fill(self.dmg_palette[3]);
+            self.framebuffer_indices.fill(3);
+            self.frame_rendered = true;
+            self.frame_ready = true;
+        }
+    }
+
     /// Returns true if a full frame has been rendered and is ready to display.
     pub fn frame_ready(&self) -> bool {
         self.frame_ready
@@ -4420,6 +4950,118 @@ impl Ppu {
         &self.framebuffer
     }
 
+    /// Returns the palette index each pixel of [`Self::framebuffer`] was
+    /// drawn with, written alongside it, for recoloring on the host.
+    ///
+    /// On DMG this is the shade 0-3 after BGP/OBP0/OBP1, i.e. the entry of
+    /// the display palette set with [`Self::set_dmg_palette`]. With CGB
+    /// palettes (CGB games and DMG games on a CGB) it is the palette RAM
+    /// slot: `palette * 4 + color` for BG pixels (0-31) and `32 + palette * 4
+    /// + color` for OBJ pixels (32-63).
+    pub fn framebuffer_indices(&self) -> &[u8; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        &self.framebuffer_indices
+    }
+
+    /// Number of lines of [`Self::framebuffer`], from the top, already drawn
+    /// for the frame in progress. The rest still hold the previous frame.
+    ///
+    /// A line counts once its pixels are all written, at the end of mode 3
+    /// (a little into HBlank for some DMG palette timings). All 144 lines
+    /// count during VBlank; none do with the LCD off or while the frame is
+    /// being skipped by [`Self::set_frame_skip`].
+    pub fn lines_drawn(&self) -> usize {
+        if !self.lcd_enabled() || self.skip_render {
+            return 0;
+        }
+        let ly = usize::from(self.ly);
+        if ly >= SCREEN_HEIGHT {
+            SCREEN_HEIGHT
+        } else if self.mode == MODE_HBLANK && !self.dmg_hblank_render_pending {
+            ly + 1
+        } else {
+            ly
+        }
+    }
+
+    /// The frame in progress as drawn so far: the first
+    /// [`Self::lines_drawn`] lines of the framebuffer, with the lines below
+    /// blanked to the color of a disabled LCD (white on CGB, the lightest
+    /// display shade on DMG) instead of showing the previous frame.
+    pub fn partial_frame(&self) -> Vec<u32> {
+        let blank = if self.cgb {
+            0x00FF_FFFF
+        } else {
+            self.dmg_palette[0]
+        };
+        let drawn = self.lines_drawn() * SCREEN_WIDTH;
+        let mut frame = self.framebuffer[..drawn].to_vec();
+        frame.resize(SCREEN_WIDTH * SCREEN_HEIGHT, blank);
+        frame
+    }
+
+    /// Copies the framebuffer into `dst` as RGBA8888 bytes (`[R, G, B, 255]`
+    /// per pixel).
+    ///
+    /// Panics if `dst` is shorter than `160 * 144 * 4` bytes.
+    pub fn framebuffer_rgba8888(&self, dst: &mut [u8]) {
+        Self::rgba8888_from(&self.framebuffer, dst);
+    }
+
+    /// Converts a copy of the framebuffer, such as one sent to another
+    /// thread, to RGBA8888 as [`Self::framebuffer_rgba8888`] does.
+    ///
+    /// Panics if `dst` is shorter than `src.len() * 4` bytes.
+    pub fn rgba8888_from(src: &[u32], dst: &mut [u8]) {
+        assert!(dst.len() >= src.len() * 4);
+        for (out, &px) in dst.chunks_exact_mut(4).zip(src) {
+            out.copy_from_slice(&[(px >> 16) as u8, (px >> 8) as u8, px as u8, 0xFF]);
+        }
+    }
+
+    /// Copies the framebuffer into `dst` as RGBA8888 upscaled by an integer
+    /// `scale`, using alpha to mark the LCD pixel grid: the last row and
+    /// column of every `scale`x`scale` cell get `grid_alpha`, the rest 255.
+    /// Frontends can darken or blend by alpha for a dot-matrix look that
+    /// stays aligned to Game Boy pixels. At scale 1 there is no grid.
+    ///
+    /// This is a core-only helper for frontends that upload their own
+    /// textures; the desktop UI draws the plain 160x144 framebuffer and does
+    /// not use it.
+    ///
+    /// Panics if `dst` is shorter than `160 * scale * 144 * scale * 4` bytes.
+    pub fn framebuffer_with_grid_alpha(&self, scale: usize, grid_alpha: u8, dst: &mut [u8]) {
+        let scale = scale.max(1);
+        let width = SCREEN_WIDTH * scale;
+        assert!(dst.len() >= width * SCREEN_HEIGHT * scale * 4);
+        for (y, row) in dst
+            .chunks_exact_mut(width * 4)
+            .take(SCREEN_HEIGHT * scale)
+            .enumerate()
+        {
+            let src = &self.framebuffer[(y / scale) * SCREEN_WIDTH..][..SCREEN_WIDTH];
+            let grid_row = scale > 1 && y % scale == scale - 1;
+            for (x, out) in row.chunks_exact_mut(4).enumerate() {
+                let px = src[x / scale];
+                let grid = grid_row || (scale > 1 && x % scale == scale - 1);
+                let alpha = if grid { grid_alpha } else { 0xFF };
+                out.copy_from_slice(&[(px >> 16) as u8, (px >> 8) as u8, px as u8, alpha]);
+            }
+        }
+    }
+
+    /// Copies the framebuffer into `dst` as RGB565, truncating each channel.
+    ///
+    /// Panics if `dst` is shorter than `160 * 144` pixels.
+    pub fn framebuffer_rgb565(&self, dst: &mut [u16]) {
+        assert!(dst.len() >= self.framebuffer.len());
+        for (out, &px) in dst.iter_mut().zip(self.framebuffer.iter()) {
+            let r = ((px >> 19) & 0x1F) as u16;
+            let g = ((px >> 10) & 0x3F) as u16;
+            let b = ((px >> 3) & 0x1F) as u16;
+            *out = (r << 11) | (g << 5) | b;
+        }
+    }
+
     /// Clears the frame ready flag after a frame has been consumed.
     pub fn clear_frame_flag(&mut self) {
         self.frame_ready = false;
@@ -4430,11 +5072,27 @@ impl Ppu {
         self.frame_counter
     }
 
+    /// Canonical frame index: the number of times the PPU has entered
+    /// VBlank (and set [`Self::frame_ready`]) since the last reset.
+    ///
+    /// Unlike [`Self::frames`], which ticks when LY wraps back to 0, this
+    /// ticks as each frame becomes presentable, and is saved with the machine
+    /// state. It does not advance while the LCD is off.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
     /// Returns true if the PPU is running in Game Boy Color mode.
     pub fn is_cgb(&self) -> bool {
         self.cgb
     }
 
+    /// Reconfigures the hardware revisions used by revision-specific quirks.
+    pub fn set_revisions(&mut self, dmg_revision: DmgRevision, cgb_revision: CgbRevision) {
+        self.dmg_revision = dmg_revision;
+        self.cgb_revision = cgb_revision;
+    }
+
     /// Get a CGB background palette color as 0x00RRGGBB.
     pub fn bg_palette_color(&self, palette: usize, color_id: usize) -> u32 {
         let off = palette * 8 + color_id * 2;
@@ -4499,15 +5157,18 @@ impl Ppu {
         Self::decode_cgb_color(self.obpd[off], self.obpd[off + 1])
     }
 
+    /// Returns the colour of a DMG BG/window pixel and its palette index
+    /// (the BGP shade, which is also the slot in CGB BG palette 0).
     #[inline]
-    fn dmg_bg_color_for_pixel(&self, x: usize, color_id: u8) -> u32 {
+    fn dmg_bg_pixel(&self, x: usize, color_id: u8) -> (u32, u8) {
         let bgp = self.dmg_bgp_for_pixel(x);
         let shade = Self::dmg_shade(bgp, color_id);
-        if self.dmg_compat {
+        let color = if self.dmg_compat {
             self.cgb_bg_color_from_color_id(0, shade)
         } else {
             self.dmg_palette[shade as usize]
-        }
+        };
+        (color, shade)
     }
 
     #[inline]
@@ -4561,10 +5222,13 @@ impl Ppu {
         }
     }
 
+    /// Whether a CPU read of OAM would currently see real data (blocked in
+    /// modes 2 and 3). OAM DMA blocking is handled by the MMU, not here.
     pub fn oam_read_accessible(&self) -> bool {
         self.oam_accessible_internal(true)
     }
 
+    /// Whether a CPU write to OAM would currently land.
     pub fn oam_write_accessible(&self) -> bool {
         self.oam_accessible_internal(false)
     }
@@ -4636,7 +5300,7 @@ impl Ppu {
     }
 
     fn oam_bug_current_row(&self) -> Option<usize> {
-        if self.cgb {
+        if !self.oam_bug_enabled {
             return None;
         }
         if self.mode != MODE_OAM {
@@ -4658,7 +5322,7 @@ impl Ppu {
 
     #[inline]
     fn oam_bug_current_accessed_oam_row(&self) -> Option<usize> {
-        if self.cgb {
+        if !self.oam_bug_enabled {
             return None;
         }
         if self.mode != MODE_OAM {
@@ -5046,10 +5710,13 @@ impl Ppu {
         self.oam_bug_copy_row_to_two_predecessors(accessed_oam_row);
     }
 
+    /// Whether a CPU read of VRAM would currently see real data (blocked in
+    /// mode 3).
     pub fn vram_read_accessible(&self) -> bool {
         self.vram_accessible_internal(true)
     }
 
+    /// Whether a CPU write to VRAM would currently land.
     pub fn vram_write_accessible(&self) -> bool {
         self.vram_accessible_internal(false)
     }
@@ -5130,8 +5797,10 @@ impl Ppu {
         )
     }
 
-    pub fn read_reg(&mut self, addr: u16) -> u8 {
-        let value = match addr {
+    /// Reads an LCD register (0xFF40-0xFF4B) as the CPU would see it, without
+    /// any side effects.
+    pub fn peek_reg(&self, addr: u16) -> u8 {
+        match addr {
             0xFF40 => self.lcdc,
             0xFF41 => {
                 (self.stat & 0x78)
@@ -5163,6 +5832,13 @@ impl Ppu {
             0xFF49 => self.obp1,
             0xFF4A => self.wy,
             0xFF4B => self.wx,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn read_reg(&mut self, addr: u16) -> u8 {
+        let value = match addr {
+            0xFF40..=0xFF4B => self.peek_reg(addr),
             0xFF68 => {
                 if self.cgb {
                     self.bgpi
@@ -5695,6 +6371,27 @@ impl Ppu {
         }
     }
 
+    /// Stands in for [`Self::render_scanline`] on a skipped frame, keeping the
+    /// state that outlives the line in step without drawing anything.
+    fn skip_scanline(&mut self) {
+        let cgb_render = self.is_cgb_native_mode();
+        let bg_enabled = cgb_render || (self.mode3_lcdc_base & 0x01 != 0);
+        let window_line_active = bg_enabled
+            && (self.mode3_lcdc_base & 0x20) != 0
+            && self.ly >= self.mode3_wy_base
+            && self.mode3_wx_base <= WINDOW_X_MAX;
+        if window_line_active {
+            self.win_line_counter = self.win_line_counter.wrapping_add(1);
+        }
+        if cgb_render {
+            self.dmg_prev2_line_window_active = false;
+            self.dmg_prev_line_window_active = false;
+        } else {
+            self.dmg_prev2_line_window_active = self.dmg_prev_line_window_active;
+            self.dmg_prev_line_window_active = window_line_active;
+        }
+    }
+
     fn render_scanline(&mut self) {
         if self.lcdc & 0x80 == 0 || self.ly as usize >= SCREEN_HEIGHT {
             self.dmg_prev_line_window_active = false;
@@ -5702,6 +6399,11 @@ impl Ppu {
             return;
         }
 
+        if self.skip_render {
+            self.skip_scanline();
+            return;
+        }
+
         self.line_priority.fill(false);
         self.line_color_zero.fill(false);
         self.cgb_line_obj_enabled.fill(self.lcdc & 0x02 != 0);
@@ -5718,11 +6420,13 @@ impl Ppu {
         let cgb_bg_color0 = self.cgb_bg_color_from_color_id(0, 0);
         for x in 0..SCREEN_WIDTH {
             let idx = self.ly as usize * SCREEN_WIDTH + x;
-            self.framebuffer[idx] = if cgb_render {
-                cgb_bg_color0
+            let (color, index) = if cgb_render {
+                (cgb_bg_color0, 0)
             } else {
-                self.dmg_bg_color_for_pixel(x, 0)
+                self.dmg_bg_pixel(x, 0)
             };
+            self.framebuffer[idx] = color;
+            self.framebuffer_indices[idx] = index;
             self.line_color_zero[x] = true;
         }
 
@@ -6042,17 +6746,22 @@ impl Ppu {
                     let bg_zero = !bg_enabled
                         || (!cgb_render && !self.dmg_bg_en_for_pixel(sx as usize))
                         || self.line_color_zero[sx as usize];
-                    if master_priority {
-                        if cgb_render && self.line_priority[sx as usize] && !bg_zero {
-                            continue;
-                        }
-                        if s.flags & 0x80 != 0 && !bg_zero {
-                            continue;
-                        }
+                    if master_priority
+                        && !bg_zero
+                        && ((cgb_render && self.line_priority[sx as usize]) || s.flags & 0x80 != 0)
+                    {
+                        // The highest-priority opaque OBJ pixel still owns this
+                        // position when it loses to the BG, so lower-priority
+                        // sprites must not show through.
+                        drawn[sx as usize] = true;
+                        continue;
                     }
-                    let color = if cgb_render {
-                        let palette = (s.flags & 0x07) as usize;
-                        self.cgb_obj_color_from_color_id(palette as u8, color_id)
+                    let (color, index) = if cgb_render {
+                        let palette = s.flags & 0x07;
+                        (
+                            self.cgb_obj_color_from_color_id(palette, color_id),
+                            32 + palette * 4 + color_id,
+                        )
                     } else {
                         // DMG and CGB DMG-compat both use OBP0/OBP1 mapping.
                         let (pal_reg, pal_idx) = if s.flags & 0x10 != 0 {
@@ -6066,11 +6775,20 @@ impl Ppu {
                             (self.dmg_obp0_for_pixel(sample_x), 0usize)
                         };
                         first_nonzero_obj_pixel = false;
-                        let shade = Self::dmg_shade(pal_reg, color_id) as usize;
-                        self.dmg_obj_color_from_shade(pal_idx, shade)
+                        let shade = Self::dmg_shade(pal_reg, color_id);
+                        let index = if self.dmg_compat {
+                            32 + pal_idx as u8 * 4 + shade
+                        } else {
+                            shade
+                        };
+                        (
+                            self.dmg_obj_color_from_shade(pal_idx, shade as usize),
+                            index,
+                        )
                     };
                     let idx = self.ly as usize * SCREEN_WIDTH + sx as usize;
                     self.framebuffer[idx] = color;
+                    self.framebuffer_indices[idx] = index;
                     drawn[sx as usize] = true;
                 }
             }
@@ -6165,7 +6883,14 @@ impl Ppu {
                         self.ly_for_comparison = self.ly;
                         self.update_lyc_compare();
                         if self.ly == SCREEN_HEIGHT as u8 {
+                            if !self.skip_render {
+                                self.apply_ghosting();
+                            }
+                            self.advance_frame_skip();
+                            self.capture_vblank_line(self.ly);
                             self.frame_ready = true;
+                            self.frame_count = self.frame_count.wrapping_add(1);
+                            core_event!(DEBUG, frame = self.frame_count, "frame complete");
                             self.set_mode(MODE_VBLANK);
                             if self.is_dmg_mode() {
                                 self.dmg_mode2_vblank_irq_pending = true;
@@ -6227,6 +6952,9 @@ impl Ppu {
                             if self.ly == 153 {
                                 self.cgb_line153_ly0_triggered = false;
                             }
+                            if self.ly < SCREEN_HEIGHT as u8 + VBLANK_LINES {
+                                self.capture_vblank_line(self.ly);
+                            }
                             if self.ly > SCREEN_HEIGHT as u8 + VBLANK_LINES - 1 {
                                 self.ly = 0;
                                 self.ly_for_comparison = 0;
@@ -6270,10 +6998,19 @@ impl Ppu {
                     }
                 }
                 MODE_TRANSFER => {
-                    self.mode3_latch_sprite_attributes();
+                    if !self.latch_objects_per_line {
+                        self.mode3_latch_sprite_attributes();
+                    }
                     let target = self.mode3_target_cycles;
                     if self.mode_clock >= target {
                         self.mode_clock -= target;
+                        if self.latch_objects_per_line {
+                            self.latch_unfetched_object_rows(-7..SCREEN_WIDTH as i16);
+                        } else if self.is_dmg_mode() {
+                            self.latch_unfetched_object_rows(
+                                SCREEN_WIDTH as i16 - 7..SCREEN_WIDTH as i16,
+                            );
+                        }
                         if self.is_dmg_mode() {
                             let obj_toggle_line = self.is_dmg_mode()
                                 && self.mode3_lcdc_events[..self.mode3_lcdc_event_count]
@@ -6383,9 +7120,10 @@ impl Ppu {
             let lo = self.vram_read_for_render(0, addr_lo);
             let hi = self.vram_read_for_render(0, addr_hi);
             let color_id = ((hi >> bit) & 1) << 1 | ((lo >> bit) & 1);
-            let color = self.dmg_bg_color_for_pixel(x as usize, color_id);
+            let (color, index) = self.dmg_bg_pixel(x as usize, color_id);
             let idx_fb = self.ly as usize * SCREEN_WIDTH + x as usize;
             self.framebuffer[idx_fb] = color;
+            self.framebuffer_indices[idx_fb] = index;
             // OBJ priority compares against raw BG color ID zero, not BGP-mapped shade.
             self.line_color_zero[x as usize] = color_id == 0;
         }
@@ -6428,9 +7166,10 @@ impl Ppu {
                 let lo = self.vram_read_for_render(0, addr_lo);
                 let hi = self.vram_read_for_render(0, addr_hi);
                 let color_id = ((hi >> bit) & 1) << 1 | ((lo >> bit) & 1);
-                let color = self.dmg_bg_color_for_pixel(x as usize, color_id);
+                let (color, index) = self.dmg_bg_pixel(x as usize, color_id);
                 let idx_fb = self.ly as usize * SCREEN_WIDTH + x as usize;
                 self.framebuffer[idx_fb] = color;
+                self.framebuffer_indices[idx_fb] = index;
                 if (x as usize) < SCREEN_WIDTH {
                     // Same rule for window pixels: priority uses raw color ID.
                     self.line_color_zero[x as usize] = color_id == 0;
@@ -6444,7 +7183,7 @@ impl Ppu {
     }
 
     fn render_dmg_bg_window_scanline_with_mode3_fetcher(&mut self) {
-        use std::collections::VecDeque;
+        use alloc::collections::VecDeque;
         const FETCH_GET_TILE_T1: u8 = 0;
         const FETCH_GET_TILE_T2: u8 = 1;
         const FETCH_GET_LO_T1: u8 = 2;
@@ -6678,21 +7417,25 @@ impl Ppu {
                                 }
                             }
                             let sample_t = sample_t.clamp(0, max_t) as u16;
-                            let color = if self.is_cgb_native_mode() {
+                            let (color, index) = if self.is_cgb_native_mode() {
                                 let off = (color_id as usize) * 2;
-                                Self::decode_cgb_color(self.bgpd[off], self.bgpd[off + 1])
+                                let color =
+                                    Self::decode_cgb_color(self.bgpd[off], self.bgpd[off + 1]);
+                                (color, color_id)
                             } else {
                                 let bgp = self.dmg_bgp_for_mode3_t(sample_t);
                                 let shade = Self::dmg_shade(bgp, color_id);
-                                if self.dmg_compat {
+                                let color = if self.dmg_compat {
                                     let off = (shade as usize) * 2;
                                     Self::decode_cgb_color(self.bgpd[off], self.bgpd[off + 1])
                                 } else {
                                     self.dmg_palette[shade as usize]
-                                }
+                                };
+                                (color, shade)
                             };
                             let idx_fb = self.ly as usize * SCREEN_WIDTH + out_x;
                             self.framebuffer[idx_fb] = color;
+                            self.framebuffer_indices[idx_fb] = index;
                             // Sprite priority compares against the raw BG color ID
                             // (color-0 test), not the post-BGP mapped shade.
                             self.line_color_zero[out_x] = color_id == 0;
@@ -7172,7 +7915,7 @@ impl Ppu {
     }
 
     fn render_cgb_bg_window_scanline_with_mode3_lcdc(&mut self) {
-        use std::collections::VecDeque;
+        use alloc::collections::VecDeque;
 
         #[derive(Clone, Copy)]
         struct FifoPixel {
@@ -7273,8 +8016,9 @@ impl Ppu {
                 // The window layer is not affected by SCX fine-scroll.
                 // If the window starts at or before X=0 (WX<=7), applying the
                 // background discard here causes the window to appear to lag and
-                // "catch up" when SCX changes.
-                discard = 0;
+                // "catch up" when SCX changes. Instead, WX=0-6 drops the window's
+                // own leftmost 7-WX pixels.
+                discard = 7u8.saturating_sub(wx);
                 fifo.clear();
                 fetcher_step = 0;
                 fetcher_subdot = 0;
@@ -7390,6 +8134,7 @@ impl Ppu {
                     let color = self.cgb_bg_color_from_color_id(pix.palette, pix.color_id);
                     let idx_fb = ly as usize * SCREEN_WIDTH + out_x;
                     self.framebuffer[idx_fb] = color;
+                    self.framebuffer_indices[idx_fb] = pix.palette * 4 + pix.color_id;
                     self.line_priority[out_x] = pix.priority;
                     self.line_color_zero[out_x] = pix.color_id == 0;
                     out_x += 1;
@@ -7604,6 +8349,235 @@ impl Default for Ppu {
     }
 }
 
+state_enum!(OamBugAccess {
+    OamBugAccess::Read,
+    OamBugAccess::Write,
+    OamBugAccess::ReadDuringIncDec,
+});
+
+state_record!(Sprite {
+    x,
+    y,
+    tile,
+    flags,
+    oam_index,
+    fetched,
+    obj_row_addr,
+    obj_row_valid,
+    obj_size16_low,
+    obj_lo,
+    obj_hi,
+    obj_data_valid,
+    fetch_t,
+    fetch_t_valid,
+});
+state_record!(DmgBgpEvent { t, x, val });
+state_record!(PendingRegWrite { addr, val, delay });
+state_record!(Mode3LcdcEvent {
+    t,
+    x,
+    val,
+    bg_fifo,
+    fetcher_state,
+});
+state_record!(Mode3RegEvent { t, val });
+state_record!(Mode3PopEvent {
+    t,
+    position_in_line,
+});
+
+// Hardware mode/revisions and the DMG display palette are configuration
+// rather than machine state and are not saved.
+state_fields!(Ppu {
+    vram,
+    vram_bank,
+    oam,
+    render_vram_blocked,
+    dmg_compat,
+    lcdc,
+    stat,
+    scy,
+    scx,
+    ly,
+    lyc,
+    lyc_eq_ly,
+    ly_for_comparison,
+    dma,
+    bgp,
+    obp0,
+    obp1,
+    wy,
+    wx,
+    win_line_counter,
+    bgpi,
+    bgpd,
+    obpi,
+    obpd,
+    opri,
+    mode_clock,
+    mode,
+    stat_mode,
+    stat_mode_delay,
+    mode3_target_cycles,
+    mode0_target_cycles,
+    boot_hold_cycles,
+    framebuffer,
+    framebuffer_indices,
+    line_priority,
+    line_color_zero,
+    cgb_line_obj_enabled,
+    dmg_line_lcdc_at_pixel,
+    dmg_line_mode3_t_at_pixel,
+    dmg_line_obj_size_16,
+    line_sprites,
+    sprite_count,
+    oam_scan_index,
+    oam_scan_dot,
+    oam_scan_phase,
+    mode2_y_bus,
+    mode2_x_bus,
+    oam_scan_entry_y,
+    oam_scan_entry_visible,
+    mode3_sprite_latch_index,
+    mode3_position_in_line,
+    mode3_lcd_x,
+    mode3_bg_fifo,
+    mode3_fetcher_state,
+    mode3_obj_fetch_active,
+    mode3_obj_fetch_stage,
+    mode3_obj_fetch_sprite_index,
+    mode3_render_delay,
+    mode3_last_match_x,
+    mode3_same_x_toggle,
+    oam_dma_current_dest,
+    frame_ready,
+    stat_irq_line,
+    dmg_mode2_vblank_irq_pending,
+    cgb_line153_ly0_triggered,
+    frame_counter,
+    frame_count,
+    dmg_startup_cycle,
+    dmg_startup_stage,
+    dmg_post_startup_line2,
+    dmg_line_bgp_base,
+    dmg_line_bgp_at_pixel,
+    dmg_bgp_event_count,
+    dmg_bgp_events,
+    dmg_line_obp0_base,
+    dmg_obp0_event_count,
+    dmg_obp0_events,
+    dmg_hblank_render_pending,
+    mode3_lcdc_base,
+    mode3_lcdc_event_count,
+    mode3_lcdc_events,
+    mode3_scx_base,
+    mode3_scx_event_count,
+    mode3_scx_events,
+    mode3_scy_base,
+    mode3_scy_event_count,
+    mode3_scy_events,
+    mode3_wx_base,
+    mode3_wx_event_count,
+    mode3_wx_events,
+    mode3_wy_base,
+    mode3_wy_event_count,
+    mode3_wy_events,
+    mode3_obj_fetch_base,
+    mode3_obj_fetch_event_count,
+    mode3_obj_fetch_events,
+    mode3_pop_event_count,
+    mode3_pop_events,
+    pending_reg_write_count,
+    pending_reg_writes,
+    dmg_prev_line_window_active,
+    dmg_prev2_line_window_active,
+});
+
+impl Ppu {
+    pub(crate) fn save_state(&self) -> Record {
+        let mut rec = Record::new();
+        self.save_state_fields(&mut rec);
+        rec
+    }
+
+    pub(crate) fn load_state(&mut self, rec: &Record) -> Result<(), StateError> {
+        self.load_state_fields(rec)?;
+
+        // Indices and counts are used unchecked while rendering.
+        let limits: [(&str, usize, usize); 15] = [
+            ("vram_bank", self.vram_bank, 1),
+            ("sprite_count", self.sprite_count, MAX_SPRITES_PER_LINE),
+            ("oam_scan_index", self.oam_scan_index, TOTAL_SPRITES),
+            (
+                "mode3_sprite_latch_index",
+                self.mode3_sprite_latch_index,
+                MAX_SPRITES_PER_LINE,
+            ),
+            (
+                "mode3_obj_fetch_sprite_index",
+                self.mode3_obj_fetch_sprite_index,
+                MAX_SPRITES_PER_LINE,
+            ),
+            (
+                "dmg_bgp_event_count",
+                self.dmg_bgp_event_count,
+                DMG_BGP_EVENTS_MAX,
+            ),
+            (
+                "dmg_obp0_event_count",
+                self.dmg_obp0_event_count,
+                MODE3_REG_EVENTS_MAX,
+            ),
+            (
+                "mode3_lcdc_event_count",
+                self.mode3_lcdc_event_count,
+                MODE3_LCDC_EVENTS_MAX,
+            ),
+            (
+                "mode3_scx_event_count",
+                self.mode3_scx_event_count,
+                MODE3_REG_EVENTS_MAX,
+            ),
+            (
+                "mode3_scy_event_count",
+                self.mode3_scy_event_count,
+                MODE3_REG_EVENTS_MAX,
+            ),
+            (
+                "mode3_wx_event_count",
+                self.mode3_wx_event_count,
+                MODE3_REG_EVENTS_MAX,
+            ),
+            (
+                "mode3_wy_event_count",
+                self.mode3_wy_event_count,
+                MODE3_REG_EVENTS_MAX,
+            ),
+            (
+                "mode3_obj_fetch_event_count",
+                self.mode3_obj_fetch_event_count,
+                MODE3_REG_EVENTS_MAX,
+            ),
+            (
+                "mode3_pop_event_count",
+                self.mode3_pop_event_count,
+                MODE3_POP_EVENTS_MAX,
+            ),
+            (
+                "pending_reg_write_count",
+                self.pending_reg_write_count,
+                PENDING_REG_WRITES_MAX,
+            ),
+        ];
+        for (name, value, max) in limits {
+            if value > max {
+                return Err(StateError::InvalidField(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod mode3_timing_tests {
     use super::*;
@@ -7725,7 +8699,7 @@ mod mode3_timing_tests {
                 _ => 15,
             };
             let mut xs: Vec<u8> = vec![n; 5];
-            xs.extend(std::iter::repeat_n(n + 160, 5));
+            xs.extend(core::iter::repeat_n(n + 160, 5));
             let got = dmg_mode3_cycles_with_sprites_at_oam_x(&xs);
             let expected = MODE3_CYCLES + (m * 4);
             check(format!("split_5_5_a={n}_b={}", n + 160), got, expected);
@@ -7737,7 +8711,7 @@ mod mode3_timing_tests {
                 _ => 15,
             };
             let mut xs: Vec<u8> = vec![n; 5];
-            xs.extend(std::iter::repeat_n(n + 96, 5));
+            xs.extend(core::iter::repeat_n(n + 96, 5));
             let got = dmg_mode3_cycles_with_sprites_at_oam_x(&xs);
             let expected = MODE3_CYCLES + (m * 4);
             check(format!("split_5_5_a={n}_b={}", n + 96), got, expected);
@@ -7964,6 +8938,28 @@ mod mode3_timing_tests {
         );
     }
 
+    #[test]
+    fn oam_scan_selects_off_screen_x_but_not_other_lines() {
+        let mut ppu = Ppu::new_with_mode(false);
+        ppu.write_reg(0xFF40, 0x82);
+        ppu.skip_startup_for_test();
+        // Y=16 covers line 0 whatever the X; Y=0 and Y=160 never reach it.
+        let entries = [(0, 8), (160, 8), (16, 0), (16, 168), (16, 255), (16, 80)];
+        for (i, &(y, x)) in entries.iter().enumerate() {
+            ppu.oam[i * 4] = y;
+            ppu.oam[i * 4 + 1] = x;
+        }
+        let mut if_reg = 0;
+        ppu.step(MODE2_CYCLES, &mut if_reg);
+        assert_eq!(ppu.mode, MODE_TRANSFER);
+        let mut selected: Vec<usize> = ppu.line_sprites[..ppu.sprite_count]
+            .iter()
+            .map(|s| s.oam_index)
+            .collect();
+        selected.sort_unstable();
+        assert_eq!(selected, [2, 3, 4, 5]);
+    }
+
     #[test]
     fn dmg_mode3_cycles_split_0_and_160() {
         // Mooneye expects +17 M-cycles (68 T-cycles) for 5 sprites at X=0 and 5 at X=160.
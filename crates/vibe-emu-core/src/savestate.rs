@@ -0,0 +1,678 @@
+//! Versioned save-state container.
+//!
+//! A state is a header followed by a tree of [`Record`]s. A record is an
+//! ordered list of named fields; each field holds either little-endian
+//! primitive data or a nested record. Components look their fields up by
+//! name when loading, so:
+//!
+//! - adding a field does not invalidate older states (the field keeps the
+//!   value of a freshly constructed machine), and
+//! - fields removed in newer builds are simply ignored.
+//!
+//! Changes that alter the *meaning* of an existing field bump
+//! [`STATE_VERSION`] and register an upgrade step in `MIGRATIONS`, which
+//! rewrites an older record tree in place before it is loaded.
+//!
+//! Layout:
+//!
+//! ```text
+//! state  := "VBST" version:u16 record
+//! record := count:u32 field*
+//! field  := name_len:u8 name:[u8] len:u32 value:[u8; len]
+//! ```
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::cell::Cell;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+/// Magic bytes at the start of every save state.
+pub const STATE_MAGIC: [u8; 4] = *b"VBST";
+
+/// Version written by this build.
+pub const STATE_VERSION: u16 = 1;
+
+/// Oldest version this build can upgrade and load.
+pub const OLDEST_STATE_VERSION: u16 = 1;
+
+/// Upgrades a root record from one version to the next.
+pub type Migration = fn(&mut Record) -> Result<(), StateError>;
+
+/// Upgrade steps; `MIGRATIONS[i]` turns a version `OLDEST_STATE_VERSION + i`
+/// state into version `OLDEST_STATE_VERSION + i + 1`.
+const MIGRATIONS: &[Migration] = &[];
+
+const _: () = assert!(MIGRATIONS.len() == (STATE_VERSION - OLDEST_STATE_VERSION) as usize);
+
+/// Errors produced while decoding or applying a save state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The data does not start with [`STATE_MAGIC`].
+    BadMagic,
+    /// The data ended in the middle of a header, record or field.
+    Truncated,
+    /// The state predates [`OLDEST_STATE_VERSION`] and cannot be upgraded.
+    VersionTooOld { version: u16, oldest: u16 },
+    /// The state was written by a newer build than this one.
+    VersionTooNew { version: u16, newest: u16 },
+    /// A field is present but its contents are malformed.
+    InvalidField(String),
+    /// A field required to restore the machine is missing.
+    MissingField(String),
+    /// The state was saved with a different cartridge inserted.
+    CartridgeMismatch,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => f.write_str("not a save state"),
+            StateError::Truncated => f.write_str("save state is truncated"),
+            StateError::VersionTooOld { version, oldest } => write!(
+                f,
+                "save state version {version} is too old (oldest supported is {oldest})"
+            ),
+            StateError::VersionTooNew { version, newest } => write!(
+                f,
+                "save state version {version} is newer than this build supports ({newest})"
+            ),
+            StateError::InvalidField(name) => write!(f, "save state field '{name}' is invalid"),
+            StateError::MissingField(name) => write!(f, "save state field '{name}' is missing"),
+            StateError::CartridgeMismatch => {
+                f.write_str("save state belongs to a different cartridge")
+            }
+        }
+    }
+}
+
+impl core::error::Error for StateError {}
+
+/// An ordered list of named fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    fields: Vec<(String, Vec<u8>)>,
+}
+
+impl Record {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Field names in storage order.
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Raw encoded value of a field.
+    pub fn bytes(&self, name: &str) -> Option<&[u8]> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    /// Sets a field's raw encoded value, replacing any existing value.
+    pub fn put_bytes(&mut self, name: &str, bytes: Vec<u8>) {
+        debug_assert!(name.len() <= u8::MAX as usize);
+        match self.fields.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = bytes,
+            None => self.fields.push((name.to_string(), bytes)),
+        }
+    }
+
+    /// Removes a field, returning its raw value.
+    pub fn remove(&mut self, name: &str) -> Option<Vec<u8>> {
+        let idx = self.fields.iter().position(|(n, _)| n == name)?;
+        Some(self.fields.remove(idx).1)
+    }
+
+    /// Renames a field, returning `false` if it does not exist.
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        let Some(bytes) = self.remove(from) else {
+            return false;
+        };
+        self.put_bytes(to, bytes);
+        true
+    }
+
+    /// Decodes a nested record.
+    pub fn record(&self, name: &str) -> Result<Option<Record>, StateError> {
+        self.bytes(name)
+            .map(|bytes| Record::decode(bytes).map_err(|e| e.in_field(name)))
+            .transpose()
+    }
+
+    pub fn put_record(&mut self, name: &str, record: &Record) {
+        self.put_bytes(name, record.encode());
+    }
+
+    /// Encodes the record without a state header.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    /// Decodes a record that spans all of `data`.
+    pub fn decode(mut data: &[u8]) -> Result<Record, StateError> {
+        let record = Record::decode_from(&mut data)?;
+        if !data.is_empty() {
+            return Err(StateError::InvalidField(String::new()));
+        }
+        Ok(record)
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.fields.len() as u32).to_le_bytes());
+        for (name, value) in &self.fields {
+            out.push(name.len() as u8);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            out.extend_from_slice(value);
+        }
+    }
+
+    fn decode_from(input: &mut &[u8]) -> Result<Record, StateError> {
+        let count = u32::read(input)?;
+        let mut fields = Vec::new();
+        for _ in 0..count {
+            let name_len = u8::read(input)? as usize;
+            let name = take(input, name_len)?;
+            let name = core::str::from_utf8(name)
+                .map_err(|_| StateError::InvalidField(String::from_utf8_lossy(name).into()))?;
+            let len = u32::read(input)? as usize;
+            let value = take(input, len)?;
+            fields.push((name.to_string(), value.to_vec()));
+        }
+        Ok(Record { fields })
+    }
+
+    pub(crate) fn put<T: StateValue>(&mut self, name: &str, value: &T) {
+        let mut bytes = Vec::new();
+        value.write(&mut bytes);
+        self.put_bytes(name, bytes);
+    }
+
+    /// Decodes a field into `dst`, leaving `dst` untouched if it is absent.
+    ///
+    /// A malformed field may leave `dst` partially overwritten.
+    pub(crate) fn load<T: StateValue>(&self, name: &str, dst: &mut T) -> Result<(), StateError> {
+        let Some(mut bytes) = self.bytes(name) else {
+            return Ok(());
+        };
+        dst.read_into(&mut bytes).map_err(|e| e.in_field(name))?;
+        if !bytes.is_empty() {
+            return Err(StateError::InvalidField(name.to_string()));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get<T: StateValue>(&self, name: &str) -> Result<Option<T>, StateError> {
+        let Some(mut bytes) = self.bytes(name) else {
+            return Ok(None);
+        };
+        let value = T::read(&mut bytes).map_err(|e| e.in_field(name))?;
+        if !bytes.is_empty() {
+            return Err(StateError::InvalidField(name.to_string()));
+        }
+        Ok(Some(value))
+    }
+
+    pub(crate) fn require<T: StateValue>(&self, name: &str) -> Result<T, StateError> {
+        self.get(name)?
+            .ok_or_else(|| StateError::MissingField(name.to_string()))
+    }
+}
+
+impl StateError {
+    /// Attributes a decoding failure inside a field to that field.
+    pub(crate) fn in_field(self, name: &str) -> StateError {
+        match self {
+            StateError::Truncated => StateError::InvalidField(name.to_string()),
+            StateError::InvalidField(inner) if inner.is_empty() => {
+                StateError::InvalidField(name.to_string())
+            }
+            StateError::InvalidField(inner) => StateError::InvalidField(format!("{name}.{inner}")),
+            other => other,
+        }
+    }
+}
+
+/// Path of quick-save slot `slot` for the ROM at `rom`: `<rom>.state<slot>`
+/// in the same directory.
+#[cfg(feature = "std")]
+pub fn slot_path(rom: &Path, slot: u8) -> PathBuf {
+    rom.with_extension(format!("state{slot}"))
+}
+
+/// Errors produced while loading a quick-save slot.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum SlotLoadError {
+    /// Nothing has been saved to the slot yet.
+    Empty(u8),
+    /// The slot file exists but could not be read.
+    Io(io::Error),
+    /// The slot file is not a state this machine can load.
+    State(StateError),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for SlotLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotLoadError::Empty(slot) => write!(f, "slot {slot} is empty"),
+            SlotLoadError::Io(e) => write!(f, "failed to read slot: {e}"),
+            SlotLoadError::State(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::error::Error for SlotLoadError {}
+
+/// Wraps a root record in a state header.
+pub fn encode_state(root: &Record) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&STATE_MAGIC);
+    out.extend_from_slice(&STATE_VERSION.to_le_bytes());
+    root.encode_into(&mut out);
+    out
+}
+
+/// Parses a state header and record, upgrading older versions to
+/// [`STATE_VERSION`].
+pub fn decode_state(data: &[u8]) -> Result<Record, StateError> {
+    let (version, mut root) = decode_state_raw(data)?;
+    migrate(&mut root, version, MIGRATIONS)?;
+    Ok(root)
+}
+
+/// Parses a state header and record without upgrading it.
+pub fn decode_state_raw(data: &[u8]) -> Result<(u16, Record), StateError> {
+    if data.len() < STATE_MAGIC.len() {
+        return Err(StateError::Truncated);
+    }
+    if data[..4] != STATE_MAGIC {
+        return Err(StateError::BadMagic);
+    }
+    let mut input = &data[4..];
+    let version = u16::read(&mut input)?;
+    let newest = OLDEST_STATE_VERSION + MIGRATIONS.len() as u16;
+    if version < OLDEST_STATE_VERSION {
+        return Err(StateError::VersionTooOld {
+            version,
+            oldest: OLDEST_STATE_VERSION,
+        });
+    }
+    if version > newest {
+        return Err(StateError::VersionTooNew { version, newest });
+    }
+    let root = Record::decode_from(&mut input)?;
+    if !input.is_empty() {
+        return Err(StateError::InvalidField(String::new()));
+    }
+    Ok((version, root))
+}
+
+/// Applies the upgrade steps needed to bring a `version` record up to date.
+///
+/// `migrations[i]` upgrades version `OLDEST_STATE_VERSION + i`.
+fn migrate(root: &mut Record, version: u16, migrations: &[Migration]) -> Result<(), StateError> {
+    let first = (version - OLDEST_STATE_VERSION) as usize;
+    for step in &migrations[first..] {
+        step(root)?;
+    }
+    Ok(())
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], StateError> {
+    if input.len() < len {
+        return Err(StateError::Truncated);
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Ok(head)
+}
+
+/// A value that can be stored in a state field.
+pub(crate) trait StateValue: Sized {
+    fn write(&self, out: &mut Vec<u8>);
+    fn read(input: &mut &[u8]) -> Result<Self, StateError>;
+
+    /// Decodes in place. Arrays override this so large buffers (VRAM,
+    /// framebuffers) are not built up on the stack first.
+    fn read_into(&mut self, input: &mut &[u8]) -> Result<(), StateError> {
+        *self = Self::read(input)?;
+        Ok(())
+    }
+}
+
+macro_rules! state_value_int {
+    ($($ty:ty),*) => {$(
+        impl StateValue for $ty {
+            fn write(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+                let bytes = take(input, core::mem::size_of::<$ty>())?;
+                Ok(<$ty>::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        }
+    )*};
+}
+
+state_value_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl StateValue for usize {
+    fn write(&self, out: &mut Vec<u8>) {
+        (*self as u64).write(out);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        usize::try_from(u64::read(input)?).map_err(|_| StateError::InvalidField(String::new()))
+    }
+}
+
+impl StateValue for bool {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        match u8::read(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::InvalidField(String::new())),
+        }
+    }
+}
+
+impl StateValue for f32 {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.to_bits().write(out);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        Ok(f32::from_bits(u32::read(input)?))
+    }
+}
+
+impl<T: StateValue, const N: usize> StateValue for [T; N] {
+    fn write(&self, out: &mut Vec<u8>) {
+        for v in self {
+            v.write(out);
+        }
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        let items = (0..N)
+            .map(|_| T::read(input))
+            .collect::<Result<Vec<T>, _>>()?;
+        items
+            .try_into()
+            .map_err(|_| StateError::InvalidField(String::new()))
+    }
+
+    fn read_into(&mut self, input: &mut &[u8]) -> Result<(), StateError> {
+        for v in self {
+            v.read_into(input)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: StateValue> StateValue for Vec<T> {
+    fn write(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).write(out);
+        for v in self {
+            v.write(out);
+        }
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        let len = u32::read(input)? as usize;
+        (0..len).map(|_| T::read(input)).collect()
+    }
+}
+
+impl<T: StateValue> StateValue for Option<T> {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Some(v) => {
+                out.push(1);
+                v.write(out);
+            }
+            None => out.push(0),
+        }
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        match bool::read(input)? {
+            true => Ok(Some(T::read(input)?)),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<T: StateValue + Copy> StateValue for Cell<T> {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.get().write(out);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        Ok(Cell::new(T::read(input)?))
+    }
+}
+
+impl StateValue for Record {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.encode_into(out);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, StateError> {
+        Record::decode_from(input)
+    }
+}
+
+/// Implements [`StateValue`] for a fieldless enum via its discriminant.
+macro_rules! state_enum {
+    ($ty:ty { $($variant:path),+ $(,)? }) => {
+        impl crate::savestate::StateValue for $ty {
+            fn write(&self, out: &mut ::alloc::vec::Vec<u8>) {
+                let all = [$($variant),+];
+                let idx = all.iter().position(|v| v == self).unwrap() as u8;
+                out.push(idx);
+            }
+
+            fn read(input: &mut &[u8]) -> Result<Self, crate::savestate::StateError> {
+                let all = [$($variant),+];
+                let idx = <u8 as crate::savestate::StateValue>::read(input)? as usize;
+                all.get(idx)
+                    .copied()
+                    .ok_or_else(|| crate::savestate::StateError::InvalidField(::alloc::string::String::new()))
+            }
+        }
+    };
+}
+
+/// Generates `save_state_fields`/`load_state_fields` for the listed members,
+/// tagging each with its field name. Absent fields are left untouched on load.
+macro_rules! state_fields {
+    ($ty:ty { $($field:ident),+ $(,)? }) => {
+        impl $ty {
+            fn save_state_fields(&self, rec: &mut crate::savestate::Record) {
+                $(rec.put(stringify!($field), &self.$field);)+
+            }
+
+            fn load_state_fields(
+                &mut self,
+                rec: &crate::savestate::Record,
+            ) -> Result<(), crate::savestate::StateError> {
+                $(rec.load(stringify!($field), &mut self.$field)?;)+
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Implements [`StateValue`] for a struct as a nested record with one field
+/// per listed member. Members missing from the record keep their `Default`
+/// value.
+macro_rules! state_record {
+    ($ty:ty { $($field:ident),+ $(,)? }) => {
+        crate::savestate::state_fields!($ty { $($field),+ });
+
+        impl crate::savestate::StateValue for $ty {
+            fn write(&self, out: &mut ::alloc::vec::Vec<u8>) {
+                let mut rec = crate::savestate::Record::new();
+                self.save_state_fields(&mut rec);
+                crate::savestate::StateValue::write(&rec, out);
+            }
+
+            fn read(input: &mut &[u8]) -> Result<Self, crate::savestate::StateError> {
+                let rec = <crate::savestate::Record as crate::savestate::StateValue>::read(input)?;
+                let mut value = <$ty>::default();
+                value.load_state_fields(&rec)?;
+                Ok(value)
+            }
+        }
+    };
+}
+
+pub(crate) use {state_enum, state_fields, state_record};
+
+state_enum!(crate::hardware::DmgRevision {
+    crate::hardware::DmgRevision::Rev0,
+    crate::hardware::DmgRevision::RevA,
+    crate::hardware::DmgRevision::RevB,
+    crate::hardware::DmgRevision::RevC,
+});
+
+state_enum!(crate::hardware::CgbRevision {
+    crate::hardware::CgbRevision::Rev0,
+    crate::hardware::CgbRevision::RevA,
+    crate::hardware::CgbRevision::RevB,
+    crate::hardware::CgbRevision::RevC,
+    crate::hardware::CgbRevision::RevD,
+    crate::hardware::CgbRevision::RevE,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_round_trip() {
+        let mut inner = Record::new();
+        inner.put("x", &0x1234u16);
+        let mut rec = Record::new();
+        rec.put("flag", &true);
+        rec.put("bytes", &[1u8, 2, 3]);
+        rec.put("opt", &Some(7i32));
+        rec.put_record("inner", &inner);
+
+        let decoded = Record::decode(&rec.encode()).unwrap();
+        assert_eq!(decoded, rec);
+        assert_eq!(decoded.get::<bool>("flag").unwrap(), Some(true));
+        assert_eq!(decoded.get::<[u8; 3]>("bytes").unwrap(), Some([1, 2, 3]));
+        assert_eq!(decoded.get::<Option<i32>>("opt").unwrap(), Some(Some(7)));
+        let inner = decoded.record("inner").unwrap().unwrap();
+        assert_eq!(inner.get::<u16>("x").unwrap(), Some(0x1234));
+        assert_eq!(decoded.get::<u8>("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn slot_path_replaces_rom_extension() {
+        assert_eq!(
+            slot_path(Path::new("roms/game.gbc"), 3),
+            PathBuf::from("roms/game.state3")
+        );
+        assert_eq!(
+            slot_path(Path::new("game"), 1),
+            PathBuf::from("game.state1")
+        );
+    }
+
+    #[test]
+    fn wrong_size_field_is_invalid() {
+        let mut rec = Record::new();
+        rec.put("v", &1u32);
+        assert_eq!(
+            rec.get::<u16>("v"),
+            Err(StateError::InvalidField("v".into()))
+        );
+        assert_eq!(
+            rec.get::<u64>("v"),
+            Err(StateError::InvalidField("v".into()))
+        );
+    }
+
+    #[test]
+    fn migrations_run_in_order_from_saved_version() {
+        fn v1_to_v2(root: &mut Record) -> Result<(), StateError> {
+            assert!(root.rename("old", "new"));
+            Ok(())
+        }
+        fn v2_to_v3(root: &mut Record) -> Result<(), StateError> {
+            let v: u8 = root.require("new")?;
+            root.put("new", &(v as u16 * 2));
+            Ok(())
+        }
+        let steps: &[Migration] = &[v1_to_v2, v2_to_v3];
+
+        let mut root = Record::new();
+        root.put("old", &21u8);
+        migrate(&mut root, OLDEST_STATE_VERSION, steps).unwrap();
+        assert_eq!(root.get::<u16>("new").unwrap(), Some(42));
+
+        // A state saved at the second version only needs the last step.
+        let mut root = Record::new();
+        root.put("new", &5u8);
+        migrate(&mut root, OLDEST_STATE_VERSION + 1, steps).unwrap();
+        assert_eq!(root.get::<u16>("new").unwrap(), Some(10));
+    }
+
+    #[test]
+    fn header_version_checks() {
+        let mut data = encode_state(&Record::new());
+        assert!(decode_state(&data).is_ok());
+
+        data[4..6].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            decode_state(&data),
+            Err(StateError::VersionTooNew {
+                version: STATE_VERSION + 1,
+                newest: STATE_VERSION
+            })
+        );
+
+        data[4..6].copy_from_slice(&(OLDEST_STATE_VERSION - 1).to_le_bytes());
+        assert_eq!(
+            decode_state(&data),
+            Err(StateError::VersionTooOld {
+                version: OLDEST_STATE_VERSION - 1,
+                oldest: OLDEST_STATE_VERSION
+            })
+        );
+
+        assert_eq!(decode_state(b"NOPE\x01\x00"), Err(StateError::BadMagic));
+        assert_eq!(decode_state(&data[..5]), Err(StateError::Truncated));
+    }
+}
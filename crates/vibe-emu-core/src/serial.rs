@@ -1,4 +1,8 @@
+#[cfg(feature = "std")]
+use crate::gameboy::{GameBoy, RunBudget};
 use crate::hardware::DmgRevision;
+use crate::savestate::{Record, StateError, state_fields, state_record};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
 
 /// Clock information for an in-flight serial transfer.
 ///
@@ -54,6 +58,148 @@ pub trait LinkPort: Send {
     fn try_external_transfer(&mut self, byte: u8) -> Option<u8> {
         self.try_transfer(byte)
     }
+
+    /// Called while an internally-clocked transfer is stalled waiting on
+    /// [`LinkPort::try_transfer_with_clock`], with the number of DIV ticks
+    /// that passed since the last poll.
+    ///
+    /// Returning `Some(byte)` gives up on the partner and completes the
+    /// transfer with `byte`. The default waits indefinitely.
+    fn stalled(&mut self, _cycles: u32) -> Option<u8> {
+        None
+    }
+
+    /// Called while an externally-clocked transfer of `byte` waits for its
+    /// clock, with the dot cycles that passed since the last call. Returns
+    /// how many clock pulses the partner sent in that time.
+    ///
+    /// This lets partners emulated in the same process drive the clock. The
+    /// default sends none; hosts that clock the transfer themselves call
+    /// [`Serial::external_clock_pulse`] instead.
+    fn external_clock(&mut self, _byte: u8, _dots: u32) -> u8 {
+        0
+    }
+}
+
+/// Default [`AsyncLinkAdapter`] stall budget: one frame's worth of DIV ticks
+/// (about 16.7 ms in normal speed).
+pub const DEFAULT_LINK_STALL_BUDGET: u32 = 456 * 154;
+
+/// Poll-based link endpoint for peers that answer some time after a byte is
+/// sent, such as a network socket.
+///
+/// Wrap it in an [`AsyncLinkAdapter`] to attach it with [`Serial::connect`].
+pub trait AsyncLinkPort: Send {
+    /// Sends `byte` (on the first call) and checks for the partner's byte.
+    ///
+    /// Called repeatedly with the same byte until it returns `Some`, or until
+    /// the adapter gives up and calls [`AsyncLinkPort::cancel`]. Must not
+    /// block.
+    fn try_transfer(&mut self, byte: u8) -> Option<u8>;
+
+    /// Abandons the exchange in progress after the adapter timed out. A late
+    /// reply to it should be discarded.
+    fn cancel(&mut self) {}
+}
+
+/// Adapts an [`AsyncLinkPort`] into a [`LinkPort`].
+///
+/// While the peer has not answered, the serial unit holds the shift clock of
+/// an internally-clocked transfer, so the game just sees a slower transfer.
+/// Once the stall budget (in DIV ticks: 4194304 per second, twice that in CGB
+/// double speed) runs out, the transfer completes with 0xFF as if no cable
+/// were attached. Blocking [`LinkPort::transfer`] calls have no emulated
+/// clock and instead wait for the same span of wall-clock time.
+///
+/// Externally-clocked transfers are never timed out: the partner drives the
+/// clock, so they simply wait for it.
+pub struct AsyncLinkAdapter<P> {
+    port: P,
+    stall_budget: u32,
+    waited: u32,
+}
+
+impl<P: AsyncLinkPort> AsyncLinkAdapter<P> {
+    /// Wraps `port` with [`DEFAULT_LINK_STALL_BUDGET`].
+    pub fn new(port: P) -> Self {
+        Self::with_stall_budget(port, DEFAULT_LINK_STALL_BUDGET)
+    }
+
+    /// Wraps `port`, giving up on a reply after `cycles` DIV ticks.
+    pub fn with_stall_budget(port: P, cycles: u32) -> Self {
+        Self {
+            port,
+            stall_budget: cycles,
+            waited: 0,
+        }
+    }
+
+    pub fn stall_budget(&self) -> u32 {
+        self.stall_budget
+    }
+
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+
+    fn give_up(&mut self) -> u8 {
+        self.waited = 0;
+        self.port.cancel();
+        0xFF
+    }
+}
+
+impl<P: AsyncLinkPort> LinkPort for AsyncLinkAdapter<P> {
+    #[cfg(feature = "std")]
+    fn transfer(&mut self, byte: u8) -> u8 {
+        let timeout = std::time::Duration::from_nanos(
+            u64::from(self.stall_budget) * 1_000_000_000 / 4_194_304,
+        );
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            if let Some(incoming) = LinkPort::try_transfer(self, byte) {
+                return incoming;
+            }
+            if std::time::Instant::now() >= deadline {
+                return self.give_up();
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    /// Without a host clock, polls the partner up to the stall budget's
+    /// worth of times before giving up.
+    #[cfg(not(feature = "std"))]
+    fn transfer(&mut self, byte: u8) -> u8 {
+        for _ in 0..self.stall_budget.max(1) {
+            if let Some(incoming) = LinkPort::try_transfer(self, byte) {
+                return incoming;
+            }
+            core::hint::spin_loop();
+        }
+        self.give_up()
+    }
+
+    fn try_transfer(&mut self, byte: u8) -> Option<u8> {
+        let incoming = self.port.try_transfer(byte);
+        if incoming.is_some() {
+            self.waited = 0;
+        }
+        incoming
+    }
+
+    fn stalled(&mut self, cycles: u32) -> Option<u8> {
+        self.waited = self.waited.saturating_add(cycles);
+        (self.waited >= self.stall_budget).then(|| self.give_up())
+    }
 }
 
 /// A stub link port used when no cable is attached.
@@ -81,6 +227,150 @@ impl LinkPort for NullLinkPort {
     }
 }
 
+/// Link cable between two machines emulated in the same process, for
+/// deterministic tests of link games without networking.
+///
+/// Attach one end from [`Self::ends`] to each machine. When one side starts
+/// an internally-clocked transfer while the other is waiting on an
+/// externally-clocked one, the bytes are swapped and the bus clocks the
+/// waiting side at the first side's bit rate, so both finish about the same
+/// time. A side that starts an internally-clocked transfer with no partner
+/// waiting reads 0xFF, as with no cable.
+///
+/// Run the two machines with [`Self::step_locked`] so their clocks stay
+/// close and every run with the same inputs is byte-for-byte identical.
+/// A transfer takes 8 bits of the clocking side's rate: 4096 dots at the
+/// normal 8192 Hz clock, or 128 dots with the CGB fast clock, both halved in
+/// double speed.
+#[cfg(feature = "std")]
+pub struct LinkBus {
+    sides: std::sync::Arc<std::sync::Mutex<[BusSide; 2]>>,
+}
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct BusSide {
+    /// Byte this side is waiting to send as the externally-clocked partner.
+    listening: Option<u8>,
+    /// Byte received from the clocking partner, not yet latched.
+    received: Option<u8>,
+    /// Clock pulses still to deliver to this side.
+    pulses_left: u8,
+    dots_per_bit: u32,
+    dots: u32,
+}
+
+#[cfg(feature = "std")]
+impl LinkBus {
+    pub fn new() -> Self {
+        Self {
+            sides: Default::default(),
+        }
+    }
+
+    /// Returns the two ends of the cable.
+    pub fn ends(&self) -> (LinkBusEnd, LinkBusEnd) {
+        let end = |side| LinkBusEnd {
+            sides: std::sync::Arc::clone(&self.sides),
+            side,
+        };
+        (end(0), end(1))
+    }
+
+    /// Dot cycles [`Self::step_locked`] runs one machine before switching to
+    /// the other.
+    pub const LOCKSTEP_SLICE: u64 = 16;
+
+    /// Runs two machines attached to this bus for `cycles` dot cycles each,
+    /// alternating in [`Self::LOCKSTEP_SLICE`] steps with `a` going first.
+    ///
+    /// Each step runs a machine until its [`GameBoy::cycles`] reaches the
+    /// next slice boundary counted from the call's start, so the two clocks
+    /// never drift further apart than one slice plus one instruction, and
+    /// the outcome depends only on the machines' states and `cycles`, never
+    /// on host timing.
+    pub fn step_locked(&self, a: &mut GameBoy, b: &mut GameBoy, cycles: u64) {
+        let starts = [a.cycles(), b.cycles()];
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed = (elapsed + Self::LOCKSTEP_SLICE).min(cycles);
+            for (gb, start) in [&mut *a, &mut *b].into_iter().zip(starts) {
+                // Runs that stop early, e.g. on a halt loop, are resumed so
+                // an idle machine keeps time with its partner.
+                while gb.cycles() - start < elapsed {
+                    gb.run(RunBudget::Cycles(elapsed - (gb.cycles() - start)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for LinkBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One end of a [`LinkBus`].
+#[cfg(feature = "std")]
+pub struct LinkBusEnd {
+    sides: std::sync::Arc<std::sync::Mutex<[BusSide; 2]>>,
+    side: usize,
+}
+
+#[cfg(feature = "std")]
+impl LinkBusEnd {
+    fn with_sides<R>(&self, f: impl FnOnce(&mut BusSide, &mut BusSide) -> R) -> R {
+        let mut sides = self.sides.lock().unwrap_or_else(|e| e.into_inner());
+        let [first, second] = &mut *sides;
+        if self.side == 0 {
+            f(first, second)
+        } else {
+            f(second, first)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl LinkPort for LinkBusEnd {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        self.try_transfer_with_clock(byte, SerialTransferClock::default())
+            .unwrap_or(0xFF)
+    }
+
+    fn try_transfer_with_clock(&mut self, byte: u8, clock: SerialTransferClock) -> Option<u8> {
+        self.with_sides(|_, other| {
+            let Some(incoming) = other.listening.take() else {
+                return Some(0xFF);
+            };
+            other.received = Some(byte);
+            other.pulses_left = 8;
+            other.dots_per_bit = clock.dot_cycles_per_bit.max(1);
+            other.dots = 0;
+            Some(incoming)
+        })
+    }
+
+    fn try_external_transfer(&mut self, _byte: u8) -> Option<u8> {
+        self.with_sides(|this, _| this.received.take())
+    }
+
+    fn external_clock(&mut self, byte: u8, dots: u32) -> u8 {
+        self.with_sides(|this, _| {
+            if this.pulses_left == 0 {
+                this.listening = Some(byte);
+                return 0;
+            }
+            this.dots += dots;
+            let pulses = (this.dots / this.dots_per_bit).min(u32::from(this.pulses_left)) as u8;
+            this.dots -= u32::from(pulses) * this.dots_per_bit;
+            this.pulses_left -= pulses;
+            pulses
+        })
+    }
+}
+
 /// Represents the Game Boy serial registers.
 /// This struct handles SB/SC behavior and raises the serial interrupt
 /// when a transfer completes.
@@ -90,11 +380,14 @@ pub struct Serial {
     pub(crate) out_buf: Vec<u8>,
     sb_out_buf: Vec<u8>,
     port: Box<dyn LinkPort + Send>,
+    /// Bytes queued by [`Serial::inject_byte`], received ahead of the port.
+    injected: VecDeque<u8>,
     transfer: Option<TransferState>,
     cgb_mode: bool,
     dmg_revision: DmgRevision,
 }
 
+#[derive(Default)]
 struct TransferState {
     remaining_bits: u8,
     outgoing: u8,
@@ -137,6 +430,19 @@ impl TransferState {
     }
 }
 
+state_record!(TransferState {
+    remaining_bits,
+    outgoing,
+    pending_in,
+    incoming_latched,
+    internal_clock,
+    fast_clock,
+});
+
+// The attached port, injected bytes and captured output buffers belong to the
+// host and are not part of the machine state.
+state_fields!(Serial { sb, sc, transfer });
+
 impl Serial {
     /// Creates a new serial unit.
     pub fn new(cgb: bool, dmg_revision: DmgRevision) -> Self {
@@ -146,17 +452,55 @@ impl Serial {
             out_buf: Vec::new(),
             sb_out_buf: Vec::new(),
             port: Box::new(NullLinkPort::default()),
+            injected: VecDeque::new(),
             transfer: None,
             cgb_mode: cgb,
             dmg_revision,
         }
     }
 
+    /// Changes the DMG revision used for the serial clock phase.
+    pub fn set_dmg_revision(&mut self, dmg_revision: DmgRevision) {
+        self.dmg_revision = dmg_revision;
+    }
+
+    pub(crate) fn save_state(&self) -> Record {
+        let mut rec = Record::new();
+        self.save_state_fields(&mut rec);
+        rec
+    }
+
+    pub(crate) fn load_state(&mut self, rec: &Record) -> Result<(), StateError> {
+        self.load_state_fields(rec)
+    }
+
+    /// Moves the attached link port from `other` into this unit.
+    pub(crate) fn adopt_port(&mut self, other: &mut Serial) {
+        core::mem::swap(&mut self.port, &mut other.port);
+    }
+
     /// Attaches a link cable endpoint.
     pub fn connect(&mut self, port: Box<dyn LinkPort + Send>) {
         self.port = port;
     }
 
+    /// Unplugs the attached endpoint, leaving a [`NullLinkPort`] in its
+    /// place, and returns it.
+    pub fn disconnect(&mut self) -> Box<dyn LinkPort + Send> {
+        core::mem::replace(&mut self.port, Box::new(NullLinkPort::default()))
+    }
+
+    /// Queues `byte` to be received by the next transfer in place of the
+    /// connected port's reply, as if the partner had sent it. Queued bytes
+    /// are used in order, one per transfer; the port is not consulted for
+    /// those transfers.
+    ///
+    /// Together with [`Self::take_output`] this gives homebrew a two-way
+    /// debug console over the link port.
+    pub fn inject_byte(&mut self, byte: u8) {
+        self.injected.push_back(byte);
+    }
+
     /// Reads the SB/SC registers.
     pub fn read(&self, addr: u16) -> u8 {
         match addr {
@@ -267,17 +611,33 @@ impl Serial {
             return;
         };
         if !state.internal_clock {
+            let outgoing = state.outgoing;
+            let dots = u32::from(curr_div.wrapping_sub(prev_div));
+            let pulses = self.port.external_clock(outgoing, dots);
+            if pulses > 0 {
+                self.external_clock_pulse(pulses, if_reg);
+            }
             return;
         }
 
-        // In master mode, defer clocking until we have the partner byte.
+        // In master mode, defer clocking until we have the partner byte, or
+        // the port gives up waiting for it.
         if !self.poll_transfer_byte(true, double_speed) {
-            return;
+            let waited = u32::from(curr_div.wrapping_sub(prev_div));
+            let Some(incoming) = self.port.stalled(waited) else {
+                return;
+            };
+            if let Some(state) = self.transfer.as_mut() {
+                state.latch_incoming(incoming);
+            }
         }
 
         let (clock_bit, phase) = if let Some(state) = self.transfer.as_ref() {
+            // A bit shifts on each falling edge of the divider bit whose
+            // full cycle is one bit period.
+            let period = self.clock_period(state.fast_clock, double_speed);
             (
-                clock_bit_index(self.cgb_mode, double_speed, state.fast_clock),
+                period.trailing_zeros() - 1,
                 self.phase_adjust(double_speed, state.fast_clock),
             )
         } else {
@@ -341,6 +701,38 @@ impl Serial {
             .is_some_and(|state| !state.internal_clock)
     }
 
+    /// Lower bound, in dot cycles, on when an internally clocked transfer in
+    /// progress completes and requests the serial interrupt. `None` when no
+    /// such transfer is running (an external clock is driven by the partner).
+    pub fn dots_until_transfer_done(&self, double_speed: bool) -> Option<u32> {
+        let state = self
+            .transfer
+            .as_ref()
+            .filter(|state| state.internal_clock)?;
+        let per_bit = self.clock_period(state.fast_clock, double_speed);
+        Some(u32::from(state.remaining_bits.saturating_sub(1)) * per_bit)
+    }
+
+    /// Dot cycles per bit of an internally clocked transfer, from the table
+    /// on [`serial_dot_cycles_per_bit`]: SC bit 1 picks the CGB fast clock
+    /// and `double_speed` halves the period. Both are ignored on DMG, which
+    /// always shifts at 8192 Hz. A whole byte takes eight periods.
+    ///
+    /// Reflects the clock of the transfer in progress, otherwise the one SC
+    /// selects for the next. Externally clocked transfers run at the
+    /// partner's rate instead.
+    pub fn bit_period_cycles(&self, double_speed: bool) -> u32 {
+        let fast_clock = match &self.transfer {
+            Some(state) => state.fast_clock,
+            None => self.sc & 0x02 != 0,
+        };
+        self.clock_period(fast_clock, double_speed)
+    }
+
+    fn clock_period(&self, fast_clock: bool, double_speed: bool) -> u32 {
+        serial_dot_cycles_per_bit(self.cgb_mode && fast_clock, self.cgb_mode && double_speed)
+    }
+
     /// Returns the outgoing byte for a pending external clock transfer.
     ///
     /// Returns `None` if no external clock transfer is pending.
@@ -351,6 +743,19 @@ impl Serial {
             .map(|state| state.outgoing)
     }
 
+    /// The serial shift register (SB). During a transfer it holds the
+    /// outgoing byte shifted left by [`Self::bits_transferred`] bits, with
+    /// the incoming bits shifted in from the right.
+    pub fn shift_register(&self) -> u8 {
+        self.sb
+    }
+
+    /// Bits shifted so far in the transfer in progress (0-7), or `None`
+    /// when no transfer is running.
+    pub fn bits_transferred(&self) -> Option<u8> {
+        self.transfer.as_ref().map(|state| 8 - state.remaining_bits)
+    }
+
     fn phase_adjust(&self, double_speed: bool, fast_clock: bool) -> u16 {
         if self.cgb_mode {
             return 0;
@@ -375,10 +780,12 @@ impl Serial {
         }
 
         let outgoing = state.outgoing;
-        let incoming = if internal_clock {
+        let incoming = if let Some(byte) = self.injected.pop_front() {
+            Some(byte)
+        } else if internal_clock {
             let clock = if self.cgb_mode {
                 SerialTransferClock {
-                    dot_cycles_per_bit: serial_dot_cycles_per_bit(state.fast_clock, double_speed),
+                    dot_cycles_per_bit: self.clock_period(state.fast_clock, double_speed),
                     high_speed: state.fast_clock,
                     double_speed,
                 }
@@ -427,20 +834,6 @@ pub fn serial_dot_cycles_per_bit(high_speed: bool, double_speed: bool) -> u32 {
     }
 }
 
-fn clock_bit_index(cgb_mode: bool, double_speed: bool, fast_clock: bool) -> u32 {
-    if !cgb_mode {
-        // DMG hardware has no double-speed mode.
-        8
-    } else {
-        match (fast_clock, double_speed) {
-            (false, false) => 8,
-            (false, true) => 7,
-            (true, false) => 3,
-            (true, true) => 2,
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::{LinkPort, Serial, serial_dot_cycles_per_bit};
@@ -0,0 +1,202 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::gameboy::{GameBoy, PowerState};
+
+/// Copy of the machine state a telemetry or debugging tool needs, taken by
+/// [`GameBoy::publish_snapshot`].
+///
+/// Everything is read without side effects, so capturing never changes how
+/// the emulation proceeds. Capturing copies the registers below and the
+/// 160x144 framebuffer into storage the snapshot already owns; nothing is
+/// allocated after [`Snapshot::new`].
+#[derive(Clone)]
+pub struct Snapshot {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+    pub power: PowerState,
+    pub double_speed: bool,
+    /// [`GameBoy::cycles`] at capture time.
+    pub cycles: u64,
+    /// Frames completed by the PPU, as [`Ppu::frames`](crate::ppu::Ppu::frames).
+    pub frame: u64,
+    pub if_reg: u8,
+    pub ie_reg: u8,
+    /// LCD registers FF40-FF4B as a CPU read would see them, indexed by
+    /// `addr - 0xFF40` (LCDC, STAT, SCY, SCX, LY, LYC, DMA, BGP, OBP0, OBP1,
+    /// WY, WX).
+    pub lcd_regs: [u8; 12],
+    /// DIV, TIMA, TMA and TAC as a CPU read would see them.
+    pub timer_regs: [u8; 4],
+    /// ROM bank mapped at 4000-7FFF; 0 without a cartridge.
+    pub rom_bank: u16,
+    /// The PPU's framebuffer. Mid-frame, the lines from LY down still hold
+    /// the previous frame.
+    pub framebuffer: Box<[u32; 160 * 144]>,
+}
+
+impl Snapshot {
+    pub fn new() -> Self {
+        Self {
+            a: 0,
+            f: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
+            sp: 0,
+            pc: 0,
+            ime: false,
+            power: PowerState::Running,
+            double_speed: false,
+            cycles: 0,
+            frame: 0,
+            if_reg: 0,
+            ie_reg: 0,
+            lcd_regs: [0; 12],
+            timer_regs: [0; 4],
+            rom_bank: 0,
+            framebuffer: Box::new([0; 160 * 144]),
+        }
+    }
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameBoy {
+    /// Copies the state described on [`Snapshot`] into `out`.
+    ///
+    /// Takes `&self` and allocates nothing, so it is cheap enough to call
+    /// after every frame. To hand snapshots to another thread without
+    /// locking, use [`snapshot_channel`].
+    pub fn publish_snapshot(&self, out: &mut Snapshot) {
+        let cpu = &self.cpu;
+        out.a = cpu.a;
+        out.f = cpu.f;
+        out.b = cpu.b;
+        out.c = cpu.c;
+        out.d = cpu.d;
+        out.e = cpu.e;
+        out.h = cpu.h;
+        out.l = cpu.l;
+        out.sp = cpu.sp;
+        out.pc = cpu.pc;
+        out.ime = cpu.ime;
+        out.power = self.power_state();
+        out.double_speed = cpu.double_speed;
+        out.cycles = self.cycles();
+
+        let mmu = &self.mmu;
+        out.frame = mmu.ppu.frames();
+        out.if_reg = mmu.if_reg;
+        out.ie_reg = mmu.ie_reg;
+        for (addr, reg) in (0xFF40..).zip(out.lcd_regs.iter_mut()) {
+            *reg = mmu.ppu.peek_reg(addr);
+        }
+        for (addr, reg) in (0xFF04..).zip(out.timer_regs.iter_mut()) {
+            *reg = mmu.timer.read(addr);
+        }
+        out.rom_bank = mmu.cart.as_ref().map_or(0, |c| c.current_rom_bank());
+        out.framebuffer.copy_from_slice(mmu.ppu.framebuffer());
+    }
+}
+
+/// Hands [`Snapshot`]s from the emulation thread to one reader without locks.
+///
+/// Three snapshots are shared: the publisher fills one, the reader holds one,
+/// and the third is the newest complete snapshot waiting to be picked up.
+/// Publishing and reading only swap indices, so neither side ever waits for
+/// the other; a reader polling less often than snapshots are published simply
+/// skips the ones in between.
+pub fn snapshot_channel() -> (SnapshotPublisher, SnapshotReader) {
+    let inner = Arc::new(Inner {
+        slots: [
+            UnsafeCell::new(Snapshot::new()),
+            UnsafeCell::new(Snapshot::new()),
+            UnsafeCell::new(Snapshot::new()),
+        ],
+        shared: AtomicUsize::new(1),
+    });
+
+    (
+        SnapshotPublisher {
+            inner: Arc::clone(&inner),
+            back: 0,
+        },
+        SnapshotReader { inner, front: 2 },
+    )
+}
+
+/// Set in `Inner::shared` when its slot was published after the reader last
+/// took one.
+const FRESH: usize = 4;
+
+struct Inner {
+    slots: [UnsafeCell<Snapshot>; 3],
+    /// Index of the slot owned by neither side, plus [`FRESH`].
+    shared: AtomicUsize,
+}
+
+// Safe because:
+// - The publisher only touches the slot at its `back` index.
+// - The reader only touches the slot at its `front` index.
+// - Ownership of slots only changes through swaps of `shared`.
+unsafe impl Sync for Inner {}
+
+/// Emulation-side end of [`snapshot_channel`].
+pub struct SnapshotPublisher {
+    inner: Arc<Inner>,
+    back: usize,
+}
+
+impl SnapshotPublisher {
+    /// Captures `gb` and makes it the snapshot the reader sees next.
+    pub fn publish(&mut self, gb: &GameBoy) {
+        // SAFETY: the publisher owns `back` until it swaps it into `shared`.
+        let slot = unsafe { &mut *self.inner.slots[self.back].get() };
+        gb.publish_snapshot(slot);
+        let prev = self.inner.shared.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = prev & !FRESH;
+    }
+}
+
+/// Tool-side end of [`snapshot_channel`].
+pub struct SnapshotReader {
+    inner: Arc<Inner>,
+    front: usize,
+}
+
+impl SnapshotReader {
+    /// Whether a snapshot was published since the last [`Self::latest`].
+    pub fn has_update(&self) -> bool {
+        self.inner.shared.load(Ordering::Acquire) & FRESH != 0
+    }
+
+    /// The newest published snapshot, or a zeroed [`Snapshot::new`] before
+    /// the first publish.
+    pub fn latest(&mut self) -> &Snapshot {
+        if self.has_update() {
+            let prev = self.inner.shared.swap(self.front, Ordering::AcqRel);
+            self.front = prev & !FRESH;
+        }
+        // SAFETY: the reader owns `front` until it swaps it into `shared`.
+        unsafe { &*self.inner.slots[self.front].get() }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::savestate::state_record;
+
 pub struct Timer {
     /// 16-bit internal divider counter. DIV register is the upper 8 bits.
     pub div: u16,
@@ -33,6 +35,48 @@ impl Timer {
         }
     }
 
+    /// CPU-visible DIV register (upper 8 bits of the internal divider).
+    ///
+    /// Unlike a write to 0xFF04, reading has no side effects. The divider is
+    /// clocked at the CPU rate, so in CGB double-speed mode DIV counts at
+    /// 32768 Hz instead of 16384 Hz.
+    pub fn div(&self) -> u8 {
+        (self.div >> 8) as u8
+    }
+
+    /// Full 16-bit internal divider that the timer, APU frame sequencer and
+    /// serial clock tap into. Increments once per CPU clock.
+    pub fn internal_divider(&self) -> u16 {
+        self.div
+    }
+
+    /// Bit of the internal divider that TAC currently selects to clock TIMA
+    /// (9, 3, 5 or 7). TIMA increments on its falling edge while TAC bit 2 is
+    /// set.
+    pub fn tap_bit(&self) -> u8 {
+        match self.tac & 0x03 {
+            0x00 => 9,
+            0x01 => 3,
+            0x02 => 5,
+            _ => 7,
+        }
+    }
+
+    /// CPU cycles until TIMA next overflows and requests the timer
+    /// interrupt, assuming no register writes in between. `None` while the
+    /// timer is stopped.
+    pub fn cycles_until_overflow(&self) -> Option<u32> {
+        if self.pending_reload.is_some() {
+            return Some(u32::from(self.reload_delay));
+        }
+        if self.tac & 0x04 == 0 {
+            return None;
+        }
+        let period = 1u32 << (self.tap_bit() + 1);
+        let to_first_tick = period - (u32::from(self.div) & (period - 1));
+        Some(to_first_tick + u32::from(0xFF - self.tima) * period)
+    }
+
     pub fn read(&self, addr: u16) -> u8 {
         match addr {
             0xFF04 => (self.div >> 8) as u8,
@@ -190,3 +234,15 @@ impl Default for Timer {
         Self::new()
     }
 }
+
+state_record!(Timer {
+    div,
+    tima,
+    tma,
+    tac,
+    last_signal,
+    tma_latch,
+    pending_reload,
+    reload_delay,
+    reloading,
+});
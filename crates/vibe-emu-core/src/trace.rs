@@ -0,0 +1,283 @@
+use core::fmt;
+
+use crate::gameboy::GameBoy;
+
+/// Longest a single instruction may take to retire while comparing traces,
+/// in dot cycles. Covers a HALT waiting out a whole frame with room to spare.
+const MAX_INSTRUCTION_CYCLES: u64 = 4 * 456 * 154;
+
+/// CPU state right before an instruction executes, as emulator trace logs
+/// record it.
+///
+/// The text form is the widely used "Gameboy Doctor" line:
+///
+/// ```text
+/// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+/// ```
+///
+/// [`TraceEntry::parse`] also accepts register pairs (`AF:01B0`, `BC:0013`,
+/// ...) and an `OP:xx` field, so logs exported from BGB or SameBoy in a
+/// similar key:value layout can be read as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct TraceEntry {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    /// Opcode at PC. Traces that do not record it leave it `None`, and it is
+    /// then not compared.
+    pub opcode: Option<u8>,
+}
+
+impl TraceEntry {
+    /// Captures the state of `gb` before its next instruction.
+    pub fn capture(gb: &GameBoy) -> Self {
+        let cpu = &gb.cpu;
+        Self {
+            a: cpu.a,
+            f: cpu.f,
+            b: cpu.b,
+            c: cpu.c,
+            d: cpu.d,
+            e: cpu.e,
+            h: cpu.h,
+            l: cpu.l,
+            sp: cpu.sp,
+            pc: cpu.pc,
+            opcode: Some(gb.mmu.peek_byte(cpu.pc)),
+        }
+    }
+
+    /// Parses one trace log line. Returns `None` if a register is missing
+    /// or a value is not hexadecimal.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut regs: [Option<u8>; 8] = [None; 8];
+        let mut sp = None;
+        let mut pc = None;
+        let mut opcode = None;
+
+        for field in line.split(|c: char| c.is_whitespace() || c == '|') {
+            let Some((key, value)) = field.split_once(':') else {
+                continue;
+            };
+            let byte = || u8::from_str_radix(value, 16).ok();
+            let word = || u16::from_str_radix(value, 16).ok();
+            let mut pair = |hi: usize| {
+                let [h, l] = word()?.to_be_bytes();
+                regs[hi] = Some(h);
+                regs[hi + 1] = Some(l);
+                Some(())
+            };
+            match key.to_ascii_uppercase().as_str() {
+                "A" => regs[0] = Some(byte()?),
+                "F" => regs[1] = Some(byte()?),
+                "B" => regs[2] = Some(byte()?),
+                "C" => regs[3] = Some(byte()?),
+                "D" => regs[4] = Some(byte()?),
+                "E" => regs[5] = Some(byte()?),
+                "H" => regs[6] = Some(byte()?),
+                "L" => regs[7] = Some(byte()?),
+                "AF" => pair(0)?,
+                "BC" => pair(2)?,
+                "DE" => pair(4)?,
+                "HL" => pair(6)?,
+                "SP" => sp = Some(word()?),
+                "PC" => pc = Some(word()?),
+                "OP" => opcode = Some(byte()?),
+                "PCMEM" => {
+                    let first = value.split(',').next()?;
+                    opcode = Some(u8::from_str_radix(first, 16).ok()?);
+                }
+                _ => {}
+            }
+        }
+
+        let [a, f, b, c, d, e, h, l] = regs;
+        Some(Self {
+            a: a?,
+            f: f?,
+            b: b?,
+            c: c?,
+            d: d?,
+            e: e?,
+            h: h?,
+            l: l?,
+            sp: sp?,
+            pc: pc?,
+            opcode,
+        })
+    }
+
+    /// Whether `self` and `other` hold the same registers, comparing opcodes
+    /// only when both recorded one.
+    pub fn matches(&self, other: &TraceEntry) -> bool {
+        let opcodes_match = match (self.opcode, other.opcode) {
+            (Some(x), Some(y)) => x == y,
+            _ => true,
+        };
+        opcodes_match
+            && TraceEntry {
+                opcode: None,
+                ..*self
+            } == TraceEntry {
+                opcode: None,
+                ..*other
+            }
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X}",
+            self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l, self.sp, self.pc
+        )?;
+        if let Some(op) = self.opcode {
+            write!(f, " OP:{op:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+/// First WRAM or HRAM byte that differs between two machines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryMismatch {
+    /// Address the byte is mapped at: C000-DFFF for WRAM, FF80-FFFE for HRAM.
+    pub addr: u16,
+    /// WRAM bank holding the byte when `addr` is in D000-DFFF, otherwise 0.
+    pub bank: u8,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+/// Where a machine first stopped matching its reference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Zero-based index of the instruction about to execute, counted from
+    /// the start of the comparison.
+    pub instruction: u64,
+    /// Reference state before that instruction.
+    pub expected: TraceEntry,
+    /// The machine's state before that instruction.
+    pub actual: TraceEntry,
+    /// First WRAM or HRAM byte that differs, if any. Only comparisons
+    /// between two machines check memory.
+    pub memory: Option<MemoryMismatch>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "diverged before instruction {}", self.instruction)?;
+        writeln!(f, "expected: {}", self.expected)?;
+        write!(f, "actual:   {}", self.actual)?;
+        if let Some(m) = self.memory {
+            write!(
+                f,
+                "\nmemory:   {:04X} (bank {}) expected {:02X}, got {:02X}",
+                m.addr, m.bank, m.expected, m.actual
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl GameBoy {
+    /// Runs the machine one instruction at a time alongside `reference` and
+    /// returns the first instruction whose state does not match, or `None`
+    /// if the whole reference matched.
+    ///
+    /// Each reference entry is compared with the state right before the
+    /// matching instruction, so the machine should start where the trace
+    /// does (e.g. at 0100 after the boot ROM). Time spent halted, in STOP or
+    /// in HDMA does not produce entries, as in trace logs. If an instruction
+    /// takes longer than four frames to retire, the state at that point is
+    /// compared instead, which will normally report the divergence there.
+    pub fn find_divergence<I>(&mut self, reference: I) -> Option<Divergence>
+    where
+        I: IntoIterator<Item = TraceEntry>,
+    {
+        for (instruction, expected) in (0u64..).zip(reference) {
+            let actual = TraceEntry::capture(self);
+            if !expected.matches(&actual) {
+                return Some(Divergence {
+                    instruction,
+                    expected,
+                    actual,
+                    memory: None,
+                });
+            }
+            self.trace_step();
+        }
+        None
+    }
+
+    /// Runs the next instruction, waiting out HALT, STOP and HDMA first.
+    fn trace_step(&mut self) {
+        let deadline = self.cpu.cycles.saturating_add(MAX_INSTRUCTION_CYCLES);
+        let retired = self.cpu.instructions_retired();
+        while self.cpu.instructions_retired() == retired && self.cpu.cycles < deadline {
+            self.cpu.step(&mut self.mmu);
+        }
+    }
+}
+
+/// Runs `expected` and `actual` in lockstep for up to `max_instructions`
+/// instructions and returns the first one before which their CPU registers,
+/// WRAM (all banks) or HRAM differ. Returns `None` if they stayed in step.
+pub fn find_divergence_between(
+    expected: &mut GameBoy,
+    actual: &mut GameBoy,
+    max_instructions: u64,
+) -> Option<Divergence> {
+    for instruction in 0..max_instructions {
+        let e = TraceEntry::capture(expected);
+        let a = TraceEntry::capture(actual);
+        let memory = memory_mismatch(expected, actual);
+        if !e.matches(&a) || memory.is_some() {
+            return Some(Divergence {
+                instruction,
+                expected: e,
+                actual: a,
+                memory,
+            });
+        }
+        expected.trace_step();
+        actual.trace_step();
+    }
+    None
+}
+
+fn memory_mismatch(expected: &GameBoy, actual: &GameBoy) -> Option<MemoryMismatch> {
+    let (x, y) = (&expected.mmu, &actual.mmu);
+    for (bank, (xb, yb)) in x.wram.iter().zip(y.wram.iter()).enumerate() {
+        if xb == yb {
+            continue;
+        }
+        let offset = xb.iter().zip(yb.iter()).position(|(p, q)| p != q)?;
+        let (base, bank) = if bank == 0 {
+            (0xC000, 0)
+        } else {
+            (0xD000, bank as u8)
+        };
+        return Some(MemoryMismatch {
+            addr: base + offset as u16,
+            bank,
+            expected: xb[offset],
+            actual: yb[offset],
+        });
+    }
+    let offset = x.hram.iter().zip(y.hram.iter()).position(|(p, q)| p != q)?;
+    Some(MemoryMismatch {
+        addr: 0xFF80 + offset as u16,
+        bank: 0,
+        expected: x.hram[offset],
+        actual: y.hram[offset],
+    })
+}
@@ -1,4 +1,5 @@
-use std::ops::RangeInclusive;
+use alloc::{string::String, vec::Vec};
+use core::ops::RangeInclusive;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WatchpointTrigger {
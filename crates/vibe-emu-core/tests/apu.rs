@@ -1,4 +1,9 @@
-use vibe_emu_core::apu::Apu;
+use vibe_emu_core::apu::{
+    AUDIO_LATENCY_MS, Apu, CGB_WAVE_RAM_POWER_ON, DMG_WAVE_RAM_POWER_ON, MAX_AUDIO_LATENCY_MS,
+    MIN_AUDIO_LATENCY_MS, RegWrite,
+};
+use vibe_emu_core::cartridge::Cartridge;
+use vibe_emu_core::gameboy::GameBoy;
 use vibe_emu_core::hardware::CgbRevision;
 use vibe_emu_core::mmu::Mmu;
 
@@ -10,6 +15,7 @@ fn tick_machine(apu: &mut Apu, div: &mut u16, cycles: u16) {
 }
 
 #[test]
+#[allow(deprecated)]
 fn frame_sequencer_tick() {
     let mut apu = Apu::new();
     let mut div = 0u16;
@@ -24,6 +30,61 @@ fn frame_sequencer_tick() {
     assert_eq!(apu.sequencer_step(), 0);
 }
 
+#[test]
+fn frame_sequencer_advances_on_div_falling_edges() {
+    let mut apu = Apu::new();
+    let mut div = 0u16;
+    assert_eq!(apu.frame_sequencer_step(), 0);
+    assert_eq!(apu.div_apu_phase(), 0);
+
+    // In single speed the sequencer is clocked when DIV bit 12 (bit 4 of
+    // rDIV) falls, i.e. every 0x2000 dots.
+    for expected in [1, 2, 3, 4, 5, 6, 7, 0, 1] {
+        let before = apu.frame_sequencer_step();
+        let edge = (div | 0x1FFF).wrapping_add(1);
+        apu.tick_frame_sequencer(div, edge.wrapping_sub(1), false);
+        assert_eq!(apu.frame_sequencer_step(), before, "advanced before edge");
+        apu.tick_frame_sequencer(edge.wrapping_sub(1), edge, false);
+        assert_eq!(apu.frame_sequencer_step(), expected);
+        assert_eq!(apu.div_apu_phase(), expected);
+        div = edge;
+    }
+}
+
+#[test]
+fn latency_sets_queue_capacity() {
+    let mut apu = Apu::new();
+    let consumer = apu.enable_output(48_000);
+    assert_eq!(apu.latency_ms(), AUDIO_LATENCY_MS);
+    assert_eq!(apu.max_queue_capacity(), 48_000 * 40 / 1000);
+
+    apu.set_latency_ms(100);
+    assert_eq!(apu.max_queue_capacity(), 4_800);
+    for _ in 0..6_000 {
+        apu.push_samples(1, 1);
+    }
+    assert_eq!(apu.queued_frames(), 4_800);
+
+    // Lowering the latency keeps queued frames and drops new ones until the
+    // backend drains below the new limit.
+    apu.set_latency_ms(20);
+    assert_eq!(apu.max_queue_capacity(), 960);
+    apu.push_samples(2, 2);
+    assert_eq!(apu.queued_frames(), 4_800);
+    while consumer.len() > 900 {
+        assert_eq!(consumer.pop_stereo(), Some((1, 1)));
+    }
+    apu.push_samples(2, 2);
+    assert_eq!(apu.queued_frames(), 901);
+
+    apu.set_latency_ms(1);
+    assert_eq!(apu.latency_ms(), MIN_AUDIO_LATENCY_MS);
+    assert_eq!(apu.max_queue_capacity(), 480);
+    apu.set_latency_ms(10_000);
+    assert_eq!(apu.latency_ms(), MAX_AUDIO_LATENCY_MS);
+    assert_eq!(apu.max_queue_capacity(), 24_000);
+}
+
 #[test]
 fn sample_generation() {
     let mut apu = Apu::new();
@@ -52,6 +113,21 @@ fn read_mask_unused_bits() {
     assert_eq!(apu.read_reg(0xFF11), 0xBF);
 }
 
+#[test]
+fn peek_reg_covers_the_sound_range_only() {
+    let mut apu = Apu::new();
+    apu.write_reg(0xFF26, 0x80);
+    apu.write_reg(0xFF11, 0xA2);
+    apu.write_reg(0xFF30, 0x12);
+    assert_eq!(apu.peek_reg(0xFF11), 0xBF);
+    assert_eq!(apu.peek_reg(0xFF27), 0xFF);
+    assert_eq!(apu.peek_reg(0xFF30), 0x12);
+    // Neighbouring I/O and the rest of the map read open bus, not a panic.
+    for addr in [0x0000, 0xFF0F, 0xFF40, 0xFFFF] {
+        assert_eq!(apu.peek_reg(addr), 0xFF, "{addr:04X}");
+    }
+}
+
 #[test]
 fn register_write_read_fidelity() {
     let mut apu = Apu::new();
@@ -86,6 +162,20 @@ fn wave_ram_access() {
     assert_eq!(apu.read_reg(0xFF30), 0x56);
 }
 
+#[test]
+fn wave_ram_power_on_pattern_matches_model() {
+    let wave_ram =
+        |apu: &mut Apu| -> Vec<u8> { (0xFF30..=0xFF3F).map(|a| apu.read_reg(a)).collect() };
+
+    let mut dmg = Apu::new_with_mode(false);
+    assert_eq!(wave_ram(&mut dmg), DMG_WAVE_RAM_POWER_ON);
+    assert_eq!(dmg.read_reg(0xFF30), 0x84);
+
+    let mut cgb = Apu::new_with_mode(true);
+    assert_eq!(wave_ram(&mut cgb), CGB_WAVE_RAM_POWER_ON);
+    assert_eq!(cgb.read_reg(0xFF31), 0xFF);
+}
+
 #[test]
 fn dac_off_disables_channel() {
     let mut apu = Apu::new();
@@ -651,6 +741,144 @@ fn nr31_write_updates_length_when_disabled_on_dmg() {
     assert_eq!(apu.ch3_length(), 256 - 0x40);
 }
 
+#[test]
+fn length_writes_while_powered_off_dmg_only() {
+    for cgb in [false, true] {
+        let mut apu = Apu::new_with_mode(cgb);
+        apu.write_reg(0xFF26, 0x00);
+        apu.write_reg(0xFF11, 0xC5);
+        apu.write_reg(0xFF16, 0x83);
+        apu.write_reg(0xFF1B, 0x40);
+        apu.write_reg(0xFF20, 0x07);
+        apu.write_reg(0xFF26, 0x80);
+        // CGB drops the writes, leaving the counters cleared by power-off.
+        let expected = if cgb {
+            (0, 0, 0, 0)
+        } else {
+            (64 - 5, 64 - 3, 256 - 0x40, 64 - 7)
+        };
+        assert_eq!(
+            (
+                apu.ch1_length(),
+                apu.ch2_length(),
+                apu.ch3_length(),
+                apu.ch4_length()
+            ),
+            expected,
+            "cgb={cgb}"
+        );
+        // Duty bits are not part of the length write and stay cleared.
+        assert_eq!(apu.read_reg(0xFF11), 0x3F, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF16), 0x3F, "cgb={cgb}");
+    }
+}
+
+#[test]
+fn length_counters_survive_power_cycle_on_dmg_only() {
+    for cgb in [false, true] {
+        let mut apu = Apu::new_with_mode(cgb);
+        apu.write_reg(0xFF26, 0x80);
+        apu.write_reg(0xFF11, 0x85);
+        apu.write_reg(0xFF16, 0x43);
+        apu.write_reg(0xFF1B, 0x40);
+        apu.write_reg(0xFF20, 0x07);
+        let before = (
+            apu.ch1_length(),
+            apu.ch2_length(),
+            apu.ch3_length(),
+            apu.ch4_length(),
+        );
+        assert_eq!(before, (64 - 5, 64 - 3, 256 - 0x40, 64 - 7));
+
+        apu.write_reg(0xFF26, 0x00);
+        apu.write_reg(0xFF26, 0x80);
+        let after = (
+            apu.ch1_length(),
+            apu.ch2_length(),
+            apu.ch3_length(),
+            apu.ch4_length(),
+        );
+        let expected = if cgb { (0, 0, 0, 0) } else { before };
+        assert_eq!(after, expected, "cgb={cgb}");
+        // The duty halves of NR11/NR21 are cleared on both models.
+        assert_eq!(apu.read_reg(0xFF11), 0x3F, "cgb={cgb}");
+    }
+}
+
+#[test]
+fn power_cycle_mid_frame_restarts_the_frame_sequencer() {
+    // Runs the sequencer over the next `n` DIV bit 12 falling edges.
+    let run_edges = |apu: &mut Apu, div: &mut u16, n: usize| {
+        for _ in 0..n {
+            let edge = (*div | 0x1FFF).wrapping_add(1);
+            apu.tick_frame_sequencer(*div, edge, false);
+            *div = edge;
+        }
+    };
+    let mut apu = Apu::new();
+    let mut div = 0u16;
+    run_edges(&mut apu, &mut div, 3);
+    assert_eq!(apu.frame_sequencer_step(), 3);
+
+    apu.write_reg(0xFF26, 0x00);
+    run_edges(&mut apu, &mut div, 2);
+    apu.write_reg(0xFF26, 0x80);
+    assert_eq!(apu.frame_sequencer_step(), 0);
+    run_edges(&mut apu, &mut div, 1);
+    assert_eq!(apu.frame_sequencer_step(), 1);
+}
+
+#[test]
+fn power_off_keeps_host_audio_flowing() {
+    let mut apu = Apu::new();
+    let consumer = apu.enable_output(44_100);
+    apu.write_reg(0xFF26, 0x80);
+    apu.write_reg(0xFF26, 0x00);
+    let mut div = 0u16;
+    for _ in 0..1000 {
+        tick_machine(&mut apu, &mut div, 4);
+    }
+    assert!(apu.queued_frames() > 0);
+    assert_eq!(consumer.pop_stereo(), Some((0, 0)));
+
+    apu.write_reg(0xFF26, 0x80);
+    while consumer.pop_stereo().is_some() {}
+    for _ in 0..1000 {
+        tick_machine(&mut apu, &mut div, 4);
+    }
+    assert!(consumer.pop_stereo().is_some());
+}
+
+#[test]
+fn other_register_writes_ignored_while_powered_off() {
+    for cgb in [false, true] {
+        let mut apu = Apu::new_with_mode(cgb);
+        apu.write_reg(0xFF26, 0x00);
+        apu.write_reg(0xFF10, 0x7F);
+        apu.write_reg(0xFF12, 0xF3);
+        apu.write_reg(0xFF17, 0xF3);
+        apu.write_reg(0xFF1A, 0x80);
+        apu.write_reg(0xFF1C, 0x60);
+        apu.write_reg(0xFF21, 0xF3);
+        apu.write_reg(0xFF22, 0x55);
+        apu.write_reg(0xFF24, 0x77);
+        apu.write_reg(0xFF25, 0xFF);
+        apu.write_reg(0xFF14, 0xC7);
+        apu.write_reg(0xFF26, 0x80);
+        assert_eq!(apu.read_reg(0xFF10), 0x80, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF12), 0x00, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF14), 0xBF, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF17), 0x00, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF1A), 0x7F, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF1C), 0x9F, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF21), 0x00, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF22), 0x00, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF24), 0x00, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF25), 0x00, "cgb={cgb}");
+        assert_eq!(apu.read_reg(0xFF26) & 0x0F, 0x00, "cgb={cgb}");
+    }
+}
+
 #[test]
 fn nr33_write_sets_frequency_low_bits_and_is_write_only() {
     let mut apu = Apu::new();
@@ -962,3 +1190,195 @@ fn pcm_mask_defaults_to_full_on_reve() {
     assert_eq!(apu.pcm_mask()[0], 0xFF);
     assert_eq!(apu.pcm_mask()[1], 0xFF);
 }
+
+#[test]
+fn is_silent_after_window_and_cleared_by_trigger() {
+    let mut apu = Apu::new();
+    let mut div = 0u16;
+    apu.set_silence_window_frames(2);
+    apu.write_reg(0xFF12, 0x00);
+    assert!(!apu.is_silent());
+
+    let frame = 456 * 154;
+    for _ in 0..(frame / 4) {
+        tick_machine(&mut apu, &mut div, 4);
+    }
+    assert!(!apu.is_silent());
+    for _ in 0..(frame / 4 + 1) {
+        tick_machine(&mut apu, &mut div, 4);
+    }
+    assert!(apu.is_silent());
+
+    apu.write_reg(0xFF12, 0xF0);
+    apu.write_reg(0xFF14, 0x80);
+    assert!(!apu.is_silent());
+
+    // Powering off counts as silence too.
+    apu.write_reg(0xFF26, 0x00);
+    for _ in 0..(2 * frame / 4 + 1) {
+        tick_machine(&mut apu, &mut div, 4);
+    }
+    assert!(apu.is_silent());
+}
+
+#[test]
+fn take_frame_samples_captures_every_sample() {
+    let mut apu = Apu::new();
+    let consumer = apu.enable_output(48_000);
+    apu.set_latency_ms(MAX_AUDIO_LATENCY_MS);
+    assert!(apu.take_frame_samples().is_empty());
+    apu.set_sample_capture(true);
+
+    // 512 Hz square wave on channel 2: 131072 / (2048 - 1792).
+    apu.write_reg(0xFF24, 0x77);
+    apu.write_reg(0xFF25, 0x22);
+    apu.write_reg(0xFF16, 0x80); // 50% duty
+    apu.write_reg(0xFF17, 0xF0);
+    apu.write_reg(0xFF18, 0x00);
+    apu.write_reg(0xFF19, 0x87);
+
+    let mut div = 0u16;
+    let mut counts = Vec::new();
+    for _ in 0..3 {
+        for _ in 0..(456 * 154 / 4) {
+            tick_machine(&mut apu, &mut div, 4);
+        }
+        let samples = apu.take_frame_samples();
+        counts.push(samples.len());
+
+        // 70224 cycles at 48 kHz is 803.6 samples.
+        assert!((803..=804).contains(&samples.len()), "{}", samples.len());
+        // Same samples as the output queue, in order.
+        for &sample in &samples {
+            assert_eq!(consumer.pop_stereo(), Some(sample));
+        }
+        assert!(samples.iter().all(|&(l, r)| l == r));
+        // About 8.6 periods per frame, two sign changes each.
+        let crossings = samples
+            .windows(2)
+            .filter(|w| (w[0].0 < 0) != (w[1].0 < 0))
+            .count();
+        assert!((15..=19).contains(&crossings), "{crossings} crossings");
+    }
+    assert_eq!(counts.iter().sum::<usize>(), 2410);
+    assert!(apu.take_frame_samples().is_empty());
+
+    apu.set_sample_capture(false);
+    tick_machine(&mut apu, &mut div, 4096);
+    assert!(apu.take_frame_samples().is_empty());
+}
+
+#[test]
+fn sample_capture_keeps_the_latest_second() {
+    let mut apu = Apu::new();
+    apu.set_sample_rate(48_000);
+    apu.set_sample_capture(true);
+    let pushed: Vec<(i16, i16)> = (0..48_010u32)
+        .map(|i| (i as i16, (i >> 8) as i16))
+        .collect();
+    for &(l, r) in &pushed {
+        apu.push_samples(l, r);
+    }
+
+    // Never taken, so only the newest 48000 remain.
+    assert_eq!(apu.take_frame_samples(), pushed[10..]);
+}
+
+/// One frame of channel 1 panned left and channel 2 panned centre, mixed
+/// at stereo width `width` (left at the default when `None`).
+fn panned_frame(width: Option<f32>) -> Vec<(i16, i16)> {
+    let mut apu = Apu::new();
+    apu.set_sample_rate(48_000);
+    apu.set_sample_capture(true);
+    if let Some(width) = width {
+        apu.set_stereo_width(width);
+    }
+    apu.write_reg(0xFF24, 0x77);
+    apu.write_reg(0xFF25, 0x32);
+    apu.write_reg(0xFF11, 0x80);
+    apu.write_reg(0xFF12, 0xF0);
+    apu.write_reg(0xFF13, 0x00);
+    apu.write_reg(0xFF14, 0x87);
+    apu.write_reg(0xFF16, 0x40);
+    apu.write_reg(0xFF17, 0xA0);
+    apu.write_reg(0xFF18, 0x80);
+    apu.write_reg(0xFF19, 0x86);
+
+    let mut div = 0u16;
+    for _ in 0..(456 * 154 / 4) {
+        tick_machine(&mut apu, &mut div, 4);
+    }
+    apu.take_frame_samples()
+}
+
+fn separation(samples: &[(i16, i16)]) -> i64 {
+    samples
+        .iter()
+        .map(|&(l, r)| (i64::from(l) - i64::from(r)).abs())
+        .sum()
+}
+
+#[test]
+fn stereo_width_scales_left_right_separation() {
+    let default = panned_frame(None);
+    assert!(separation(&default) > 0);
+    assert_eq!(panned_frame(Some(1.0)), default);
+
+    let wide = panned_frame(Some(2.0));
+    assert_eq!(wide.len(), default.len());
+    assert!(separation(&wide) > separation(&default));
+    // The mid signal is kept.
+    for (&(l, r), &(wl, wr)) in default.iter().zip(&wide) {
+        assert!((i32::from(l) + i32::from(r) - i32::from(wl) - i32::from(wr)).abs() <= 2);
+    }
+
+    assert!(panned_frame(Some(0.0)).iter().all(|&(l, r)| l == r));
+    assert!(panned_frame(Some(-1.0)).iter().all(|&(l, r)| l == r));
+}
+
+#[test]
+fn reg_log_records_writes_in_order() {
+    let mut rom = vec![0u8; 0x8000];
+    #[rustfmt::skip]
+    let program = [
+        0x3E, 0x80, 0xE0, 0x26, // LD A,$80 / LDH (NR52),A
+        0x3E, 0x77, 0xE0, 0x24, // LD A,$77 / LDH (NR50),A
+        0x3E, 0xF3, 0xE0, 0x12, // LD A,$F3 / LDH (NR12),A
+        0x3E, 0x87, 0xE0, 0x14, // LD A,$87 / LDH (NR14),A
+        0x3E, 0x5A, 0xE0, 0x30, // LD A,$5A / LDH ($FF30),A
+        0x18, 0xFE,             // JR -2
+    ];
+    rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+
+    gb.mmu.apu.start_reg_log();
+    while gb.cpu.pc != 0x114 {
+        gb.cpu.step(&mut gb.mmu);
+    }
+    let log = gb.mmu.apu.take_reg_log();
+    let writes: Vec<(u16, u8)> = log.iter().map(|w| (w.addr, w.value)).collect();
+    assert_eq!(
+        writes,
+        [
+            (0xFF26, 0x80),
+            (0xFF24, 0x77),
+            (0xFF12, 0xF3),
+            (0xFF14, 0x87),
+            (0xFF30, 0x5A),
+        ]
+    );
+    // Each write is 5 M-cycles after the last.
+    for pair in log.windows(2) {
+        assert_eq!(pair[1].cycle - pair[0].cycle, 20, "{pair:?}");
+    }
+
+    gb.mmu.write_byte(0xFF25, 0xFF);
+    let after: &[RegWrite] = &gb.mmu.apu.take_reg_log();
+    assert_eq!(after.len(), 1);
+    assert!(after[0].cycle > log[4].cycle);
+
+    gb.mmu.apu.stop_reg_log();
+    gb.mmu.write_byte(0xFF25, 0x00);
+    assert!(gb.mmu.apu.take_reg_log().is_empty());
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "capi")]
+
+use vibe_emu_core::capi::*;
+
+/// DMG ROM that waits in HALT for a joypad press, then sets BGP to 0xFF.
+fn joypad_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]); // NOP; JP 0x0150
+    rom[0x150..0x15F].copy_from_slice(&[
+        0x3E, 0x10, // LD A,0x10
+        0xE0, 0xFF, // LDH (IE),A
+        0xAF, // XOR A
+        0xE0, 0x0F, // LDH (IF),A
+        0x76, // HALT
+        0x00, // NOP
+        0x3E, 0xFF, // LD A,0xFF
+        0xE0, 0x47, // LDH (BGP),A
+        0x18, 0xFE, // JR -2
+    ]);
+    rom
+}
+
+unsafe fn frame(gb: *mut VibeGb) -> Vec<u32> {
+    let fb = unsafe { vibe_gb_framebuffer(gb) };
+    assert!(!fb.is_null());
+    unsafe { std::slice::from_raw_parts(fb, 160 * 144) }.to_vec()
+}
+
+#[test]
+fn drive_core_through_c_api() {
+    let rom = joypad_rom();
+    unsafe {
+        let gb = vibe_gb_new();
+        assert!(!gb.is_null());
+        assert!(!vibe_gb_load_rom(gb, std::ptr::null(), 0));
+        assert!(vibe_gb_load_rom(gb, rom.as_ptr(), rom.len()));
+
+        for _ in 0..2 {
+            assert!(vibe_gb_run_frame(gb));
+        }
+        let before = frame(gb);
+        // Nothing changes while the ROM is halted.
+        assert!(vibe_gb_run_frame(gb));
+        assert_eq!(frame(gb), before);
+
+        vibe_gb_set_buttons(gb, 0x10); // A
+        for _ in 0..2 {
+            assert!(vibe_gb_run_frame(gb));
+        }
+        let after = frame(gb);
+        assert!(after.iter().all(|&px| px == after[0]));
+        assert_ne!(after, before);
+
+        vibe_gb_free(gb);
+    }
+}
+
+#[test]
+fn null_handles_are_rejected() {
+    unsafe {
+        assert!(!vibe_gb_run_frame(std::ptr::null_mut()));
+        assert!(vibe_gb_framebuffer(std::ptr::null()).is_null());
+        vibe_gb_set_buttons(std::ptr::null_mut(), 0xFF);
+        vibe_gb_free(std::ptr::null_mut());
+    }
+}
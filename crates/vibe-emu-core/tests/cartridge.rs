@@ -1,6 +1,11 @@
 use std::fs;
+use std::sync::{Arc, Mutex};
 use tempfile::tempdir;
-use vibe_emu_core::cartridge::{Cartridge, MbcType};
+use vibe_emu_core::cartridge::{
+    BankEvent, BankKind, Cartridge, CgbSupport, Header, HeaderAnomaly, MAX_RAM_SLOT, MapperKind,
+    MbcType, ModeMismatch, RtcState,
+};
+use vibe_emu_core::mmu::Mmu;
 
 #[test]
 fn battery_ram_saved_to_disk() {
@@ -21,6 +26,69 @@ fn battery_ram_saved_to_disk() {
     assert_eq!(data[0], 0xAA);
 }
 
+#[test]
+fn sram_writes_mark_ram_dirty() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x03; // MBC1 + RAM + Battery
+    rom[0x0149] = 0x02; // 8KB RAM
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(rom));
+    let dirty = |mmu: &Mmu| mmu.cart.as_ref().unwrap().ram_dirty();
+    assert!(!dirty(&mmu));
+
+    // Mapper registers and WRAM leave it clean.
+    mmu.write_byte(0x0000, 0x0A);
+    mmu.write_byte(0x2000, 0x01);
+    mmu.write_byte(0xC000, 0x12);
+    assert!(!dirty(&mmu));
+
+    mmu.write_byte(0xBFFF, 0x34);
+    assert!(dirty(&mmu));
+    mmu.cart.as_mut().unwrap().save_ram().unwrap();
+    assert!(!dirty(&mmu));
+}
+
+#[test]
+fn battery_ram_slots_are_independent() {
+    let dir = tempdir().unwrap();
+    let rom_path = dir.path().join("game.gb");
+
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x03; // MBC1 + RAM + Battery
+    rom[0x0149] = 0x02; // 8KB RAM
+    fs::write(&rom_path, &rom).unwrap();
+
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::from_file(&rom_path).unwrap());
+    mmu.write_byte(0x0000, 0x0A);
+    mmu.write_byte(0xA000, 0x11);
+    mmu.write_byte(0xBFFF, 0x10);
+    mmu.save_cart_ram_slot(1).unwrap();
+    mmu.write_byte(0xA000, 0x22);
+    mmu.write_byte(0xBFFF, 0x33);
+    mmu.save_cart_ram_slot(2).unwrap();
+    mmu.write_byte(0xA000, 0x44);
+    mmu.save_cart_ram();
+
+    assert!(dir.path().join("game.slot1.sav").exists());
+    assert!(dir.path().join("game.slot2.sav").exists());
+
+    mmu.load_cart_ram_slot(1).unwrap();
+    assert_eq!(mmu.read_byte(0xA000), 0x11);
+    assert_eq!(mmu.read_byte(0xBFFF), 0x10);
+    mmu.load_cart_ram_slot(2).unwrap();
+    assert_eq!(mmu.read_byte(0xA000), 0x22);
+    assert_eq!(mmu.read_byte(0xBFFF), 0x33);
+
+    // The default save is untouched by slot operations.
+    let cart = Cartridge::from_file(&rom_path).unwrap();
+    assert_eq!(cart.ram[0], 0x44);
+
+    assert!(mmu.load_cart_ram_slot(3).is_err());
+    assert!(mmu.save_cart_ram_slot(0).is_err());
+    assert!(mmu.save_cart_ram_slot(MAX_RAM_SLOT + 1).is_err());
+}
+
 #[test]
 fn mbc30_header_detection() {
     let mut rom = vec![0u8; 0x8000];
@@ -67,3 +135,412 @@ fn mbc3_rtc_state_roundtrips_to_disk() {
     assert_eq!(minutes, 34);
     assert_eq!(control & 0x40, 0x40);
 }
+
+#[test]
+fn only_timer_mbc3_types_have_an_rtc() {
+    let dir = tempdir().unwrap();
+    for (cart_type, has_rtc) in [
+        (0x0F, true),
+        (0x10, true),
+        (0x11, false),
+        (0x12, false),
+        (0x13, false),
+    ] {
+        let rom_path = dir.path().join(format!("mbc3_{cart_type:02x}.gb"));
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0147] = cart_type;
+        rom[0x0149] = 0x03;
+        fs::write(&rom_path, &rom).unwrap();
+
+        let mut cart = Cartridge::from_file(&rom_path).unwrap();
+        assert_eq!(cart.mbc, MbcType::Mbc3, "{cart_type:#04x}");
+        assert_eq!(cart.has_rtc(), has_rtc, "{cart_type:#04x}");
+        assert_eq!(cart.rtc().is_some(), has_rtc, "{cart_type:#04x}");
+        cart.save_ram().unwrap();
+        assert_eq!(
+            rom_path.with_extension("rtc").exists(),
+            has_rtc,
+            "{cart_type:#04x}"
+        );
+    }
+
+    // Without a clock, the RTC register banks are unmapped.
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x13;
+    rom[0x0149] = 0x03;
+    let mut cart = Cartridge::load(rom);
+    cart.write(0x0000, 0x0A);
+    assert!(!cart.set_rtc(RtcState::default()));
+    assert_eq!(read_rtc_reg(&mut cart, 0x08), 0xFF);
+}
+
+fn read_rtc_reg(cart: &mut Cartridge, reg: u8) -> u8 {
+    cart.write(0x4000, reg);
+    cart.read(0xA000)
+}
+
+#[test]
+fn mbc3_set_rtc_is_visible_to_latched_reads() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x10; // MBC3 + Timer + RAM + Battery
+    rom[0x0149] = 0x03;
+    let mut cart = Cartridge::load(rom);
+    cart.write(0x0000, 0x0A);
+    cart.write(0x6000, 0x00);
+    cart.write(0x6000, 0x01);
+
+    let state = RtcState {
+        seconds: 5,
+        minutes: 43,
+        hours: 21,
+        days: 0x123,
+        halt: true,
+        carry: false,
+    };
+    assert!(cart.set_rtc(state));
+    assert_eq!(cart.rtc(), Some(state));
+    assert_eq!(cart.rtc_latched(), Some(state));
+
+    // Already latched before the change, but the game still sees it.
+    assert_eq!(read_rtc_reg(&mut cart, 0x08), 5);
+
+    cart.write(0x6000, 0x00);
+    cart.write(0x6000, 0x01);
+    assert_eq!(read_rtc_reg(&mut cart, 0x08), 5);
+    assert_eq!(read_rtc_reg(&mut cart, 0x09), 43);
+    assert_eq!(read_rtc_reg(&mut cart, 0x0A), 21);
+    assert_eq!(read_rtc_reg(&mut cart, 0x0B), 0x23);
+    assert_eq!(read_rtc_reg(&mut cart, 0x0C), 0x41);
+
+    // Running clock: the latched copy only moves on the next latch.
+    cart.set_rtc(RtcState {
+        halt: false,
+        ..state
+    });
+    // Just over one second of 4 MiHz cycles.
+    for _ in 0..65 {
+        cart.step_rtc(u16::MAX);
+    }
+    assert_eq!(cart.rtc().unwrap().seconds, 6);
+    assert_eq!(read_rtc_reg(&mut cart, 0x08), 5);
+    cart.write(0x6000, 0x00);
+    cart.write(0x6000, 0x01);
+    assert_eq!(read_rtc_reg(&mut cart, 0x08), 6);
+}
+
+#[test]
+fn set_rtc_masks_fields_and_needs_a_clock() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x0F; // MBC3 + Timer + Battery
+    let mut cart = Cartridge::load(rom);
+    cart.set_rtc(RtcState {
+        seconds: 0xFF,
+        minutes: 0xFF,
+        hours: 0xFF,
+        days: 0xFFFF,
+        halt: true,
+        carry: true,
+    });
+    let rtc = cart.rtc().unwrap();
+    assert_eq!(
+        (rtc.seconds, rtc.minutes, rtc.hours, rtc.days),
+        (0x3F, 0x3F, 0x1F, 0x1FF)
+    );
+
+    let mut cart = Cartridge::load(vec![0u8; 0x8000]);
+    assert_eq!(cart.rtc(), None);
+    assert!(!cart.set_rtc(RtcState::default()));
+}
+
+fn rom_with_size_byte(len: usize, size_byte: u8) -> Vec<u8> {
+    let mut rom = vec![0u8; len];
+    rom[0x0147] = 0x19; // MBC5
+    rom[0x0148] = size_byte;
+    rom
+}
+
+#[test]
+fn geometry_follows_standard_size_tables() {
+    // (ROM size byte, banks), per the header's 32 KiB << n table.
+    for (size_byte, banks) in [(0x00, 2), (0x01, 4), (0x05, 64), (0x08, 512)] {
+        let cart = Cartridge::load(rom_with_size_byte(0x8000 << size_byte, size_byte));
+        assert_eq!(cart.rom_bank_count(), banks, "size byte {size_byte:#04X}");
+        assert_eq!(cart.rom_size_bytes(), banks * 0x4000);
+    }
+
+    // (RAM size byte, bytes, banks)
+    for (size_byte, bytes, banks) in [
+        (0x00, 0, 0),
+        (0x01, 0x800, 1),
+        (0x02, 0x2000, 1),
+        (0x03, 0x8000, 4),
+        (0x04, 0x20000, 16),
+        (0x05, 0x10000, 8),
+    ] {
+        let mut rom = rom_with_size_byte(0x8000, 0x00);
+        rom[0x0149] = size_byte;
+        let cart = Cartridge::load(rom);
+        assert_eq!(cart.ram_size_bytes(), bytes, "size byte {size_byte:#04X}");
+        assert_eq!(cart.ram_bank_count(), banks);
+    }
+
+    let mut rom = rom_with_size_byte(0x40000, 0x03);
+    rom[0x0147] = 0x06; // MBC2+BATTERY
+    let cart = Cartridge::load(rom);
+    assert_eq!(cart.mapper_kind(), MapperKind::Mbc2);
+    assert_eq!(cart.ram_size_bytes(), 0x200);
+    assert_eq!(cart.ram_bank_count(), 1);
+
+    let mut rom = rom_with_size_byte(0x400000, 0x07);
+    rom[0x0149] = 0x04;
+    let cart = Cartridge::load(rom);
+    assert_eq!(
+        format!(
+            "{}, {} KiB ROM / {} KiB RAM",
+            cart.mapper_kind(),
+            cart.rom_size_bytes() / 1024,
+            cart.ram_size_bytes() / 1024
+        ),
+        "MBC5, 4096 KiB ROM / 128 KiB RAM"
+    );
+
+    // (cartridge type, mapper)
+    for (cart_type, kind) in [
+        (0x00, MapperKind::None),
+        (0x03, MapperKind::Mbc1),
+        (0x10, MapperKind::Mbc3 { rtc: true }),
+        (0x13, MapperKind::Mbc3 { rtc: false }),
+        (0x1B, MapperKind::Mbc5 { rumble: false }),
+        (0x1E, MapperKind::Mbc5 { rumble: true }),
+        (0xFC, MapperKind::Unknown(0xFC)),
+    ] {
+        let mut rom = rom_with_size_byte(0x8000, 0x00);
+        rom[0x0147] = cart_type;
+        let cart = Cartridge::load(rom);
+        assert_eq!(cart.mapper_kind(), kind, "{cart_type:#04x}");
+    }
+    assert_eq!(MapperKind::Mbc3 { rtc: true }.to_string(), "MBC3+RTC");
+}
+
+#[test]
+fn matching_rom_size_has_no_anomalies() {
+    let rom = rom_with_size_byte(0x10000, 0x01);
+    assert_eq!(Header::parse(&rom).anomalies(), Vec::new());
+    assert!(Cartridge::load(rom).header_anomalies().is_empty());
+}
+
+#[test]
+fn padded_rom_reports_trailing_data() {
+    // Declares 32 KiB but carries a 512-byte trailer.
+    let rom = rom_with_size_byte(0x8000 + 0x200, 0x00);
+    assert_eq!(
+        Header::parse(&rom).anomalies(),
+        vec![
+            HeaderAnomaly::NonPowerOfTwoSize { len: 0x8200 },
+            HeaderAnomaly::TrailingData {
+                declared: 0x8000,
+                len: 0x8200
+            },
+        ]
+    );
+
+    // Power-of-two padding only reports the size mismatch.
+    let rom = rom_with_size_byte(0x20000, 0x01);
+    assert_eq!(
+        Header::parse(&rom).anomalies(),
+        vec![HeaderAnomaly::TrailingData {
+            declared: 0x10000,
+            len: 0x20000
+        }]
+    );
+}
+
+#[test]
+fn truncated_rom_reports_missing_banks_and_still_loads() {
+    let rom = rom_with_size_byte(0xC000, 0x02);
+    let mut cart = Cartridge::load(rom);
+    assert_eq!(
+        cart.header_anomalies(),
+        vec![
+            HeaderAnomaly::NonPowerOfTwoSize { len: 0xC000 },
+            HeaderAnomaly::Truncated {
+                declared: 0x20000,
+                len: 0xC000
+            },
+        ]
+    );
+    assert_eq!(cart.mbc, MbcType::Mbc5);
+    cart.write(0x2000, 2);
+    let _ = cart.read(0x4000);
+}
+
+#[test]
+fn unknown_rom_size_byte_is_reported() {
+    let rom = rom_with_size_byte(0x8000, 0x20);
+    assert_eq!(
+        Header::parse(&rom).anomalies(),
+        vec![HeaderAnomaly::UnknownRomSize(0x20)]
+    );
+    assert!(Header::parse(&[0u8; 0x100]).anomalies().is_empty());
+}
+
+fn rom_with_cgb_flag(flag: u8) -> Cartridge {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0143] = flag;
+    Cartridge::load(rom)
+}
+
+#[test]
+fn cgb_flag_maps_to_support_level() {
+    assert_eq!(rom_with_cgb_flag(0x00).cgb_support(), CgbSupport::DmgOnly);
+    assert_eq!(rom_with_cgb_flag(0x80).cgb_support(), CgbSupport::Enhanced);
+    assert_eq!(rom_with_cgb_flag(0xC0).cgb_support(), CgbSupport::CgbOnly);
+    // Some titles store other low bits alongside the flag.
+    assert_eq!(rom_with_cgb_flag(0xC4).cgb_support(), CgbSupport::CgbOnly);
+    assert!(rom_with_cgb_flag(0xC0).cgb);
+}
+
+#[test]
+fn check_mode_flags_cgb_only_games_in_dmg_mode() {
+    for (flag, cgb, expected) in [
+        (0x00, false, None),
+        (0x00, true, None),
+        (0x80, false, None),
+        (0x80, true, None),
+        (0xC0, true, None),
+        (0xC0, false, Some(ModeMismatch::CgbOnlyOnDmg)),
+    ] {
+        assert_eq!(
+            rom_with_cgb_flag(flag).check_mode(cgb),
+            expected,
+            "flag {flag:02X}, cgb {cgb}"
+        );
+    }
+    assert!(
+        ModeMismatch::CgbOnlyOnDmg
+            .to_string()
+            .contains("requires a Game Boy Color")
+    );
+}
+
+#[test]
+fn mbc5_bank_writes_fire_the_bank_callback() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x1B; // MBC5+RAM+BATTERY
+    rom[0x0149] = 0x03;
+    let mut cart = Cartridge::load(rom);
+
+    // Not set yet: nothing to call.
+    cart.write(0x2000, 0x05);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    cart.set_bank_callback(Box::new(move |event| sink.lock().unwrap().push(event)));
+    cart.write(0x0000, 0x0A); // RAM enable is not a bank register
+    cart.write(0x2000, 0x2A);
+    cart.write(0x3000, 0x01);
+    cart.write(0x4000, 0x03);
+    cart.write(0xA000, 0x55);
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            BankEvent {
+                addr: 0x2000,
+                kind: BankKind::Rom,
+                bank: 0x02A,
+            },
+            BankEvent {
+                addr: 0x3000,
+                kind: BankKind::Rom,
+                bank: 0x12A,
+            },
+            BankEvent {
+                addr: 0x4000,
+                kind: BankKind::Ram,
+                bank: 3,
+            },
+        ]
+    );
+
+    cart.clear_bank_callback();
+    cart.write(0x2000, 0x01);
+    assert_eq!(events.lock().unwrap().len(), 3);
+}
+
+fn rom_with_type(cart_type: u8, ram_size_byte: u8) -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = cart_type;
+    rom[0x0149] = ram_size_byte;
+    rom
+}
+
+#[test]
+fn mbc2_ram_stores_only_the_low_nibble() {
+    let mut cart = Cartridge::load(rom_with_type(0x06, 0x00));
+    assert_eq!(cart.ram_size_bytes(), 0x200);
+    cart.write(0x0000, 0x0A);
+
+    cart.write(0xA000, 0xFF);
+    assert_eq!(cart.read(0xA000), 0xFF);
+    cart.write(0xA001, 0x5A);
+    assert_eq!(cart.read(0xA001), 0xFA);
+    cart.write(0xA002, 0x00);
+    assert_eq!(cart.read(0xA002), 0xF0);
+    assert_eq!(&cart.ram[..3], [0x0F, 0x0A, 0x00]);
+
+    // The 512 nibbles mirror across $A000-$BFFF.
+    assert_eq!(cart.read(0xA201), 0xFA);
+    assert_eq!(cart.read(0xBE01), 0xFA);
+    cart.write(0xB203, 0x07);
+    assert_eq!(cart.read(0xA003), 0xF7);
+
+    // A save with junk in the upper nibbles still reads back as 1s there.
+    cart.ram[4] = 0x63;
+    assert_eq!(cart.read(0xA004), 0xF3);
+}
+
+#[test]
+fn ram_banking_survives_non_standard_sizes() {
+    for cart_type in [0x03, 0x06, 0x10, 0x1B] {
+        for ram_size in [0, 1, 0x100, 0x200, 0x800, 0x3000, 0x5000, 0x2_0000] {
+            let mut cart = Cartridge::from_bytes_with_ram(rom_with_type(cart_type, 0x03), ram_size);
+            cart.write(0x0000, 0x0A);
+            cart.write(0x6000, 0x01); // MBC1 banking mode; RTC latch elsewhere
+            for bank in [0x00, 0x01, 0x02, 0x03, 0x05, 0x07, 0x0F, 0xFF] {
+                cart.write(0x4000, bank);
+                for addr in [0xA000, 0xA7FF, 0xB000, 0xBFFF] {
+                    cart.write(addr, 0x5A);
+                    let value = cart.read(addr);
+                    // Plain RAM banks read back what was written.
+                    if ram_size != 0 && bank < 4 {
+                        let expected = if cart_type == 0x06 { 0xFA } else { 0x5A };
+                        assert_eq!(
+                            value, expected,
+                            "type {cart_type:#04X}, {ram_size:#X} bytes, bank {bank}, {addr:#06X}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn rom_hash_identifies_the_rom_image() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0134..0x0138].copy_from_slice(b"GAME");
+    let a = Cartridge::load(rom.clone());
+    let b = Cartridge::load(rom.clone());
+    assert_eq!(a.rom_hash(), b.rom_hash());
+
+    // A single byte anywhere in the image changes the hash.
+    rom[0x7FFF] = 1;
+    let c = Cartridge::load(rom);
+    assert_ne!(a.rom_hash(), c.rom_hash());
+
+    assert_ne!(
+        Cartridge::load(vec![0; 0x8000]).rom_hash(),
+        Cartridge::load(vec![0; 0x10000]).rom_hash()
+    );
+}
@@ -0,0 +1,74 @@
+use vibe_emu_core::{
+    cartridge::Cartridge,
+    cheats::{Comparison, RamSearch},
+    gameboy::GameBoy,
+};
+
+fn machine_with_sram() -> Box<GameBoy> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x147] = 0x03; // MBC1+RAM+BATTERY
+    rom[0x149] = 0x02; // 8 KiB
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb
+}
+
+#[test]
+fn changed_filter_narrows_to_the_written_address() {
+    let mut gb = Box::new(GameBoy::new());
+    let mut search = RamSearch::new();
+    search.snapshot(&gb);
+    assert_eq!(search.filter(Comparison::Changed), 0x2000);
+
+    let lives = gb.mmu.peek_byte(0xC123);
+    gb.mmu.write_byte(0xC123, lives.wrapping_add(1));
+    search.snapshot(&gb);
+    assert_eq!(search.filter(Comparison::Changed), 1);
+    assert_eq!(search.candidates(), vec![0xC123]);
+
+    search.snapshot(&gb);
+    assert_eq!(search.filter(Comparison::Unchanged), 1);
+    search.reset();
+    assert!(search.candidates().is_empty());
+}
+
+#[test]
+fn increased_and_decreased_track_a_counter_in_sram() {
+    let mut gb = machine_with_sram();
+    let mut search = RamSearch::new();
+    gb.mmu.write_byte(0x0000, 0x0A); // enable RAM
+    gb.mmu.write_byte(0xA010, 3);
+    gb.mmu.write_byte(0xD010, 3);
+    gb.mmu.write_byte(0x0000, 0x00); // disable RAM again
+    search.snapshot(&gb);
+    assert_eq!(search.candidates().len(), 0x4000);
+    assert_eq!(search.value(0xA010), Some(3));
+
+    gb.mmu.write_byte(0x0000, 0x0A);
+    gb.mmu.write_byte(0xA010, 2);
+    gb.mmu.write_byte(0xD010, 4);
+    gb.mmu.write_byte(0x0000, 0x00);
+    search.snapshot(&gb);
+    search.filter(Comparison::Changed);
+    assert_eq!(search.candidates(), vec![0xA010, 0xD010]);
+    assert_eq!(search.filter(Comparison::Decreased), 1);
+    assert_eq!(search.candidates(), vec![0xA010]);
+    assert_eq!(search.value(0xA010), Some(2));
+}
+
+#[test]
+fn carts_without_ram_only_search_wram() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x147] = 0x00;
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    let mut search = RamSearch::new();
+    search.snapshot(&gb);
+    let candidates = search.candidates();
+    assert_eq!(candidates.first(), Some(&0xC000));
+    assert_eq!(candidates.last(), Some(&0xDFFF));
+
+    gb.mmu.write_byte(0xC000, 0x42);
+    search.snapshot(&gb);
+    assert_eq!(search.filter(Comparison::Increased), 1);
+}
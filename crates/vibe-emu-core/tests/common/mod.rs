@@ -4,6 +4,8 @@ use std::fs::{self, File};
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, OnceLock};
+use vibe_emu_core::cartridge::Cartridge;
+use vibe_emu_core::mmu::Mmu;
 
 #[derive(Clone)]
 struct CachedPng {
@@ -409,3 +411,65 @@ pub fn serial_contains_result(serial: &[u8], checked_up_to: &mut usize) -> bool
     *checked_up_to = serial.len();
     found
 }
+
+/// Builds a cartridge that exchanges `bytes` over the link one at a time (as
+/// the clocking side when `internal_clock`), storing what comes back from
+/// $FF80 on, then spins. Each byte waits `delay` countdown loops first; 0
+/// counts 256.
+#[allow(dead_code)]
+pub fn link_cart(bytes: &[u8], internal_clock: bool, delay: u8) -> Cartridge {
+    let mut rom = vec![0u8; 0x8000];
+    #[rustfmt::skip]
+    let code = [
+        0x11, 0x00, 0x02,                  // LD DE,$0200
+        0x21, 0x80, 0xFF,                  // LD HL,$FF80
+        0x06, delay,                       // LD B,delay
+        0x05,                              // DEC B
+        0x20, 0xFD,                        // JR NZ,-3
+        0x1A,                              // LD A,(DE)
+        0xE0, 0x01,                        // LDH (SB),A
+        0x3E, 0x80 | internal_clock as u8, // LD A,$80/$81
+        0xE0, 0x02,                        // LDH (SC),A
+        0xF0, 0x02,                        // LDH A,(SC)
+        0xCB, 0x7F,                        // BIT 7,A
+        0x20, 0xFA,                        // JR NZ,-6
+        0xF0, 0x01,                        // LDH A,(SB)
+        0x22,                              // LD (HL+),A
+        0x1C,                              // INC E
+        0x7B,                              // LD A,E
+        0xFE, bytes.len() as u8,           // CP len
+        0x20, 0xE5,                        // JR NZ,-27
+        0x18, 0xFE,                        // JR -2
+    ];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    rom[0x200..0x200 + bytes.len()].copy_from_slice(bytes);
+    Cartridge::load(rom)
+}
+
+/// Fills OAM with a recognisable pattern and parks the PPU at the start of
+/// mode 2, where OAM accesses trigger the DMG OAM bug. Returns the pattern.
+#[allow(dead_code)]
+pub fn start_oam_scan(mmu: &mut Mmu) -> [u8; 0xA0] {
+    let orig = std::array::from_fn(|i| (i as u8).wrapping_mul(37).wrapping_add(11));
+    mmu.ppu.oam = orig;
+    mmu.ppu.mode = 2;
+    orig
+}
+
+/// Little-endian OAM word `word`, as the OAM bug corrupts them.
+#[allow(dead_code)]
+pub fn oam_word(oam: &[u8], word: usize) -> u16 {
+    u16::from_le_bytes([oam[word * 2], oam[word * 2 + 1]])
+}
+
+/// `orig` after an OAM bug write while row 1 is being scanned: row 1 word 0
+/// becomes ((a ^ c) & (b ^ c)) ^ c of itself and row 0's words 0 and 2, and
+/// the rest of row 1 is copied from row 0.
+#[allow(dead_code)]
+pub fn oam_after_write_corruption(orig: &[u8; 0xA0]) -> [u8; 0xA0] {
+    let (a, b, c) = (oam_word(orig, 4), oam_word(orig, 0), oam_word(orig, 2));
+    let mut expected = *orig;
+    expected[8..10].copy_from_slice(&(((a ^ c) & (b ^ c)) ^ c).to_le_bytes());
+    expected.copy_within(2..8, 10);
+    expected
+}
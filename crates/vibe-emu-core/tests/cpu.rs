@@ -1,4 +1,11 @@
-use vibe_emu_core::{cartridge::Cartridge, cpu::Cpu, mmu::Mmu};
+mod common;
+
+use vibe_emu_core::{
+    cartridge::Cartridge,
+    cpu::Cpu,
+    disasm::{ControlFlow, RegisterSet},
+    mmu::Mmu,
+};
 
 #[test]
 fn simple_program() {
@@ -56,6 +63,147 @@ fn interrupt_handling() {
     assert_eq!(cpu.cycles, 24); // 4 for NOP + 20 for interrupt
 }
 
+#[test]
+fn instructions_retired_counts_instructions() {
+    let program = vec![
+        0x3E, 0x12, // LD A,0x12
+        0xCB, 0x37, // SWAP A
+        0x00, // NOP
+        0x76, // HALT
+    ];
+
+    let mut cpu = Cpu::new();
+    cpu.pc = 0;
+    cpu.sp = 0xC100;
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(program));
+    mmu.if_reg = 0x00;
+    mmu.ie_reg = 0x01;
+
+    for _ in 0..4 {
+        cpu.step(&mut mmu);
+    }
+    assert_eq!(cpu.a, 0x21);
+    assert!(cpu.halted);
+    assert_eq!(cpu.instructions_retired(), 4);
+
+    // Idle steps while halted do not retire anything.
+    for _ in 0..10 {
+        cpu.step(&mut mmu);
+    }
+    assert_eq!(cpu.instructions_retired(), 4);
+
+    // Neither does the interrupt dispatch that ends the HALT.
+    cpu.ime = true;
+    mmu.if_reg = 0x01;
+    cpu.step(&mut mmu);
+    assert!(!cpu.halted);
+    assert_eq!(cpu.pc, 0x0040);
+    assert_eq!(cpu.instructions_retired(), 4);
+}
+
+#[test]
+fn illegal_opcodes_lock_the_cpu() {
+    for opcode in [
+        0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+    ] {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0;
+        cpu.ime = true;
+        let mut mmu = Mmu::new();
+        mmu.load_cart(Cartridge::load(vec![0x00, opcode, 0x3C, 0x3C]));
+        mmu.ie_reg = 0x1F;
+        mmu.if_reg = 0x00;
+
+        cpu.step(&mut mmu);
+        assert!(!cpu.is_locked());
+        cpu.step(&mut mmu);
+        assert!(cpu.is_locked(), "opcode {opcode:02X}");
+        assert_eq!(cpu.locked_at(), Some(0x0001));
+
+        // Nothing runs afterwards, not even a pending interrupt, but time
+        // still passes.
+        mmu.if_reg = 0x1F;
+        let (a, pc, cycles) = (cpu.a, cpu.pc, cpu.cycles);
+        for _ in 0..100 {
+            cpu.step(&mut mmu);
+        }
+        assert_eq!((cpu.a, cpu.pc), (a, pc));
+        assert_eq!(cpu.instructions_retired(), 2);
+        assert_eq!(cpu.cycles, cycles + 400);
+        assert_eq!(mmu.if_reg, 0x1F);
+    }
+}
+
+/// Runs a NOP at `pc` with IME set and returns once the following interrupt
+/// dispatch (if any) has completed.
+fn dispatch_after_nop(pc: u16, sp: u16, ie: u8, if_: u8) -> (Cpu, Mmu) {
+    let mut cpu = Cpu::new();
+    cpu.pc = pc;
+    cpu.sp = sp;
+    cpu.ime = true;
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(vec![0x00; 0x8000]));
+    mmu.ie_reg = ie;
+    mmu.if_reg = if_;
+    let start = cpu.cycles;
+    cpu.step(&mut mmu);
+    // NOP plus the 5 M-cycle dispatch, even when the dispatch is cancelled.
+    assert_eq!(cpu.cycles - start, 24);
+    assert!(!cpu.ime);
+    (cpu, mmu)
+}
+
+#[test]
+fn interrupt_dispatch_services_highest_priority_only() {
+    let (cpu, mmu) = dispatch_after_nop(0x0000, 0xC100, 0x1F, 0x06);
+    assert_eq!(cpu.pc, 0x0048);
+    assert_eq!(cpu.sp, 0xC0FE);
+    assert_eq!(mmu.if_reg & 0x1F, 0x04);
+}
+
+#[test]
+fn ie_write_by_upper_push_cancels_dispatch() {
+    // SP=0000: the upper byte of the return address (0x00) lands in IE.
+    let (cpu, mut mmu) = dispatch_after_nop(0x0000, 0x0000, 0x01, 0x01);
+    assert_eq!(cpu.pc, 0x0000);
+    assert_eq!(cpu.sp, 0xFFFE);
+    assert_eq!(mmu.ie_reg, 0x00);
+    // Nothing was acknowledged, the low byte still went out.
+    assert_eq!(mmu.if_reg & 0x1F, 0x01);
+    assert_eq!(mmu.read_byte(0xFFFE), 0x01);
+}
+
+#[test]
+fn ie_write_by_upper_push_redirects_dispatch() {
+    // Return address 0x0401: the upper push writes 0x04 to IE, leaving only
+    // the timer interrupt enabled even though VBlank has priority.
+    let (cpu, mmu) = dispatch_after_nop(0x0400, 0x0000, 0x05, 0x05);
+    assert_eq!(cpu.pc, 0x0050);
+    assert_eq!(cpu.sp, 0xFFFE);
+    assert_eq!(mmu.ie_reg, 0x04);
+    assert_eq!(mmu.if_reg & 0x1F, 0x01);
+}
+
+#[test]
+fn if_write_by_lower_push_is_seen_by_dispatch() {
+    // SP=FF11: the lower byte of the return address (0x04) lands in IF, so
+    // the pending VBlank request is replaced by a timer request.
+    let (cpu, mmu) = dispatch_after_nop(0x0003, 0xFF11, 0x1F, 0x01);
+    assert_eq!(cpu.pc, 0x0050);
+    assert_eq!(cpu.sp, 0xFF0F);
+    assert_eq!(mmu.if_reg & 0x1F, 0x00);
+}
+
+#[test]
+fn if_cleared_by_lower_push_cancels_dispatch() {
+    // Return address 0x0100: the lower push writes 0x00 to IF.
+    let (cpu, mmu) = dispatch_after_nop(0x00FF, 0xFF11, 0x1F, 0x01);
+    assert_eq!(cpu.pc, 0x0000);
+    assert_eq!(cpu.sp, 0xFF0F);
+    assert_eq!(mmu.if_reg & 0x1F, 0x00);
+}
+
 #[test]
 fn jr_nz_cycles() {
     // JR NZ should take 12 cycles when branch taken and 8 when not
@@ -328,3 +476,455 @@ fn stop_resets_div_and_pauses() {
     cpu.step(&mut mmu);
     assert_eq!(mmu.timer.div, div_after.wrapping_add(4));
 }
+
+// Documented M-cycle counts for every unprefixed opcode, with conditional
+// branches not taken. 0 marks opcodes that are skipped: STOP, HALT, the CB
+// prefix and the illegal opcodes.
+#[rustfmt::skip]
+const OPCODE_M_CYCLES: [u8; 256] = [
+//  x0 x1 x2 x3 x4 x5 x6 x7 x8 x9 xA xB xC xD xE xF
+    1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, // 0x
+    0, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1x
+    2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 2x
+    2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 3x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6x
+    2, 2, 2, 2, 2, 2, 0, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Ax
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Bx
+    2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4, // Cx
+    2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4, // Dx
+    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // Ex
+    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4, // Fx
+];
+
+/// Conditional opcodes: (opcode, flags that make the branch not taken,
+/// flags that make it taken, M-cycles when taken).
+const CONDITIONAL_M_CYCLES: [(u8, u8, u8, u8); 16] = [
+    (0x20, 0x80, 0x00, 3), // JR NZ
+    (0x28, 0x00, 0x80, 3), // JR Z
+    (0x30, 0x10, 0x00, 3), // JR NC
+    (0x38, 0x00, 0x10, 3), // JR C
+    (0xC0, 0x80, 0x00, 5), // RET NZ
+    (0xC8, 0x00, 0x80, 5), // RET Z
+    (0xD0, 0x10, 0x00, 5), // RET NC
+    (0xD8, 0x00, 0x10, 5), // RET C
+    (0xC2, 0x80, 0x00, 4), // JP NZ
+    (0xCA, 0x00, 0x80, 4), // JP Z
+    (0xD2, 0x10, 0x00, 4), // JP NC
+    (0xDA, 0x00, 0x10, 4), // JP C
+    (0xC4, 0x80, 0x00, 6), // CALL NZ
+    (0xCC, 0x00, 0x80, 6), // CALL Z
+    (0xD4, 0x10, 0x00, 6), // CALL NC
+    (0xDC, 0x00, 0x10, 6), // CALL C
+];
+
+/// Runs a single instruction from a fresh machine and returns its M-cycles.
+///
+/// All memory operands point into WRAM/HRAM: immediates are $80/$C0 (so a16
+/// is $C080 and a8 is $FF80), the register pairs hold $C080 and SP is $DFF0.
+fn run_timed(bytes: &[u8], flags: u8) -> u32 {
+    let mut program = bytes.to_vec();
+    program.extend_from_slice(&[0x80, 0xC0]);
+    let mut cpu = Cpu::new();
+    cpu.pc = 0;
+    cpu.sp = 0xDFF0;
+    cpu.f = flags;
+    cpu.b = 0xC0;
+    cpu.c = 0x80;
+    cpu.d = 0xC0;
+    cpu.e = 0x80;
+    cpu.h = 0xC0;
+    cpu.l = 0x80;
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(program));
+    mmu.ie_reg = 0;
+    cpu.step(&mut mmu);
+    cpu.last_instruction_cycles()
+}
+
+#[test]
+fn instruction_timing_matches_golden_table() {
+    let mut mismatches = Vec::new();
+
+    for (opcode, &expected) in OPCODE_M_CYCLES.iter().enumerate() {
+        if expected == 0 {
+            continue;
+        }
+        let opcode = opcode as u8;
+        // Default flags leave every conditional branch not taken.
+        let flags = CONDITIONAL_M_CYCLES
+            .iter()
+            .find(|(op, ..)| *op == opcode)
+            .map_or(0, |&(_, not_taken, ..)| not_taken);
+        let actual = run_timed(&[opcode], flags);
+        if actual != expected as u32 {
+            mismatches.push(format!("{opcode:02X}: expected {expected}, got {actual}"));
+        }
+    }
+
+    for (opcode, _, taken, expected) in CONDITIONAL_M_CYCLES {
+        let actual = run_timed(&[opcode], taken);
+        if actual != expected as u32 {
+            mismatches.push(format!(
+                "{opcode:02X} (taken): expected {expected}, got {actual}"
+            ));
+        }
+    }
+
+    for cb in 0..=0xFFu8 {
+        let expected = match (cb & 0x07, cb) {
+            (6, 0x40..=0x7F) => 3, // BIT n,(HL)
+            (6, _) => 4,
+            _ => 2,
+        };
+        let actual = run_timed(&[0xCB, cb], 0);
+        if actual != expected {
+            mismatches.push(format!("CB {cb:02X}: expected {expected}, got {actual}"));
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "instruction timing drift:\n{}",
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn last_instruction_cycles_excludes_interrupt_dispatch() {
+    let mut cpu = Cpu::new();
+    cpu.pc = 0;
+    cpu.sp = 0xC100;
+    cpu.ime = true;
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(vec![0x00]));
+    mmu.if_reg = 0x01;
+    mmu.ie_reg = 0x01;
+
+    cpu.step(&mut mmu);
+
+    assert_eq!(cpu.pc, 0x0040);
+    assert_eq!(cpu.last_instruction_cycles(), 1);
+}
+
+#[test]
+fn last_instruction_cycles_counts_dmg_stop_idle() {
+    // STOP
+    let mut cpu = Cpu::new();
+    cpu.pc = 0;
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(vec![0x10, 0x00]));
+
+    cpu.step(&mut mmu);
+    assert!(cpu.stopped);
+    cpu.step(&mut mmu);
+    assert_eq!(cpu.last_instruction_cycles(), 1);
+}
+
+/// Runs `program` from 0xC000 with HL=0xFE00 while the PPU sits at the start
+/// of mode 2, returning the original and resulting OAM.
+fn run_oam_bug_instruction(program: &[u8], enabled: bool) -> ([u8; 0xA0], [u8; 0xA0]) {
+    let mut cpu = Cpu::new();
+    cpu.pc = 0xC000;
+    cpu.h = 0xFE;
+    cpu.l = 0x00;
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(vec![0x00; 0x8000]));
+    for (i, &b) in program.iter().enumerate() {
+        mmu.write_byte(0xC000 + i as u16, b);
+    }
+    mmu.ppu.set_oam_bug_enabled(enabled);
+    let orig = common::start_oam_scan(&mut mmu);
+    cpu.step(&mut mmu);
+    (orig, mmu.ppu.oam)
+}
+
+#[test]
+fn inc_dec_hl_in_oam_during_mode2_triggers_write_corruption() {
+    for opcode in [0x23, 0x2B] {
+        let (orig, oam) = run_oam_bug_instruction(&[opcode], true);
+        assert_eq!(
+            oam,
+            common::oam_after_write_corruption(&orig),
+            "opcode {opcode:02X}"
+        );
+
+        let (orig, oam) = run_oam_bug_instruction(&[opcode], false);
+        assert_eq!(oam, orig, "opcode {opcode:02X} with the bug disabled");
+    }
+}
+
+#[test]
+fn ld_a_hl_inc_in_oam_during_mode2_triggers_read_corruption() {
+    let (orig, oam) = run_oam_bug_instruction(&[0x2A], true);
+    // The combined read + increment on row 1 copies row 0 into row 1 and
+    // glitches its first word as b | (a & c).
+    let (a, b, c) = (
+        common::oam_word(&orig, 4),
+        common::oam_word(&orig, 0),
+        common::oam_word(&orig, 2),
+    );
+    let mut expected = orig;
+    expected.copy_within(0..8, 8);
+    expected[8..10].copy_from_slice(&(b | (a & c)).to_le_bytes());
+    assert_eq!(oam, expected);
+
+    let (orig, oam) = run_oam_bug_instruction(&[0x2A], false);
+    assert_eq!(oam, orig);
+}
+
+/// Runs the single instruction at the start of `program` on a fresh CPU
+/// state, reusing `mmu` so exhaustive sweeps stay fast.
+fn run_one(mmu: &mut Mmu, setup: impl FnOnce(&mut Cpu)) -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.pc = 0;
+    setup(&mut cpu);
+    cpu.step(mmu);
+    cpu
+}
+
+/// DAA as described by Pan Docs: the adjustment is picked from the flags
+/// the previous ADD/SUB left behind and, after an addition, from A itself.
+fn reference_daa(a: u8, f: u8) -> (u8, u8) {
+    let (n, h, c) = (f & 0x40 != 0, f & 0x20 != 0, f & 0x10 != 0);
+    let mut adjust = 0;
+    let mut carry = c;
+    if n {
+        if h {
+            adjust |= 0x06;
+        }
+        if c {
+            adjust |= 0x60;
+        }
+        let result = a.wrapping_sub(adjust);
+        return (
+            result,
+            zero_flag(result) | 0x40 | if carry { 0x10 } else { 0 },
+        );
+    }
+    if h || a & 0x0F > 0x09 {
+        adjust |= 0x06;
+    }
+    if c || a > 0x99 {
+        adjust |= 0x60;
+        carry = true;
+    }
+    let result = a.wrapping_add(adjust);
+    (result, zero_flag(result) | if carry { 0x10 } else { 0 })
+}
+
+fn zero_flag(value: u8) -> u8 {
+    if value == 0 { 0x80 } else { 0 }
+}
+
+fn bcd(n: u32) -> u8 {
+    (((n / 10) << 4) | (n % 10)) as u8
+}
+
+#[test]
+fn daa_matches_reference_for_every_input() {
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(vec![0x27])); // DAA
+    for a in 0..=0xFFu8 {
+        for f in (0..16u8).map(|flags| flags << 4) {
+            let cpu = run_one(&mut mmu, |cpu| {
+                cpu.a = a;
+                cpu.f = f;
+            });
+            assert_eq!(
+                (cpu.a, cpu.f),
+                reference_daa(a, f),
+                "DAA with A={a:02X} F={f:02X}"
+            );
+        }
+    }
+
+    // Spot checks of out-of-range inputs against known hardware results.
+    for (a, f, expected) in [
+        (0x9A, 0x00, (0x00, 0x90)),
+        (0xFF, 0x00, (0x65, 0x10)),
+        (0x00, 0x70, (0x9A, 0x50)),
+        (0x0A, 0x20, (0x10, 0x00)),
+        (0x00, 0xF0, (0x9A, 0x50)),
+    ] {
+        let cpu = run_one(&mut mmu, |cpu| {
+            cpu.a = a;
+            cpu.f = f;
+        });
+        assert_eq!((cpu.a, cpu.f), expected, "DAA with A={a:02X} F={f:02X}");
+    }
+}
+
+#[test]
+fn daa_corrects_bcd_addition_and_subtraction() {
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(vec![0x88, 0x27])); // ADC A,B; DAA
+    let mut sub = Mmu::new();
+    sub.load_cart(Cartridge::load(vec![0x98, 0x27])); // SBC A,B; DAA
+    for x in 0..100 {
+        for y in 0..100 {
+            for carry_in in [0, 1] {
+                let setup = |cpu: &mut Cpu| {
+                    cpu.a = bcd(x);
+                    cpu.b = bcd(y);
+                    cpu.f = (carry_in as u8) << 4;
+                };
+
+                let mut cpu = run_one(&mut mmu, setup);
+                cpu.step(&mut mmu);
+                let sum = x + y + carry_in;
+                assert_eq!(cpu.a, bcd(sum % 100), "{x} + {y} + {carry_in}");
+                assert_eq!(cpu.f & 0x10 != 0, sum >= 100, "{x} + {y} + {carry_in}");
+                assert_eq!(cpu.f & 0x80 != 0, sum % 100 == 0);
+
+                let mut cpu = run_one(&mut sub, setup);
+                cpu.step(&mut sub);
+                let borrow = x < y + carry_in;
+                let diff = (x + 100 - y - carry_in) % 100;
+                assert_eq!(cpu.a, bcd(diff), "{x} - {y} - {carry_in}");
+                assert_eq!(cpu.f & 0x10 != 0, borrow, "{x} - {y} - {carry_in}");
+                assert_eq!(cpu.f & 0x60, 0x40);
+            }
+        }
+    }
+}
+
+#[test]
+fn sp_relative_flags_come_from_the_unsigned_low_byte() {
+    let mut add_sp = Mmu::new();
+    let mut ld_hl = Mmu::new();
+    for (sp, offset, result, flags) in [
+        (0x0000u16, 0x01u8, 0x0001u16, 0x00u8),
+        (0x000F, 0x01, 0x0010, 0x20),
+        (0x00F0, 0x10, 0x0100, 0x10),
+        (0x00FF, 0x01, 0x0100, 0x30),
+        (0xFFFF, 0x01, 0x0000, 0x30), // Z stays clear on a zero result
+        (0x0000, 0xFF, 0xFFFF, 0x00), // -1 with no low-byte carry
+        (0x0001, 0xFF, 0x0000, 0x30), // -1 carries out of both nibbles
+        (0x1000, 0x80, 0x0F80, 0x00), // -128
+        (0x10FF, 0x80, 0x107F, 0x10),
+        (0xC008, 0xF8, 0xC000, 0x30), // -8
+        (0xC007, 0xF8, 0xBFFF, 0x00),
+    ] {
+        add_sp.load_cart(Cartridge::load(vec![0xE8, offset])); // ADD SP,r8
+        ld_hl.load_cart(Cartridge::load(vec![0xF8, offset])); // LD HL,SP+r8
+        let setup = |cpu: &mut Cpu| {
+            cpu.sp = sp;
+            cpu.f = 0xF0;
+        };
+
+        let cpu = run_one(&mut add_sp, setup);
+        assert_eq!(
+            (cpu.sp, cpu.f),
+            (result, flags),
+            "ADD SP,{offset:02X} at {sp:04X}"
+        );
+
+        let cpu = run_one(&mut ld_hl, setup);
+        assert_eq!(cpu.h, (result >> 8) as u8);
+        assert_eq!(cpu.l, result as u8);
+        assert_eq!(
+            (cpu.sp, cpu.f),
+            (sp, flags),
+            "LD HL,SP+{offset:02X} at {sp:04X}"
+        );
+    }
+
+    // Every low byte and offset: H and C come from adding the offset as an
+    // unsigned byte to SP's low byte, whatever its sign.
+    for offset in 0..=0xFFu8 {
+        add_sp.load_cart(Cartridge::load(vec![0xE8, offset]));
+        for low in 0..=0xFFu16 {
+            let sp = 0x8000 | low;
+            let cpu = run_one(&mut add_sp, |cpu| cpu.sp = sp);
+            let half = (low & 0x0F) + u16::from(offset & 0x0F) > 0x0F;
+            let full = low + u16::from(offset) > 0xFF;
+            assert_eq!(cpu.sp, sp.wrapping_add(offset as i8 as u16));
+            assert_eq!(
+                cpu.f,
+                if half { 0x20 } else { 0 } | if full { 0x10 } else { 0 }
+            );
+        }
+    }
+}
+
+#[test]
+fn preview_next_reports_targets_and_effects() {
+    let program = vec![
+        0xCD, 0x34, 0x12, // CALL $1234
+        0xC2, 0x00, 0x20, // JP NZ,$2000
+        0xFF, // RST $38
+        0xC8, // RET Z
+        0x77, // LD (HL),A
+        0xCB, 0x46, // BIT 0,(HL)
+        0x80, // ADD A,B
+    ];
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(program));
+    mmu.write_byte(0xC0F0, 0x78);
+    mmu.write_byte(0xC0F1, 0x56);
+    let mut cpu = Cpu::new();
+    cpu.pc = 0;
+    cpu.sp = 0xC0F0;
+    cpu.f = 0x80; // Z set
+    cpu.h = 0xC0;
+    cpu.l = 0x10;
+    let mut preview = |pc: u16| {
+        cpu.pc = pc;
+        cpu.preview_next(&mmu)
+    };
+
+    let call = preview(0);
+    assert_eq!(call.mnemonic, "CALL $1234");
+    assert_eq!(call.flow, ControlFlow::Call);
+    assert!(call.taken && !call.conditional);
+    assert_eq!(call.branch_target, Some(0x1234));
+    assert_eq!(call.return_address, Some(0x0003));
+    assert_eq!(call.push_address, Some(0xC0EE));
+    assert_eq!(call.next_pcs().collect::<Vec<_>>(), [0x1234]);
+    assert_eq!(call.registers_written, RegisterSet::SP);
+
+    let jp = preview(3);
+    assert_eq!(jp.flow, ControlFlow::Jump);
+    assert!(
+        jp.conditional && !jp.taken,
+        "Z is set, so JP NZ falls through"
+    );
+    assert_eq!(jp.next_pcs().collect::<Vec<_>>(), [0x2000, 0x0006]);
+    assert_eq!(jp.return_address, None);
+    assert_eq!(jp.push_address, None);
+
+    let rst = preview(6);
+    assert_eq!(rst.flow, ControlFlow::Call);
+    assert_eq!(rst.branch_target, Some(0x0038));
+    assert_eq!(rst.return_address, Some(0x0007));
+
+    let ret = preview(7);
+    assert_eq!(ret.flow, ControlFlow::Return);
+    assert!(ret.conditional && ret.taken);
+    assert_eq!(ret.next_pcs().collect::<Vec<_>>(), [0x5678, 0x0008]);
+
+    let store = preview(8);
+    assert_eq!(store.memory_address, Some(0xC010));
+    assert!(store.registers_written.is_empty());
+    assert_eq!(store.flags_affected, 0);
+
+    let bit = preview(9);
+    assert_eq!((bit.len, bit.memory_address), (2, Some(0xC010)));
+    assert_eq!(bit.flags_affected, 0xE0);
+
+    let add = preview(11);
+    assert_eq!(add.flow, ControlFlow::Sequential);
+    assert_eq!(add.next_pcs().collect::<Vec<_>>(), [0x000C]);
+    assert_eq!(add.registers_written, RegisterSet::A);
+    assert_eq!(add.flags_affected, 0xF0);
+
+    // Previewing executes nothing.
+    assert_eq!(cpu.pc, 11);
+    assert_eq!(cpu.cycles, 0);
+    assert_eq!(cpu.sp, 0xC0F0);
+}
@@ -7,12 +7,6 @@ fn run_for_frames(gb: &mut GameBoy, frames: u32) {
     while seen < frames {
         gb.cpu.step(&mut gb.mmu);
 
-        // In DMG mode, STOP halts the whole system in this emulator model, so
-        // we'll never see another completed frame after STOP executes.
-        if gb.cpu.stopped && !gb.mmu.is_cgb() {
-            break;
-        }
-
         if gb.mmu.ppu.frame_ready() {
             gb.mmu.ppu.clear_frame_flag();
             seen += 1;
@@ -165,6 +159,21 @@ fn daid_stop_instr_dmg() {
     assert_eq!(gb.cpu.pc, stopped_pc, "PC changed while stopped");
 }
 
+#[test]
+fn daid_stop_instr_dmg_screen() {
+    // On DMG, STOP with the LCD on freezes the PPU and the panel goes dark.
+    let mut gb = GameBoy::new_with_mode(false);
+    let rom = std::fs::read(common::rom_path("daid/stop_instr.gb")).expect("rom not found");
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb.mmu
+        .ppu
+        .set_dmg_palette([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+
+    run_for_frames(&mut gb, 120);
+    assert!(gb.cpu.stopped, "CPU never entered STOP in DMG mode");
+    assert_framebuffer_matches_png(&gb, "daid/stop_instr.dmg.png");
+}
+
 #[test]
 fn daid_stop_instr_cgb() {
     // In CGB mode, STOP should keep the PPU running but prevent it from accessing
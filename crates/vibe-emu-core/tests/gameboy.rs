@@ -0,0 +1,1841 @@
+use std::time::Duration;
+use tempfile::tempdir;
+use vibe_emu_core::{
+    cartridge::Cartridge,
+    gameboy::{AccuracyLevel, GameBoy, PowerState, RunBudget, RunOutcome},
+    hardware::{CgbRevision, DmgRevision, HardwareRevision, Model},
+    input::Buttons,
+    savestate::{self, OLDEST_STATE_VERSION, Record, STATE_VERSION, SlotLoadError, StateError},
+    snapshot::{Snapshot, snapshot_channel},
+};
+
+#[test]
+fn switching_cgb_revision_reconfigures_apu_quirks() {
+    let mut gb = GameBoy::new_with_revision(true, CgbRevision::RevB);
+    assert_eq!(gb.mmu.apu.cgb_revision(), CgbRevision::RevB);
+    assert!(!gb.mmu.apu.uses_de_quirks());
+
+    gb.mmu.wram[0][0x10] = 0x5A;
+    gb.set_cgb_revision(CgbRevision::RevD);
+    assert_eq!(gb.cgb_revision, CgbRevision::RevD);
+    assert_eq!(gb.mmu.cgb_revision(), CgbRevision::RevD);
+    assert_eq!(gb.mmu.apu.cgb_revision(), CgbRevision::RevD);
+    assert!(gb.mmu.apu.uses_de_quirks());
+    // State survives the switch until the caller resets.
+    assert_eq!(gb.mmu.wram[0][0x10], 0x5A);
+    assert!(gb.revision_reset_pending());
+
+    gb.reset();
+    assert!(!gb.revision_reset_pending());
+    assert_eq!(gb.mmu.apu.cgb_revision(), CgbRevision::RevD);
+
+    gb.set_cgb_revision(CgbRevision::RevE);
+    assert_eq!(gb.mmu.apu.cgb_revision(), CgbRevision::RevE);
+    assert!(gb.mmu.apu.uses_de_quirks());
+}
+
+#[test]
+fn switching_dmg_revision_updates_devices() {
+    let mut gb = GameBoy::new();
+    gb.set_dmg_revision(DmgRevision::RevB);
+    assert_eq!(gb.dmg_revision, DmgRevision::RevB);
+    assert_eq!(gb.mmu.dmg_revision(), DmgRevision::RevB);
+    assert_eq!(gb.mmu.apu.dmg_revision(), DmgRevision::RevB);
+    assert!(!gb.mmu.apu.uses_de_quirks());
+    assert!(gb.revision_reset_pending());
+
+    gb.reset_power_on();
+    assert!(!gb.revision_reset_pending());
+    assert_eq!(gb.mmu.apu.dmg_revision(), DmgRevision::RevB);
+}
+
+#[test]
+fn setting_same_revision_does_not_require_reset() {
+    let mut gb = GameBoy::new_with_mode(true);
+    gb.set_cgb_revision(CgbRevision::default());
+    assert!(!gb.revision_reset_pending());
+}
+
+fn looping_cart() -> Cartridge {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100] = 0x18; // JR -2
+    rom[0x101] = 0xFE;
+    Cartridge::load(rom)
+}
+
+#[test]
+fn elapsed_ns_tracks_emulated_cycles() {
+    let mut gb = GameBoy::new_with_mode(true);
+    gb.mmu.load_cart(looping_cart());
+    gb.cpu.cycles = 0;
+    assert_eq!(gb.elapsed_ns(), 0);
+
+    gb.cpu.cycles = 4_194_304;
+    assert_eq!(gb.cycles(), 4_194_304);
+    assert_eq!(gb.elapsed_ns(), 1_000_000_000);
+
+    // One frame is ~16.74 ms of emulated time.
+    assert!(gb.step_to_ly(50));
+    let start = gb.elapsed_ns();
+    assert!(gb.step_to_ly(50));
+    let frame_ns = gb.elapsed_ns() - start;
+    assert!((16_700_000..16_800_000).contains(&frame_ns), "{frame_ns}");
+
+    // Double speed runs twice the instructions in the same emulated time.
+    gb.cpu.double_speed = true;
+    assert!(gb.step_to_ly(50));
+    let start = gb.elapsed_ns();
+    assert!(gb.step_to_ly(50));
+    let frame_ns = gb.elapsed_ns() - start;
+    assert!((16_700_000..16_800_000).contains(&frame_ns), "{frame_ns}");
+}
+
+#[test]
+fn step_to_ly_stops_on_target_line() {
+    let mut gb = GameBoy::new();
+    gb.mmu.load_cart(looping_cart());
+
+    assert!(gb.step_to_ly(67));
+    assert_eq!(gb.mmu.ppu.ly(), 67);
+
+    // Already on the target: runs to the same line of the next frame.
+    let frames = gb.mmu.ppu.frames();
+    assert!(gb.step_to_ly(67));
+    assert_eq!(gb.mmu.ppu.ly(), 67);
+    assert_eq!(gb.mmu.ppu.frames(), frames + 1);
+}
+
+#[test]
+fn step_to_next_scanline_advances_one_line() {
+    let mut gb = GameBoy::new();
+    gb.mmu.load_cart(looping_cart());
+    assert!(gb.step_to_ly(10));
+
+    assert!(gb.step_to_next_scanline());
+    assert_eq!(gb.mmu.ppu.ly(), 11);
+}
+
+#[test]
+fn step_to_ly_gives_up_when_lcd_is_off() {
+    let mut gb = GameBoy::new();
+    gb.mmu.load_cart(looping_cart());
+    gb.mmu.write_byte(0xFF40, 0x00);
+
+    assert!(!gb.step_to_ly(67));
+    assert!(!gb.step_to_next_scanline());
+}
+
+/// Runs `gb.cycles_until_vblank()` cycles and checks VBlank starts in the
+/// last instruction, not before.
+fn run_exactly_to_vblank(gb: &mut GameBoy) {
+    let budget = gb.cycles_until_vblank();
+    let end = gb.cycles() + u64::from(budget);
+    gb.mmu.ppu.clear_frame_flag();
+    while gb.cycles() < end {
+        assert!(!gb.mmu.ppu.frame_ready(), "VBlank came early");
+        gb.cpu.step(&mut gb.mmu);
+    }
+    assert!(
+        gb.mmu.ppu.frame_ready(),
+        "VBlank late after {budget} cycles"
+    );
+    assert_eq!(gb.mmu.ppu.ly(), 144);
+}
+
+#[test]
+fn cycles_until_vblank_lands_on_vblank_entry() {
+    let mut gb = GameBoy::new();
+    gb.mmu.load_cart(looping_cart());
+    for ly in [0, 10, 143, 144, 150, 153] {
+        assert!(gb.step_to_ly(ly));
+        run_exactly_to_vblank(&mut gb);
+    }
+    // Mid-line, and straight out of VBlank.
+    for _ in 0..7 {
+        gb.cpu.step(&mut gb.mmu);
+    }
+    run_exactly_to_vblank(&mut gb);
+    run_exactly_to_vblank(&mut gb);
+
+    // The DMG LCD startup sequence after switching the LCD back on.
+    gb.mmu.write_byte(0xFF40, 0x00);
+    gb.mmu.write_byte(0xFF40, 0x91);
+    run_exactly_to_vblank(&mut gb);
+
+    let mut gb = GameBoy::new_with_mode(true);
+    gb.mmu.load_cart(looping_cart());
+    gb.cpu.double_speed = true;
+    for ly in [20, 100, 152] {
+        assert!(gb.step_to_ly(ly));
+        run_exactly_to_vblank(&mut gb);
+    }
+}
+
+#[test]
+fn cycles_until_vblank_is_one_frame_with_lcd_off() {
+    let mut gb = GameBoy::new();
+    gb.mmu.load_cart(looping_cart());
+    gb.mmu.write_byte(0xFF40, 0x00);
+    assert_eq!(gb.cycles_until_vblank(), 456 * 154);
+}
+
+#[test]
+fn save_state_round_trips_running_machine() {
+    let mut gb = GameBoy::new_with_mode(true);
+    gb.mmu.load_cart(looping_cart());
+    assert!(gb.step_to_ly(100));
+    gb.mmu.wram[3][0x20] = 0xA5;
+    let state = gb.save_state();
+    let (pc, cycles) = (gb.cpu.pc, gb.cpu.cycles);
+
+    assert!(gb.step_to_ly(20));
+    gb.mmu.wram[3][0x20] = 0;
+    gb.load_state(&state).unwrap();
+    assert_eq!(gb.cpu.pc, pc);
+    assert_eq!(gb.cpu.cycles, cycles);
+    assert_eq!(gb.mmu.ppu.ly(), 100);
+    assert_eq!(gb.mmu.wram[3][0x20], 0xA5);
+    assert!(gb.mmu.cart.is_some());
+
+    // Saving again right away reproduces the same bytes.
+    assert_eq!(gb.save_state(), state);
+}
+
+#[test]
+fn state_slots_round_trip_next_to_rom() {
+    let dir = tempdir().unwrap();
+    let rom = dir.path().join("game.gb");
+    let mut gb = GameBoy::new();
+    gb.mmu.load_cart(looping_cart());
+
+    assert!(matches!(
+        gb.load_state_slot(&rom, 1),
+        Err(SlotLoadError::Empty(1))
+    ));
+
+    assert!(gb.step_to_ly(100));
+    gb.save_state_slot(&rom, 1).unwrap();
+    assert!(dir.path().join("game.state1").exists());
+    assert!(gb.step_to_ly(20));
+    gb.save_state_slot(&rom, 2).unwrap();
+
+    gb.load_state_slot(&rom, 1).unwrap();
+    assert_eq!(gb.mmu.ppu.ly(), 100);
+    gb.load_state_slot(&rom, 2).unwrap();
+    assert_eq!(gb.mmu.ppu.ly(), 20);
+
+    std::fs::write(savestate::slot_path(&rom, 3), b"junk").unwrap();
+    assert!(matches!(
+        gb.load_state_slot(&rom, 3),
+        Err(SlotLoadError::State(StateError::BadMagic))
+    ));
+    assert_eq!(gb.mmu.ppu.ly(), 20);
+}
+
+fn field(bytes: &[u8]) -> Vec<u8> {
+    bytes.to_vec()
+}
+
+/// Hand-assembles a minimal state at the oldest supported version, as a
+/// build predating most fields would have written it: only a handful of
+/// fields, no APU, serial or HDMA records.
+fn sparse_state() -> Vec<u8> {
+    let mut machine = Record::new();
+    machine.put_bytes("cgb", field(&[0]));
+    machine.put_bytes("dmg_revision", field(&[3]));
+    machine.put_bytes("cgb_revision", field(&[5]));
+
+    let mut cpu = Record::new();
+    cpu.put_bytes("a", field(&[0x42]));
+    cpu.put_bytes("pc", field(&0x0150u16.to_le_bytes()));
+    cpu.put_bytes("sp", field(&0xDFF0u16.to_le_bytes()));
+    // A field from some other build; ignored on load.
+    cpu.put_bytes("retired", field(&[1, 2, 3]));
+
+    let mut mmu = Record::new();
+    mmu.put_bytes("ie_reg", field(&[0x05]));
+    mmu.put_record("ppu", &Record::new());
+
+    let mut root = Record::new();
+    root.put_record("machine", &machine);
+    root.put_record("cpu", &cpu);
+    root.put_record("mmu", &mmu);
+
+    let mut data = b"VBST".to_vec();
+    data.extend_from_slice(&OLDEST_STATE_VERSION.to_le_bytes());
+    data.extend_from_slice(&root.encode());
+    data
+}
+
+#[test]
+fn load_state_fills_missing_fields_from_defaults() {
+    let data = sparse_state();
+    assert_eq!(
+        savestate::decode_state_raw(&data).unwrap().0,
+        OLDEST_STATE_VERSION
+    );
+
+    let mut gb = GameBoy::new_with_mode(true);
+    gb.load_state(&data).unwrap();
+    let mut fresh = GameBoy::new();
+
+    assert!(!gb.cgb);
+    assert_eq!(gb.dmg_revision, DmgRevision::RevC);
+    assert_eq!(gb.cpu.a, 0x42);
+    assert_eq!(gb.cpu.pc, 0x0150);
+    assert_eq!(gb.cpu.sp, 0xDFF0);
+    assert_eq!(gb.mmu.ie_reg, 0x05);
+    // Fields the state does not carry keep their post-boot values.
+    assert_eq!(gb.cpu.f, fresh.cpu.f);
+    assert_eq!(gb.cpu.b, fresh.cpu.b);
+    assert_eq!(gb.mmu.ppu.ly(), fresh.mmu.ppu.ly());
+    assert_eq!(gb.mmu.read_byte(0xFF26), fresh.mmu.read_byte(0xFF26));
+
+    // The machine runs and re-saves at the current version.
+    for _ in 0..1000 {
+        gb.cpu.step(&mut gb.mmu);
+    }
+    let resaved = gb.save_state();
+    assert_eq!(
+        savestate::decode_state_raw(&resaved).unwrap().0,
+        STATE_VERSION
+    );
+}
+
+/// The cartridge `fixtures/state_v1_first_build.bin` was saved with: it
+/// enables cart RAM, then counts $C000 up forever, mirroring it to $A000.
+fn state_fixture_cart() -> Cartridge {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0134..0x013C].copy_from_slice(b"STATEFIX");
+    rom[0x0147] = 0x03; // MBC1 + RAM + Battery
+    rom[0x0149] = 0x02; // 8KB RAM
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]); // JP $0150
+    rom[0x0150..0x015F].copy_from_slice(&[
+        0x3E, 0x0A, // LD A,$0A
+        0xEA, 0x00, 0x00, // LD ($0000),A
+        0x21, 0x00, 0xC0, // LD HL,$C000
+        0x34, // INC (HL)
+        0x7E, // LD A,(HL)
+        0xEA, 0x00, 0xA0, // LD ($A000),A
+        0x18, 0xF6, // JR -10
+    ]);
+    Cartridge::load(rom)
+}
+
+#[test]
+fn loads_state_written_by_the_first_versioned_build() {
+    // Saved by the first build with versioned states, three frames and
+    // 1000 instructions into `state_fixture_cart` on a DMG. Fields added
+    // since then are missing from it.
+    let state = include_bytes!("fixtures/state_v1_first_build.bin");
+    assert_eq!(
+        u16::from_le_bytes([state[4], state[5]]),
+        OLDEST_STATE_VERSION
+    );
+
+    let mut gb = GameBoy::new_with_mode(false);
+    gb.mmu.load_cart(state_fixture_cart());
+    gb.load_state(state).unwrap();
+    assert_eq!(gb.cpu.pc, 0x015D);
+    assert_eq!(gb.cpu.cycles, 221_492);
+    assert_eq!(gb.mmu.ppu.ly(), 16);
+    assert_eq!(gb.mmu.wram[0][0], 0x6B);
+    assert_eq!(gb.mmu.cart.as_ref().unwrap().ram[0], 0x6B);
+
+    // The machine carries on from there.
+    assert!(gb.run_frame());
+    assert!((0x0155..0x015F).contains(&gb.cpu.pc));
+    let counter = gb.mmu.wram[0][0];
+    let mirrored = gb.mmu.cart.as_ref().unwrap().ram[0];
+    assert!(counter.wrapping_sub(mirrored) <= 1);
+    assert_ne!(counter, 0x6B);
+}
+
+#[test]
+fn load_state_rejects_unsupported_versions() {
+    let mut gb = GameBoy::new();
+    let mut data = gb.save_state();
+
+    data[4..6].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        gb.load_state(&data),
+        Err(StateError::VersionTooNew {
+            version: STATE_VERSION + 1,
+            newest: STATE_VERSION,
+        })
+    );
+
+    data[4..6].copy_from_slice(&(OLDEST_STATE_VERSION - 1).to_le_bytes());
+    assert_eq!(
+        gb.load_state(&data),
+        Err(StateError::VersionTooOld {
+            version: OLDEST_STATE_VERSION - 1,
+            oldest: OLDEST_STATE_VERSION,
+        })
+    );
+}
+
+#[test]
+fn load_state_marks_cart_ram_dirty() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x03; // MBC1 + RAM + Battery
+    rom[0x0149] = 0x02; // 8KB RAM
+    let mut gb = GameBoy::new();
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb.mmu.write_byte(0x0000, 0x0A);
+    gb.mmu.write_byte(0xA000, 0x5A);
+    let state = gb.save_state();
+
+    gb.mmu.cart.as_mut().unwrap().save_ram().unwrap();
+    assert!(!gb.mmu.cart.as_ref().unwrap().ram_dirty());
+    gb.load_state(&state).unwrap();
+    let cart = gb.mmu.cart.as_ref().unwrap();
+    assert!(cart.ram_dirty());
+    assert_eq!(cart.ram[0], 0x5A);
+}
+
+#[test]
+fn load_state_keeps_mbc3_without_clock_rtc_free() {
+    let mbc3 = |cart_type| {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0147] = cart_type;
+        rom[0x0149] = 0x03;
+        let mut gb = GameBoy::new();
+        gb.mmu.load_cart(Cartridge::load(rom));
+        gb
+    };
+    // Same title, checksum and size, so the state is accepted.
+    let state = mbc3(0x10).save_state();
+
+    let mut gb = mbc3(0x13);
+    gb.load_state(&state).unwrap();
+    assert!(gb.mmu.cart.as_ref().unwrap().rtc().is_none());
+}
+
+#[test]
+fn load_state_rejects_other_cartridge() {
+    let mut gb = GameBoy::new();
+    gb.mmu.load_cart(looping_cart());
+    let state = gb.save_state();
+
+    let mut other = looping_cart();
+    other.rom[0x14E] = 0x12;
+    let mut gb = GameBoy::new();
+    gb.mmu.load_cart(other);
+    gb.cpu.a = 0x99;
+    assert_eq!(gb.load_state(&state), Err(StateError::CartridgeMismatch));
+    // A failed load leaves the machine and cartridge in place.
+    assert_eq!(gb.cpu.a, 0x99);
+    assert_eq!(gb.mmu.cart.as_ref().unwrap().rom[0x14E], 0x12);
+
+    let mut empty = GameBoy::new();
+    assert_eq!(empty.load_state(&state), Err(StateError::CartridgeMismatch));
+}
+
+/// ROM whose picture depends on the joypad: it keeps copying P1 into SCX
+/// and a running sum of P1 reads into BGP, so earlier inputs stay visible.
+fn input_driven_cart() -> Cartridge {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]); // NOP; JP 0x0150
+    rom[0x150..0x15E].copy_from_slice(&[
+        0x3E, 0x10, // LD A,0x10 (select action buttons)
+        0xE0, 0x00, // LDH (P1),A
+        0xF0, 0x00, // LDH A,(P1)
+        0xE0, 0x43, // LDH (SCX),A
+        0x80, // ADD A,B
+        0x47, // LD B,A
+        0xE0, 0x47, // LDH (BGP),A
+        0x18, 0xF6, // JR 0x0154
+    ]);
+    Cartridge::load(rom)
+}
+
+fn frame_hash(gb: &GameBoy) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    gb.mmu.ppu.framebuffer().hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn resimulate_matches_live_run() {
+    let inputs: Vec<u8> = (0..12u8)
+        .map(|i| match i % 5 {
+            0 => (Buttons::A | Buttons::START).bits(),
+            1 | 2 => Buttons::B.bits(),
+            _ => 0,
+        })
+        .collect();
+
+    let mut gb = GameBoy::new_with_mode(false);
+    gb.mmu.load_cart(input_driven_cart());
+    for _ in 0..2 {
+        assert!(gb.run_frame());
+    }
+    let snapshot = gb.save_state();
+
+    let mut live = Vec::new();
+    for &input in &inputs {
+        assert!(gb.run_frame_with_buttons(Buttons::from_bits(input)));
+        live.push(frame_hash(&gb));
+    }
+    assert!(live.windows(2).any(|w| w[0] != w[1]));
+    let live_state = gb.save_state();
+
+    // Roll the same machine back and replay.
+    gb.resimulate(&snapshot, &inputs).unwrap();
+    assert_eq!(frame_hash(&gb), live[live.len() - 1]);
+    assert_eq!(gb.save_state(), live_state);
+
+    // A second machine with the same cartridge replays identically.
+    let mut peer = Box::new(GameBoy::new_with_mode(false));
+    peer.mmu.load_cart(input_driven_cart());
+    peer.resimulate(&snapshot, &inputs).unwrap();
+    assert_eq!(frame_hash(&peer), live[live.len() - 1]);
+
+    // Different inputs diverge.
+    let mut other = inputs.clone();
+    other[3] = Buttons::A.bits();
+    peer.resimulate(&snapshot, &other).unwrap();
+    assert_ne!(frame_hash(&peer), live[live.len() - 1]);
+}
+
+/// Enables the VBlank interrupt (handler is a bare RETI) and spins, after
+/// running `prologue`.
+fn idle_cart(prologue: &[u8]) -> Box<GameBoy> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x40] = 0xD9; // RETI
+    let mut code = prologue.to_vec();
+    code.extend_from_slice(&[
+        0x3E, 0x01, // LD A,$01
+        0xE0, 0xFF, // LDH (IE),A
+        0xFB, // EI
+        0x18, 0xFE, // JR -2
+    ]);
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb
+}
+
+#[test]
+fn run_stops_when_an_illegal_opcode_locks_the_cpu() {
+    let mut gb = idle_cart(&[0x00, 0xDD]);
+    assert_eq!(gb.run(RunBudget::Frames(5)), RunOutcome::Locked(0x0101));
+    assert_eq!(gb.power_state(), PowerState::Locked);
+    assert_eq!(gb.run(RunBudget::Frames(1)), RunOutcome::Locked(0x0101));
+
+    gb.reset();
+    assert_eq!(gb.power_state(), PowerState::Running);
+}
+
+#[test]
+fn stop_switches_speed_only_when_key1_is_armed() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x143] = 0x80;
+    rom[0x100..0x112].copy_from_slice(&[
+        // Wait for HBlank, so the switches start from a running LCD rather
+        // than the post-boot frame.
+        0xF0, 0x41, // LDH A,(STAT)
+        0xE6, 0x03, // AND 3
+        0x20, 0xFA, // JR NZ,-6
+        0x3E, 0x01, // LD A,$01
+        0xE0, 0x4D, // LDH (KEY1),A
+        0x10, 0x00, // STOP
+        0xE0, 0x4D, // LDH (KEY1),A
+        0x10, 0x00, // STOP
+        0x10, 0x00, // STOP
+    ]);
+    let mut gb = Box::new(GameBoy::new_with_mode(true));
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb.mmu.if_reg = 0;
+    gb.mmu.ie_reg = 0;
+    while gb.cpu.pc != 0x0106 {
+        gb.cpu.step(&mut gb.mmu);
+    }
+
+    gb.cpu.step(&mut gb.mmu);
+    assert!(!gb.speed_switch_armed());
+    assert_eq!(gb.mmu.read_byte(0xFF4D), 0x7E);
+    gb.cpu.step(&mut gb.mmu);
+    assert!(gb.speed_switch_armed());
+    assert_eq!(gb.mmu.read_byte(0xFF4D), 0x7F);
+
+    // Armed STOP: switch to double speed and disarm, without stopping. The
+    // CPU stalls until HBlank of LY 0x85 with DIV reset and frozen.
+    let before = gb.cpu.cycles;
+    gb.cpu.step(&mut gb.mmu);
+    assert_eq!(gb.cpu.cycles - before, 60_770);
+    assert_eq!(gb.mmu.ppu.ly(), 0x85);
+    assert!(gb.mmu.ppu.in_hblank());
+    assert_eq!(gb.mmu.read_byte(0xFF04), 0);
+    assert!(gb.cpu.double_speed);
+    assert!(!gb.speed_switch_armed());
+    assert_eq!(gb.mmu.read_byte(0xFF4D), 0xFE);
+    assert_eq!(gb.power_state(), PowerState::Running);
+    assert_eq!(gb.cpu.pc, 0x010C);
+
+    // And back to normal speed.
+    gb.cpu.step(&mut gb.mmu);
+    assert_eq!(gb.mmu.read_byte(0xFF4D), 0xFF);
+    // Already at the resume point, so only the STOP fetches take time.
+    let before = gb.cpu.cycles;
+    gb.cpu.step(&mut gb.mmu);
+    assert_eq!(gb.cpu.cycles - before, 4);
+    assert_eq!(gb.mmu.ppu.ly(), 0x85);
+    assert_eq!(gb.mmu.read_byte(0xFF04), 0);
+    assert!(!gb.cpu.double_speed);
+    assert_eq!(gb.mmu.read_byte(0xFF4D), 0x7E);
+    assert_eq!(gb.power_state(), PowerState::Running);
+
+    // Unarmed STOP really stops and keeps the speed.
+    gb.cpu.step(&mut gb.mmu);
+    assert!(!gb.cpu.double_speed);
+    assert_eq!(gb.mmu.read_byte(0xFF4D), 0x7E);
+    assert_eq!(gb.power_state(), PowerState::Stopped);
+}
+
+#[test]
+fn key1_is_not_writable_on_dmg() {
+    let mut gb = idle_cart(&[]);
+    gb.mmu.write_byte(0xFF4D, 0x01);
+    assert!(!gb.speed_switch_armed());
+    assert_eq!(gb.mmu.read_byte(0xFF4D), 0xFF);
+}
+
+#[test]
+fn run_stops_after_cycle_budget() {
+    let mut gb = idle_cart(&[]);
+    let start = gb.cycles();
+    let RunOutcome::CyclesElapsed(ran) = gb.run(RunBudget::Cycles(10_000)) else {
+        panic!("expected a cycle stop");
+    };
+    assert!((10_000..10_100).contains(&ran), "ran {ran}");
+    assert_eq!(gb.cycles() - start, ran);
+}
+
+#[test]
+fn run_counts_frames() {
+    let mut gb = idle_cart(&[]);
+    assert_eq!(gb.run(RunBudget::Frames(0)), RunOutcome::FramesElapsed(0));
+    let start = gb.cycles();
+    assert_eq!(gb.run(RunBudget::Frames(2)), RunOutcome::FramesElapsed(2));
+    let ran = gb.cycles() - start;
+    assert!(ran > 456 * 154 && ran <= 2 * 456 * 154, "ran {ran}");
+}
+
+#[test]
+fn run_stops_at_breakpoint_before_executing_it() {
+    let mut gb = idle_cart(&[]);
+    let outcome = gb.run(RunBudget::Breakpoint {
+        addrs: &[0x0040],
+        max_cycles: 2 * 456 * 154,
+    });
+    assert_eq!(outcome, RunOutcome::Breakpoint(0x0040));
+    assert_eq!(gb.cpu.pc, 0x0040);
+
+    // Unreachable addresses fall back to the cycle cap.
+    let outcome = gb.run(RunBudget::Breakpoint {
+        addrs: &[0x1234],
+        max_cycles: 1000,
+    });
+    assert!(matches!(outcome, RunOutcome::CyclesElapsed(1000..)));
+}
+
+#[test]
+fn run_with_hook_stops_where_the_hook_says() {
+    let mut gb = idle_cart(&[]);
+    let mut steps = 0u32;
+    let outcome = gb.run_with_hook(RunBudget::Frames(2), |gb| {
+        steps += 1;
+        gb.cpu.pc == 0x0040
+    });
+    assert_eq!(outcome, RunOutcome::Breakpoint(0x0040));
+    assert_eq!(gb.cpu.pc, 0x0040);
+    assert!(steps > 1);
+
+    // The hook sees every instruction but otherwise leaves the budget alone.
+    let mut steps = 0u32;
+    let outcome = gb.run_with_hook(RunBudget::Frames(1), |_| {
+        steps += 1;
+        false
+    });
+    assert_eq!(outcome, RunOutcome::FramesElapsed(1));
+    assert!(steps > 1000, "{steps} steps");
+}
+
+#[test]
+fn run_stops_on_serial_output() {
+    let mut gb = idle_cart(&[
+        0x3E, 0x42, // LD A,$42
+        0xE0, 0x01, // LDH (SB),A
+        0x3E, 0x81, // LD A,$81
+        0xE0, 0x02, // LDH (SC),A
+    ]);
+    let outcome = gb.run(RunBudget::SerialOutput {
+        max_cycles: 456 * 154,
+    });
+    assert_eq!(outcome, RunOutcome::SerialOutput(0x42));
+
+    // Nothing else is sent.
+    let outcome = gb.run(RunBudget::SerialOutput { max_cycles: 20_000 });
+    assert!(matches!(outcome, RunOutcome::CyclesElapsed(_)));
+}
+
+#[test]
+fn run_to_vblank_count_counts_frames() {
+    let mut gb = idle_cart(&[]);
+    let start = gb.mmu.ppu.frame_count();
+    assert!(gb.run_to_vblank_count(5));
+    assert_eq!(gb.mmu.ppu.frame_count() - start, 5);
+    assert_eq!(gb.mmu.ppu.ly(), 144);
+
+    // No frames ever complete with the LCD off.
+    let mut gb = idle_cart(&[
+        0xAF, // XOR A
+        0xE0, 0x40, // LDH (LCDC),A
+    ]);
+    assert!(!gb.run_to_vblank_count(3));
+}
+
+#[test]
+fn run_frame_av_returns_one_frame_of_video_and_audio() {
+    let mut gb = idle_cart(&[]);
+    gb.mmu.apu.set_sample_rate(48_000);
+    // Line the machine up on a frame boundary first.
+    assert!(gb.run_frame());
+
+    let start = gb.mmu.ppu.frame_count();
+    for _ in 0..3 {
+        let (frame, samples) = gb.run_frame_av();
+        assert_eq!(frame.len(), 160 * 144);
+        // 70224 cycles at 48 kHz is 803.6 samples.
+        assert!((803..=804).contains(&samples.len()), "{}", samples.len());
+    }
+    assert_eq!(gb.mmu.ppu.frame_count() - start, 3);
+    let frame = gb.run_frame_av().0.to_vec();
+    assert_eq!(frame, &gb.mmu.ppu.framebuffer()[..]);
+
+    // Capture is only on for the call itself.
+    assert!(!gb.mmu.apu.sample_capture());
+    assert!(gb.run_frame());
+    let (_, samples) = gb.run_frame_av();
+    assert!((803..=804).contains(&samples.len()), "{}", samples.len());
+
+    // With capture left on, audio from frames run in between is carried
+    // over, not dropped.
+    gb.mmu.apu.set_sample_capture(true);
+    assert!(gb.run_frame());
+    let (_, samples) = gb.run_frame_av();
+    assert!((1607..=1608).contains(&samples.len()), "{}", samples.len());
+    assert!(gb.mmu.apu.sample_capture());
+}
+
+#[test]
+fn step_frame_into_fills_caller_buffers_frame_over_frame() {
+    let mut gb = idle_cart(&[]);
+    gb.mmu.apu.set_sample_rate(48_000);
+    assert!(gb.run_frame());
+
+    let mut video = vec![0u32; 160 * 144];
+    let mut audio = Vec::new();
+    let mut previous = 0;
+    for _ in 0..4 {
+        assert!(gb.step_frame_into(&mut video, &mut audio));
+        assert_eq!(video, &gb.mmu.ppu.framebuffer()[..]);
+        // Each frame appends its 803.6 samples without touching earlier ones.
+        let added = audio.len() - previous;
+        assert!((803..=804).contains(&added), "{added}");
+        previous = audio.len();
+    }
+    assert!((3214..=3215).contains(&audio.len()), "{}", audio.len());
+    assert!(!gb.mmu.apu.sample_capture());
+
+    // Draining the same stream through both APIs yields the same samples.
+    let mut a = idle_cart(&[]);
+    let mut b = idle_cart(&[]);
+    a.mmu.apu.set_sample_rate(48_000);
+    b.mmu.apu.set_sample_rate(48_000);
+    let mut via_into = Vec::new();
+    let mut via_av = Vec::new();
+    for _ in 0..3 {
+        assert!(a.step_frame_into(&mut video, &mut via_into));
+        via_av.extend(b.run_frame_av().1);
+    }
+    assert_eq!(via_into, via_av);
+}
+
+#[test]
+fn frame_skip_draws_every_other_frame_without_changing_timing() {
+    // Inverts BGP in every VBlank handler with the window on, so each frame
+    // looks different from the last.
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x40..0x46].copy_from_slice(&[
+        0xF0, 0x47, // LDH A,(BGP)
+        0x2F, // CPL
+        0xE0, 0x47, // LDH (BGP),A
+        0xD9, // RETI
+    ]);
+    rom[0x100..0x10B].copy_from_slice(&[
+        0x3E, 0xB1, // LD A,$B1
+        0xE0, 0x40, // LDH (LCDC),A
+        0x3E, 0x01, // LD A,$01
+        0xE0, 0xFF, // LDH (IE),A
+        0xFB, // EI
+        0x18, 0xFE, // JR -2
+    ]);
+    let mut full = Box::new(GameBoy::new());
+    full.mmu.load_cart(Cartridge::load(rom.clone()));
+    let mut skipped = Box::new(GameBoy::new());
+    skipped.mmu.load_cart(Cartridge::load(rom));
+    skipped.set_frame_skip(1);
+    assert!(full.run_frame());
+    assert!(skipped.run_frame());
+    assert!(skipped.mmu.ppu.frame_rendered());
+
+    let mut last_drawn = skipped.mmu.ppu.framebuffer().to_vec();
+    for i in 0..6 {
+        assert!(full.step_to_ly(100));
+        assert!(skipped.step_to_ly(100));
+        assert_eq!(
+            full.mmu.ppu.window_line_counter(),
+            skipped.mmu.ppu.window_line_counter()
+        );
+        assert!(full.run_frame());
+        assert!(skipped.run_frame());
+        assert_eq!(full.cycles(), skipped.cycles(), "frame {i}");
+        assert_eq!(full.mmu.read_byte(0xFF47), skipped.mmu.read_byte(0xFF47));
+
+        let drawn = i % 2 == 1;
+        assert_eq!(skipped.mmu.ppu.frame_rendered(), drawn, "frame {i}");
+        if drawn {
+            assert_eq!(skipped.mmu.ppu.framebuffer(), full.mmu.ppu.framebuffer());
+            last_drawn = skipped.mmu.ppu.framebuffer().to_vec();
+        } else {
+            assert_eq!(skipped.mmu.ppu.framebuffer()[..], last_drawn[..]);
+            assert_ne!(full.mmu.ppu.framebuffer()[..], last_drawn[..]);
+        }
+    }
+}
+
+/// Fills the tile data and BG map with patterns, places 20 objects down a
+/// diagonal, turns on the window and scrolls SCX by one every VBlank.
+fn tilemap_cart(cgb_flag: u8) -> Cartridge {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x40..0x46].copy_from_slice(&[
+        0xF0, 0x43, // LDH A,(SCX)
+        0x3C, // INC A
+        0xE0, 0x43, // LDH (SCX),A
+        0xD9, // RETI
+    ]);
+    rom[0x143] = cgb_flag;
+    let code = [
+        0xF3, // DI
+        0xF0, 0x44, // LDH A,(LY)
+        0xFE, 0x90, // CP 144
+        0x38, 0xFA, // JR C,-6
+        0xAF, // XOR A
+        0xE0, 0x40, // LDH (LCDC),A
+        0x21, 0x00, 0x80, // LD HL,$8000
+        0x7D, // LD A,L
+        0xAC, // XOR H
+        0x22, // LD (HL+),A
+        0x7C, // LD A,H
+        0xFE, 0x90, // CP $90
+        0x20, 0xF8, // JR NZ,-8
+        0x21, 0x00, 0x98, // LD HL,$9800
+        0x7D, // LD A,L
+        0x22, // LD (HL+),A
+        0x7C, // LD A,H
+        0xFE, 0x9C, // CP $9C
+        0x20, 0xF9, // JR NZ,-7
+        0x21, 0x00, 0xFE, // LD HL,$FE00
+        0x7D, // LD A,L
+        0x87, // ADD A,A
+        0xC6, 0x10, // ADD A,$10
+        0x22, // LD (HL+),A
+        0x7D, // LD A,L
+        0xFE, 0x50, // CP $50
+        0x20, 0xF6, // JR NZ,-10
+        0x3E, 0xE4, // LD A,$E4
+        0xE0, 0x47, // LDH (BGP),A
+        0xE0, 0x48, // LDH (OBP0),A
+        0x3E, 0x1B, // LD A,$1B
+        0xE0, 0x49, // LDH (OBP1),A
+        0x3E, 0x50, // LD A,$50
+        0xE0, 0x4A, // LDH (WY),A
+        0x3E, 0x57, // LD A,$57
+        0xE0, 0x4B, // LDH (WX),A
+        0x3E, 0xB3, // LD A,$B3
+        0xE0, 0x40, // LDH (LCDC),A
+        0x3E, 0x01, // LD A,$01
+        0xE0, 0xFF, // LDH (IE),A
+        0xFB, // EI
+        0x18, 0xFE, // JR -2
+    ];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    Cartridge::load(rom)
+}
+
+#[test]
+fn relaxed_accuracy_draws_the_same_frames_for_plain_tilemaps() {
+    for (cgb, cgb_flag) in [(false, 0x00), (true, 0x00), (true, 0x80)] {
+        let mut accurate = Box::new(GameBoy::new_with_mode(cgb));
+        accurate.mmu.load_cart(tilemap_cart(cgb_flag));
+        let mut relaxed = Box::new(GameBoy::new_with_mode(cgb));
+        relaxed.mmu.load_cart(tilemap_cart(cgb_flag));
+        relaxed.set_accuracy(AccuracyLevel::Relaxed);
+        assert_eq!(accurate.accuracy(), AccuracyLevel::CycleAccurate);
+        assert_eq!(relaxed.accuracy(), AccuracyLevel::Relaxed);
+
+        // Filling VRAM with the LCD off takes a few frames' worth of cycles.
+        for gb in [&mut accurate, &mut relaxed] {
+            gb.run(RunBudget::Cycles(4 * 456 * 154));
+            assert_eq!(gb.mmu.read_byte(0xFF40), 0xB3);
+        }
+        for frame in 0..8 {
+            assert!(accurate.run_frame());
+            assert!(relaxed.run_frame());
+            assert_eq!(
+                accurate.cycles(),
+                relaxed.cycles(),
+                "cgb={cgb} frame {frame}"
+            );
+            let expected = accurate.mmu.ppu.framebuffer();
+            assert_eq!(
+                relaxed.mmu.ppu.framebuffer(),
+                expected,
+                "cgb={cgb} flag={cgb_flag:02X} frame {frame}"
+            );
+        }
+        let mut shades = accurate.mmu.ppu.framebuffer().to_vec();
+        shades.sort_unstable();
+        shades.dedup();
+        assert!(shades.len() > 1, "cgb={cgb} flag={cgb_flag:02X}");
+    }
+}
+
+#[test]
+fn accuracy_level_survives_reset_and_load_state() {
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(tilemap_cart(0));
+    gb.set_accuracy(AccuracyLevel::Relaxed);
+    gb.run(RunBudget::Cycles(456 * 154));
+    let state = gb.save_state();
+
+    gb.reset();
+    assert!(gb.mmu.ppu.latch_objects_per_line());
+    assert!(gb.mmu.apu.fast_mix());
+    gb.load_state(&state).unwrap();
+    assert_eq!(gb.accuracy(), AccuracyLevel::Relaxed);
+    assert!(gb.mmu.ppu.latch_objects_per_line());
+
+    gb.set_accuracy(AccuracyLevel::CycleAccurate);
+    assert!(!gb.mmu.ppu.latch_objects_per_line());
+    assert!(!gb.mmu.apu.fast_mix());
+}
+
+/// Changes every PPU and APU setting a frontend applies away from its
+/// default.
+fn apply_host_settings(gb: &mut GameBoy) {
+    gb.mmu
+        .ppu
+        .set_dmg_palette([0x111111, 0x222222, 0x333333, 0x444444]);
+    gb.mmu.ppu.set_ghosting(0.5);
+    gb.mmu.ppu.set_frame_skip(2);
+    gb.mmu.ppu.set_oam_bug_enabled(false);
+    gb.mmu.ppu.set_capture_vblank_lines(true);
+    gb.mmu.ppu.set_scroll_logging(true);
+    gb.mmu.apu.set_silence_window_frames(7);
+    gb.mmu.apu.start_reg_log();
+    gb.mmu.apu.set_stereo_width(0.25);
+    gb.mmu.apu.set_latency_ms(90);
+    gb.mmu.apu.set_sample_capture(true);
+}
+
+fn assert_host_settings(gb: &mut GameBoy) {
+    let ppu = &gb.mmu.ppu;
+    assert_eq!(ppu.ghosting(), 0.5);
+    assert_eq!(ppu.frame_skip(), 2);
+    assert!(!ppu.oam_bug_enabled());
+    assert!(ppu.capture_vblank_lines());
+    assert!(ppu.scroll_logging());
+    assert_eq!(gb.mmu.apu.silence_window_frames(), 7);
+    assert_eq!(gb.mmu.apu.stereo_width(), 0.25);
+    assert_eq!(gb.mmu.apu.latency_ms(), 90);
+    assert!(gb.mmu.apu.sample_capture());
+    gb.mmu.write_byte(0xFF24, 0x77);
+    let log = gb.mmu.apu.take_reg_log();
+    assert_eq!(log.last().map(|w| (w.addr, w.value)), Some((0xFF24, 0x77)));
+}
+
+#[test]
+fn host_settings_survive_load_state() {
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(tilemap_cart(0));
+    let state = gb.save_state();
+    apply_host_settings(&mut gb);
+    gb.load_state(&state).unwrap();
+    assert_host_settings(&mut gb);
+}
+
+#[test]
+fn ghosting_does_not_blend_frames_from_before_a_load() {
+    let mut gb = idle_cart(&[]);
+    gb.mmu
+        .ppu
+        .set_dmg_palette([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+    gb.mmu.ppu.set_ghosting(0.5);
+    gb.mmu.write_byte(0xFF47, 0x00); // every pixel white
+    gb.run(RunBudget::Frames(2));
+    let state = gb.save_state();
+
+    gb.mmu.write_byte(0xFF47, 0xFF); // every pixel black
+    gb.run(RunBudget::Frames(20));
+    assert!(gb.mmu.ppu.framebuffer()[0] <= 0x010101);
+
+    gb.load_state(&state).unwrap();
+    assert_eq!(gb.mmu.ppu.ghosting(), 0.5);
+    gb.run(RunBudget::Frames(1));
+    assert_eq!(gb.mmu.ppu.framebuffer()[0], 0xFFFFFF);
+}
+
+#[test]
+fn host_settings_survive_reset() {
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(tilemap_cart(0));
+    apply_host_settings(&mut gb);
+    gb.reset();
+    assert_host_settings(&mut gb);
+    gb.reset_power_on();
+    assert_host_settings(&mut gb);
+}
+
+#[test]
+fn sample_capture_keeps_collecting_after_reset() {
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(tilemap_cart(0));
+    gb.mmu.apu.set_sample_capture(true);
+    gb.reset();
+    gb.run(RunBudget::Frames(1));
+    assert!(!gb.mmu.apu.take_frame_samples().is_empty());
+}
+
+/// Sends `bytes` over serial, waiting for each byte to go out. A byte takes
+/// 4096 dots on the internal clock.
+fn serial_sender(bytes: &[u8]) -> Box<GameBoy> {
+    let mut code = Vec::new();
+    for &byte in bytes {
+        code.extend_from_slice(&[
+            0x3E, byte, // LD A,byte
+            0xE0, 0x01, // LDH (SB),A
+            0x3E, 0x81, // LD A,$81
+            0xE0, 0x02, // LDH (SC),A
+            0xF0, 0x02, // LDH A,(SC)
+            0x87, // ADD A,A
+            0x38, 0xFB, // JR C,-5
+        ]);
+    }
+    idle_cart(&code)
+}
+
+#[test]
+fn run_until_serial_stops_on_marker() {
+    let mut gb = serial_sender(b"xxOK");
+
+    assert!(!gb.run_until_serial(b"OK", 6000));
+    assert_eq!(gb.mmu.serial.peek_output(), b"x");
+    assert!(gb.run_until_serial(b"OK", 456 * 154));
+    assert_eq!(gb.mmu.serial.peek_output(), b"xxOK");
+    // Output from before the call doesn't count.
+    assert!(!gb.run_until_serial(b"OK", 456 * 154));
+}
+
+#[test]
+fn run_until_serial_accepts_unbounded_budget() {
+    let mut gb = serial_sender(b"xxOK");
+    assert!(gb.run_until_serial(b"OK", u64::MAX));
+    assert_eq!(gb.mmu.serial.peek_output(), b"xxOK");
+}
+
+#[test]
+fn run_detects_halt_loops() {
+    // JR -2 with interrupts disabled.
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(looping_cart());
+    assert_eq!(gb.run(RunBudget::Frames(10)), RunOutcome::HaltLoop(0x0100));
+
+    // HALT with nothing in IE.
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0xAF, 0xE0, 0xFF, 0x76]); // XOR A; LDH (IE),A; HALT
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    assert_eq!(gb.run(RunBudget::Frames(10)), RunOutcome::HaltLoop(0x0104));
+
+    // Spinning with an interrupt enabled is not a halt loop.
+    let mut gb = idle_cart(&[]);
+    assert_eq!(gb.run(RunBudget::Frames(1)), RunOutcome::FramesElapsed(1));
+}
+
+#[test]
+fn frame_count_tracks_completed_frames_until_reset() {
+    let mut gb = idle_cart(&[]);
+    assert_eq!(gb.mmu.ppu.frame_count(), 0);
+    for _ in 0..3 {
+        assert!(gb.run_frame());
+    }
+    assert_eq!(gb.mmu.ppu.frame_count(), 3);
+
+    let state = gb.save_state();
+    gb.run_frame();
+    assert_eq!(gb.mmu.ppu.frame_count(), 4);
+    gb.load_state(&state).unwrap();
+    assert_eq!(gb.mmu.ppu.frame_count(), 3);
+
+    gb.reset();
+    assert_eq!(gb.mmu.ppu.frame_count(), 0);
+}
+
+#[test]
+fn new_post_boot_matches_documented_register_table() {
+    // A, F, B, C, D, E, H, L per Pan Docs' power-up table.
+    let table = [
+        (
+            Model::Dmg,
+            HardwareRevision::Dmg(DmgRevision::Rev0),
+            [0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03],
+        ),
+        (
+            Model::Dmg,
+            HardwareRevision::Dmg(DmgRevision::RevC),
+            [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+        ),
+        (
+            Model::Cgb,
+            HardwareRevision::Cgb(CgbRevision::RevE),
+            [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+        ),
+        (
+            Model::Agb,
+            HardwareRevision::Cgb(CgbRevision::RevE),
+            [0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+        ),
+    ];
+    for (model, revision, regs) in table {
+        let mut gb = Box::new(GameBoy::new_post_boot(model, revision));
+        for _ in 0..2 {
+            let cpu = &gb.cpu;
+            assert_eq!(
+                [cpu.a, cpu.f, cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l],
+                regs,
+                "{model:?} {revision}"
+            );
+            assert_eq!((cpu.sp, cpu.pc), (0xFFFE, 0x0100), "{model:?}");
+            assert_eq!(gb.cgb, model.is_cgb());
+
+            let io = [
+                (0xFF40, 0x91), // LCDC
+                (0xFF47, 0xFC), // BGP
+                (0xFF0F, 0xE1), // IF
+                (0xFFFF, 0x00), // IE
+                (0xFF07, 0xF8), // TAC
+                (0xFF24, 0x77), // NR50
+                (0xFF25, 0xF3), // NR51
+                (0xFF26, 0xF1), // NR52
+            ];
+            for (addr, val) in io {
+                assert_eq!(gb.mmu.read_byte(addr), val, "{model:?} {addr:04X}");
+            }
+            // The preset is kept across a reset.
+            gb.reset();
+        }
+    }
+
+    let gb = Box::new(GameBoy::new_post_boot(
+        Model::Dmg,
+        HardwareRevision::Dmg(DmgRevision::RevB),
+    ));
+    assert_eq!(gb.dmg_revision, DmgRevision::RevB);
+    assert_eq!(gb.cgb_revision, CgbRevision::default());
+}
+
+#[test]
+fn post_boot_preset_survives_load_state_and_reset() {
+    let model = Model::Agb;
+    let revision = HardwareRevision::Cgb(CgbRevision::RevE);
+    let mut gb = Box::new(GameBoy::new_post_boot(model, revision));
+    let state = gb.save_state();
+    gb.load_state(&state).unwrap();
+    gb.reset();
+
+    let cpu = &gb.cpu;
+    assert_eq!(
+        [cpu.a, cpu.f, cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l],
+        [0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D]
+    );
+    assert_eq!((cpu.sp, cpu.pc), (0xFFFE, 0x0100));
+}
+
+#[test]
+fn model_signature_registers_at_pc_handoff() {
+    // (model, revision, A, B): A tells DMG from CGB, B bit 0 marks the AGB.
+    let table = [
+        (
+            Model::Dmg,
+            HardwareRevision::Dmg(DmgRevision::Rev0),
+            0x01,
+            0xFF,
+        ),
+        (
+            Model::Dmg,
+            HardwareRevision::Dmg(DmgRevision::RevA),
+            0x01,
+            0x00,
+        ),
+        (
+            Model::Dmg,
+            HardwareRevision::Dmg(DmgRevision::RevC),
+            0x01,
+            0x00,
+        ),
+        (
+            Model::Cgb,
+            HardwareRevision::Cgb(CgbRevision::RevE),
+            0x11,
+            0x00,
+        ),
+        (
+            Model::Agb,
+            HardwareRevision::Cgb(CgbRevision::RevE),
+            0x11,
+            0x01,
+        ),
+    ];
+    for (model, revision, a, b) in table {
+        let mut gb = Box::new(GameBoy::new_post_boot(model, revision));
+        assert_eq!(gb.cpu.pc, 0x0100);
+        assert_eq!((gb.cpu.a, gb.cpu.b), (a, b), "{model:?} {revision}");
+        assert_eq!(gb.detected_model_byte(), a, "{model:?}");
+
+        // The reported byte describes the hardware, not the live register.
+        gb.cpu.a = 0x00;
+        assert_eq!(gb.detected_model_byte(), a, "{model:?}");
+    }
+
+    // A DMG cartridge on a CGB still sees the CGB signature.
+    for (cgb, a) in [(false, 0x01), (true, 0x11)] {
+        let gb = Box::new(GameBoy::new_with_mode(cgb));
+        assert_eq!((gb.cpu.a, gb.cpu.b), (a, 0x00), "cgb={cgb}");
+        assert_eq!(gb.detected_model_byte(), a);
+    }
+}
+
+#[test]
+fn dump_state_text_labels_units_and_disassembles_pc() {
+    let mut gb = idle_cart(&[]);
+    let dump = gb.dump_state_text();
+    for label in [
+        "CPU ", "AF=", "SP=", "PC=0100", "IME=", "INT ", "IE=", "IF=", "LCD ", "LCDC=", "STAT=",
+        "LY=", "TIMER ", "DIV=", "TAC=", "APU ", "NR10=", "NR52=", "BANK ", "ROM=001", "WRAM=",
+    ] {
+        assert!(dump.contains(label), "missing {label:?} in:\n{dump}");
+    }
+    assert!(dump.contains("PC    0100: 3E 01  LD A,"), "{dump}");
+    // Dumping must not disturb the machine.
+    assert_eq!(gb.dump_state_text(), dump);
+
+    gb.run(RunBudget::Cycles(1_000));
+    let dump = gb.dump_state_text();
+    let pc = gb.cpu.pc;
+    assert!(dump.contains(&format!("PC={pc:04X}")), "{dump}");
+    assert!(
+        dump.contains(&format!("PC    {pc:04X}: 18 FE  JR")),
+        "{dump}"
+    );
+}
+
+/// Enables the interrupts in `ie`, runs `setup`, then does EI; HALT. Every
+/// handler is RETI, which resumes at the HALT.
+fn halting_cart(ie: u8, setup: &[u8]) -> Box<GameBoy> {
+    let mut rom = vec![0u8; 0x8000];
+    for vector in [0x40, 0x48, 0x50, 0x58, 0x60] {
+        rom[vector] = 0xD9; // RETI
+    }
+    let mut code = vec![
+        0x3E, ie, // LD A,ie
+        0xE0, 0xFF, // LDH (IE),A
+    ];
+    code.extend_from_slice(setup);
+    code.extend_from_slice(&[
+        0xFB, // EI
+        0x76, // HALT
+        0x18, 0xFE, // JR -2
+    ]);
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb
+}
+
+/// Steps into HALT, checks the predicted wake-up against the cycles it
+/// actually takes, and returns the prediction.
+fn halt_until_interrupt(gb: &mut GameBoy) -> u64 {
+    assert_eq!(gb.power_state(), PowerState::Running);
+    while gb.power_state() == PowerState::Running {
+        gb.cpu.step(&mut gb.mmu);
+    }
+    assert_eq!(gb.power_state(), PowerState::Halted);
+    let predicted = gb
+        .cycles_until_next_interrupt()
+        .expect("timed source enabled");
+    assert!(predicted > 0);
+
+    let start = gb.cycles();
+    while gb.power_state() == PowerState::Halted {
+        gb.cpu.step(&mut gb.mmu);
+    }
+    let actual = gb.cycles() - start;
+    assert!(
+        (predicted..=predicted + 32).contains(&actual),
+        "predicted {predicted}, woke after {actual}"
+    );
+    assert_eq!(gb.power_state(), PowerState::Running);
+    predicted
+}
+
+#[test]
+fn halt_reports_power_state_and_next_vblank() {
+    let mut gb = halting_cart(0x01, &[]);
+    let predicted = halt_until_interrupt(&mut gb);
+    assert!(predicted <= 456 * 154);
+
+    // The handler returns to the HALT, which waits for the next frame.
+    let predicted = halt_until_interrupt(&mut gb);
+    assert!((456 * 153..456 * 154).contains(&predicted), "{predicted}");
+}
+
+#[test]
+fn halt_predicts_timer_overflow() {
+    let mut gb = halting_cart(
+        0x04,
+        &[
+            0xAF, // XOR A
+            0xE0, 0x05, // LDH (TIMA),A
+            0x3E, 0x05, // LD A,$05 (enable, 16 cycles per tick)
+            0xE0, 0x07, // LDH (TAC),A
+        ],
+    );
+    let predicted = halt_until_interrupt(&mut gb);
+    assert!(
+        (256 * 16 - 64..=256 * 16).contains(&predicted),
+        "{predicted}"
+    );
+
+    // Only the joypad left: nothing timed can wake the CPU.
+    gb.mmu.ie_reg = 0x10;
+    gb.mmu.if_reg = 0;
+    assert_eq!(gb.cycles_until_next_interrupt(), None);
+}
+
+#[test]
+fn dmg_stop_with_lcd_on_shows_dark_frames() {
+    let mut gb = idle_cart(&[0x10, 0x00]); // STOP
+    gb.mmu
+        .ppu
+        .set_dmg_palette([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+    while gb.power_state() != PowerState::Stopped {
+        gb.cpu.step(&mut gb.mmu);
+    }
+
+    let ly = gb.mmu.ppu.ly();
+    let start = gb.cycles();
+    assert!(gb.run_frame());
+    assert!(gb.cycles() - start <= 456 * 154);
+    assert!(gb.mmu.ppu.framebuffer().iter().all(|&px| px == 0x000000));
+    assert_eq!(gb.mmu.ppu.ly(), ly, "the PPU is frozen while stopped");
+    assert_eq!(gb.power_state(), PowerState::Stopped);
+}
+
+#[test]
+fn skip_boot_animation_runs_until_the_boot_rom_unmaps() {
+    let mut boot = vec![0u8; 0x100];
+    let code = [
+        0x01, 0x00, 0x40, // LD BC,$4000
+        0x0B, // DEC BC
+        0x78, // LD A,B
+        0xB1, // OR C
+        0x20, 0xFB, // JR NZ,-5
+        0x3E, 0x11, // LD A,$11
+        0xC3, 0xFE, 0x00, // JP $00FE
+    ];
+    boot[..code.len()].copy_from_slice(&code);
+    boot[0xFE..].copy_from_slice(&[0xE0, 0x50]); // LDH ($50),A
+    let mut rom = vec![0u8; 0x8000];
+    rom[0] = 0xC7;
+
+    let mut gb = Box::new(GameBoy::new_power_on_with_revisions(
+        false,
+        DmgRevision::default(),
+        CgbRevision::default(),
+    ));
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb.mmu.load_boot_rom(boot);
+    assert!(gb.skip_boot_animation());
+    assert!(!gb.mmu.boot_mapped);
+    assert_eq!(gb.cpu.pc, 0x0100);
+    assert_eq!(gb.cpu.a, 0x11);
+    assert_eq!(gb.mmu.read_byte(0x0000), 0xC7);
+    assert!(gb.cycles() > 3 * 456 * 154);
+    assert!(gb.skip_boot_animation());
+}
+
+/// CALLs a subroutine at $0200 that counts B down from 5, with an
+/// interrupt handler at $0040 that counts C up.
+fn subroutine_cart() -> Cartridge {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0040..0x0042].copy_from_slice(&[0x0C, 0xD9]); // INC C; RETI
+    rom[0x0100..0x0106].copy_from_slice(&[
+        0xCD, 0x00, 0x02, // CALL $0200
+        0x00, // NOP
+        0x18, 0xFE, // JR -2
+    ]);
+    rom[0x0200..0x0206].copy_from_slice(&[
+        0x06, 0x05, // LD B,5
+        0x05, // DEC B
+        0x20, 0xFD, // JR NZ,-3
+        0xC9, // RET
+    ]);
+    Cartridge::load(rom)
+}
+
+#[test]
+fn step_over_runs_through_calls_and_interrupts() {
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(subroutine_cart());
+    gb.mmu.ie_reg = 0;
+    let sp = gb.cpu.sp;
+
+    assert!(gb.step_over(10_000));
+    assert_eq!(gb.cpu.pc, 0x0103);
+    assert_eq!(gb.cpu.sp, sp);
+    assert_eq!(gb.cpu.b, 0, "the subroutine ran");
+
+    // The NOP is followed by a VBlank interrupt, whose handler runs too.
+    gb.cpu.ime = true;
+    gb.mmu.ie_reg = 0x01;
+    gb.mmu.if_reg = 0x01;
+    let c = gb.cpu.c;
+    assert!(gb.step_over(10_000));
+    assert_eq!(gb.cpu.pc, 0x0104);
+    assert_eq!(gb.cpu.sp, sp);
+    assert_eq!(gb.cpu.c, c.wrapping_add(1));
+
+    // Plain instructions just step.
+    assert!(gb.step_over(10_000));
+    assert_eq!(gb.cpu.pc, 0x0104);
+    assert!(!gb.step_over(0));
+}
+
+#[test]
+fn step_out_returns_to_the_caller() {
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(subroutine_cart());
+    gb.mmu.ie_reg = 0;
+    let sp = gb.cpu.sp;
+
+    let outcome = gb.run(RunBudget::Breakpoint {
+        addrs: &[0x0202],
+        max_cycles: 10_000,
+    });
+    assert_eq!(outcome, RunOutcome::Breakpoint(0x0202));
+    assert_eq!(gb.cpu.sp, sp.wrapping_sub(2));
+
+    assert!(gb.step_out(10_000));
+    assert_eq!(gb.cpu.pc, 0x0103);
+    assert_eq!(gb.cpu.sp, sp);
+    assert_eq!(gb.cpu.b, 0);
+
+    // At the top level there is no caller to return to.
+    assert!(!gb.step_out(10_000));
+}
+
+#[test]
+fn snapshot_matches_live_state_at_capture() {
+    let mut gb = idle_cart(&[
+        0x06, 0x12, // LD B,$12
+        0x31, 0xF0, 0xCF, // LD SP,$CFF0
+        0x3E, 0x34, // LD A,$34
+        0xE0, 0x43, // LDH (SCX),A
+        0x3E, 0x05, // LD A,$05
+        0xE0, 0x07, // LDH (TAC),A
+    ]);
+    gb.run(RunBudget::Cycles(3 * 456 * 154 + 1234));
+
+    let mut snap = Snapshot::new();
+    gb.publish_snapshot(&mut snap);
+
+    assert_eq!(
+        (snap.a, snap.b, snap.sp, snap.pc),
+        (gb.cpu.a, 0x12, 0xCFF0, gb.cpu.pc)
+    );
+    assert_eq!(snap.f, gb.cpu.f);
+    assert_eq!(snap.ime, gb.cpu.ime);
+    assert_eq!(snap.power, gb.power_state());
+    assert_eq!(snap.cycles, gb.cycles());
+    assert_eq!(snap.frame, gb.mmu.ppu.frames());
+    assert_eq!((snap.if_reg, snap.ie_reg), (gb.mmu.if_reg, 0x01));
+    assert_eq!(snap.rom_bank, 1);
+    for (addr, &value) in (0xFF40..).zip(snap.lcd_regs.iter()) {
+        assert_eq!(value, gb.mmu.ppu.read_reg(addr), "{addr:04X}");
+    }
+    assert_eq!(snap.lcd_regs[3], 0x34);
+    assert_eq!(snap.lcd_regs[4], gb.mmu.ppu.ly());
+    for (addr, &value) in (0xFF04..).zip(snap.timer_regs.iter()) {
+        assert_eq!(value, gb.mmu.timer.read(addr), "{addr:04X}");
+    }
+    assert_eq!(snap.timer_regs[3], 0xFD);
+    assert_eq!(&snap.framebuffer[..], &gb.mmu.ppu.framebuffer()[..]);
+
+    // Later captures into the same snapshot overwrite every field.
+    gb.run(RunBudget::Cycles(1000));
+    gb.publish_snapshot(&mut snap);
+    assert_eq!(snap.cycles, gb.cycles());
+    assert_eq!(snap.lcd_regs[4], gb.mmu.ppu.ly());
+}
+
+#[test]
+fn snapshot_channel_hands_the_newest_snapshot_to_another_thread() {
+    let mut gb = idle_cart(&[]);
+    let (mut publisher, mut reader) = snapshot_channel();
+    assert!(!reader.has_update());
+    assert_eq!(reader.latest().cycles, 0);
+
+    let published = std::thread::spawn(move || {
+        let mut cycles = Vec::new();
+        for _ in 0..3 {
+            gb.run(RunBudget::Frames(1));
+            publisher.publish(&gb);
+            cycles.push(gb.cycles());
+        }
+        cycles
+    })
+    .join()
+    .unwrap();
+
+    // Only the newest snapshot is kept; the ones in between are skipped.
+    assert!(reader.has_update());
+    assert_eq!(reader.latest().cycles, published[2]);
+    assert!(!reader.has_update());
+    assert_eq!(reader.latest().cycles, published[2]);
+}
+
+#[test]
+fn autosave_writes_dirty_sram_once_per_interval() {
+    let dir = tempdir().unwrap();
+    let rom_path = dir.path().join("game.gb");
+    let sav_path = rom_path.with_extension("sav");
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x03; // MBC1 + RAM + Battery
+    rom[0x0149] = 0x02; // 8KB RAM
+    rom[0x100..0x10C].copy_from_slice(&[
+        0x3E, 0x0A, // LD A,$0A
+        0xEA, 0x00, 0x00, // LD ($0000),A
+        0x3E, 0x5A, // LD A,$5A
+        0xEA, 0x00, 0xA0, // LD ($A000),A
+        0x18, 0xFE, // JR -2
+    ]);
+    std::fs::write(&rom_path, &rom).unwrap();
+
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::from_file(&rom_path).unwrap());
+    assert_eq!(gb.autosave_interval(), None);
+    gb.run_frame();
+    assert!(!sav_path.exists(), "autosave is off by default");
+
+    gb.set_autosave_interval(Some(Duration::from_millis(10)));
+    assert!(
+        gb.autosave_interval()
+            .unwrap()
+            .abs_diff(Duration::from_millis(10))
+            < Duration::from_micros(1)
+    );
+    gb.run_frame();
+    assert_eq!(std::fs::read(&sav_path).unwrap()[0], 0x5A);
+    assert!(!gb.mmu.cart.as_ref().unwrap().ram_dirty());
+
+    // Periods without SRAM writes leave the file alone.
+    std::fs::write(&sav_path, b"marker").unwrap();
+    gb.run(RunBudget::Frames(5));
+    assert_eq!(std::fs::read(&sav_path).unwrap(), b"marker");
+
+    gb.mmu.write_byte(0xA001, 0x77);
+    assert!(gb.run_frame());
+    assert_eq!(std::fs::read(&sav_path).unwrap()[..2], [0x5A, 0x77]);
+}
+
+#[test]
+fn shutdown_saves_sram_and_unplugs_the_link_port() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use vibe_emu_core::serial::LinkPort;
+
+    struct Peripheral(Arc<AtomicBool>);
+    impl LinkPort for Peripheral {
+        fn transfer(&mut self, _byte: u8) -> u8 {
+            0xFF
+        }
+    }
+    impl Drop for Peripheral {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    let dir = tempdir().unwrap();
+    let rom_path = dir.path().join("game.gb");
+    let sav_path = rom_path.with_extension("sav");
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x03; // MBC1 + RAM + Battery
+    rom[0x0149] = 0x02; // 8KB RAM
+    std::fs::write(&rom_path, &rom).unwrap();
+
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::from_file(&rom_path).unwrap());
+    let stopped = Arc::new(AtomicBool::new(false));
+    gb.attach_serial(Box::new(Peripheral(stopped.clone())));
+    gb.mmu.write_byte(0x0000, 0x0A);
+    gb.mmu.write_byte(0xA123, 0x5A);
+    assert!(!sav_path.exists());
+
+    gb.shutdown().unwrap();
+    assert!(stopped.load(Ordering::Relaxed));
+    assert!(!gb.mmu.cart.as_ref().unwrap().ram_dirty());
+    assert_eq!(std::fs::read(&sav_path).unwrap()[0x123], 0x5A);
+    gb.shutdown().unwrap();
+    assert_eq!(std::fs::read(&sav_path).unwrap()[0x123], 0x5A);
+
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::from_file(&rom_path).unwrap());
+    gb.mmu.write_byte(0x0000, 0x0A);
+    assert_eq!(gb.mmu.read_byte(0xA123), 0x5A);
+
+    // Without a cartridge there is nothing to write.
+    let mut gb = Box::new(GameBoy::new());
+    gb.shutdown().unwrap();
+}
+
+#[test]
+fn current_frame_so_far_blanks_lines_not_yet_drawn() {
+    let mut gb = idle_cart(&[]);
+    gb.mmu.ppu.vram[0].fill(0xFF); // every BG pixel is color 3
+    let row = |frame: &[u32], y: usize| frame[y * 160..(y + 1) * 160].to_vec();
+
+    gb.mmu.write_byte(0xFF47, 0x00); // all colors to the lightest shade
+    gb.run_frame();
+    gb.run_frame();
+    let light = gb.mmu.ppu.framebuffer()[0];
+    assert_eq!(gb.mmu.ppu.lines_drawn(), 144);
+    assert_eq!(
+        gb.render_current_frame_so_far(),
+        gb.mmu.ppu.framebuffer().to_vec()
+    );
+
+    gb.mmu.write_byte(0xFF47, 0xFF); // darkest
+    gb.run_frame();
+    let dark = gb.mmu.ppu.framebuffer()[0];
+    gb.mmu.write_byte(0xFF47, 0x40); // color 3 to shade 1
+    while !(gb.mmu.ppu.line() == 72 && gb.mmu.ppu.lines_drawn() == 73) {
+        gb.cpu.step(&mut gb.mmu);
+    }
+    let before = gb.mmu.ppu.framebuffer().to_vec();
+
+    let frame = gb.render_current_frame_so_far();
+    assert_eq!(frame.len(), 160 * 144);
+    let mid = frame[0];
+    assert!(mid != light && mid != dark);
+    for y in 0..=72 {
+        assert_eq!(row(&frame, y), vec![mid; 160], "line {y}");
+    }
+    for y in 73..144 {
+        assert_eq!(row(&frame, y), vec![light; 160], "line {y}");
+        assert_eq!(row(&before, y), vec![dark; 160], "line {y}");
+    }
+    assert_eq!(gb.mmu.ppu.framebuffer().to_vec(), before);
+}
+
+#[test]
+fn last_oam_dma_records_source_and_start_cycle() {
+    let mut gb = idle_cart(&[
+        0x3E, 0xC1, // LD A,$C1
+        0xE0, 0x46, // LDH (DMA),A
+    ]);
+    for i in 0..0xA0u16 {
+        gb.mmu.write_byte(0xC100 + i, (i as u8) ^ 0xA5);
+    }
+    assert_eq!(gb.mmu.last_oam_dma(), None);
+
+    let outcome = gb.run(RunBudget::Breakpoint {
+        addrs: &[0x0104],
+        max_cycles: 1000,
+    });
+    assert_eq!(outcome, RunOutcome::Breakpoint(0x0104));
+    // The FF46 write is the last M-cycle of LDH.
+    assert_eq!(gb.mmu.last_oam_dma(), Some((0xC100, gb.cycles() - 4)));
+
+    gb.run(RunBudget::Cycles(8 + 640));
+    assert!(!gb.mmu.dma_active());
+    let source: Vec<u8> = (0..0xA0u16).map(|i| gb.mmu.peek_byte(0xC100 + i)).collect();
+    assert_eq!(&gb.mmu.ppu.oam[..], &source[..]);
+}
+
+#[test]
+fn restart_point_restores_the_exact_state() {
+    let mut gb = idle_cart(&[]);
+    assert!(!gb.has_restart_point());
+    assert_eq!(gb.restart(), Ok(false));
+
+    gb.set_autosave_interval(Some(Duration::from_secs(30)));
+    gb.run(RunBudget::Frames(3));
+    gb.set_restart_point();
+    let checkpoint = gb.save_state();
+
+    gb.run(RunBudget::Frames(5));
+    gb.mmu.write_byte(0xC000, 0x42);
+    assert_ne!(gb.save_state(), checkpoint);
+    assert_eq!(gb.restart(), Ok(true));
+    assert_eq!(gb.save_state(), checkpoint);
+    assert_eq!(gb.autosave_interval(), Some(Duration::from_secs(30)));
+
+    // The point survives a reset and can be used again.
+    gb.reset();
+    assert!(gb.has_restart_point());
+    assert_eq!(gb.restart(), Ok(true));
+    assert_eq!(gb.save_state(), checkpoint);
+
+    // A different ROM discards it.
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x134] = b'X';
+    gb.mmu.load_cart(Cartridge::load(rom));
+    assert_eq!(gb.restart(), Err(StateError::CartridgeMismatch));
+    assert!(!gb.has_restart_point());
+}
+
+/// Dot cycles at which the VBlank handler is entered over `frames` frames,
+/// with the CPU halted in between, under timing fuzz `seed`.
+fn vblank_entries(seed: Option<u64>, frames: usize) -> (Vec<u64>, Box<GameBoy>) {
+    let mut gb = idle_cart(&[
+        0xAF, // XOR A
+        0xE0, 0x0F, // LDH (IF),A
+        0x3E, 0x01, // LD A,$01
+        0xE0, 0xFF, // LDH (IE),A
+        0xFB, // EI
+        0x76, // HALT
+        0x18, 0xFD, // JR -3
+    ]);
+    gb.set_timing_fuzz(seed);
+    let mut entries = Vec::new();
+    for _ in 0..frames {
+        let outcome = gb.run(RunBudget::Breakpoint {
+            addrs: &[0x0040],
+            max_cycles: 2 * 456 * 154,
+        });
+        assert_eq!(outcome, RunOutcome::Breakpoint(0x0040));
+        entries.push(gb.cycles());
+        gb.cpu.step(&mut gb.mmu);
+    }
+    (entries, gb)
+}
+
+#[test]
+fn timing_fuzz_is_off_and_deterministic_by_default() {
+    let (first, a) = vblank_entries(None, 10);
+    let (second, b) = vblank_entries(None, 10);
+    assert_eq!(a.timing_fuzz(), None);
+    assert_eq!(first, second);
+    assert_eq!(frame_hash(&a), frame_hash(&b));
+    assert_eq!(a.save_state(), b.save_state());
+}
+
+#[test]
+fn timing_fuzz_delays_dispatch_by_at_most_one_m_cycle() {
+    let (exact, _) = vblank_entries(None, 10);
+    let (fuzzed, gb) = vblank_entries(Some(7), 10);
+    assert_eq!(gb.timing_fuzz(), Some(7));
+    assert_eq!(vblank_entries(Some(7), 10).0, fuzzed);
+
+    let delays: Vec<u64> = fuzzed.iter().zip(&exact).map(|(f, e)| f - e).collect();
+    assert!(delays.iter().all(|&d| d == 0 || d == 4), "{delays:?}");
+    assert!(delays.contains(&0) && delays.contains(&4), "{delays:?}");
+
+    // The seed is host configuration: it survives a reset.
+    let mut gb = gb;
+    gb.reset();
+    assert_eq!(gb.timing_fuzz(), Some(7));
+}
+
+#[test]
+fn bench_run_reports_emulated_cycles_and_wall_time() {
+    let mut gb = idle_cart(&[]);
+    let start = gb.cycles();
+    let result = gb.bench_run(456 * 154);
+    assert_eq!(result.cycles, gb.cycles() - start);
+    // Overshoots by less than the longest instruction or interrupt entry.
+    assert!((456 * 154..456 * 154 + 24).contains(&result.cycles));
+    assert!(result.wall > Duration::ZERO);
+    assert!(result.cycles_per_second() > 0.0);
+    assert!((result.speed() - result.cycles_per_second() / 4_194_304.0).abs() < 1e-9);
+}
+
+#[test]
+fn reset_restores_the_initial_ram_contents() {
+    for power_on in [false, true] {
+        let mut gb = Box::new(if power_on {
+            GameBoy::new_power_on_with_revisions(
+                true,
+                DmgRevision::default(),
+                CgbRevision::default(),
+            )
+        } else {
+            GameBoy::new_with_mode(true)
+        });
+        let fresh: Vec<Vec<u8>> = (0..8).map(|bank| gb.mmu.wram(bank).to_vec()).collect();
+        if power_on {
+            assert!(fresh[0].iter().any(|&b| b != 0), "power-on WRAM is seeded");
+        } else {
+            assert!(fresh.iter().flatten().all(|&b| b == 0));
+        }
+
+        gb.mmu.write_byte(0xFF70, 0x05);
+        gb.mmu.clear_region(0xC000..=0xFFFE, 0x5A);
+        if power_on {
+            gb.reset_power_on();
+        } else {
+            gb.reset();
+        }
+        for (bank, expected) in fresh.iter().enumerate() {
+            assert_eq!(gb.mmu.wram(bank), &expected[..], "bank {bank}");
+        }
+        assert!(gb.mmu.hram.iter().all(|&b| b == 0x00));
+    }
+}
+
+#[test]
+fn power_on_reset_fills_wram_with_the_revisions_pattern() {
+    let wram = |gb: &GameBoy| -> Vec<u8> { (0..8).flat_map(|b| gb.mmu.wram(b).to_vec()).collect() };
+    let rev_e = Box::new(GameBoy::new_power_on_with_revision(true, CgbRevision::RevE));
+    let rev_b = Box::new(GameBoy::new_power_on_with_revision(true, CgbRevision::RevB));
+    assert_ne!(wram(&rev_e), wram(&rev_b), "each revision has its own seed");
+
+    // A reset after switching revisions must not reuse the old pattern.
+    let mut gb = Box::new(GameBoy::new_power_on_with_revision(true, CgbRevision::RevE));
+    gb.set_cgb_revision(CgbRevision::RevB);
+    gb.mmu.clear_region(0xC000..=0xFFFE, 0x5A);
+    gb.reset_power_on();
+    assert_eq!(wram(&gb), wram(&rev_b));
+    assert!(gb.mmu.hram.iter().all(|&b| b == 0x00));
+
+    gb.set_cgb_revision(CgbRevision::RevE);
+    gb.reset_power_on();
+    gb.reset_power_on();
+    assert_eq!(wram(&gb), wram(&rev_e));
+}
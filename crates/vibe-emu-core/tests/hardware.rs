@@ -0,0 +1,45 @@
+use vibe_emu_core::hardware::{CgbRevision, DmgRevision, HardwareRevision};
+
+#[test]
+fn revision_names() {
+    let dmg: Vec<String> = DmgRevision::all().iter().map(|r| r.to_string()).collect();
+    assert_eq!(dmg, ["DMG-0", "DMG-A", "DMG-B", "DMG-C"]);
+
+    let cgb: Vec<&str> = CgbRevision::all().iter().map(|r| r.name()).collect();
+    assert_eq!(cgb, ["CGB-0", "CGB-A", "CGB-B", "CGB-C", "CGB-D", "CGB-E"]);
+}
+
+#[test]
+fn cgb_revision_quirk_flags() {
+    let expected = [
+        (CgbRevision::Rev0, false, true),
+        (CgbRevision::RevA, false, true),
+        (CgbRevision::RevB, false, true),
+        (CgbRevision::RevC, false, true),
+        (CgbRevision::RevD, true, false),
+        (CgbRevision::RevE, true, false),
+    ];
+    assert_eq!(expected.len(), CgbRevision::all().len());
+    for (rev, de_window, pcm_glitch) in expected {
+        assert_eq!(rev.supports_de_window(), de_window, "{rev}");
+        assert_eq!(rev.has_pcm_mask_glitch(), pcm_glitch, "{rev}");
+    }
+}
+
+#[test]
+fn default_revision_per_mode() {
+    assert_eq!(
+        HardwareRevision::default_for_mode(false),
+        HardwareRevision::Dmg(DmgRevision::RevC)
+    );
+    assert_eq!(
+        HardwareRevision::default_for_mode(true),
+        HardwareRevision::Cgb(CgbRevision::RevE)
+    );
+    assert_eq!(
+        HardwareRevision::default_for_mode(true).to_string(),
+        "CGB-E"
+    );
+    assert_eq!(HardwareRevision::all_for_mode(false).len(), 4);
+    assert_eq!(HardwareRevision::all_for_mode(true).len(), 6);
+}
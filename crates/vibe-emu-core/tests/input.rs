@@ -0,0 +1,165 @@
+use vibe_emu_core::input::{Buttons, Input};
+
+#[test]
+fn set_buttons_reads_back_and_fires_interrupt() {
+    let mut input = Input::new();
+    let mut if_reg = 0u8;
+    input.set_buttons(Buttons::A | Buttons::START, &mut if_reg);
+    assert_eq!(if_reg & 0x10, 0x10);
+    assert_eq!(input.buttons(), Buttons::A | Buttons::START);
+
+    // Action buttons: A is bit 0, Start is bit 3, both active-low.
+    input.write(0x10, &mut if_reg);
+    assert_eq!(input.read() & 0x0F, 0x06);
+    // No D-pad buttons are held.
+    input.write(0x20, &mut if_reg);
+    assert_eq!(input.read() & 0x0F, 0x0F);
+
+    // Holding the same buttons again is not a new press.
+    if_reg = 0;
+    input.set_buttons(Buttons::START | Buttons::A, &mut if_reg);
+    assert_eq!(if_reg, 0);
+
+    // Releasing never fires the interrupt.
+    input.set_buttons(Buttons::empty(), &mut if_reg);
+    assert_eq!(if_reg, 0);
+    assert!(input.buttons().is_empty());
+}
+
+#[test]
+fn buttons_match_raw_mask() {
+    let mut input = Input::new();
+    let mut if_reg = 0u8;
+    input.set_buttons(Buttons::UP, &mut if_reg);
+    let mut raw = Input::new();
+    raw.update_state(!0x04, &mut if_reg);
+    for sel in [0x10, 0x20] {
+        input.write(sel, &mut if_reg);
+        raw.write(sel, &mut if_reg);
+        assert_eq!(input.read(), raw.read());
+    }
+    assert!(input.buttons().contains(Buttons::UP));
+    assert!(!input.buttons().contains(Buttons::UP | Buttons::DOWN));
+}
+
+#[test]
+fn joypad_interrupt_follows_the_selected_lines() {
+    const DPAD: [Buttons; 4] = [Buttons::RIGHT, Buttons::LEFT, Buttons::UP, Buttons::DOWN];
+    const ACTION: [Buttons; 4] = [Buttons::A, Buttons::B, Buttons::SELECT, Buttons::START];
+    // (P1 value, D-pad selected, action buttons selected)
+    for (select, dpad, action) in [
+        (0x30, false, false),
+        (0x20, true, false),
+        (0x10, false, true),
+        (0x00, true, true),
+    ] {
+        for (button, selected) in DPAD
+            .iter()
+            .map(|&b| (b, dpad))
+            .chain(ACTION.iter().map(|&b| (b, action)))
+        {
+            let mut input = Input::new();
+            let mut if_reg = 0u8;
+            input.write(select, &mut if_reg);
+            assert_eq!(if_reg, 0);
+
+            input.set_buttons(button, &mut if_reg);
+            assert_eq!(if_reg & 0x10 != 0, selected, "{select:02X} {button:?}");
+            assert_eq!(
+                input.last_interrupt_cause(),
+                selected.then_some(button),
+                "{select:02X} {button:?}"
+            );
+
+            // Selecting every group afterwards pulls an unselected line low.
+            if_reg = 0;
+            input.write(0x00, &mut if_reg);
+            assert_eq!(if_reg & 0x10 != 0, !selected, "{select:02X} {button:?}");
+            assert_eq!(input.last_interrupt_cause(), Some(button));
+        }
+    }
+
+    // With both groups selected, a line already held low by one button
+    // doesn't fall again when its partner is pressed.
+    let mut input = Input::new();
+    let mut if_reg = 0u8;
+    input.write(0x00, &mut if_reg);
+    input.set_buttons(Buttons::RIGHT, &mut if_reg);
+    assert_eq!(input.read() & 0x0F, 0x0E);
+    if_reg = 0;
+    input.set_buttons(Buttons::RIGHT | Buttons::A, &mut if_reg);
+    assert_eq!(if_reg, 0);
+    input.set_buttons(Buttons::RIGHT | Buttons::A | Buttons::B, &mut if_reg);
+    assert_eq!(if_reg, 0x10);
+    assert_eq!(input.last_interrupt_cause(), Some(Buttons::B));
+}
+
+#[test]
+fn button_press_wakes_the_cpu_from_stop() {
+    use vibe_emu_core::{cartridge::Cartridge, cpu::Cpu, mmu::Mmu};
+
+    // Interrupts are off, so only the joypad line can end STOP.
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x109].copy_from_slice(&[
+        0xF3, // DI
+        0x3E, 0x20, // LD A,$20 (select the D-pad)
+        0xE0, 0x00, // LDH (P1),A
+        0x10, 0x00, // STOP
+        0x18, 0xFE, // JR -2
+    ]);
+    let mut cpu = Cpu::new();
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::load(rom));
+    mmu.ie_reg = 0;
+    while !cpu.stopped {
+        cpu.step(&mut mmu);
+    }
+    assert_eq!(cpu.pc, 0x107);
+
+    // A button on the deselected group leaves it stopped.
+    mmu.input.set_buttons(Buttons::START, &mut mmu.if_reg);
+    for _ in 0..100 {
+        cpu.step(&mut mmu);
+    }
+    assert!(cpu.stopped);
+    assert_eq!(cpu.pc, 0x107);
+
+    mmu.input
+        .set_buttons(Buttons::START | Buttons::DOWN, &mut mmu.if_reg);
+    cpu.step(&mut mmu);
+    assert!(!cpu.stopped);
+    assert_eq!(cpu.pc, 0x107);
+    assert_eq!(mmu.input.last_interrupt_cause(), Some(Buttons::DOWN));
+}
+
+#[test]
+fn input_set_before_a_frame_is_read_by_that_frames_vblank_handler() {
+    use vibe_emu_core::{cartridge::Cartridge, gameboy::GameBoy};
+
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x40..0x49].copy_from_slice(&[
+        0x3E, 0x10, // LD A,$10 (select action buttons)
+        0xE0, 0x00, // LDH (P1),A
+        0xF0, 0x00, // LDH A,(P1)
+        0xE0, 0x80, // LDH ($FF80),A
+        0xD9, // RETI
+    ]);
+    rom[0x100..0x108].copy_from_slice(&[
+        0x3E, 0x01, // LD A,$01
+        0xE0, 0xFF, // LDH (IE),A
+        0xFB, // EI
+        0x76, // HALT
+        0x18, 0xFD, // JR -3
+    ]);
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb.run_frame();
+    gb.run_frame();
+    assert_eq!(gb.mmu.peek_byte(0xFF80) & 0x0F, 0x0F);
+
+    // A frame ends as VBlank begins, so the game polls the joypad at the top
+    // of the next one. Input applied between frames is seen without lag.
+    gb.mmu.input.set_state(!Buttons::A.bits());
+    gb.run_frame();
+    assert_eq!(gb.mmu.peek_byte(0xFF80) & 0x0F, 0x0E);
+}
@@ -4,6 +4,7 @@ use vibe_emu_core::{
     cartridge::Cartridge,
     hardware::{CgbRevision, DmgRevision},
     mmu::Mmu,
+    ppu::ObjectPriorityMode,
 };
 
 #[test]
@@ -59,6 +60,55 @@ fn wram_echo_and_bank_switch() {
     assert_eq!(mmu.read_byte(0xD000), 0xCC);
 }
 
+#[test]
+fn dmg_echo_ram_aliases_wram() {
+    let mut mmu = Mmu::new();
+    for (echo, wram) in [
+        (0xE000, 0xC000),
+        (0xEFFF, 0xCFFF),
+        (0xF000, 0xD000),
+        (0xFDFF, 0xDDFF),
+    ] {
+        mmu.write_byte(echo, 0x5A);
+        assert_eq!(mmu.read_byte(wram), 0x5A, "{echo:04X} -> {wram:04X}");
+        mmu.write_byte(wram, 0xA5);
+        assert_eq!(mmu.read_byte(echo), 0xA5, "{wram:04X} -> {echo:04X}");
+    }
+    // Echo stops at $FDFF; $DE00-$DFFF has no mirror.
+    mmu.write_byte(0xDE00, 0x77);
+    assert_ne!(mmu.read_byte(0xFE00), 0x77);
+}
+
+#[test]
+fn cgb_echo_ram_follows_selected_wram_bank() {
+    let mut mmu = Mmu::new_with_mode(true);
+    mmu.write_byte(0xE010, 0x11);
+    assert_eq!(mmu.read_byte(0xC010), 0x11);
+
+    mmu.write_byte(0xFF70, 0x03);
+    mmu.write_byte(0xF123, 0x33);
+    assert_eq!(mmu.read_byte(0xD123), 0x33);
+
+    mmu.write_byte(0xFF70, 0x05);
+    assert_eq!(mmu.read_byte(0xF123), mmu.read_byte(0xD123));
+    assert_ne!(mmu.read_byte(0xF123), 0x33);
+    mmu.write_byte(0xD456, 0x55);
+    assert_eq!(mmu.read_byte(0xF456), 0x55);
+    // Bank 0 is not banked, so its echo is the same in every bank.
+    assert_eq!(mmu.read_byte(0xE010), 0x11);
+
+    mmu.write_byte(0xFF70, 0x03);
+    assert_eq!(mmu.read_byte(0xF123), 0x33);
+    assert_ne!(mmu.read_byte(0xF456), 0x55);
+
+    // Bank 0 in SVBK selects bank 1, for echo as well.
+    mmu.write_byte(0xFF70, 0x01);
+    mmu.write_byte(0xF200, 0x99);
+    mmu.write_byte(0xFF70, 0x00);
+    assert_eq!(mmu.read_byte(0xF200), 0x99);
+    assert_eq!(mmu.read_byte(0xD200), 0x99);
+}
+
 #[test]
 fn vram_bank_switch() {
     let mut mmu = Mmu::new_with_mode(true);
@@ -74,6 +124,25 @@ fn vram_bank_switch() {
     assert_eq!(mmu.read_byte(0x8000), 0x11);
 }
 
+#[test]
+fn raw_memory_slices_reflect_writes() {
+    let mut mmu = Mmu::new_with_mode(true);
+    mmu.write_byte(0xFF4F, 0x01);
+    mmu.write_byte(0x9FFF, 0x5A);
+    mmu.write_byte(0xFF70, 0x05);
+    mmu.write_byte(0xD123, 0xA5);
+    mmu.write_byte(0xFE9F, 0x3C);
+
+    assert_eq!(mmu.vram(0).len(), 0x2000);
+    assert_eq!(mmu.vram(1).len(), 0x2000);
+    assert_eq!(mmu.vram(1)[0x1FFF], 0x5A);
+    assert_ne!(mmu.vram(0)[0x1FFF], 0x5A);
+    assert_eq!(mmu.wram(5).len(), 0x1000);
+    assert_eq!(mmu.wram(5)[0x123], 0xA5);
+    assert_eq!(mmu.oam().len(), 0xA0);
+    assert_eq!(mmu.oam()[0x9F], 0x3C);
+}
+
 #[test]
 fn boot_rom_disable() {
     let mut mmu = Mmu::new();
@@ -199,6 +268,61 @@ fn cartridge_ram_access() {
     assert_eq!(mmu.read_byte(0xBFFF), 0xAA);
 }
 
+/// Collects warnings emitted on the calling thread. The log sink is process
+/// wide, so it is installed once and routes into a per-thread buffer; tests
+/// running in parallel never see each other's messages.
+fn capture_warnings<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+    use std::cell::RefCell;
+    use std::fmt;
+    use std::sync::Once;
+    use vibe_emu_core::diagnostics::{Level, LogSink, try_set_log_sink};
+
+    thread_local! {
+        static WARNINGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+    struct Capture;
+    impl LogSink for Capture {
+        fn log(&self, level: Level, _target: &'static str, args: fmt::Arguments) {
+            if level == Level::Warn {
+                WARNINGS.with(|w| w.borrow_mut().push(args.to_string()));
+            }
+        }
+    }
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let _ = try_set_log_sink(Box::new(Capture));
+    });
+
+    WARNINGS.with(|w| w.borrow_mut().clear());
+    let result = f();
+    (result, WARNINGS.with(|w| w.take()))
+}
+
+#[test]
+fn cgb_only_cart_in_dmg_mode_warns_once() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0134..0x013D].copy_from_slice(b"COLORONLY");
+    rom[0x0143] = 0xC0;
+    let ours = |warnings: &[String]| {
+        warnings
+            .iter()
+            .filter(|w| w.starts_with("COLORONLY"))
+            .count()
+    };
+
+    let ((), warnings) =
+        capture_warnings(|| Mmu::new_with_mode(true).load_cart(Cartridge::load(rom.clone())));
+    assert_eq!(ours(&warnings), 0);
+
+    let ((), warnings) = capture_warnings(|| Mmu::new().load_cart(Cartridge::load(rom)));
+    assert_eq!(ours(&warnings), 1, "{warnings:?}");
+    assert!(
+        warnings
+            .iter()
+            .any(|w| w.contains("requires a Game Boy Color"))
+    );
+}
+
 #[test]
 fn mbc1_rom_bank_switching() {
     let mut rom = vec![0u8; 35 * 0x4000];
@@ -245,6 +369,61 @@ fn mbc1_ram_enable() {
     assert_eq!(mmu.read_byte(0xA000), 0xFF);
 }
 
+#[test]
+fn mbc1_ram_banking_follows_mode() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0147] = 0x03; // MBC1 + RAM + Battery
+    rom[0x0149] = 0x03; // 32KB RAM
+    let mut mmu = Mmu::new();
+    mmu.load_cart(Cartridge::from_bytes_with_ram(rom, 0x8000));
+    mmu.write_byte(0x0000, 0x1A); // only the low nibble has to be $A
+
+    // Mode 0: the secondary register does not reach RAM, so every access
+    // goes to bank 0.
+    for bank in 0..4u8 {
+        mmu.write_byte(0x4000, bank);
+        mmu.write_byte(0xA000 + u16::from(bank), 0x10 + bank);
+    }
+    for bank in 0..4u8 {
+        mmu.write_byte(0x4000, bank);
+        assert_eq!(mmu.read_byte(0xA000), 0x10, "bank register {bank}");
+        assert_eq!(mmu.read_byte(0xA003), 0x13, "bank register {bank}");
+    }
+
+    // Mode 1: the secondary register selects the RAM bank.
+    mmu.write_byte(0x6000, 0x01);
+    for bank in 0..4u8 {
+        mmu.write_byte(0x4000, 0xFC | bank); // upper bits ignored
+        mmu.write_byte(0xBFFF, 0xA0 | bank);
+    }
+    for bank in 0..4u8 {
+        mmu.write_byte(0x4000, bank);
+        assert_eq!(mmu.read_byte(0xBFFF), 0xA0 | bank, "bank {bank}");
+    }
+    mmu.write_byte(0x4000, 0x02);
+    assert_eq!(mmu.read_byte(0xA002), 0x00);
+
+    // Disabled RAM ignores writes in either mode.
+    mmu.write_byte(0x0000, 0x00);
+    mmu.write_byte(0xBFFF, 0x55);
+    assert_eq!(mmu.read_byte(0xBFFF), 0xFF);
+    mmu.write_byte(0x0000, 0x0A);
+    assert_eq!(mmu.read_byte(0xBFFF), 0xA2);
+
+    // Back in mode 0 the mode-1 writes to bank 0 are visible, the rest not.
+    mmu.write_byte(0x6000, 0x00);
+    assert_eq!(mmu.read_byte(0xBFFF), 0xA0);
+    assert_eq!(mmu.read_byte(0xA002), 0x12);
+    let ram = mmu.cart.as_ref().unwrap().ram.clone();
+    for bank in 0..4usize {
+        assert_eq!(
+            ram[bank * 0x2000 + 0x1FFF],
+            0xA0 | bank as u8,
+            "bank {bank}"
+        );
+    }
+}
+
 #[test]
 fn oam_dma_transfer() {
     let mut mmu = Mmu::new();
@@ -314,3 +493,277 @@ fn vram_oam_access_blocking() {
     mmu.write_byte(0xFE00, 0x56);
     assert_eq!(mmu.read_byte(0xFE00), 0x56);
 }
+
+#[test]
+fn access_heatmap_counts_reads_and_writes() {
+    let mut mmu = Mmu::new();
+    assert!(mmu.access_heatmap().is_empty());
+
+    mmu.enable_profiling(true);
+    for _ in 0..25 {
+        mmu.read_byte(0xC123);
+    }
+    mmu.write_byte(0xC123, 0x42);
+    mmu.write_byte(0xFF80, 0x01);
+
+    assert_eq!(mmu.read_heatmap()[0xC123], 25);
+    assert_eq!(mmu.write_heatmap()[0xC123], 1);
+    assert_eq!(mmu.access_heatmap()[0xC123], 26);
+    assert_eq!(mmu.access_heatmap()[0xFF80], 1);
+    assert_eq!(mmu.access_heatmap()[0xC124], 0);
+
+    mmu.clear_heatmap();
+    assert!(mmu.profiling_enabled());
+    assert_eq!(mmu.access_heatmap()[0xC123], 0);
+
+    mmu.enable_profiling(false);
+    mmu.read_byte(0xC123);
+    assert!(mmu.access_heatmap().is_empty());
+}
+
+#[test]
+fn opri_is_only_writable_while_boot_rom_is_mapped() {
+    let mut mmu =
+        Mmu::new_power_on_with_revisions(true, DmgRevision::default(), CgbRevision::default());
+    mmu.load_boot_rom(vec![0; 0x900]);
+    assert_eq!(mmu.ppu.object_priority_mode(), ObjectPriorityMode::OamIndex);
+    mmu.write_byte(0xFF6C, 0x01);
+    assert_eq!(mmu.read_byte(0xFF6C), 0xFF);
+    assert_eq!(
+        mmu.ppu.object_priority_mode(),
+        ObjectPriorityMode::Coordinate
+    );
+
+    mmu.write_byte(0xFF50, 0x11);
+    mmu.write_byte(0xFF6C, 0x00);
+    assert_eq!(mmu.read_byte(0xFF6C), 0xFF);
+    assert_eq!(
+        mmu.ppu.object_priority_mode(),
+        ObjectPriorityMode::Coordinate
+    );
+
+    // Skipping the boot ROM leaves the CGB default latched.
+    let mut mmu = Mmu::new_with_mode(true);
+    mmu.write_byte(0xFF6C, 0x01);
+    assert_eq!(mmu.read_byte(0xFF6C), 0xFE);
+    assert_eq!(mmu.ppu.object_priority_mode(), ObjectPriorityMode::OamIndex);
+
+    // ...unless a DMG cartridge puts the CGB in compatibility mode.
+    mmu.load_cart(Cartridge::load(vec![0; 0x8000]));
+    assert_eq!(
+        mmu.ppu.object_priority_mode(),
+        ObjectPriorityMode::Coordinate
+    );
+
+    let mut mmu = Mmu::new();
+    assert_eq!(mmu.read_byte(0xFF6C), 0xFF);
+    assert_eq!(
+        mmu.ppu.object_priority_mode(),
+        ObjectPriorityMode::Coordinate
+    );
+}
+
+#[test]
+fn undocumented_registers_read_back_per_model() {
+    fn write_all(mmu: &mut Mmu, val: u8) -> [u8; 6] {
+        for addr in 0xFF72..=0xFF77 {
+            mmu.write_byte(addr, val);
+        }
+        std::array::from_fn(|i| mmu.read_byte(0xFF72 + i as u16))
+    }
+
+    // CGB running a CGB cartridge: FF72-FF74 are plain R/W, FF75 keeps bits
+    // 4-6, and the PCM registers ignore writes (all channels are silent).
+    let mut rom = vec![0; 0x8000];
+    rom[0x143] = 0x80;
+    let mut cgb = Mmu::new_with_mode(true);
+    cgb.load_cart(Cartridge::load(rom));
+    assert_eq!(
+        write_all(&mut cgb, 0x00),
+        [0x00, 0x00, 0x00, 0x8F, 0x00, 0x00]
+    );
+    assert_eq!(
+        write_all(&mut cgb, 0xFF),
+        [0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]
+    );
+    assert_eq!(
+        write_all(&mut cgb, 0x5A),
+        [0x5A, 0x5A, 0x5A, 0xDF, 0x00, 0x00]
+    );
+
+    // A DMG cartridge puts the CGB in compatibility mode, which locks FF74.
+    let mut compat = Mmu::new_with_mode(true);
+    compat.load_cart(Cartridge::load(vec![0; 0x8000]));
+    assert_eq!(
+        write_all(&mut compat, 0x5A),
+        [0x5A, 0x5A, 0xFF, 0xDF, 0x00, 0x00]
+    );
+    assert_eq!(
+        write_all(&mut compat, 0x00),
+        [0x00, 0x00, 0xFF, 0x8F, 0x00, 0x00]
+    );
+
+    // None of them exist on DMG.
+    let mut dmg = Mmu::new();
+    assert_eq!(write_all(&mut dmg, 0x00), [0xFF; 6]);
+    assert_eq!(write_all(&mut dmg, 0x5A), [0xFF; 6]);
+}
+
+#[test]
+fn io_registers_force_unused_bits_high() {
+    // (address, readback after writing $00, readback after writing $FF).
+    // Registers not listed are unmapped or write-only and always read $FF.
+    // APU registers have their own tests; LY, DIV and HDMA5 change on their
+    // own or start a transfer.
+    const COMMON: &[(u16, u8, u8)] = &[
+        (0xFF00, 0xCF, 0xFF), // P1, no buttons held
+        (0xFF01, 0x00, 0xFF), // SB
+        (0xFF05, 0x00, 0xFF), // TIMA
+        (0xFF06, 0x00, 0xFF), // TMA
+        (0xFF07, 0xF8, 0xFF), // TAC
+        (0xFF0F, 0xE0, 0xFF), // IF
+        (0xFF40, 0x00, 0xFF), // LCDC
+        (0xFF41, 0x80, 0xF8), // STAT, ignoring the live mode and LY=LYC bits
+        (0xFF42, 0x00, 0xFF), // SCY
+        (0xFF43, 0x00, 0xFF), // SCX
+        (0xFF45, 0x00, 0xFF), // LYC
+        (0xFF46, 0x00, 0xFF), // DMA
+        (0xFF47, 0x00, 0xFF), // BGP
+        (0xFF48, 0x00, 0xFF), // OBP0
+        (0xFF49, 0x00, 0xFF), // OBP1
+        (0xFF4A, 0x00, 0xFF), // WY
+        (0xFF4B, 0x00, 0xFF), // WX
+    ];
+    const DMG_ONLY: &[(u16, u8, u8)] = &[
+        (0xFF02, 0x7E, 0xFF), // SC
+    ];
+    const CGB_ONLY: &[(u16, u8, u8)] = &[
+        (0xFF02, 0x7C, 0xFF), // SC
+        (0xFF4D, 0x7E, 0x7F), // KEY1, single speed
+        (0xFF4F, 0xFE, 0xFF), // VBK
+        (0xFF56, 0x3E, 0xFF), // RP, no IR signal
+        (0xFF68, 0x40, 0xFF), // BCPS
+        (0xFF69, 0x00, 0xFF), // BCPD
+        (0xFF6A, 0x40, 0xFF), // OCPS
+        (0xFF6B, 0x00, 0xFF), // OCPD
+        (0xFF6C, 0xFE, 0xFE), // OPRI, locked after boot
+        (0xFF70, 0xF9, 0xFF), // SVBK, 0 selects bank 1
+        (0xFF72, 0x00, 0xFF),
+        (0xFF73, 0x00, 0xFF),
+        (0xFF74, 0x00, 0xFF),
+        (0xFF75, 0x8F, 0xFF),
+        (0xFF76, 0x00, 0x00), // PCM12, all channels silent
+        (0xFF77, 0x00, 0x00), // PCM34
+    ];
+    const SKIPPED: &[u16] = &[0xFF04, 0xFF44, 0xFF55];
+
+    fn check(new_mmu: impl Fn() -> Mmu, expected: &[(u16, u8, u8)]) {
+        for addr in (0xFF00..=0xFF0F).chain(0xFF40..=0xFF7F) {
+            if SKIPPED.contains(&addr) {
+                continue;
+            }
+            let (_, after_00, after_ff) = expected
+                .iter()
+                .copied()
+                .find(|&(a, ..)| a == addr)
+                .unwrap_or((addr, 0xFF, 0xFF));
+            for (val, want) in [(0x00, after_00), (0xFF, after_ff)] {
+                let mut mmu = new_mmu();
+                if addr != 0xFF40 {
+                    mmu.write_byte(0xFF40, 0x00);
+                }
+                mmu.write_byte(addr, val);
+                let mut read = mmu.read_byte(addr);
+                if addr == 0xFF41 {
+                    read &= 0xF8;
+                }
+                assert_eq!(read, want, "{addr:04X} after writing {val:02X}");
+            }
+        }
+    }
+
+    check(Mmu::new, &[COMMON, DMG_ONLY].concat());
+    let cgb = || {
+        let mut rom = vec![0; 0x8000];
+        rom[0x143] = 0x80;
+        let mut mmu = Mmu::new_with_mode(true);
+        mmu.load_cart(Cartridge::load(rom));
+        mmu
+    };
+    check(cgb, &[COMMON, CGB_ONLY].concat());
+}
+
+#[test]
+fn dmg_oam_bug_write_during_mode2_corrupts_scanned_row() {
+    let mut mmu = Mmu::new();
+    let orig = common::start_oam_scan(&mut mmu);
+    mmu.write_byte(0xFE00, 0x56);
+    assert_eq!(mmu.ppu.oam, common::oam_after_write_corruption(&orig));
+}
+
+#[test]
+fn dmg_oam_bug_read_during_mode2_corrupts_scanned_row() {
+    let mut mmu = Mmu::new();
+    let orig = common::start_oam_scan(&mut mmu);
+    assert_eq!(mmu.read_byte(0xFE00), 0xFF);
+
+    // Row 0 word 0 becomes b | (a & c), then row 0 is copied into row 1.
+    let (a, b, c) = (
+        common::oam_word(&orig, 4),
+        common::oam_word(&orig, 0),
+        common::oam_word(&orig, 2),
+    );
+    let glitched = (b | (a & c)).to_le_bytes();
+    let mut expected = orig;
+    expected[0..2].copy_from_slice(&glitched);
+    expected.copy_within(0..8, 8);
+    assert_eq!(mmu.ppu.oam, expected);
+}
+
+#[test]
+fn oam_bug_toggle_defaults_off_on_cgb_and_can_be_disabled() {
+    let mut cgb = Mmu::new_with_mode(true);
+    assert!(!cgb.ppu.oam_bug_enabled());
+    let orig = common::start_oam_scan(&mut cgb);
+    cgb.write_byte(0xFE00, 0x56);
+    cgb.read_byte(0xFE10);
+    assert_eq!(cgb.ppu.oam, orig);
+
+    let mut dmg = Mmu::new();
+    assert!(dmg.ppu.oam_bug_enabled());
+    dmg.ppu.set_oam_bug_enabled(false);
+    let orig = common::start_oam_scan(&mut dmg);
+    dmg.write_byte(0xFE00, 0x56);
+    dmg.read_byte(0xFE10);
+    assert_eq!(dmg.ppu.oam, orig);
+}
+
+#[test]
+fn clear_region_fills_mapped_ram_and_skips_the_rest() {
+    let mut mmu = Mmu::new_with_mode(true);
+    mmu.write_byte(0xFF70, 0x03); // SVBK: WRAM bank 3 at D000
+    mmu.ppu.take_dirty_tiles();
+
+    mmu.clear_region(0x8008..=0x8027, 0x11);
+    mmu.clear_region(0xCFFE..=0xD001, 0x22);
+    mmu.clear_region(0xFDFF..=0xFF81, 0x33);
+
+    let vram = mmu.vram(0);
+    assert_eq!(
+        &vram[0x07..0x29],
+        &[[0x00].as_slice(), &[0x11; 0x20], &[0x00]].concat()[..]
+    );
+    let dirty = mmu.ppu.take_dirty_tiles();
+    assert_eq!(dirty.iter().collect::<Vec<_>>(), [(0, 0), (0, 1), (0, 2)]);
+
+    assert_eq!(&mmu.wram(0)[0xFFD..], &[0x00, 0x22, 0x22]);
+    assert_eq!(&mmu.wram(3)[..3], &[0x22, 0x22, 0x00]);
+    assert_eq!(mmu.wram(1)[0], 0x00);
+
+    // Echo RAM maps to bank 3 too; I/O registers and IE are left alone.
+    assert_eq!(mmu.wram(3)[0xDFF], 0x33);
+    assert!(mmu.oam().iter().all(|&b| b == 0x33));
+    assert_eq!(mmu.read_byte(0xFF70) & 0x07, 0x03);
+    assert_eq!(mmu.ie_reg, 0x00);
+    assert_eq!(&mmu.hram[..3], &[0x33, 0x33, 0x00]);
+}
@@ -1,4 +1,4 @@
-use vibe_emu_core::ppu::Ppu;
+use vibe_emu_core::ppu::{ObjectPriorityMode, Ppu};
 
 #[test]
 fn register_access() {
@@ -79,6 +79,57 @@ fn sprite_8x16_tile_offset() {
     assert_eq!(ppu.framebuffer[8 * 160], 0x00306230);
 }
 
+/// Renders the top 16 lines with an 8x16 object at the top-left corner
+/// and returns the color ID of its leftmost column on each line. Tile 2 is
+/// color 1 with a color 3 first row and tile 3 is color 2 with a color 3
+/// last row, so every row tells which half and which line of it was drawn.
+fn tall_sprite_column(cgb: bool, tile: u8, flags: u8) -> Vec<u8> {
+    let mut ppu = Ppu::new_with_mode(cgb);
+    ppu.write_reg(0xFF40, 0x86); // LCD on, OBJ on, 8x16
+    ppu.skip_startup_for_test();
+    ppu.write_reg(0xFF48, 0xE4);
+    for row in 0..8 {
+        let top = 2 * 16 + row * 2;
+        let (lo, hi) = if row == 0 { (0xFF, 0xFF) } else { (0xFF, 0x00) };
+        ppu.vram[0][top] = lo;
+        ppu.vram[0][top + 1] = hi;
+        let bottom = 3 * 16 + row * 2;
+        let (lo, hi) = if row == 7 { (0xFF, 0xFF) } else { (0x00, 0xFF) };
+        ppu.vram[0][bottom] = lo;
+        ppu.vram[0][bottom + 1] = hi;
+    }
+    ppu.oam[0] = 16;
+    ppu.oam[1] = 8;
+    ppu.oam[2] = tile;
+    ppu.oam[3] = flags;
+    let mut if_reg = 0u8;
+    (0..16)
+        .map(|line| {
+            ppu.step(456, &mut if_reg);
+            // CGB OBJ indices are palette slots 32-35 here.
+            ppu.framebuffer_indices()[line * 160] & 0x03
+        })
+        .collect()
+}
+
+#[test]
+fn sprite_8x16_ignores_tile_index_bit0() {
+    let expected = [3, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 3];
+    for cgb in [false, true] {
+        assert_eq!(tall_sprite_column(cgb, 2, 0x00), expected, "cgb={cgb}");
+        assert_eq!(tall_sprite_column(cgb, 3, 0x00), expected, "cgb={cgb}");
+    }
+}
+
+#[test]
+fn sprite_8x16_vertical_flip_swaps_the_halves() {
+    let expected = [3, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 3];
+    for cgb in [false, true] {
+        assert_eq!(tall_sprite_column(cgb, 2, 0x40), expected, "cgb={cgb}");
+        assert_eq!(tall_sprite_column(cgb, 3, 0x40), expected, "cgb={cgb}");
+    }
+}
+
 #[test]
 fn sprite_x_priority() {
     let mut ppu = Ppu::new();
@@ -196,6 +247,124 @@ fn obj_priority_color0() {
     assert_eq!(ppu.framebuffer[0], 0x008BAC0F);
 }
 
+#[test]
+fn obj_behind_nonzero_bg() {
+    let mut ppu = Ppu::new();
+    ppu.write_reg(0xFF40, 0x93); // LCD on, BG and OBJ, 0x8000 tile data
+    ppu.skip_startup_for_test();
+    let mut if_reg = 0u8;
+    ppu.write_reg(0xFF47, 0xE4);
+    ppu.write_reg(0xFF48, 0xE4);
+    // BG tile -> color 1
+    ppu.vram[0][0] = 0xFF;
+    ppu.vram[0][1] = 0x00;
+    ppu.vram[0][0x1800] = 0x00;
+    // sprite tile -> color 2
+    ppu.vram[0][16] = 0x00;
+    ppu.vram[0][17] = 0xFF;
+    ppu.oam[0] = 16;
+    ppu.oam[1] = 8;
+    ppu.oam[2] = 1;
+    ppu.oam[3] = 0x80; // behind BG
+    ppu.step(456, &mut if_reg);
+    assert_eq!(ppu.framebuffer[0], 0x008BAC0F);
+}
+
+#[test]
+fn obj_behind_bg_hides_lower_priority_obj() {
+    let mut ppu = Ppu::new();
+    ppu.write_reg(0xFF40, 0x93); // LCD on, BG and OBJ, 0x8000 tile data
+    ppu.skip_startup_for_test();
+    let mut if_reg = 0u8;
+    ppu.write_reg(0xFF47, 0xE4);
+    ppu.write_reg(0xFF48, 0xE4);
+    // BG tile -> color 1
+    ppu.vram[0][0] = 0xFF;
+    ppu.vram[0][1] = 0x00;
+    ppu.vram[0][0x1800] = 0x00;
+    // sprite tile -> color 2
+    ppu.vram[0][16] = 0x00;
+    ppu.vram[0][17] = 0xFF;
+    // sprite 0 at x=8 wins on X but sits behind the BG
+    ppu.oam[0] = 16;
+    ppu.oam[1] = 8;
+    ppu.oam[2] = 1;
+    ppu.oam[3] = 0x80;
+    // sprite 1 at x=9 is in front of the BG but has lower priority
+    ppu.oam[4] = 16;
+    ppu.oam[5] = 9;
+    ppu.oam[6] = 1;
+    ppu.oam[7] = 0;
+    ppu.step(456, &mut if_reg);
+    assert_eq!(ppu.framebuffer[1], 0x008BAC0F);
+    // Where only sprite 1 is opaque it is drawn normally.
+    assert_eq!(ppu.framebuffer[8], 0x00306230);
+}
+
+/// Fills tiles 0-3 with solid color IDs 0-3 and maps them to the first four
+/// columns of the top BG row.
+fn solid_color_tiles(ppu: &mut Ppu) {
+    for tile in 0..4usize {
+        for row in 0..8 {
+            let addr = tile * 16 + row * 2;
+            ppu.vram[0][addr] = if tile & 1 != 0 { 0xFF } else { 0x00 };
+            ppu.vram[0][addr + 1] = if tile & 2 != 0 { 0xFF } else { 0x00 };
+        }
+        ppu.vram[0][0x1800 + tile] = tile as u8;
+    }
+}
+
+#[test]
+fn framebuffer_indices_follow_tile_colors() {
+    let mut ppu = Ppu::new();
+    ppu.write_reg(0xFF40, 0x93); // LCD, BG and OBJ on, tile data at 8000
+    ppu.skip_startup_for_test();
+    ppu.write_reg(0xFF47, 0xE4);
+    ppu.write_reg(0xFF48, 0x1B); // OBP0 reversed
+    solid_color_tiles(&mut ppu);
+    ppu.oam[0] = 16; // y
+    ppu.oam[1] = 8 + 32; // x: column 4
+    ppu.oam[2] = 1; // color 1 everywhere
+    let mut if_reg = 0u8;
+    ppu.step(456, &mut if_reg);
+
+    let line = &ppu.framebuffer_indices()[..40];
+    for (x, &index) in line.iter().enumerate() {
+        let expected = if x < 32 { (x / 8) as u8 } else { 2 };
+        assert_eq!(index, expected, "x={x}");
+    }
+
+    // Indices are shades after BGP: reversing BGP reverses them.
+    ppu.write_reg(0xFF47, 0x1B);
+    for _ in 0..154 {
+        ppu.step(456, &mut if_reg);
+    }
+    for x in 0..32 {
+        assert_eq!(ppu.framebuffer_indices()[x], 3 - (x / 8) as u8, "x={x}");
+    }
+}
+
+#[test]
+fn cgb_framebuffer_indices_are_palette_slots() {
+    let mut ppu = Ppu::new_with_mode(true);
+    ppu.write_reg(0xFF40, 0x93);
+    solid_color_tiles(&mut ppu);
+    ppu.vram[1][0x1802] = 0x05; // column 2 uses BG palette 5
+    ppu.oam[0] = 16;
+    ppu.oam[1] = 8 + 32;
+    ppu.oam[2] = 3;
+    ppu.oam[3] = 0x06; // OBJ palette 6
+    let mut if_reg = 0u8;
+    ppu.step(456, &mut if_reg);
+
+    let indices = ppu.framebuffer_indices();
+    assert_eq!(indices[0], 0);
+    assert_eq!(indices[8], 1);
+    assert_eq!(indices[16], 5 * 4 + 2);
+    assert_eq!(indices[24], 3);
+    assert_eq!(indices[32], 32 + 6 * 4 + 3);
+}
+
 #[test]
 fn cgb_bg_attr_priority() {
     let mut ppu = Ppu::new_with_mode(true);
@@ -264,6 +433,86 @@ fn cgb_master_priority() {
     assert_eq!(ppu.framebuffer[0], 0x000000FF);
 }
 
+#[test]
+fn cgb_oam_priority_behind_bg() {
+    let mut ppu = Ppu::new_with_mode(true);
+    ppu.write_reg(0xFF40, 0x93); // BG and OBJ
+    // BG palette 0 color1 -> red
+    ppu.write_reg(0xFF68, 0x80);
+    ppu.write_reg(0xFF69, 0x00);
+    ppu.write_reg(0xFF69, 0x00);
+    ppu.write_reg(0xFF69, 0x1F);
+    ppu.write_reg(0xFF69, 0x00);
+    // sprite palette 0 color1 -> blue
+    ppu.write_reg(0xFF6A, 0x80);
+    ppu.write_reg(0xFF6B, 0x00);
+    ppu.write_reg(0xFF6B, 0x00);
+    ppu.write_reg(0xFF6B, 0x00);
+    ppu.write_reg(0xFF6B, 0x7C);
+    // BG tile 0: color 1 on the left half, color 0 on the right
+    ppu.vram[0][0] = 0xF0;
+    ppu.vram[0][1] = 0x00;
+    ppu.vram[0][0x1800] = 0x00;
+    ppu.vram[1][0x1800] = 0x00; // no BG attribute priority
+    // sprite tile
+    ppu.vram[0][16] = 0xFF;
+    ppu.vram[0][17] = 0x00;
+    ppu.oam[0] = 16;
+    ppu.oam[1] = 8;
+    ppu.oam[2] = 1;
+    ppu.oam[3] = 0x80; // behind BG
+    let mut if_reg = 0u8;
+    ppu.step(456, &mut if_reg);
+    assert_eq!(ppu.framebuffer[0], 0x00FF0000);
+    assert_eq!(ppu.framebuffer[4], 0x000000FF);
+}
+
+#[test]
+fn cgb_bg_priority_hides_lower_priority_obj() {
+    let mut ppu = Ppu::new_with_mode(true);
+    ppu.write_reg(0xFF40, 0x93); // BG and OBJ
+    // BG palette 0 color1 -> red
+    ppu.write_reg(0xFF68, 0x80);
+    ppu.write_reg(0xFF69, 0x00);
+    ppu.write_reg(0xFF69, 0x00);
+    ppu.write_reg(0xFF69, 0x1F);
+    ppu.write_reg(0xFF69, 0x00);
+    // sprite palette 0 color1 -> blue
+    ppu.write_reg(0xFF6A, 0x80);
+    ppu.write_reg(0xFF6B, 0x00);
+    ppu.write_reg(0xFF6B, 0x00);
+    ppu.write_reg(0xFF6B, 0x00);
+    ppu.write_reg(0xFF6B, 0x7C);
+    // BG tile
+    ppu.vram[0][0] = 0xFF;
+    ppu.vram[0][1] = 0x00;
+    ppu.vram[0][0x1800] = 0x00;
+    ppu.vram[1][0x1800] = 0x00;
+    // sprite tile
+    ppu.vram[0][16] = 0xFF;
+    ppu.vram[0][17] = 0x00;
+    // sprite 0 wins on OAM order but sits behind the BG
+    ppu.oam[0] = 16;
+    ppu.oam[1] = 8;
+    ppu.oam[2] = 1;
+    ppu.oam[3] = 0x80;
+    // sprite 1 is in front of the BG but has lower priority
+    ppu.oam[4] = 16;
+    ppu.oam[5] = 8;
+    ppu.oam[6] = 1;
+    ppu.oam[7] = 0;
+    let mut if_reg = 0u8;
+    ppu.step(456, &mut if_reg);
+    assert_eq!(ppu.framebuffer[0], 0x00FF0000);
+
+    // With LCDC.0 cleared OBJ always wins, so sprite 0 is drawn.
+    ppu.write_reg(0xFF40, 0x92);
+    for _ in 0..154 {
+        ppu.step(456, &mut if_reg);
+    }
+    assert_eq!(ppu.framebuffer[0], 0x000000FF);
+}
+
 #[test]
 fn cgb_bg_palette() {
     let mut ppu = Ppu::new_with_mode(true);
@@ -347,3 +596,677 @@ fn bg_disable_yields_color0() {
     assert_eq!(ppu.framebuffer[0], 0x009BBC0F);
     assert_eq!(ppu.framebuffer[159], 0x009BBC0F);
 }
+
+#[test]
+fn framebuffer_rgba8888_byte_order() {
+    let mut ppu = Ppu::new();
+    ppu.framebuffer[0] = 0x00123456;
+    ppu.framebuffer[160 * 144 - 1] = 0x00FF8001;
+    let mut out = vec![0u8; 160 * 144 * 4];
+    ppu.framebuffer_rgba8888(&mut out);
+    assert_eq!(&out[..4], &[0x12, 0x34, 0x56, 0xFF]);
+    assert_eq!(&out[out.len() - 4..], &[0xFF, 0x80, 0x01, 0xFF]);
+}
+
+#[test]
+fn framebuffer_with_grid_alpha_marks_gb_pixel_cells() {
+    let mut ppu = Ppu::new();
+    for (i, px) in ppu.framebuffer.iter_mut().enumerate() {
+        *px = (i as u32).wrapping_mul(0x010203) & 0x00FF_FFFF;
+    }
+    let before = ppu.framebuffer;
+
+    const SCALE: usize = 3;
+    let width = 160 * SCALE;
+    let mut out = vec![0u8; width * 144 * SCALE * 4];
+    ppu.framebuffer_with_grid_alpha(SCALE, 0x40, &mut out);
+    for y in 0..144 * SCALE {
+        for x in 0..width {
+            let px = before[(y / SCALE) * 160 + x / SCALE];
+            let i = (y * width + x) * 4;
+            assert_eq!(
+                &out[i..i + 3],
+                &[(px >> 16) as u8, (px >> 8) as u8, px as u8],
+                "({x},{y})"
+            );
+            let on_grid = x % SCALE == SCALE - 1 || y % SCALE == SCALE - 1;
+            assert_eq!(out[i + 3], if on_grid { 0x40 } else { 0xFF }, "({x},{y})");
+        }
+    }
+    assert_eq!(ppu.framebuffer, before);
+
+    // Without room for a gap, scale 1 is the plain RGBA framebuffer.
+    let mut plain = vec![0u8; 160 * 144 * 4];
+    let mut grid = vec![0u8; 160 * 144 * 4];
+    ppu.framebuffer_rgba8888(&mut plain);
+    ppu.framebuffer_with_grid_alpha(1, 0x40, &mut grid);
+    assert_eq!(grid, plain);
+}
+
+#[test]
+fn framebuffer_rgb565_quantizes() {
+    let mut ppu = Ppu::new();
+    ppu.framebuffer[0] = 0x00FFFFFF;
+    ppu.framebuffer[1] = 0x00000000;
+    ppu.framebuffer[2] = 0x00FF0000;
+    ppu.framebuffer[3] = 0x0000FF00;
+    ppu.framebuffer[4] = 0x000000FF;
+    // Low bits that don't fit in 5/6/5 are dropped.
+    ppu.framebuffer[5] = 0x000F070F;
+    ppu.framebuffer[6] = 0x00080408;
+    let mut out = vec![0u16; 160 * 144];
+    ppu.framebuffer_rgb565(&mut out);
+    assert_eq!(
+        &out[..7],
+        &[0xFFFF, 0x0000, 0xF800, 0x07E0, 0x001F, 0x0821, 0x0821]
+    );
+}
+
+#[test]
+fn vram_oam_accessibility_follows_mode() {
+    for cgb in [false, true] {
+        let mut ppu = Ppu::new_with_mode(cgb);
+        ppu.write_reg(0xFF40, 0x91);
+        ppu.skip_startup_for_test();
+        let mut if_reg = 0u8;
+        // Skip past the first lines so LCD-enable quirks are out of the way.
+        for _ in 0..4 {
+            ppu.step(456, &mut if_reg);
+        }
+        assert_eq!(ppu.read_reg(0xFF44), 4);
+
+        let mut seen = [false; 4];
+        for _ in 0..456 {
+            let mode = ppu.mode();
+            seen[mode as usize] = true;
+            assert_eq!(ppu.vram_accessible(), mode != 3, "cgb={cgb} mode={mode}");
+            assert_eq!(
+                ppu.oam_accessible(),
+                mode != 2 && mode != 3,
+                "cgb={cgb} mode={mode}"
+            );
+            ppu.step(1, &mut if_reg);
+        }
+        assert_eq!(seen, [true, false, true, true]);
+    }
+}
+
+/// Runs DMG frames with BGP flipping between white and black each frame and
+/// returns pixel 0 of every finished frame.
+fn flicker_frames(ghosting: f32, frames: usize) -> Vec<u32> {
+    ghosted_frames(
+        ghosting,
+        frames,
+        |frame| if frame % 2 == 0 { 0x00 } else { 0xFF },
+    )
+}
+
+/// Runs DMG frames with BGP set by `bgp` for each frame and returns pixel 0
+/// of every finished frame.
+fn ghosted_frames(ghosting: f32, frames: usize, bgp: impl Fn(usize) -> u8) -> Vec<u32> {
+    let mut ppu = Ppu::new();
+    ppu.set_dmg_palette([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+    ppu.set_ghosting(ghosting);
+    ppu.write_reg(0xFF40, 0x91);
+    ppu.skip_startup_for_test();
+    let mut if_reg = 0u8;
+    let mut out = Vec::new();
+    while out.len() < frames {
+        ppu.write_reg(0xFF47, bgp(out.len()));
+        ppu.clear_frame_flag();
+        while !ppu.frame_ready() {
+            ppu.step(4, &mut if_reg);
+        }
+        out.push(ppu.framebuffer[0]);
+    }
+    out
+}
+
+#[test]
+fn ghosting_blends_alternating_pixels_toward_grey() {
+    let sharp = flicker_frames(0.0, 4);
+    assert_eq!(sharp, vec![0xFFFFFF, 0x000000, 0xFFFFFF, 0x000000]);
+
+    let ghosted = flicker_frames(0.5, 12);
+    assert_eq!(ghosted[0], 0xFFFFFF);
+    // Once there is a previous frame, no pixel reaches full black or white.
+    for &px in &ghosted[1..] {
+        assert!((0x01..0xFF).contains(&(px & 0xFF)), "{ghosted:06X?}");
+    }
+    // Steady state of a 50% blend of a 0/255 square wave is 85/170.
+    for &px in &ghosted[10..] {
+        assert!((0x50..=0xB0).contains(&(px & 0xFF)), "{px:06X}");
+        assert_eq!(px & 0xFF, (px >> 8) & 0xFF);
+    }
+}
+
+#[test]
+fn full_ghosting_still_lets_new_frames_through() {
+    // White, then black from the second frame on.
+    let frames = ghosted_frames(1.0, 6, |frame| if frame == 0 { 0x00 } else { 0xFF });
+    assert_eq!(frames[0], 0xFFFFFF);
+    for pair in frames.windows(2) {
+        assert!(pair[1] & 0xFF < pair[0] & 0xFF, "{frames:06X?}");
+    }
+}
+
+#[test]
+fn opri_selects_overlapping_object_order() {
+    for (opri, mode, winner_color) in [
+        (0x00, ObjectPriorityMode::OamIndex, 0x0000FF),
+        (0x01, ObjectPriorityMode::Coordinate, 0xFF0000),
+    ] {
+        let mut ppu = Ppu::new_with_mode(true);
+        ppu.write_reg(0xFF40, 0x82);
+        // Tile 0 is solid color 1.
+        for row in 0..8 {
+            ppu.vram[0][row * 2] = 0xFF;
+        }
+        // OAM 0 at X=9 uses palette 0 (blue), OAM 1 at X=8 palette 1 (red).
+        ppu.oam[0..8].copy_from_slice(&[16, 9, 0, 0, 16, 8, 0, 1]);
+        ppu.write_reg(0xFF6A, 0x82);
+        ppu.write_reg(0xFF6B, 0x00);
+        ppu.write_reg(0xFF6B, 0x7C);
+        ppu.write_reg(0xFF6A, 0x8A);
+        ppu.write_reg(0xFF6B, 0x1F);
+        ppu.write_reg(0xFF6B, 0x00);
+
+        ppu.write_reg(0xFF6C, opri);
+        assert_eq!(ppu.object_priority_mode(), mode);
+        assert_eq!(ppu.read_reg(0xFF6C), 0xFE | opri);
+
+        let mut if_reg = 0u8;
+        ppu.step(456, &mut if_reg);
+        // X=1 is covered by both objects.
+        assert_eq!(ppu.framebuffer[1], winner_color, "OPRI={opri}");
+    }
+}
+
+/// Renders a few lines of a window made of tiles whose leftmost pixel is
+/// black, over a blank background, and returns the black columns of line 1.
+fn window_marker_columns(cgb: bool, wx: u8) -> Vec<usize> {
+    let mut ppu = Ppu::new_with_mode(cgb);
+    ppu.write_reg(0xFF40, 0xF1); // LCD, window map $9C00, window, $8000 tiles, BG
+    ppu.skip_startup_for_test();
+    ppu.write_reg(0xFF47, 0xE4);
+    if cgb {
+        ppu.write_reg(0xFF68, 0x80);
+        for color in [0x7FFFu16, 0x5294, 0x294A, 0x0000] {
+            ppu.write_reg(0xFF69, color as u8);
+            ppu.write_reg(0xFF69, (color >> 8) as u8);
+        }
+    }
+    for row in 0..8 {
+        ppu.vram[0][0x10 + row * 2] = 0x80;
+        ppu.vram[0][0x11 + row * 2] = 0x80;
+    }
+    ppu.vram[0][0x1C00..0x2000].fill(1);
+    ppu.write_reg(0xFF4A, 0);
+    ppu.write_reg(0xFF4B, wx);
+
+    let mut if_reg = 0u8;
+    ppu.step(456 * 3, &mut if_reg);
+    let black = if cgb { 0x000000 } else { 0x000F380F };
+    let line = &ppu.framebuffer[160..320];
+    (0..160).filter(|&x| line[x] == black).collect()
+}
+
+#[test]
+fn window_left_and_right_edges_follow_wx() {
+    for cgb in [false, true] {
+        // The window starts at WX-7; below 7 its first 7-WX pixels are cut off.
+        let cols = window_marker_columns(cgb, 7);
+        assert_eq!(cols[..3], [0, 8, 16], "cgb={cgb} wx=7");
+        assert_eq!(cols.len(), 20);
+        let cols = window_marker_columns(cgb, 6);
+        assert_eq!(cols[..3], [7, 15, 23], "cgb={cgb} wx=6");
+        let cols = window_marker_columns(cgb, 0);
+        assert_eq!(cols[..3], [1, 9, 17], "cgb={cgb} wx=0");
+
+        // WX=166 leaves a single window column at the right edge; anything
+        // above it keeps the window off.
+        assert_eq!(window_marker_columns(cgb, 166), [159], "cgb={cgb}");
+        assert!(window_marker_columns(cgb, 167).is_empty(), "cgb={cgb}");
+    }
+}
+
+fn dmg_header(title: &[u8], old_licensee: u8, new_licensee: &[u8; 2]) -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+    rom[0x0144..0x0146].copy_from_slice(new_licensee);
+    rom[0x014B] = old_licensee;
+    rom
+}
+
+#[test]
+fn title_checksum_follows_the_boot_rom() {
+    use vibe_emu_core::colorization::title_checksum;
+
+    // Documented checksums: TETRIS is $DB, ZELDA is $70, ALLEY WAY is $88.
+    assert_eq!(
+        title_checksum(&dmg_header(b"TETRIS", 0x01, b"\0\0")),
+        Some(0xDB)
+    );
+    assert_eq!(
+        title_checksum(&dmg_header(b"ZELDA", 0x01, b"\0\0")),
+        Some(0x70)
+    );
+    assert_eq!(
+        title_checksum(&dmg_header(b"ALLEY WAY", 0x33, b"01")),
+        Some(0x88)
+    );
+
+    // Other licensees aren't looked up at all.
+    assert_eq!(title_checksum(&dmg_header(b"TETRIS", 0x33, b"08")), None);
+    assert_eq!(title_checksum(&dmg_header(b"TETRIS", 0x08, b"01")), None);
+    assert_eq!(title_checksum(&[0u8; 0x100]), None);
+}
+
+#[test]
+fn title_palette_lookup_uses_the_fourth_letter_for_shared_checksums() {
+    use vibe_emu_core::colorization::{
+        CompatPalettes, DEFAULT_PALETTES, TitlePalette, lookup_in, title_checksum,
+    };
+
+    let gray = CompatPalettes {
+        bg: [0xFFFFFF, 0xA5A5A5, 0x525252, 0x000000],
+        ..DEFAULT_PALETTES
+    };
+    let red = CompatPalettes {
+        bg: [0xFFFFFF, 0xFF8484, 0x943939, 0x000000],
+        ..DEFAULT_PALETTES
+    };
+    // "AB-D" and "AC-C" share a checksum and differ in the 4th letter.
+    let abd = dmg_header(b"AB-D", 0x01, b"\0\0");
+    let acc = dmg_header(b"AC-C", 0x01, b"\0\0");
+    let checksum = title_checksum(&abd).unwrap();
+    assert_eq!(title_checksum(&acc), Some(checksum));
+    let table = [
+        TitlePalette {
+            checksum,
+            fourth_letter: Some(b'D'),
+            palettes: gray,
+        },
+        TitlePalette {
+            checksum,
+            fourth_letter: Some(b'C'),
+            palettes: red,
+        },
+    ];
+    assert_eq!(lookup_in(&abd, &table), gray);
+    assert_eq!(lookup_in(&acc, &table), red);
+    assert_eq!(
+        lookup_in(&dmg_header(b"AB-E", 0x01, b"\0\0"), &table),
+        DEFAULT_PALETTES
+    );
+    // A third-party game with the same title keeps the default.
+    assert_eq!(
+        lookup_in(&dmg_header(b"AB-D", 0x08, b"\0\0"), &table),
+        DEFAULT_PALETTES
+    );
+}
+
+#[test]
+fn built_in_title_palettes_match_the_boot_rom() {
+    use vibe_emu_core::colorization::{CompatPalettes, DEFAULT_PALETTES, lookup};
+
+    let yellow = [0xFFFFFF, 0xFFFF00, 0xFF0000, 0x000000];
+    let multi = [0xFFFFFF, 0x5ABDFF, 0xFF0000, 0x0000FF];
+    let purple = [0xFFFFFF, 0x8C8CDE, 0x52528C, 0x000000];
+    let gold = [0xFFC542, 0xFFD600, 0x943A00, 0x4A0000];
+
+    assert_eq!(
+        lookup(&dmg_header(b"TETRIS", 0x01, b"\0\0")),
+        CompatPalettes {
+            bg: yellow,
+            obj0: yellow,
+            obj1: yellow,
+        }
+    );
+    // TETRIS2 and POKEBOM both sum to $0D; the 4th letter picks the row.
+    assert_eq!(
+        lookup(&dmg_header(b"TETRIS2", 0x01, b"\0\0")),
+        CompatPalettes {
+            bg: yellow,
+            obj0: yellow,
+            obj1: multi,
+        }
+    );
+    assert_eq!(
+        lookup(&dmg_header(b"POKEBOM", 0x33, b"01")),
+        CompatPalettes {
+            bg: purple,
+            obj0: gold,
+            obj1: gold,
+        }
+    );
+    // A shared checksum with an unlisted 4th letter gets the default.
+    assert_eq!(
+        lookup(&dmg_header(b"TETQIT2", 0x01, b"\0\0")),
+        DEFAULT_PALETTES
+    );
+    assert_eq!(
+        lookup(&dmg_header(b"TETRIS", 0x08, b"\0\0")),
+        DEFAULT_PALETTES
+    );
+}
+
+#[test]
+fn dmg_colorization_replaces_compatibility_palettes() {
+    use vibe_emu_core::{cartridge::Cartridge, colorization::DEFAULT_PALETTES, mmu::Mmu};
+
+    let mut mmu = Mmu::new_with_mode(true);
+    mmu.load_cart(Cartridge::load(dmg_header(b"TETRIS", 0x08, b"\0\0")));
+    let colors = |ppu: &Ppu| {
+        (
+            std::array::from_fn::<u32, 4, _>(|i| ppu.bg_palette_color(0, i)),
+            std::array::from_fn::<u32, 4, _>(|i| ppu.ob_palette_color(0, i)),
+            std::array::from_fn::<u32, 4, _>(|i| ppu.ob_palette_color(1, i)),
+        )
+    };
+    assert_eq!(
+        colors(&mmu.ppu),
+        (
+            DEFAULT_PALETTES.bg,
+            DEFAULT_PALETTES.obj0,
+            DEFAULT_PALETTES.obj1
+        )
+    );
+
+    let bg = [0xFFFFFF, 0xFFFF00, 0xFF0000, 0x000000];
+    let obj0 = [0xFFFFFF, 0x7BFF31, 0x008400, 0x000000];
+    // Low bits below 15-bit precision are dropped.
+    let obj1 = [0xFFFFFF, 0x8D8DDF, 0x53538D, 0x000000];
+    mmu.ppu.apply_dmg_colorization(bg, obj0, obj1);
+    assert_eq!(
+        colors(&mmu.ppu),
+        (bg, obj0, [0xFFFFFF, 0x8C8CDE, 0x52528C, 0x000000])
+    );
+
+    // DMG hardware has no colour palettes to change.
+    let mut dmg = Ppu::new_with_mode(false);
+    let before = colors(&dmg);
+    dmg.apply_dmg_colorization(bg, obj0, obj1);
+    assert_eq!(colors(&dmg), before);
+}
+
+#[test]
+fn load_cart_colors_dmg_games_by_title() {
+    use vibe_emu_core::{cartridge::Cartridge, mmu::Mmu};
+
+    let colors = |ppu: &Ppu| {
+        (
+            std::array::from_fn::<u32, 4, _>(|i| ppu.bg_palette_color(0, i)),
+            std::array::from_fn::<u32, 4, _>(|i| ppu.ob_palette_color(0, i)),
+            std::array::from_fn::<u32, 4, _>(|i| ppu.ob_palette_color(1, i)),
+        )
+    };
+    let red = [0xFFFFFF, 0xFF8484, 0x943939, 0x000000];
+    let green = [0xFFFFFF, 0x7BFF31, 0x008400, 0x000000];
+
+    let mut mmu = Mmu::new_with_mode(true);
+    mmu.load_cart(Cartridge::load(dmg_header(b"POKEMON RED", 0x01, b"\0\0")));
+    assert_eq!(colors(&mmu.ppu), (red, green, red));
+
+    // ZELDA has its own BG colours.
+    let mut mmu = Mmu::new_with_mode(true);
+    mmu.load_cart(Cartridge::load(dmg_header(b"ZELDA", 0x01, b"\0\0")));
+    assert_eq!(colors(&mmu.ppu).0, [0xFFFFFF, 0x00FF00, 0x318400, 0x004A00]);
+}
+
+/// Draws line 0 of a DMG screen with a blank background and the given
+/// `(oam_y, oam_x)` sprites, all solid colour 3.
+fn render_line0_with_sprites(sprites: &[(u8, u8)]) -> Vec<u32> {
+    let mut ppu = Ppu::new();
+    ppu.write_reg(0xFF40, 0x82); // LCD on, sprites enabled, BG off
+    ppu.skip_startup_for_test();
+    ppu.write_reg(0xFF48, 0xE4);
+    ppu.vram[0][..16].fill(0xFF);
+    for (i, &(y, x)) in sprites.iter().enumerate() {
+        ppu.oam[i * 4..i * 4 + 4].copy_from_slice(&[y, x, 0, 0]);
+    }
+    let mut if_reg = 0u8;
+    ppu.step(456, &mut if_reg);
+    ppu.framebuffer()[..160].to_vec()
+}
+
+#[test]
+fn off_screen_sprites_still_use_the_line_budget() {
+    let visible = (16, 50);
+    let drawn = |line: &[u32]| line.iter().filter(|&&px| px == 0x000F380F).count();
+    assert_eq!(drawn(&render_line0_with_sprites(&[visible])), 8);
+
+    // Ten sprites hidden off the left or right edge fill the line, so the
+    // eleventh is dropped.
+    for hidden_x in [0, 168, 200] {
+        let mut sprites = vec![(16, hidden_x); 10];
+        sprites.push(visible);
+        assert_eq!(
+            drawn(&render_line0_with_sprites(&sprites)),
+            0,
+            "X={hidden_x}"
+        );
+    }
+
+    // Sprites on other lines are never selected and leave room for it.
+    for hidden_y in [0, 8, 17, 160, 255] {
+        let mut sprites = vec![(hidden_y, 20); 10];
+        sprites.push(visible);
+        assert_eq!(
+            drawn(&render_line0_with_sprites(&sprites)),
+            8,
+            "Y={hidden_y}"
+        );
+    }
+}
+
+#[test]
+fn sprites_on_the_screen_edges_are_clipped() {
+    let line = render_line0_with_sprites(&[(16, 4)]);
+    let drawn: Vec<usize> = (0..160).filter(|&x| line[x] == 0x000F380F).collect();
+    assert_eq!(drawn, [0, 1, 2, 3]);
+
+    let line = render_line0_with_sprites(&[(16, 164)]);
+    let drawn: Vec<usize> = (0..160).filter(|&x| line[x] == 0x000F380F).collect();
+    assert_eq!(drawn, [156, 157, 158, 159]);
+
+    // Only the bottom row of a sprite at Y=9 reaches line 0; one at Y=8 sits
+    // entirely above the screen.
+    let line = render_line0_with_sprites(&[(9, 50)]);
+    assert_eq!(line[42], 0x000F380F);
+    let line = render_line0_with_sprites(&[(8, 50)]);
+    assert_eq!(line[42], line[0]);
+}
+
+#[test]
+fn vram_writes_mark_only_the_written_tile_dirty() {
+    use vibe_emu_core::mmu::Mmu;
+    use vibe_emu_core::ppu::TileDirtyMask;
+
+    let mut mmu = Mmu::new_with_mode(true);
+    mmu.write_byte(0xFF40, 0x00); // LCD off so VRAM is always writable
+    assert_eq!(mmu.ppu.take_dirty_tiles(), TileDirtyMask::all());
+    assert!(mmu.ppu.take_dirty_tiles().is_empty());
+
+    for offset in 0..16 {
+        mmu.write_byte(0x8050 + offset, 0xA5);
+    }
+    let dirty = mmu.ppu.take_dirty_tiles();
+    assert_eq!(dirty.iter().collect::<Vec<_>>(), [(0, 5)]);
+    assert!(dirty.is_dirty(0, 5));
+    assert!(!dirty.is_dirty(1, 5));
+    assert!(mmu.ppu.take_dirty_tiles().is_empty());
+
+    // Rewriting the same bytes and writing the tile maps change no tiles.
+    mmu.write_byte(0x8050, 0xA5);
+    mmu.write_byte(0x9800, 0x12);
+    assert!(mmu.ppu.take_dirty_tiles().is_empty());
+
+    mmu.write_byte(0xFF4F, 0x01);
+    mmu.write_byte(0x97FF, 0x01);
+    mmu.write_byte(0x9000, 0x01);
+    let dirty = mmu.ppu.take_dirty_tiles();
+    assert_eq!(dirty.iter().collect::<Vec<_>>(), [(1, 256), (1, 383)]);
+    assert_eq!(dirty.count(), 2);
+}
+
+#[test]
+fn vblank_capture_records_the_backdrop_of_every_vblank_line() {
+    use vibe_emu_core::cartridge::Cartridge;
+    use vibe_emu_core::gameboy::GameBoy;
+    use vibe_emu_core::ppu::CAPTURE_LINES;
+
+    // Makes color 0 black from the middle of line 148 until LY reads 0.
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x115].copy_from_slice(&[
+        0xF0, 0x44, // LDH A,(LY)
+        0xFE, 0x94, // CP 148
+        0x20, 0xFA, // JR NZ,-6
+        0x3E, 0xFF, // LD A,$FF
+        0xE0, 0x47, // LDH (BGP),A
+        0xF0, 0x44, // LDH A,(LY)
+        0xA7, // AND A
+        0x20, 0xFB, // JR NZ,-5
+        0x3E, 0xFC, // LD A,$FC
+        0xE0, 0x47, // LDH (BGP),A
+        0x18, 0xEB, // JR -21
+    ]);
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    let palette = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000];
+    gb.mmu.ppu.set_dmg_palette(palette);
+    assert_eq!(gb.mmu.ppu.extended_framebuffer(), None);
+
+    gb.mmu.ppu.set_capture_vblank_lines(true);
+    assert!(gb.mmu.ppu.capture_vblank_lines());
+    for _ in 0..3 {
+        assert!(gb.run_frame());
+    }
+    assert_eq!(gb.mmu.ppu.line(), 144);
+
+    let ppu = &gb.mmu.ppu;
+    let capture = ppu.extended_framebuffer().unwrap();
+    assert_eq!(capture.len(), 160 * CAPTURE_LINES);
+    assert_eq!(CAPTURE_LINES, 154);
+    let (frame, vblank) = capture.split_at(160 * 144);
+    assert_eq!(frame, ppu.framebuffer());
+    for (i, row) in vblank.chunks(160).enumerate() {
+        let line = 144 + i;
+        let expected = if (149..=153).contains(&line) {
+            palette[3]
+        } else {
+            palette[0]
+        };
+        assert!(row.iter().all(|&p| p == expected), "line {line}");
+    }
+
+    gb.mmu.ppu.set_capture_vblank_lines(false);
+    assert_eq!(gb.mmu.ppu.extended_framebuffer(), None);
+}
+
+#[test]
+fn scroll_log_records_per_line_scroll_registers() {
+    let mut ppu = Ppu::new();
+    ppu.write_reg(0xFF40, 0x91);
+    ppu.skip_startup_for_test();
+    let mut if_reg = 0u8;
+    ppu.step(456, &mut if_reg);
+    assert!(ppu.scroll_log().is_empty());
+
+    ppu.set_scroll_logging(true);
+    assert!(ppu.scroll_logging());
+    while ppu.line() != 0 {
+        ppu.step(4, &mut if_reg);
+    }
+    // Each write lands in mode 2 of the line it is meant for.
+    for line in 0..144u8 {
+        assert_eq!(ppu.line(), line);
+        ppu.write_reg(0xFF43, line.wrapping_mul(3));
+        ppu.write_reg(0xFF42, 144 - line);
+        ppu.write_reg(0xFF4B, 7 + line / 16);
+        ppu.write_reg(0xFF4A, 40);
+        ppu.step(456, &mut if_reg);
+    }
+
+    let log = ppu.scroll_log();
+    assert_eq!(log.len(), 144);
+    for (line, &entry) in (0u8..).zip(log) {
+        assert_eq!(
+            entry,
+            (line.wrapping_mul(3), 144 - line, 7 + line / 16, 40),
+            "line {line}"
+        );
+    }
+
+    ppu.set_scroll_logging(false);
+    assert!(ppu.scroll_log().is_empty());
+}
+
+/// Mode 3 length on line 20 with `objects` 8×8 objects at OAM X `x`, checked
+/// against the dot HBlank actually starts at.
+fn mode3_length(cgb: bool, scx: u8, objects: usize, x: u8) -> u16 {
+    use vibe_emu_core::cartridge::Cartridge;
+    use vibe_emu_core::gameboy::GameBoy;
+
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]); // JR -2
+    if cgb {
+        rom[0x143] = 0x80;
+    }
+    let mut gb = Box::new(GameBoy::new_with_mode(cgb));
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb.mmu.write_byte(0xFF40, 0x00);
+    for i in 0..40u16 {
+        let y = if (i as usize) < objects { 20 + 16 } else { 0 };
+        gb.mmu.write_byte(0xFE00 + i * 4, y);
+        gb.mmu.write_byte(0xFE01 + i * 4, x);
+    }
+    gb.mmu.write_byte(0xFF43, scx);
+    gb.mmu.write_byte(0xFF40, 0x83);
+    assert!(gb.step_to_ly(20));
+    while gb.mmu.ppu.mode() != 3 {
+        gb.cpu.step(&mut gb.mmu);
+    }
+    let start = gb.cycles();
+    let length = gb.mmu.ppu.current_mode3_length();
+    assert_eq!(80 + length + gb.mmu.ppu.hblank_target_cycles(), 456);
+    while gb.mmu.ppu.mode() == 3 {
+        gb.cpu.step(&mut gb.mmu);
+    }
+    let measured = gb.cycles() - start;
+    assert!(
+        measured.abs_diff(u64::from(length)) <= 12,
+        "HBlank began {measured} dots into mode 3, expected {length}"
+    );
+    length
+}
+
+#[test]
+fn mode3_length_varies_with_scx_and_objects() {
+    let measure = |scx, objects, x| mode3_length(false, scx, objects, x);
+
+    // SCX % 8 delays the first pixel, rounded up to whole M-cycles.
+    for (scx, extra) in [(0, 0), (1, 4), (4, 4), (5, 8), (7, 8), (8, 0), (0x0D, 8)] {
+        assert_eq!(measure(scx, 0, 0), 172 + extra, "SCX={scx}");
+    }
+
+    // Every object on the line stalls the fetcher.
+    assert_eq!(measure(0, 1, 8), 172 + 8);
+    assert_eq!(measure(0, 1, 12), 172 + 4);
+    for (count, m_cycles) in [(1, 2), (2, 4), (5, 8), (10, 16)] {
+        assert_eq!(measure(0, count, 0), 172 + m_cycles * 4, "{count} objects");
+    }
+    // The OAM scan keeps only the first ten objects on a line.
+    assert_eq!(measure(0, 40, 0), 172 + 64);
+}
+
+#[test]
+fn cgb_mode3_length_only_models_objects_at_the_left_edge() {
+    let measure = |scx, objects, x| mode3_length(true, scx, objects, x);
+
+    assert_eq!(measure(0, 0, 0), 172);
+    // Objects starting left of the screen need an extra fetch.
+    assert_eq!(measure(0, 1, 0), 172 + 6);
+    assert_eq!(measure(0, 1, 8), 172 + 6);
+    assert_eq!(measure(0, 10, 0), 172 + 6);
+}
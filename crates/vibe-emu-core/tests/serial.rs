@@ -5,9 +5,13 @@
 //! 2. Internal vs external clock transfers
 //! 3. Two-device link cable simulation
 
+mod common;
+
 use std::collections::VecDeque;
 use vibe_emu_core::hardware::DmgRevision;
-use vibe_emu_core::serial::{LinkPort, NullLinkPort, Serial};
+use vibe_emu_core::serial::{
+    AsyncLinkAdapter, AsyncLinkPort, LinkBus, LinkPort, NullLinkPort, Serial,
+};
 
 /// A link port that records all bytes sent and returns pre-programmed responses.
 struct RecordingLinkPort {
@@ -86,6 +90,51 @@ fn serial_sc_cgb_preserves_fast_clock_bit() {
     assert_eq!(serial.read(0xFF02), 0x02);
 }
 
+/// Dots from writing `sc` until the transfer requests its interrupt, with
+/// the divider starting at 0.
+fn transfer_dots(serial: &mut Serial, sc: u8, double_speed: bool) -> u32 {
+    serial.write(0xFF01, 0x12);
+    serial.write(0xFF02, sc);
+    let mut if_reg = 0u8;
+    let mut div = 0u16;
+    while if_reg & 0x08 == 0 {
+        assert!(div < 8192, "transfer never finished");
+        serial.step(div, div + 1, double_speed, &mut if_reg);
+        div += 1;
+    }
+    div.into()
+}
+
+#[test]
+fn serial_bit_period_follows_fast_clock_and_double_speed() {
+    // (SC, double speed, dots per bit): 8192, 16384, 262144 and 524288 Hz.
+    for (sc, double_speed, period) in [
+        (0x81, false, 512),
+        (0x81, true, 256),
+        (0x83, false, 16),
+        (0x83, true, 8),
+    ] {
+        let mut serial = Serial::new(true, DmgRevision::default());
+        serial.write(0xFF02, sc & 0x7F);
+        assert_eq!(
+            serial.bit_period_cycles(double_speed),
+            period,
+            "SC={sc:02X}"
+        );
+        assert_eq!(
+            transfer_dots(&mut serial, sc, double_speed),
+            8 * period,
+            "SC={sc:02X}"
+        );
+
+        // DMG has neither the fast clock nor double speed.
+        let mut serial = Serial::new(false, DmgRevision::default());
+        serial.write(0xFF02, sc & 0x7F);
+        assert_eq!(serial.bit_period_cycles(double_speed), 512);
+        assert_eq!(transfer_dots(&mut serial, sc, false), 8 * 512);
+    }
+}
+
 #[test]
 fn internal_clock_transfer_exchanges_bytes() {
     let responses = RecordingLinkPort::new([0xAB]);
@@ -103,6 +152,190 @@ fn internal_clock_transfer_exchanges_bytes() {
     assert!(if_reg & 0x08 != 0); // serial IRQ
 }
 
+#[test]
+fn shift_register_shows_a_partially_shifted_byte() {
+    let mut serial = Serial::new(false, DmgRevision::default());
+    serial.connect(Box::new(RecordingLinkPort::new([0xAB])));
+    assert_eq!(serial.bits_transferred(), None);
+
+    serial.write(0xFF01, 0x12);
+    serial.write(0xFF02, 0x81);
+    assert_eq!(serial.bits_transferred(), Some(0));
+    assert_eq!(serial.shift_register(), 0x12);
+
+    // Three bits at 512 cycles each: 0x12 moves left three places and the
+    // top three bits of 0xAB (101) come in from the right.
+    let mut if_reg = 0u8;
+    serial.step(0, 3 * 512, false, &mut if_reg);
+    assert_eq!(serial.bits_transferred(), Some(3));
+    assert_eq!(serial.shift_register(), 0x95);
+    assert_eq!(serial.read(0xFF01), 0x95);
+    assert_eq!(if_reg & 0x08, 0);
+
+    // Reading is side-effect free.
+    assert_eq!(serial.shift_register(), 0x95);
+    assert_eq!(serial.bits_transferred(), Some(3));
+
+    serial.step(3 * 512, 8 * 512, false, &mut if_reg);
+    assert_eq!(serial.bits_transferred(), None);
+    assert_eq!(serial.shift_register(), 0xAB);
+    assert_ne!(if_reg & 0x08, 0);
+}
+
+#[test]
+fn injected_bytes_are_received_before_the_port_reply() {
+    let mut serial = Serial::new(false, DmgRevision::default());
+    serial.connect(Box::new(RecordingLinkPort::new([0xAB])));
+    serial.inject_byte(0x42);
+    serial.inject_byte(0x43);
+
+    let mut if_reg = 0u8;
+    for expected in [0x42, 0x43, 0xAB] {
+        serial.write(0xFF01, 0x12);
+        serial.write(0xFF02, 0x81);
+        serial.step(0, 4096, false, &mut if_reg);
+        assert_eq!(serial.read(0xFF01), expected);
+    }
+    // Each transfer still sends SB as usual.
+    assert_eq!(serial.take_output(), [0x12, 0x12, 0x12]);
+}
+
+#[test]
+fn game_reads_an_injected_byte_from_sb() {
+    use vibe_emu_core::{cartridge::Cartridge, gameboy::GameBoy};
+
+    // Sends $55 as master and stores what comes back in $FF80.
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x111].copy_from_slice(&[
+        0x3E, 0x55, // LD A,$55
+        0xE0, 0x01, // LDH (SB),A
+        0x3E, 0x81, // LD A,$81
+        0xE0, 0x02, // LDH (SC),A
+        0xF0, 0x02, // LDH A,(SC)
+        0x87, // ADD A,A
+        0x38, 0xFB, // JR C,-5
+        0xF0, 0x01, // LDH A,(SB)
+        0xE0, 0x80, // LDH ($FF80),A
+    ]);
+    rom[0x111..0x113].copy_from_slice(&[0x18, 0xFE]); // JR -2
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb.inject_serial_byte(b'r');
+    assert!(gb.run_frame());
+    assert_eq!(gb.mmu.peek_byte(0xFF80), b'r');
+    assert_eq!(gb.mmu.serial.take_output(), [0x55]);
+}
+
+#[test]
+fn detached_serial_falls_back_to_no_cable() {
+    use vibe_emu_core::{cartridge::Cartridge, gameboy::GameBoy};
+
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]); // JR -2
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb.attach_serial(Box::new(RecordingLinkPort::new([0xAB, 0xCD, 0xEF])));
+
+    let transfer = |gb: &mut GameBoy, byte: u8| {
+        gb.mmu.write_byte(0xFF01, byte);
+        gb.mmu.write_byte(0xFF02, 0x81);
+        assert!(gb.run_frame());
+        assert_eq!(gb.mmu.read_byte(0xFF02) & 0x80, 0, "transfer finished");
+        gb.mmu.read_byte(0xFF01)
+    };
+    assert_eq!(transfer(&mut gb, 0x12), 0xAB);
+
+    // Unplugging mid-transfer keeps the byte the partner already sent.
+    gb.mmu.write_byte(0xFF01, 0x34);
+    gb.mmu.write_byte(0xFF02, 0x81);
+    gb.cpu.step(&mut gb.mmu);
+    let mut old = gb.detach_serial();
+    assert!(gb.run_frame());
+    assert_eq!(gb.mmu.read_byte(0xFF01), 0xCD);
+    assert_eq!(gb.mmu.read_byte(0xFF02) & 0x80, 0);
+
+    // Afterwards transfers see no cable, and the old port is untouched.
+    assert_eq!(transfer(&mut gb, 0x56), 0xFF);
+    assert_eq!(old.transfer(0x00), 0xEF);
+    assert_eq!(gb.mmu.serial.take_output(), [0x12, 0x34, 0x56]);
+}
+
+#[test]
+fn link_bus_exchanges_bytes_between_two_machines() {
+    use vibe_emu_core::gameboy::{GameBoy, RunBudget};
+
+    let (end_a, end_b) = LinkBus::new().ends();
+    let mut listener = Box::new(GameBoy::new());
+    listener.mmu.load_cart(common::link_cart(&[0x42], false, 0));
+    listener.attach_serial(Box::new(end_a));
+    let mut clocker = Box::new(GameBoy::new());
+    clocker.mmu.load_cart(common::link_cart(&[0x99], true, 0));
+    clocker.attach_serial(Box::new(end_b));
+
+    let mut done = [None; 2];
+    for _ in 0..200 {
+        for (i, gb) in [&mut listener, &mut clocker].into_iter().enumerate() {
+            gb.run(RunBudget::Cycles(64));
+            if done[i].is_none() && gb.mmu.read_byte(0xFF0F) & 0x08 != 0 {
+                done[i] = Some(gb.cycles());
+            }
+        }
+    }
+    assert_eq!(listener.mmu.read_byte(0xFF80), 0x99);
+    assert_eq!(clocker.mmu.read_byte(0xFF80), 0x42);
+    assert_eq!(listener.mmu.serial.take_output(), [0x42]);
+    assert_eq!(clocker.mmu.serial.take_output(), [0x99]);
+
+    // The listener is clocked at the clocking side's bit rate, so both
+    // transfers end within a bit of each other.
+    let (a, b) = (done[0].unwrap(), done[1].unwrap());
+    assert!(a.abs_diff(b) <= 512 + 64, "{a} vs {b}");
+
+    // With nobody listening, the clocking side sees an open line.
+    let (mut lonely, _unplugged) = LinkBus::new().ends();
+    assert_eq!(lonely.transfer(0x12), 0xFF);
+}
+
+#[test]
+fn step_locked_link_trades_are_reproducible() {
+    use vibe_emu_core::gameboy::GameBoy;
+
+    let trade = || {
+        let bus = LinkBus::new();
+        let (end_a, end_b) = bus.ends();
+        let mut listener = Box::new(GameBoy::new());
+        listener
+            .mmu
+            .load_cart(common::link_cart(&[0x10, 0x20, 0x30, 0x40], false, 0x10));
+        listener.attach_serial(Box::new(end_a));
+        let mut clocker = Box::new(GameBoy::new());
+        clocker
+            .mmu
+            .load_cart(common::link_cart(&[0xA1, 0xB2, 0xC3, 0xD4], true, 0x00));
+        clocker.attach_serial(Box::new(end_b));
+
+        bus.step_locked(&mut listener, &mut clocker, 60_000);
+        (listener, clocker)
+    };
+
+    let (mut listener, mut clocker) = trade();
+    let states = (listener.save_state(), clocker.save_state());
+    for _ in 0..3 {
+        let (again_listener, again_clocker) = trade();
+        assert!(again_listener.save_state() == states.0);
+        assert!(again_clocker.save_state() == states.1);
+    }
+
+    let received = |gb: &mut GameBoy| {
+        (0..4)
+            .map(|i| gb.mmu.read_byte(0xFF80 + i))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(received(&mut listener), [0xA1, 0xB2, 0xC3, 0xD4]);
+    assert_eq!(received(&mut clocker), [0x10, 0x20, 0x30, 0x40]);
+    assert!(listener.cycles().abs_diff(clocker.cycles()) < LinkBus::LOCKSTEP_SLICE);
+}
+
 #[test]
 fn external_clock_transfer_waits_for_pulses() {
     let responses = RecordingLinkPort::new([0xAB]);
@@ -336,3 +569,97 @@ fn serial_no_data_byte_handling() {
     serial.step(0, 4096, false, &mut if_reg);
     assert_eq!(serial.read(0xFF01), 0x42);
 }
+
+/// Async peer that answers after a fixed number of polls.
+struct DelayedPeer {
+    reply: u8,
+    polls_left: u32,
+    sent: Vec<u8>,
+    cancelled: bool,
+}
+
+impl DelayedPeer {
+    fn new(reply: u8, polls: u32) -> Self {
+        Self {
+            reply,
+            polls_left: polls,
+            sent: Vec::new(),
+            cancelled: false,
+        }
+    }
+}
+
+impl AsyncLinkPort for DelayedPeer {
+    fn try_transfer(&mut self, byte: u8) -> Option<u8> {
+        if self.sent.last() != Some(&byte) {
+            self.sent.push(byte);
+        }
+        if self.polls_left == 0 {
+            Some(self.reply)
+        } else {
+            self.polls_left -= 1;
+            None
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+/// Starts an internally-clocked DMG transfer of `out` and steps the serial
+/// unit 4 DIV ticks at a time until it completes. Returns the ticks taken.
+fn run_master_transfer(serial: &mut Serial, out: u8) -> u32 {
+    serial.write(0xFF01, out);
+    serial.write(0xFF02, 0x81);
+    let mut if_reg = 0u8;
+    let mut div = 0u16;
+    let mut ticks = 0;
+    while if_reg & 0x08 == 0 {
+        serial.step(div, div.wrapping_add(4), false, &mut if_reg);
+        div = div.wrapping_add(4);
+        ticks += 4;
+        assert!(ticks < 1_000_000, "transfer never completed");
+    }
+    ticks
+}
+
+#[test]
+fn async_peer_reply_within_budget_completes_transfer() {
+    let mut serial = Serial::new(false, DmgRevision::default());
+    serial.connect(Box::new(AsyncLinkAdapter::with_stall_budget(
+        DelayedPeer::new(0x5A, 200),
+        10_000,
+    )));
+    let ticks = run_master_transfer(&mut serial, 0x42);
+    assert_eq!(serial.read(0xFF01), 0x5A);
+    assert_eq!(serial.take_output(), vec![0x42]);
+    // The 200 polls waited (4 ticks each) come on top of the 8 bit times.
+    // The shift clock follows DIV, so waiting 800 ticks for the peer pushes
+    // the first of the 8 bit edges from tick 512 to tick 1024.
+    assert_eq!(ticks, 4096 + 512);
+}
+
+#[test]
+fn async_peer_timeout_falls_back_to_ff() {
+    let mut serial = Serial::new(false, DmgRevision::default());
+    serial.connect(Box::new(AsyncLinkAdapter::with_stall_budget(
+        DelayedPeer::new(0x5A, u32::MAX),
+        2_000,
+    )));
+    let ticks = run_master_transfer(&mut serial, 0x42);
+    assert_eq!(serial.read(0xFF01), 0xFF);
+    // Gives up at tick 2000; the bits then shift on edges 2048..=5632.
+    assert_eq!(ticks, 2048 + 7 * 512);
+}
+
+#[test]
+fn async_adapter_blocking_transfer() {
+    let mut port = AsyncLinkAdapter::new(DelayedPeer::new(0x33, 3));
+    assert_eq!(port.transfer(0x11), 0x33);
+    assert!(!port.get_ref().cancelled);
+
+    let mut port = AsyncLinkAdapter::with_stall_budget(DelayedPeer::new(0x33, u32::MAX), 4_194);
+    assert_eq!(port.transfer(0x11), 0xFF);
+    assert!(port.into_inner().cancelled);
+}
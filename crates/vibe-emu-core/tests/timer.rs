@@ -9,6 +9,45 @@ fn div_increment() {
     assert_eq!(if_reg, 0);
 }
 
+#[test]
+fn div_and_internal_divider_rates() {
+    let mut t = Timer::new();
+    let mut if_reg = 0u8;
+    // One step per CPU clock (4194304 Hz): the internal divider follows the
+    // CPU clock and DIV ticks every 256 clocks (16384 Hz).
+    for clocks in 1..=1024u16 {
+        t.step(1, &mut if_reg);
+        assert_eq!(t.internal_divider(), clocks);
+        assert_eq!(t.div(), (clocks / 256) as u8);
+        assert_eq!(t.div(), t.read(0xFF04));
+    }
+    // One second of CPU time is 16384 DIV increments, i.e. 64 wraps.
+    let mut t = Timer::new();
+    let mut wraps = 0;
+    for _ in 0..(4_194_304 / 256) {
+        let before = t.div();
+        t.step(256, &mut if_reg);
+        assert_eq!(t.div(), before.wrapping_add(1));
+        wraps += usize::from(t.div() == 0);
+    }
+    assert_eq!(wraps, 64);
+    // Reads are side-effect free.
+    let div = t.internal_divider();
+    let _ = t.div();
+    let _ = t.read(0xFF04);
+    assert_eq!(t.internal_divider(), div);
+}
+
+#[test]
+fn tap_bit_follows_tac() {
+    let mut t = Timer::new();
+    let mut if_reg = 0u8;
+    for (tac, bit) in [(0x04, 9), (0x05, 3), (0x06, 5), (0x07, 7), (0x01, 3)] {
+        t.write(0xFF07, tac, &mut if_reg);
+        assert_eq!(t.tap_bit(), bit);
+    }
+}
+
 #[test]
 fn div_resets_on_write() {
     let mut t = Timer::new();
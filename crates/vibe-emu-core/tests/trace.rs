@@ -0,0 +1,108 @@
+use vibe_emu_core::{
+    cartridge::Cartridge,
+    gameboy::GameBoy,
+    trace::{MemoryMismatch, TraceEntry, find_divergence_between},
+};
+
+fn counter_machine() -> Box<GameBoy> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x107].copy_from_slice(&[
+        0x21, 0x00, 0xC0, // LD HL,$C000
+        0x3C, // INC A
+        0x77, // LD (HL),A
+        0x18, 0xFC, // JR -4
+    ]);
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+    gb
+}
+
+/// Records the state before each of the next `n` instructions.
+fn record(gb: &mut GameBoy, n: usize) -> Vec<TraceEntry> {
+    let mut trace = Vec::new();
+    for _ in 0..n {
+        trace.push(TraceEntry::capture(gb));
+        assert!(gb.step_over(10_000));
+    }
+    trace
+}
+
+#[test]
+fn parses_gameboy_doctor_and_register_pair_lines() {
+    let doctor = TraceEntry::parse(
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02",
+    )
+    .unwrap();
+    assert_eq!(
+        doctor,
+        TraceEntry {
+            a: 0x01,
+            f: 0xB0,
+            b: 0x00,
+            c: 0x13,
+            d: 0x00,
+            e: 0xD8,
+            h: 0x01,
+            l: 0x4D,
+            sp: 0xFFFE,
+            pc: 0x0100,
+            opcode: Some(0x00),
+        }
+    );
+
+    let pairs = TraceEntry::parse("AF:01B0 BC:0013 DE:00D8 HL:014D SP:FFFE PC:0100").unwrap();
+    assert_eq!(pairs.opcode, None);
+    assert!(pairs.matches(&doctor));
+
+    assert_eq!(TraceEntry::parse(&doctor.to_string()), Some(doctor));
+    assert_eq!(TraceEntry::parse("A:01 F:B0 PC:0100"), None);
+    assert_eq!(
+        TraceEntry::parse("A:XX F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100"),
+        None
+    );
+}
+
+#[test]
+fn reports_first_instruction_off_the_reference_trace() {
+    let mut reference = record(&mut counter_machine(), 20);
+
+    let mut gb = counter_machine();
+    assert_eq!(gb.find_divergence(reference.clone()), None);
+
+    // Before instruction 9 the reference claims A is one higher than it is.
+    for entry in &mut reference[9..] {
+        entry.a = entry.a.wrapping_add(1);
+    }
+    let mut gb = counter_machine();
+    let divergence = gb.find_divergence(reference.clone()).unwrap();
+    assert_eq!(divergence.instruction, 9);
+    assert_eq!(divergence.expected, reference[9]);
+    assert_eq!(divergence.actual.a, reference[9].a.wrapping_sub(1));
+    assert_eq!(divergence.actual.pc, reference[9].pc);
+    assert_eq!(divergence.memory, None);
+    assert_eq!(TraceEntry::capture(&gb), divergence.actual);
+}
+
+#[test]
+fn lockstep_comparison_checks_memory_too() {
+    let mut expected = counter_machine();
+    let mut actual = counter_machine();
+    assert_eq!(
+        find_divergence_between(&mut expected, &mut actual, 50),
+        None
+    );
+
+    actual.mmu.wram[2][0x123] = 0x5A;
+    let divergence = find_divergence_between(&mut expected, &mut actual, 50).unwrap();
+    assert_eq!(divergence.instruction, 0);
+    assert!(divergence.expected.matches(&divergence.actual));
+    assert_eq!(
+        divergence.memory,
+        Some(MemoryMismatch {
+            addr: 0xD123,
+            bank: 2,
+            expected: 0x00,
+            actual: 0x5A,
+        })
+    );
+}
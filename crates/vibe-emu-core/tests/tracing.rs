@@ -0,0 +1,60 @@
+#![cfg(feature = "tracing")]
+
+use std::sync::{Arc, Mutex};
+
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+use vibe_emu_core::{cartridge::Cartridge, gameboy::GameBoy};
+
+/// Records the names of entered spans and the targets of events.
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<&'static str>>>,
+    entered: Arc<Mutex<Vec<&'static str>>>,
+    events: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span.metadata().name());
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        self.events.lock().unwrap().push(event.metadata().target());
+    }
+
+    fn enter(&self, span: &Id) {
+        let name = self.spans.lock().unwrap()[span.into_u64() as usize - 1];
+        self.entered.lock().unwrap().push(name);
+    }
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn run_frame_enters_a_frame_span() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]); // JR -2
+    let mut gb = Box::new(GameBoy::new());
+    gb.mmu.load_cart(Cartridge::load(rom));
+
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        assert!(gb.run_frame());
+    });
+
+    let entered = recorder.entered.lock().unwrap();
+    assert_eq!(entered.iter().filter(|&&name| name == "frame").count(), 1);
+    let events = recorder.events.lock().unwrap();
+    assert!(events.contains(&"vibe_emu_core::ppu"), "{events:?}");
+}
@@ -0,0 +1,578 @@
+#![allow(clippy::missing_safety_doc)]
+
+//! libretro core for vibeEmu.
+//!
+//! Builds as a shared library that RetroArch (or any other libretro
+//! frontend) can load. Video is handed over straight from the PPU
+//! framebuffer in XRGB8888 and audio is drained from the APU's output queue
+//! once per frame.
+//!
+//! Core options:
+//! - `vibeemu_model`: `Auto`, `DMG` or `CGB`. Applied when a game is loaded
+//!   or the core is reset.
+//! - `vibeemu_dmg_palette`: shades used for DMG games. Applied immediately.
+//!
+//! The libretro type definitions live in the public [`ffi`] module, for
+//! frontends written in Rust and for tests.
+
+pub mod ffi;
+
+use core::ffi::c_void;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_uint};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use vibe_emu_core::{
+    audio_queue::AudioConsumer, cartridge::Cartridge, gameboy::GameBoy, input::Buttons,
+};
+
+use ffi::*;
+
+const SCREEN_WIDTH: usize = 160;
+const SCREEN_HEIGHT: usize = 144;
+
+/// Dots in one full LCD frame (154 lines of 456 dots).
+const FRAME_DOTS: u64 = 456 * 154;
+
+/// 4.194304 MHz / 70224 dots per frame.
+const FPS: f64 = 4_194_304.0 / FRAME_DOTS as f64;
+
+const SAMPLE_RATE: u32 = 48_000;
+
+/// Extra room reported by `retro_serialize_size`, since the encoded state
+/// length can vary slightly between frames.
+const STATE_SLACK: usize = 1024;
+
+const OPT_MODEL: &CStr = c"vibeemu_model";
+const OPT_PALETTE: &CStr = c"vibeemu_dmg_palette";
+
+const DMG_GREEN: [u32; 4] = [0x009BBC0F, 0x008BAC0F, 0x00306230, 0x000F380F];
+const DMG_GRAYSCALE: [u32; 4] = [0x00FFFFFF, 0x00AAAAAA, 0x00555555, 0x00000000];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Model {
+    Auto,
+    Dmg,
+    Cgb,
+}
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<retro_environment_t>,
+    video_refresh: Option<retro_video_refresh_t>,
+    audio_sample: Option<retro_audio_sample_t>,
+    audio_sample_batch: Option<retro_audio_sample_batch_t>,
+    input_poll: Option<retro_input_poll_t>,
+    input_state: Option<retro_input_state_t>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+struct Core {
+    gb: Box<GameBoy>,
+    audio: AudioConsumer,
+    samples: Vec<i16>,
+    model: Model,
+    palette: [u32; 4],
+}
+
+impl Core {
+    fn new(cart: Cartridge, model: Model, palette: [u32; 4]) -> Self {
+        let mut gb = Box::new(GameBoy::new_with_mode(Self::cgb_for(model, &cart)));
+        gb.mmu.load_cart(cart);
+        gb.mmu.ppu.set_dmg_palette(palette);
+        let audio = gb.mmu.apu.enable_output(SAMPLE_RATE);
+        Self {
+            gb,
+            audio,
+            samples: Vec::new(),
+            model,
+            palette,
+        }
+    }
+
+    fn cgb_for(model: Model, cart: &Cartridge) -> bool {
+        match model {
+            Model::Auto => cart.cgb,
+            Model::Dmg => false,
+            Model::Cgb => true,
+        }
+    }
+
+    /// Power-cycles the machine, keeping the cartridge (and its RAM).
+    fn restart(&mut self) {
+        if let Some(cart) = self.gb.mmu.cart.take() {
+            *self = Self::new(cart, self.model, self.palette);
+        }
+    }
+
+    fn set_palette(&mut self, palette: [u32; 4]) {
+        self.palette = palette;
+        self.gb.mmu.ppu.set_dmg_palette(palette);
+    }
+
+    /// Runs until the next frame is ready, giving up after two frames' worth
+    /// of cycles (e.g. while the LCD is off).
+    fn run_frame(&mut self) {
+        let gb = &mut *self.gb;
+        gb.mmu.ppu.clear_frame_flag();
+        let deadline = gb.cpu.cycles + 2 * FRAME_DOTS;
+        while !gb.mmu.ppu.frame_ready() && gb.cpu.cycles < deadline {
+            gb.cpu.step(&mut gb.mmu);
+        }
+    }
+
+    fn drain_audio(&mut self) {
+        self.samples.clear();
+        while let Some((l, r)) = self.audio.pop_stereo() {
+            self.samples.push(l);
+            self.samples.push(r);
+        }
+    }
+
+    fn save_ram(&mut self) -> Option<&mut Vec<u8>> {
+        self.gb
+            .mmu
+            .cart
+            .as_mut()
+            .map(|c| &mut c.ram)
+            .filter(|ram| !ram.is_empty())
+    }
+}
+
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn core() -> MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn guard<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+/// Reads a core option through `RETRO_ENVIRONMENT_GET_VARIABLE`.
+fn variable(key: &CStr) -> Option<String> {
+    let env = callbacks().environment?;
+    let mut var = retro_variable {
+        key: key.as_ptr(),
+        value: ptr::null(),
+    };
+    // SAFETY: `var` outlives the call and the frontend fills in `value`
+    // with a string it owns.
+    let ok = unsafe {
+        env(
+            RETRO_ENVIRONMENT_GET_VARIABLE,
+            &mut var as *mut retro_variable as *mut c_void,
+        )
+    };
+    if !ok || var.value.is_null() {
+        return None;
+    }
+    // SAFETY: checked for null above; the frontend returns a C string.
+    Some(
+        unsafe { CStr::from_ptr(var.value) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+fn variables_updated() -> bool {
+    let Some(env) = callbacks().environment else {
+        return false;
+    };
+    let mut updated = false;
+    // SAFETY: `updated` outlives the call.
+    let ok = unsafe {
+        env(
+            RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+            &mut updated as *mut bool as *mut c_void,
+        )
+    };
+    ok && updated
+}
+
+fn model_option() -> Model {
+    match variable(OPT_MODEL).as_deref() {
+        Some("DMG") => Model::Dmg,
+        Some("CGB") => Model::Cgb,
+        _ => Model::Auto,
+    }
+}
+
+fn palette_option() -> [u32; 4] {
+    match variable(OPT_PALETTE).as_deref() {
+        Some("Grayscale") => DMG_GRAYSCALE,
+        _ => DMG_GREEN,
+    }
+}
+
+fn poll_buttons(cbs: &Callbacks) -> Buttons {
+    const MAP: [(c_uint, Buttons); 8] = [
+        (RETRO_DEVICE_ID_JOYPAD_A, Buttons::A),
+        (RETRO_DEVICE_ID_JOYPAD_B, Buttons::B),
+        (RETRO_DEVICE_ID_JOYPAD_SELECT, Buttons::SELECT),
+        (RETRO_DEVICE_ID_JOYPAD_START, Buttons::START),
+        (RETRO_DEVICE_ID_JOYPAD_UP, Buttons::UP),
+        (RETRO_DEVICE_ID_JOYPAD_DOWN, Buttons::DOWN),
+        (RETRO_DEVICE_ID_JOYPAD_LEFT, Buttons::LEFT),
+        (RETRO_DEVICE_ID_JOYPAD_RIGHT, Buttons::RIGHT),
+    ];
+    if let Some(poll) = cbs.input_poll {
+        // SAFETY: frontend callback.
+        unsafe { poll() };
+    }
+    let mut buttons = Buttons::empty();
+    if let Some(state) = cbs.input_state {
+        for (id, button) in MAP {
+            // SAFETY: frontend callback.
+            if unsafe { state(0, RETRO_DEVICE_JOYPAD, 0, id) } != 0 {
+                buttons |= button;
+            }
+        }
+    }
+    buttons
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_set_environment(cb: retro_environment_t) {
+    CALLBACKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .environment = Some(cb);
+    let vars = [
+        retro_variable {
+            key: OPT_MODEL.as_ptr(),
+            value: c"Model (applies on reset); Auto|DMG|CGB".as_ptr(),
+        },
+        retro_variable {
+            key: OPT_PALETTE.as_ptr(),
+            value: c"DMG palette; Green|Grayscale".as_ptr(),
+        },
+        retro_variable {
+            key: ptr::null(),
+            value: ptr::null(),
+        },
+    ];
+    // SAFETY: `vars` is null-terminated and outlives the call; the frontend
+    // copies the strings.
+    unsafe {
+        cb(
+            RETRO_ENVIRONMENT_SET_VARIABLES,
+            vars.as_ptr() as *mut c_void,
+        )
+    };
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_set_video_refresh(cb: retro_video_refresh_t) {
+    CALLBACKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .video_refresh = Some(cb);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_set_audio_sample(cb: retro_audio_sample_t) {
+    CALLBACKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .audio_sample = Some(cb);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_set_audio_sample_batch(cb: retro_audio_sample_batch_t) {
+    CALLBACKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .audio_sample_batch = Some(cb);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_set_input_poll(cb: retro_input_poll_t) {
+    CALLBACKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .input_poll = Some(cb);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_set_input_state(cb: retro_input_state_t) {
+    CALLBACKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .input_state = Some(cb);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_init() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_deinit() {
+    *core() = None;
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut retro_system_info) {
+    if info.is_null() {
+        return;
+    }
+    // SAFETY: checked for null; the frontend passes a writable struct.
+    unsafe {
+        info.write(retro_system_info {
+            library_name: c"vibeEmu".as_ptr(),
+            library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+            valid_extensions: c"gb|gbc|dmg|cgb".as_ptr(),
+            need_fullpath: false,
+            block_extract: false,
+        });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro_system_av_info) {
+    if info.is_null() {
+        return;
+    }
+    // SAFETY: checked for null; the frontend passes a writable struct.
+    unsafe {
+        info.write(retro_system_av_info {
+            geometry: retro_game_geometry {
+                base_width: SCREEN_WIDTH as c_uint,
+                base_height: SCREEN_HEIGHT as c_uint,
+                max_width: SCREEN_WIDTH as c_uint,
+                max_height: SCREEN_HEIGHT as c_uint,
+                aspect_ratio: SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32,
+            },
+            timing: retro_system_timing {
+                fps: FPS,
+                sample_rate: SAMPLE_RATE as f64,
+            },
+        });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_reset() {
+    guard((), || {
+        let model = model_option();
+        if let Some(core) = core().as_mut() {
+            core.model = model;
+            core.restart();
+        }
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_run() {
+    let cbs = callbacks();
+    let palette = variables_updated().then(palette_option);
+    let buttons = poll_buttons(&cbs);
+
+    let mut guard = core();
+    let Some(core) = guard.as_mut() else {
+        return;
+    };
+    let ran = catch_unwind(AssertUnwindSafe(|| {
+        if let Some(palette) = palette {
+            core.set_palette(palette);
+        }
+        let mmu = &mut core.gb.mmu;
+        mmu.input.set_buttons(buttons, &mut mmu.if_reg);
+        core.run_frame();
+        core.drain_audio();
+    }));
+    if ran.is_err() {
+        return;
+    }
+
+    if let Some(video) = cbs.video_refresh {
+        let fb = core.gb.mmu.ppu.framebuffer();
+        // SAFETY: frontend callback; the framebuffer outlives the call.
+        unsafe {
+            video(
+                fb.as_ptr() as *const c_void,
+                SCREEN_WIDTH as c_uint,
+                SCREEN_HEIGHT as c_uint,
+                SCREEN_WIDTH * 4,
+            )
+        };
+    }
+    if let Some(batch) = cbs.audio_sample_batch {
+        let mut rest = &core.samples[..];
+        while rest.len() >= 2 {
+            // SAFETY: frontend callback; `rest` holds whole stereo frames.
+            let taken = unsafe { batch(rest.as_ptr(), rest.len() / 2) };
+            if taken == 0 {
+                break;
+            }
+            rest = &rest[(taken * 2).min(rest.len())..];
+        }
+    } else if let Some(sample) = cbs.audio_sample {
+        for frame in core.samples.chunks_exact(2) {
+            // SAFETY: frontend callback.
+            unsafe { sample(frame[0], frame[1]) };
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_serialize_size() -> usize {
+    guard(0, || {
+        core()
+            .as_ref()
+            .map(|c| 4 + c.gb.save_state().len() + STATE_SLACK)
+            .unwrap_or(0)
+    })
+}
+
+/// States are stored as a little-endian `u32` length followed by the
+/// core's save state, so the zero padding up to `retro_serialize_size` can be
+/// told apart from the state itself.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    // SAFETY: checked for null; the frontend provides `size` writable bytes.
+    let out = unsafe { std::slice::from_raw_parts_mut(data as *mut u8, size) };
+    guard(false, || {
+        let Some(core) = core().as_ref().map(|c| c.gb.save_state()) else {
+            return false;
+        };
+        if out.len() < 4 + core.len() {
+            return false;
+        }
+        out[..4].copy_from_slice(&(core.len() as u32).to_le_bytes());
+        out[4..4 + core.len()].copy_from_slice(&core);
+        out[4 + core.len()..].fill(0);
+        true
+    })
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() || size < 4 {
+        return false;
+    }
+    // SAFETY: checked for null; the frontend provides `size` readable bytes.
+    let input = unsafe { std::slice::from_raw_parts(data as *const u8, size) };
+    guard(false, || {
+        let len = u32::from_le_bytes(input[..4].try_into().unwrap()) as usize;
+        let Some(state) = input[4..].get(..len) else {
+            return false;
+        };
+        core()
+            .as_mut()
+            .is_some_and(|c| c.gb.load_state(state).is_ok())
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_load_game(game: *const retro_game_info) -> bool {
+    if game.is_null() {
+        return false;
+    }
+    // SAFETY: checked for null; the frontend passes a valid game info.
+    let game = unsafe { &*game };
+    if game.data.is_null() || game.size == 0 {
+        return false;
+    }
+    // SAFETY: the frontend provides `size` readable bytes of ROM data.
+    let rom = unsafe { std::slice::from_raw_parts(game.data as *const u8, game.size) };
+
+    if let Some(env) = callbacks().environment {
+        let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+        // SAFETY: `format` outlives the call.
+        let ok = unsafe {
+            env(
+                RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+                &mut format as *mut c_uint as *mut c_void,
+            )
+        };
+        if !ok {
+            return false;
+        }
+    }
+
+    let model = model_option();
+    let palette = palette_option();
+    guard(false, || {
+        let cart = Cartridge::load(rom.to_vec());
+        *core() = Some(Core::new(cart, model, palette));
+        true
+    })
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const retro_game_info,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_unload_game() {
+    *core() = None;
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+/// Only `RETRO_MEMORY_SAVE_RAM` is exposed; it points straight at the
+/// cartridge RAM so the frontend's .srm file loads into and saves from it.
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    if id != RETRO_MEMORY_SAVE_RAM {
+        return ptr::null_mut();
+    }
+    guard(ptr::null_mut(), || {
+        core()
+            .as_mut()
+            .and_then(Core::save_ram)
+            .map_or(ptr::null_mut(), |ram| ram.as_mut_ptr() as *mut c_void)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    if id != RETRO_MEMORY_SAVE_RAM {
+        return 0;
+    }
+    guard(0, || {
+        core()
+            .as_mut()
+            .and_then(Core::save_ram)
+            .map_or(0, |ram| ram.len())
+    })
+}
@@ -0,0 +1,142 @@
+use std::ffi::{CStr, c_void};
+use std::os::raw::c_uint;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use vibe_emu_libretro::ffi::{retro_game_info, retro_system_av_info};
+use vibe_emu_libretro::*;
+
+static VIDEO_FRAMES: AtomicUsize = AtomicUsize::new(0);
+static VIDEO_SIZE_OK: AtomicBool = AtomicBool::new(true);
+static AUDIO_FRAMES: AtomicUsize = AtomicUsize::new(0);
+static INPUT_POLLS: AtomicUsize = AtomicUsize::new(0);
+static PIXEL_FORMAT: AtomicUsize = AtomicUsize::new(usize::MAX);
+static OPTIONS_SET: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match cmd {
+        // SET_PIXEL_FORMAT
+        10 => {
+            PIXEL_FORMAT.store(
+                unsafe { *(data as *const c_uint) } as usize,
+                Ordering::SeqCst,
+            );
+            true
+        }
+        // SET_VARIABLES: expect the model option to be announced.
+        16 => {
+            let key = unsafe { *(data as *const *const std::os::raw::c_char) };
+            let key = unsafe { CStr::from_ptr(key) };
+            OPTIONS_SET.store(key == c"vibeemu_model", Ordering::SeqCst);
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn video_refresh(
+    data: *const c_void,
+    width: c_uint,
+    height: c_uint,
+    pitch: usize,
+) {
+    if data.is_null() || width != 160 || height != 144 || pitch != 160 * 4 {
+        VIDEO_SIZE_OK.store(false, Ordering::SeqCst);
+    }
+    VIDEO_FRAMES.fetch_add(1, Ordering::SeqCst);
+}
+
+unsafe extern "C" fn audio_sample(_left: i16, _right: i16) {}
+
+unsafe extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+    AUDIO_FRAMES.fetch_add(frames, Ordering::SeqCst);
+    frames
+}
+
+unsafe extern "C" fn input_poll() {
+    INPUT_POLLS.fetch_add(1, Ordering::SeqCst);
+}
+
+unsafe extern "C" fn input_state(
+    _port: c_uint,
+    _device: c_uint,
+    _index: c_uint,
+    _id: c_uint,
+) -> i16 {
+    0
+}
+
+/// MBC1+RAM+BATTERY ROM with 8 KiB of RAM that spins forever.
+fn test_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0x18, 0xFE, 0x00]); // NOP; JR -2
+    rom[0x147] = 0x03;
+    rom[0x149] = 0x02;
+    rom
+}
+
+#[test]
+fn load_and_run_frames_through_entry_points() {
+    unsafe {
+        assert_eq!(retro_api_version(), 1);
+        retro_set_environment(environment);
+        retro_set_video_refresh(video_refresh);
+        retro_set_audio_sample(audio_sample);
+        retro_set_audio_sample_batch(audio_sample_batch);
+        retro_set_input_poll(input_poll);
+        retro_set_input_state(input_state);
+        retro_init();
+        assert!(OPTIONS_SET.load(Ordering::SeqCst));
+
+        let mut av = std::mem::zeroed::<retro_system_av_info>();
+        retro_get_system_av_info(&mut av);
+        assert_eq!(av.geometry.base_width, 160);
+        assert_eq!(av.geometry.base_height, 144);
+        assert!((av.timing.fps - 59.7275).abs() < 0.001);
+
+        let rom = test_rom();
+        let game = retro_game_info {
+            path: ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: ptr::null(),
+        };
+        assert!(retro_load_game(&game));
+        assert_eq!(PIXEL_FORMAT.load(Ordering::SeqCst), 1); // XRGB8888
+
+        assert_eq!(retro_get_memory_size(0), 0x2000);
+        assert!(!retro_get_memory_data(0).is_null());
+
+        for _ in 0..3 {
+            retro_run();
+        }
+        assert_eq!(VIDEO_FRAMES.load(Ordering::SeqCst), 3);
+        assert!(VIDEO_SIZE_OK.load(Ordering::SeqCst));
+        assert_eq!(INPUT_POLLS.load(Ordering::SeqCst), 3);
+        // 48 kHz at ~59.73 fps is ~800 frames of audio per video frame.
+        let audio = AUDIO_FRAMES.load(Ordering::SeqCst);
+        assert!((2000..=2800).contains(&audio), "audio frames {audio}");
+
+        // The frontend keeps the save RAM pointer across state loads, so
+        // unserializing must restore RAM in place rather than reallocate.
+        let sram = retro_get_memory_data(0) as *mut u8;
+        *sram.add(0x10) = 0x5A;
+        let size = retro_serialize_size();
+        assert!(size > 0);
+        let mut state = vec![0u8; size];
+        assert!(retro_serialize(state.as_mut_ptr() as *mut c_void, size));
+        retro_run();
+        *sram.add(0x10) = 0xA5;
+        assert!(retro_unserialize(state.as_ptr() as *const c_void, size));
+        assert_eq!(retro_get_memory_data(0) as *mut u8, sram);
+        assert_eq!(*sram.add(0x10), 0x5A);
+
+        retro_reset();
+        retro_run();
+        assert_eq!(VIDEO_FRAMES.load(Ordering::SeqCst), 5);
+
+        retro_unload_game();
+        assert_eq!(retro_get_memory_size(0), 0);
+        retro_deinit();
+    }
+}
@@ -228,10 +228,72 @@ pub enum MobileError {
     InvalidState(&'static str),
 }
 
+/// Millisecond time base for libmobile's `time_latch`/`time_check_ms` timers.
+///
+/// Time only moves forward through [`Self::advance`]; [`Self::sync_to`] turns
+/// an absolute emulated timestamp into such a delta.
+#[derive(Clone, Debug, Default)]
+struct MobileClock {
+    now_ms: u64,
+    latched_ms: [u64; MOBILE_MAX_TIMERS],
+    /// Last timestamp passed to `sync_to`.
+    source_ms: Option<u64>,
+}
+
+#[cfg_attr(not(any(feature = "bundled", feature = "system")), allow(dead_code))]
+impl MobileClock {
+    fn advance(&mut self, delta_ms: u64) {
+        self.now_ms = self.now_ms.saturating_add(delta_ms);
+    }
+
+    /// Advances by the time elapsed since the previous call. A timestamp
+    /// that goes backwards (e.g. after loading a save state) counts as no
+    /// time passing and becomes the new reference point.
+    fn sync_to(&mut self, source_ms: u64) {
+        let delta = self
+            .source_ms
+            .map_or(0, |prev| source_ms.saturating_sub(prev));
+        self.source_ms = Some(source_ms);
+        self.advance(delta);
+    }
+
+    fn latch(&mut self, timer: u32) {
+        if let Some(slot) = self.latched_ms.get_mut(timer as usize) {
+            *slot = self.now_ms;
+        }
+    }
+
+    fn check_ms(&self, timer: u32, ms: u32) -> bool {
+        let Some(&latched) = self.latched_ms.get(timer as usize) else {
+            return false;
+        };
+        self.now_ms.saturating_sub(latched) >= ms as u64
+    }
+}
+
+/// Last phone numbers reported through libmobile's `update_number` callback.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct PhoneNumbers {
+    user: Option<String>,
+    peer: Option<String>,
+}
+
+#[cfg_attr(not(any(feature = "bundled", feature = "system")), allow(dead_code))]
+impl PhoneNumbers {
+    fn update(&mut self, which: MobileNumber, number: Option<&str>) {
+        let slot = match which {
+            MobileNumber::User => &mut self.user,
+            MobileNumber::Peer => &mut self.peer,
+        };
+        *slot = number.map(str::to_owned);
+    }
+}
+
 /// Safe-ish wrapper around a libmobile instance.
 ///
 /// Design notes:
-/// - `poll()` must be called regularly (<= ~100ms) to drive libmobile.
+/// - `poll()` or `poll_emulated()` must be called regularly (<= ~100ms) to
+///   drive libmobile.
 /// - `transfer_byte()` is intended to be called via the emulator serial unit.
 pub struct MobileAdapter {
     #[cfg(any(feature = "bundled", feature = "system"))]
@@ -320,7 +382,7 @@ impl MobileAdapter {
                 .inner
                 .as_mut()
                 .ok_or(MobileError::InvalidState("missing inner"))?;
-            inner.emulated_time_ms = inner.emulated_time_ms.saturating_add(delta_ms as u64);
+            inner.clock.advance(delta_ms as u64);
             inner.poll();
             Ok(())
         }
@@ -332,6 +394,55 @@ impl MobileAdapter {
         }
     }
 
+    /// Drives libmobile from the emulated clock instead of host time.
+    ///
+    /// `emulated_ms` is the machine's absolute emulated time, e.g.
+    /// `GameBoy::elapsed_ns() / 1_000_000`. The adapter's timers advance by
+    /// the difference from the previous call, so they stay in step with the
+    /// game under fast-forward, slow motion and pauses. The first call only
+    /// establishes the reference point, and a timestamp earlier than the
+    /// previous one (after a reset or state load) is treated as no time
+    /// passing. Use either this or [`Self::poll`] on a given adapter, not both.
+    pub fn poll_emulated(&mut self, emulated_ms: u64) -> Result<(), MobileError> {
+        #[cfg(any(feature = "bundled", feature = "system"))]
+        {
+            let inner = self
+                .inner
+                .as_mut()
+                .ok_or(MobileError::InvalidState("missing inner"))?;
+            inner.clock.sync_to(emulated_ms);
+            inner.poll();
+            Ok(())
+        }
+
+        #[cfg(not(any(feature = "bundled", feature = "system")))]
+        {
+            let _ = emulated_ms;
+            Err(MobileError::Unavailable)
+        }
+    }
+
+    /// Returns the `(user, peer)` phone numbers last reported by libmobile.
+    ///
+    /// The user number is the one assigned to this adapter; the peer number
+    /// is the last one dialed or received. Both update as soon as libmobile
+    /// reports them (during [`Self::poll`] or a serial transfer). Always
+    /// `(None, None)` without a backend.
+    pub fn phone_numbers(&self) -> (Option<String>, Option<String>) {
+        #[cfg(any(feature = "bundled", feature = "system"))]
+        {
+            match self.inner.as_ref() {
+                Some(inner) => (inner.numbers.user.clone(), inner.numbers.peer.clone()),
+                None => (None, None),
+            }
+        }
+
+        #[cfg(not(any(feature = "bundled", feature = "system")))]
+        {
+            (None, None)
+        }
+    }
+
     /// Transfers a single serial byte through libmobile.
     ///
     /// This is intended to be called from the emulator serial unit.
@@ -405,8 +516,8 @@ impl LinkPort for MobileLinkPort {
 struct MobileAdapterInner {
     adapter: *mut sys::mobile_adapter,
     host: Box<dyn MobileHost>,
-    emulated_time_ms: u64,
-    timers_latched_ms: [u64; MOBILE_MAX_TIMERS],
+    clock: MobileClock,
+    numbers: PhoneNumbers,
     serial_enabled: bool,
     mode_32bit: bool,
 }
@@ -423,8 +534,8 @@ impl MobileAdapterInner {
         let mut inner = Box::new(Self {
             adapter: std::ptr::null_mut(),
             host,
-            emulated_time_ms: 0,
-            timers_latched_ms: [0; MOBILE_MAX_TIMERS],
+            clock: MobileClock::default(),
+            numbers: PhoneNumbers::default(),
             serial_enabled: false,
             mode_32bit: false,
         });
@@ -638,10 +749,7 @@ unsafe extern "C" fn cb_time_latch(user: *mut c_void, timer: u32) {
     }
 
     let inner = with_user(user);
-    let Some(slot) = inner.timers_latched_ms.get_mut(timer as usize) else {
-        return;
-    };
-    *slot = inner.emulated_time_ms;
+    inner.clock.latch(timer);
 }
 
 #[cfg(any(feature = "bundled", feature = "system"))]
@@ -651,10 +759,7 @@ unsafe extern "C" fn cb_time_check_ms(user: *mut c_void, timer: u32, ms: u32) ->
     }
 
     let inner = with_user(user);
-    let Some(&latched) = inner.timers_latched_ms.get(timer as usize) else {
-        return false;
-    };
-    inner.emulated_time_ms.saturating_sub(latched) >= ms as u64
+    inner.clock.check_ms(timer, ms)
 }
 
 #[cfg(any(feature = "bundled", feature = "system"))]
@@ -867,6 +972,7 @@ unsafe extern "C" fn cb_update_number(
     };
 
     if number.is_null() {
+        inner.numbers.update(which, None);
         inner.host.update_number(which, None);
         return;
     }
@@ -874,5 +980,146 @@ unsafe extern "C" fn cb_update_number(
     let Ok(s) = CStr::from_ptr(number).to_str() else {
         return;
     };
+    inner.numbers.update(which, Some(s));
     inner.host.update_number(which, Some(s));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emulated_deltas_drive_timer_expiry() {
+        let mut clock = MobileClock::default();
+        // Emulated time starts well after power-on; the first sync is only
+        // the reference point.
+        clock.sync_to(5_000);
+        clock.latch(0);
+        assert!(!clock.check_ms(0, 1));
+
+        // Three ~16.74 ms frames of emulated time.
+        for ms in [5_016, 5_033, 5_050] {
+            clock.sync_to(ms);
+        }
+        assert!(clock.check_ms(0, 50));
+        assert!(!clock.check_ms(0, 51));
+
+        // Timers are independent.
+        clock.latch(1);
+        clock.sync_to(5_100);
+        assert!(clock.check_ms(0, 100));
+        assert!(clock.check_ms(1, 50));
+        assert!(!clock.check_ms(1, 51));
+
+        // Out-of-range timers never report expiry.
+        clock.latch(MOBILE_MAX_TIMERS as u32);
+        assert!(!clock.check_ms(MOBILE_MAX_TIMERS as u32, 0));
+    }
+
+    #[test]
+    fn phone_numbers_track_latest_update() {
+        let mut numbers = PhoneNumbers::default();
+        numbers.update(MobileNumber::User, Some("0311234567"));
+        numbers.update(MobileNumber::Peer, Some("0771234567"));
+        numbers.update(MobileNumber::Peer, Some("0779999999"));
+        assert_eq!(numbers.user.as_deref(), Some("0311234567"));
+        assert_eq!(numbers.peer.as_deref(), Some("0779999999"));
+
+        numbers.update(MobileNumber::Peer, None);
+        assert_eq!(numbers.user.as_deref(), Some("0311234567"));
+        assert_eq!(numbers.peer, None);
+    }
+
+    #[cfg(any(feature = "bundled", feature = "system"))]
+    #[test]
+    fn update_number_callback_caches_and_forwards() {
+        use std::sync::{Arc, Mutex};
+
+        struct FakeHost(Arc<Mutex<Vec<(MobileNumber, Option<String>)>>>);
+
+        impl MobileHost for FakeHost {
+            fn update_number(&mut self, which: MobileNumber, number: Option<&str>) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((which, number.map(str::to_owned)));
+            }
+            fn config_read(&mut self, dest: &mut [u8], _offset: usize) -> bool {
+                dest.fill(0);
+                true
+            }
+            fn config_write(&mut self, _src: &[u8], _offset: usize) -> bool {
+                true
+            }
+            fn sock_open(&mut self, _: u32, _: MobileSockType, _: &MobileAddr, _: u16) -> bool {
+                false
+            }
+            fn sock_close(&mut self, _conn: u32) {}
+            fn sock_connect(&mut self, _conn: u32, _addr: &MobileAddr) -> i32 {
+                -1
+            }
+            fn sock_listen(&mut self, _conn: u32) -> bool {
+                false
+            }
+            fn sock_accept(&mut self, _conn: u32) -> bool {
+                false
+            }
+            fn sock_send(&mut self, _: u32, _: &[u8], _: Option<&MobileAddr>) -> i32 {
+                -1
+            }
+            fn sock_recv(
+                &mut self,
+                _: u32,
+                _: Option<&mut [u8]>,
+                _: Option<&mut MobileAddr>,
+            ) -> i32 {
+                -1
+            }
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let adapter = MobileAdapter::new(Box::new(FakeHost(Arc::clone(&seen)))).unwrap();
+        let adapter = Arc::new(Mutex::new(adapter));
+        assert_eq!(adapter.lock().unwrap().phone_numbers(), (None, None));
+
+        // Fire the callback the way libmobile does, with the user pointer it
+        // was registered with.
+        let user = {
+            let mut guard = adapter.lock().unwrap();
+            let inner = guard.inner.as_mut().unwrap();
+            (&mut **inner) as *mut MobileAdapterInner as *mut c_void
+        };
+        let number = std::ffi::CString::new("0311234567").unwrap();
+        unsafe {
+            cb_update_number(
+                user,
+                sys::mobile_number::MOBILE_NUMBER_USER,
+                number.as_ptr(),
+            );
+        }
+
+        assert_eq!(
+            adapter.lock().unwrap().phone_numbers(),
+            (Some("0311234567".to_string()), None)
+        );
+        assert_eq!(
+            seen.lock().unwrap().as_slice(),
+            &[(MobileNumber::User, Some("0311234567".to_string()))]
+        );
+    }
+
+    #[test]
+    fn emulated_time_going_backwards_does_not_stall_timers() {
+        let mut clock = MobileClock::default();
+        clock.sync_to(10_000);
+        clock.latch(0);
+        clock.sync_to(10_040);
+
+        // A state load rewinds emulated time.
+        clock.sync_to(2_000);
+        assert!(clock.check_ms(0, 40));
+        assert!(!clock.check_ms(0, 41));
+        clock.sync_to(2_060);
+        assert!(clock.check_ms(0, 100));
+    }
+}
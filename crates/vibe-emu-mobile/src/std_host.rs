@@ -21,6 +21,24 @@ enum TcpState {
     Listening,
 }
 
+/// Size of the adapter's own settings at the start of the config blob.
+const ADAPTER_CONFIG_SIZE: usize = 0xC0;
+
+/// Whether the adapter settings in `config` are unconfigured (all zero) or
+/// carry the "MA" magic and a matching checksum: the big-endian sum of the
+/// preceding bytes, stored in the last two.
+fn adapter_config_is_valid(config: &[u8]) -> bool {
+    let settings = &config[..ADAPTER_CONFIG_SIZE];
+    if settings.iter().all(|&b| b == 0) {
+        return true;
+    }
+    let (body, checksum) = settings.split_at(ADAPTER_CONFIG_SIZE - 2);
+    let sum = body
+        .iter()
+        .fold(0u16, |sum, &b| sum.wrapping_add(u16::from(b)));
+    body.starts_with(b"MA") && checksum == sum.to_be_bytes()
+}
+
 /// A non-blocking std host implementation using `socket2`.
 ///
 /// This is intended for desktop/headless use. Android wrappers can implement
@@ -34,17 +52,49 @@ pub struct StdMobileHost {
 
 impl StdMobileHost {
     pub fn new(config_path: PathBuf) -> Self {
-        let mut config = [0u8; MOBILE_CONFIG_SIZE];
-        if let Ok(bytes) = fs::read(&config_path) {
-            let len = bytes.len().min(config.len());
-            config[..len].copy_from_slice(&bytes[..len]);
-        }
-
-        Self {
+        let mut host = Self {
             config_path,
-            config,
+            config: [0u8; MOBILE_CONFIG_SIZE],
             conns: [ConnState::Empty, ConnState::Empty],
+        };
+
+        // Never hand libmobile a bad blob: it would persist the garbage
+        // back into the adapter's settings.
+        match fs::read(&host.config_path) {
+            Ok(bytes) if bytes.len() != MOBILE_CONFIG_SIZE => {
+                log::warn!(
+                    "mobile config {} is {} bytes (expected {MOBILE_CONFIG_SIZE}); resetting to defaults",
+                    host.config_path.display(),
+                    bytes.len()
+                );
+                host.save_config();
+            }
+            Ok(bytes) if !adapter_config_is_valid(&bytes) => {
+                log::warn!(
+                    "mobile config {} fails its checksum; resetting to defaults",
+                    host.config_path.display()
+                );
+                host.save_config();
+            }
+            Ok(bytes) => host.config.copy_from_slice(&bytes),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                log::warn!(
+                    "failed to read mobile config {}: {e}; using defaults",
+                    host.config_path.display()
+                );
+            }
         }
+
+        host
+    }
+
+    /// Replaces the config blob with zeroed defaults and writes it to disk.
+    ///
+    /// libmobile treats a zeroed blob as an unconfigured adapter.
+    pub fn reset_config(&mut self) {
+        self.config = [0u8; MOBILE_CONFIG_SIZE];
+        self.save_config();
     }
 
     fn save_config(&self) {
@@ -413,3 +463,85 @@ mod libc {
     #[cfg(not(windows))]
     pub const EISCONN: i32 = 106;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vibeemu-{}-{name}.config", std::process::id()))
+    }
+
+    /// A configured blob with the "MA" magic and a correct checksum.
+    fn configured_blob() -> Vec<u8> {
+        let mut blob = vec![0x42; MOBILE_CONFIG_SIZE];
+        blob[..2].copy_from_slice(b"MA");
+        let sum = blob[..ADAPTER_CONFIG_SIZE - 2]
+            .iter()
+            .fold(0u16, |sum, &b| sum.wrapping_add(u16::from(b)));
+        blob[ADAPTER_CONFIG_SIZE - 2..ADAPTER_CONFIG_SIZE].copy_from_slice(&sum.to_be_bytes());
+        blob
+    }
+
+    #[test]
+    fn truncated_config_reads_as_zeroed_defaults() {
+        let path = temp_config("truncated");
+        fs::write(&path, [0xAB; 0x80]).unwrap();
+
+        let mut host = StdMobileHost::new(path.clone());
+        let mut buf = [0xFFu8; MOBILE_CONFIG_SIZE];
+        assert!(host.config_read(&mut buf, 0));
+        assert!(buf.iter().all(|&b| b == 0));
+
+        // The bad file is replaced by a full-size default blob.
+        let on_disk = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(on_disk, vec![0u8; MOBILE_CONFIG_SIZE]);
+    }
+
+    #[test]
+    fn oversized_config_is_rejected() {
+        let path = temp_config("oversized");
+        fs::write(&path, vec![0x5A; MOBILE_CONFIG_SIZE + 1]).unwrap();
+
+        let mut host = StdMobileHost::new(path.clone());
+        let _ = fs::remove_file(&path);
+        let mut buf = [0xFFu8; 0x10];
+        assert!(host.config_read(&mut buf, 0x100));
+        assert_eq!(buf, [0; 0x10]);
+    }
+
+    #[test]
+    fn valid_config_loads_and_reset_clears_it() {
+        let path = temp_config("valid");
+        fs::write(&path, configured_blob()).unwrap();
+
+        let mut host = StdMobileHost::new(path.clone());
+        let mut buf = [0u8; 4];
+        assert!(host.config_read(&mut buf, 0));
+        assert_eq!(buf, *b"MA\x42\x42");
+
+        host.reset_config();
+        assert!(host.config_read(&mut buf, 0));
+        assert_eq!(buf, [0; 4]);
+        let on_disk = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(on_disk, vec![0u8; MOBILE_CONFIG_SIZE]);
+    }
+
+    #[test]
+    fn config_with_bad_checksum_is_reset() {
+        let path = temp_config("checksum");
+        let mut blob = configured_blob();
+        blob[0x10] ^= 0x01;
+        fs::write(&path, blob).unwrap();
+
+        let mut host = StdMobileHost::new(path.clone());
+        let mut buf = [0xFFu8; 4];
+        assert!(host.config_read(&mut buf, 0));
+        assert_eq!(buf, [0; 4]);
+        let on_disk = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(on_disk, vec![0u8; MOBILE_CONFIG_SIZE]);
+    }
+}
@@ -0,0 +1,25 @@
+#![no_std]
+
+//! Exercises the `no_std` subset of vibe-emu-core.
+//!
+//! Nothing here is meant to be called; it only has to compile. CI builds
+//! this crate for a bare-metal target so any `std` use that creeps into the
+//! core's `no_std` build fails there.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use vibe_emu_core::{cartridge::Cartridge, gameboy::GameBoy, input::Buttons};
+
+/// Loads `rom`, runs a frame with `buttons` held and returns the frame, the
+/// audio generated during it and a save state taken afterwards.
+pub fn run_one_frame(rom: Vec<u8>, buttons: Buttons) -> (Vec<u32>, Vec<(i16, i16)>, Vec<u8>) {
+    let mut gb = GameBoy::new_with_mode(rom.get(0x143).is_some_and(|&b| b & 0x80 != 0));
+    gb.mmu.load_cart(Cartridge::load(rom));
+    let mmu = &mut gb.mmu;
+    mmu.input.set_buttons(buttons, &mut mmu.if_reg);
+    let (frame, samples) = gb.run_frame_av();
+    let frame = frame.to_vec();
+    let state = gb.save_state();
+    (frame, samples, state)
+}
@@ -1,46 +1,102 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use log::{error, info, warn};
+use log::{error, info};
+use std::fmt;
 use std::sync::{
     Arc,
     atomic::{AtomicBool, Ordering},
 };
 use vibe_emu_core::apu::Apu;
 
+/// Why the audio output could not be opened.
+#[derive(Debug)]
+pub enum AudioError {
+    /// The host has no default output device.
+    NoDevice,
+    /// The device would not report a default output configuration.
+    UnsupportedConfig(cpal::DefaultStreamConfigError),
+    /// The device's default sample format is not one the mixer can write.
+    UnsupportedFormat(cpal::SampleFormat),
+    /// The output stream could not be built.
+    BuildStream(cpal::BuildStreamError),
+    /// The stream was built but would not start playing.
+    Play(cpal::PlayStreamError),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::NoDevice => write!(f, "no audio output device available"),
+            AudioError::UnsupportedConfig(e) => write!(f, "no supported output config: {e}"),
+            AudioError::UnsupportedFormat(format) => {
+                write!(f, "unsupported sample format: {format:?}")
+            }
+            AudioError::BuildStream(e) => write!(f, "failed to build audio output stream: {e}"),
+            AudioError::Play(e) => write!(f, "failed to start audio stream: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AudioError::UnsupportedConfig(e) => Some(e),
+            AudioError::BuildStream(e) => Some(e),
+            AudioError::Play(e) => Some(e),
+            AudioError::NoDevice | AudioError::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+/// Names of the host's output devices, for picking one in the options.
+/// Devices that fail to describe themselves are skipped.
+pub fn list_output_devices() -> Vec<String> {
+    let host = cpal::default_host();
+    match host.output_devices() {
+        Ok(devices) => devices.filter_map(|device| device_label(&device)).collect(),
+        Err(e) => {
+            error!("failed to enumerate audio output devices: {e}");
+            Vec::new()
+        }
+    }
+}
+
+fn device_label(device: &cpal::Device) -> Option<String> {
+    let description = device.description().ok()?;
+    Some(match description.manufacturer() {
+        Some(manufacturer) => format!("{} ({manufacturer})", description.name()),
+        None => description.name().to_string(),
+    })
+}
+
 /// Build an audio stream using `cpal` and hook it up to the APU sample queue.
 ///
 /// If `autoplay` is true the stream starts immediately; otherwise the caller is
 /// responsible for invoking [`cpal::Stream::play`] once any warm-up work
-/// completes. Returns the configured stream on success.
+/// completes. Returns the configured stream, or why the default output device
+/// could not be used.
 pub fn start_stream(
     apu: &mut Apu,
     autoplay: bool,
     sound_enabled: Arc<AtomicBool>,
-) -> Option<cpal::Stream> {
-    let host = cpal::default_host();
-    let device = match host.default_output_device() {
-        Some(device) => device,
-        None => {
-            error!("no default audio output device available");
-            return None;
-        }
-    };
-    let device_name = match device.description() {
-        Ok(description) => match description.manufacturer() {
-            Some(manufacturer) => {
-                format!("{} ({manufacturer})", description.name())
-            }
-            None => description.name().to_string(),
-        },
-        Err(_) => "<unknown>".to_string(),
-    };
-    let supported = match device.default_output_config() {
-        Ok(c) => c,
-        Err(e) => {
-            error!("no supported output config: {e}");
-            return None;
-        }
-    };
+) -> Result<cpal::Stream, AudioError> {
+    let device = cpal::default_host().default_output_device();
+    start_stream_on(device, apu, autoplay, sound_enabled)
+}
+
+/// [`start_stream`] on a given device; `None` stands for a host without one.
+fn start_stream_on(
+    device: Option<cpal::Device>,
+    apu: &mut Apu,
+    autoplay: bool,
+    sound_enabled: Arc<AtomicBool>,
+) -> Result<cpal::Stream, AudioError> {
+    let device = device.ok_or(AudioError::NoDevice)?;
+    let device_name = device_label(&device).unwrap_or_else(|| "<unknown>".to_string());
+    let supported = device
+        .default_output_config()
+        .map_err(AudioError::UnsupportedConfig)?;
     let sample_format = supported.sample_format();
+    check_sample_format(sample_format)?;
     let config: cpal::StreamConfig = supported.into();
     let consumer = apu.enable_output(config.sample_rate);
     let channels = config.channels as usize;
@@ -114,30 +170,48 @@ pub fn start_stream(
             err_fn,
             None,
         ),
-        other => {
-            error!("Unsupported sample format: {other:?}");
-            return None;
-        }
-    };
-
-    let stream = match stream {
-        Ok(stream) => stream,
-        Err(e) => {
-            error!("Failed to build audio output stream: {e}");
-            return None;
-        }
-    };
+        other => return Err(AudioError::UnsupportedFormat(other)),
+    }
+    .map_err(AudioError::BuildStream)?;
 
     if autoplay {
-        if let Err(e) = stream.play() {
-            warn!("Failed to start audio stream: {e}");
-            None
-        } else {
-            info!("Audio stream started");
-            Some(stream)
-        }
+        stream.play().map_err(AudioError::Play)?;
+        info!("Audio stream started");
     } else {
         info!("Audio stream prepared (playback deferred)");
-        Some(stream)
+    }
+    Ok(stream)
+}
+
+/// Rejects sample formats the output callbacks cannot write, before the APU
+/// output is enabled for them.
+fn check_sample_format(format: cpal::SampleFormat) -> Result<(), AudioError> {
+    match format {
+        cpal::SampleFormat::I16 | cpal::SampleFormat::U16 | cpal::SampleFormat::F32 => Ok(()),
+        other => Err(AudioError::UnsupportedFormat(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_device_is_reported_without_touching_the_apu() {
+        let mut apu = Apu::new();
+        let sound_enabled = Arc::new(AtomicBool::new(true));
+        let err = start_stream_on(None, &mut apu, true, sound_enabled).unwrap_err();
+        assert!(matches!(err, AudioError::NoDevice));
+        assert_eq!(err.to_string(), "no audio output device available");
+    }
+
+    #[test]
+    fn only_mixer_sample_formats_are_accepted() {
+        assert!(check_sample_format(cpal::SampleFormat::F32).is_ok());
+        assert!(check_sample_format(cpal::SampleFormat::I16).is_ok());
+        assert!(matches!(
+            check_sample_format(cpal::SampleFormat::U8),
+            Err(AudioError::UnsupportedFormat(cpal::SampleFormat::U8))
+        ));
     }
 }
@@ -25,12 +25,20 @@ pub fn default_keybinds_path() -> PathBuf {
     PathBuf::from("keybinds.toml")
 }
 
+/// Number of quick-save state slots with hotkeys.
+pub const STATE_SLOT_COUNT: usize = 4;
+
 #[derive(Clone)]
 pub struct KeyBindings {
     joypad: HashMap<Key, u8>,
     pause: Key,
     fast_forward: Key,
     quit: Key,
+    perf_overlay: Key,
+    /// Quick-save slot keys; the key saves, Shift + the key loads.
+    state_slots: [Key; STATE_SLOT_COUNT],
+    /// Reloads the restart point; Shift + the key sets it.
+    restart: Key,
 }
 
 impl Default for KeyBindings {
@@ -56,6 +64,9 @@ impl KeyBindings {
             pause: Key::P,
             fast_forward: Key::Space,
             quit: Key::Escape,
+            perf_overlay: Key::F12,
+            state_slots: [Key::F1, Key::F2, Key::F3, Key::F4],
+            restart: Key::F5,
         }
     }
 
@@ -132,6 +143,12 @@ impl KeyBindings {
                 "pause" => bindings.pause = code,
                 "fast_forward" => bindings.fast_forward = code,
                 "quit" => bindings.quit = code,
+                "perf_overlay" => bindings.perf_overlay = code,
+                "state_slot1" => bindings.state_slots[0] = code,
+                "state_slot2" => bindings.state_slots[1] = code,
+                "state_slot3" => bindings.state_slots[2] = code,
+                "state_slot4" => bindings.state_slots[3] = code,
+                "restart" => bindings.restart = code,
                 other => warn!(
                     "Ignoring unknown keybind name '{other}' in {}:{}",
                     path.display(),
@@ -159,6 +176,21 @@ impl KeyBindings {
         self.quit
     }
 
+    /// Key that shows or hides the performance overlay.
+    pub fn perf_overlay_key(&self) -> Key {
+        self.perf_overlay
+    }
+
+    /// Key for quick-save slot `index` (0-based).
+    pub fn state_slot_key(&self, index: usize) -> Key {
+        self.state_slots[index]
+    }
+
+    /// Key that reloads the restart point; Shift + the key sets it.
+    pub fn restart_key(&self) -> Key {
+        self.restart
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (String, &Key)> {
         let joypad_names = [
             (0x01, "right"),
@@ -199,6 +231,9 @@ impl KeyBindings {
             crate::RebindTarget::Pause => self.pause = key,
             crate::RebindTarget::FastForward => self.fast_forward = key,
             crate::RebindTarget::Quit => self.quit = key,
+            crate::RebindTarget::PerfOverlay => self.perf_overlay = key,
+            crate::RebindTarget::StateSlot(index) => self.state_slots[index] = key,
+            crate::RebindTarget::Restart => self.restart = key,
         }
     }
 
@@ -235,6 +270,14 @@ impl KeyBindings {
             key_to_string(self.fast_forward)
         ));
         lines.push(format!("quit = {}", key_to_string(self.quit)));
+        lines.push(format!(
+            "perf_overlay = {}",
+            key_to_string(self.perf_overlay)
+        ));
+        for (i, key) in self.state_slots.iter().enumerate() {
+            lines.push(format!("state_slot{} = {}", i + 1, key_to_string(*key)));
+        }
+        lines.push(format!("restart = {}", key_to_string(self.restart)));
 
         let content = lines.join("\n");
         std::fs::write(path, content)?;
@@ -307,6 +350,18 @@ fn parse_key(raw: &str) -> Option<Key> {
         "Space" => Some(Key::Space),
         "Tab" => Some(Key::Tab),
         "Backspace" => Some(Key::Backspace),
+        "F1" => Some(Key::F1),
+        "F2" => Some(Key::F2),
+        "F3" => Some(Key::F3),
+        "F4" => Some(Key::F4),
+        "F5" => Some(Key::F5),
+        "F6" => Some(Key::F6),
+        "F7" => Some(Key::F7),
+        "F8" => Some(Key::F8),
+        "F9" => Some(Key::F9),
+        "F10" => Some(Key::F10),
+        "F11" => Some(Key::F11),
+        "F12" => Some(Key::F12),
         _ => {
             if s.len() == 1 {
                 let c = s.chars().next()?;
@@ -4,8 +4,10 @@
 mod audio;
 mod keybinds;
 mod network_link;
+mod perf_stats;
 mod ui;
 mod ui_config;
+mod video_capture;
 
 use clap::{Parser, ValueEnum};
 use cpal::traits::StreamTrait;
@@ -19,8 +21,14 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, RwLock, mpsc};
 use std::thread;
 use std::time::{Duration, Instant};
-use vibe_emu_core::serial::{LinkPort, NullLinkPort};
-use vibe_emu_core::{cartridge::Cartridge, gameboy::GameBoy, hardware::CgbRevision, mmu::Mmu};
+use vibe_emu_core::serial::LinkPort;
+use vibe_emu_core::{
+    cartridge::{Cartridge, MAX_RAM_SLOT, ModeMismatch},
+    gameboy::{GameBoy, RunBudget, RunOutcome},
+    hardware::CgbRevision,
+    mmu::Mmu,
+    savestate::SlotLoadError,
+};
 use vibe_emu_mobile::{
     MobileAdapter, MobileAdapterDevice, MobileAddr, MobileConfig, MobileHost, MobileLinkPort,
     MobileNumber, MobileSockType, StdMobileHost,
@@ -30,8 +38,9 @@ use vibe_emu_mobile::{
 use gilrs::{Axis as GamepadAxis, Button as GamepadButton, GamepadId, Gilrs};
 
 use crossbeam_channel as cb;
-use keybinds::KeyBindings;
+use keybinds::{KeyBindings, STATE_SLOT_COUNT};
 use network_link::{LinkCommand, LinkEvent, NetworkLinkPort};
+use perf_stats::{FrameStats, PerfStats};
 use ui::debugger::{BreakpointSpec, DebuggerPauseReason, DebuggerState};
 use ui::snapshot::UiSnapshot;
 use ui_config::{EmulationMode, SerialPeripheralKind, UiConfig, WindowSize};
@@ -43,7 +52,9 @@ const MENU_BAR_HEIGHT: f32 = 24.0;
 const STATUS_BAR_HEIGHT: f32 = 24.0;
 const GB_FPS: f64 = 59.7275;
 const FRAME_TIME: Duration = Duration::from_nanos((1e9_f64 / GB_FPS) as u64);
+const FRAME_DOTS: u64 = 456 * 154;
 const FF_MULT: f32 = 4.0;
+const TOAST_DURATION: Duration = Duration::from_secs(2);
 
 #[cfg(not(target_os = "android"))]
 struct GamepadInput {
@@ -202,6 +213,10 @@ struct Args {
     #[arg(long, conflicts_with = "dmg")]
     cgb: bool,
 
+    /// Refuse to start a CGB-only ROM in DMG mode instead of warning.
+    #[arg(long)]
+    strict_mode: bool,
+
     #[arg(long)]
     bootrom: Option<std::path::PathBuf>,
 
@@ -223,6 +238,18 @@ struct Args {
     #[arg(long)]
     cycles: Option<u64>,
 
+    /// Flush changed battery saves every SECONDS of emulated time.
+    #[arg(long, value_name = "SECONDS")]
+    autosave: Option<u64>,
+
+    /// Jitter interrupt timing with this seed to find timing-fragile code.
+    #[arg(long, value_name = "SEED")]
+    timing_fuzz: Option<u64>,
+
+    /// Write every headless frame to an APNG (or raw RGB24 for .rgb/.raw).
+    #[arg(long, requires = "headless")]
+    record_video: Option<std::path::PathBuf>,
+
     #[arg(long)]
     mobile: bool,
 
@@ -375,8 +402,15 @@ enum EmuCommand {
     SetPaused(bool),
     SetSpeed(Speed),
     UpdateInput(u8),
+    SetLowInputLatency(bool),
+    SetPerfStats(bool),
+    SetDebuggerOpen(bool),
     UpdateBreakpoints(Vec<ui::debugger::BreakpointSpec>),
     SetRegister { reg: RegisterId, value: u16 },
+    SaveStateSlot { rom: std::path::PathBuf, slot: u8 },
+    LoadStateSlot { rom: std::path::PathBuf, slot: u8 },
+    SetRestartPoint,
+    Restart,
     Shutdown,
 }
 
@@ -391,8 +425,21 @@ enum RegisterId {
 }
 
 enum EmuEvent {
-    Frame { frame: Vec<u32>, frame_index: u64 },
-    BreakpointHit { bank: u8, addr: u16 },
+    Frame {
+        frame: Vec<u32>,
+        frame_index: u64,
+    },
+    BreakpointHit {
+        bank: u8,
+        addr: u16,
+    },
+    Stuck {
+        addr: u16,
+        locked: bool,
+        paused: bool,
+    },
+    Toast(String),
+    PerfStats(PerfStats),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -401,6 +448,9 @@ enum RebindTarget {
     Pause,
     FastForward,
     Quit,
+    PerfOverlay,
+    StateSlot(usize),
+    Restart,
 }
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
@@ -528,6 +578,217 @@ struct EmuThreadChannels {
     frame_pool_rx: cb::Receiver<Vec<u32>>,
 }
 
+/// Opens audio output for `apu`, returning the stream or why it failed.
+fn open_audio(
+    apu: &mut vibe_emu_core::apu::Apu,
+    sound_enabled: &Arc<AtomicBool>,
+) -> (Option<cpal::Stream>, Option<String>) {
+    match audio::start_stream(apu, true, sound_enabled.clone()) {
+        Ok(stream) => (Some(stream), None),
+        Err(e) => {
+            error!("Audio disabled: {e}");
+            (None, Some(e.to_string()))
+        }
+    }
+}
+
+/// Sleeps until `next_frame` and schedules the frame after it.
+fn wait_for_frame(next_frame: &mut Instant, speed: Speed) {
+    let frame_duration = Duration::from_secs_f64(1.0 / (GB_FPS * speed.factor as f64));
+
+    if !speed.fast {
+        let now = Instant::now();
+        if now < *next_frame {
+            std::thread::sleep(*next_frame - now);
+        }
+
+        // Advance next_frame by the frame duration, keeping a fixed schedule.
+        // Allow catching up from small delays (up to ~3 frames behind) naturally,
+        // only reset if we fall too far behind to prevent runaway catch-up.
+        *next_frame += frame_duration;
+        let max_behind = frame_duration * 3;
+        if *next_frame + max_behind < Instant::now() {
+            *next_frame = Instant::now();
+        }
+    } else {
+        // Fast forward: run as fast as possible, reset timing when we exit fast mode
+        *next_frame = Instant::now() + frame_duration;
+    }
+}
+
+/// The check every ROM load goes through before starting `cart` in
+/// `cgb_mode`. Under `--strict-mode` a mismatch refuses the load; otherwise
+/// the core warns about it when the cartridge is inserted.
+fn strict_mode_check(cart: &Cartridge, cgb_mode: bool, strict: bool) -> Result<(), ModeMismatch> {
+    match cart.check_mode(cgb_mode) {
+        Some(mismatch) if strict => Err(mismatch),
+        _ => Ok(()),
+    }
+}
+
+/// The ROM bank a breakpoint at `pc` is keyed on: 0 for the fixed bank,
+/// the mapped bank for $4000-$7FFF and 0xFF outside ROM.
+fn breakpoint_bank(gb: &GameBoy, pc: u16) -> u8 {
+    if (0x4000..=0x7FFF).contains(&pc) {
+        gb.mmu
+            .cart
+            .as_ref()
+            .map(|c| c.current_rom_bank().min(0xFF) as u8)
+            .unwrap_or(1)
+    } else if pc < 0x4000 {
+        0
+    } else {
+        0xFF
+    }
+}
+
+/// Runs the machine for one frame and returns the breakpoint that stopped it
+/// early, if any.
+///
+/// A CPU stuck in a halt loop or locked up still leaves the LCD running, so
+/// the frame is stepped on to its end. `on_stuck` is called with the PC and
+/// whether it locked up the first time the CPU is found stuck, and returning
+/// `true` from it stops the frame there. With the LCD off a stuck CPU never
+/// finishes a frame, so the frame ends after a frame's worth of dots.
+fn run_frame(
+    gb: &mut GameBoy,
+    mut before_step: impl FnMut(&mut GameBoy) -> bool,
+    mut on_stuck: impl FnMut(u16, bool) -> bool,
+) -> Option<u16> {
+    let start = gb.cpu.cycles;
+    let mut stuck = false;
+    loop {
+        let (pc, locked) = match gb.run_with_hook(RunBudget::Frames(1), &mut before_step) {
+            RunOutcome::Breakpoint(pc) => return Some(pc),
+            RunOutcome::HaltLoop(pc) => (pc, false),
+            RunOutcome::Locked(pc) => (pc, true),
+            _ => return None,
+        };
+        if !stuck {
+            stuck = true;
+            if on_stuck(pc, locked) {
+                return None;
+            }
+        }
+        if gb.mmu.ppu.frame_ready() || gb.cpu.cycles - start >= FRAME_DOTS {
+            return None;
+        }
+    }
+}
+
+/// Keeps the link cable in step with the CPU over one emulated frame.
+///
+/// [`Self::sync`] accounts for the time run since the previous call, so it
+/// is called before every instruction and once more after the frame.
+struct LinkPacer<'a> {
+    last_dot_div: u16,
+    bgb_timestamp: &'a mut u32,
+    external_clock_pending: &'a network_link::ExternalClockPending,
+    slave_ready: &'a network_link::SlaveReadyState,
+    link_timestamp: &'a std::sync::atomic::AtomicU32,
+    link_doublespeed: &'a AtomicBool,
+    ext_clock_active: bool,
+    ext_clock_bits_remaining: u8,
+    ext_clock_cycle_accum: u32,
+    ext_clock_dot_cycles_per_bit: u32,
+}
+
+impl<'a> LinkPacer<'a> {
+    fn new(
+        gb: &GameBoy,
+        bgb_timestamp: &'a mut u32,
+        external_clock_pending: &'a network_link::ExternalClockPending,
+        slave_ready: &'a network_link::SlaveReadyState,
+        link_timestamp: &'a std::sync::atomic::AtomicU32,
+        link_doublespeed: &'a AtomicBool,
+    ) -> Self {
+        Self {
+            last_dot_div: gb.mmu.dot_div,
+            bgb_timestamp,
+            external_clock_pending,
+            slave_ready,
+            link_timestamp,
+            link_doublespeed,
+            ext_clock_active: false,
+            ext_clock_bits_remaining: 0,
+            ext_clock_cycle_accum: 0,
+            ext_clock_dot_cycles_per_bit: 512,
+        }
+    }
+
+    fn sync(&mut self, gb: &mut GameBoy) {
+        let GameBoy { cpu, mmu, .. } = gb;
+        let dot_div_delta = mmu.dot_div.wrapping_sub(self.last_dot_div) as u32;
+        self.last_dot_div = mmu.dot_div;
+
+        // Update cumulative timestamp for BGB protocol (2 MiHz = dot_div / 2)
+        // BGB expects a 31-bit timestamp that grows continuously, not a wrapped 16-bit value
+        *self.bgb_timestamp = self.bgb_timestamp.wrapping_add(dot_div_delta / 2);
+        let bgb_ts = *self.bgb_timestamp & 0x7FFF_FFFF;
+        self.link_timestamp.store(bgb_ts, Ordering::Release);
+        self.link_doublespeed
+            .store(cpu.double_speed, Ordering::Release);
+
+        // Update slave ready state for the network thread
+        if let Some(byte) = mmu.serial.pending_external_clock_outgoing() {
+            self.slave_ready.set_ready(byte);
+        } else {
+            self.slave_ready.set_not_ready();
+        }
+
+        // Poll for pending external clock transfers (link cable slave mode)
+        // Check if the network received a byte from master AND the game has set up
+        // an external clock transfer
+        let ext_pending = self.external_clock_pending.is_pending();
+        let has_transfer = mmu.serial.has_external_clock_transfer_pending();
+        if !(ext_pending && has_transfer) {
+            // Reset pacing state if the pending flag clears or the transfer disappears.
+            self.reset_ext_clock();
+            return;
+        }
+
+        if !self.ext_clock_active {
+            self.ext_clock_active = true;
+            self.ext_clock_bits_remaining = 8;
+            self.ext_clock_cycle_accum = 0;
+            self.ext_clock_dot_cycles_per_bit =
+                self.external_clock_pending.dot_cycles_per_bit().max(1);
+            log::debug!(
+                "External clock transfer armed; pacing clock pulses at {} dot cycles/bit",
+                self.ext_clock_dot_cycles_per_bit
+            );
+        }
+
+        self.ext_clock_cycle_accum = self.ext_clock_cycle_accum.saturating_add(dot_div_delta);
+
+        while self.ext_clock_bits_remaining != 0
+            && self.ext_clock_cycle_accum >= self.ext_clock_dot_cycles_per_bit
+        {
+            self.ext_clock_cycle_accum -= self.ext_clock_dot_cycles_per_bit;
+            mmu.serial.external_clock_pulse(1, &mut mmu.if_reg);
+
+            if !mmu.serial.has_external_clock_transfer_pending() {
+                break;
+            }
+
+            self.ext_clock_bits_remaining = self.ext_clock_bits_remaining.saturating_sub(1);
+        }
+
+        if !mmu.serial.has_external_clock_transfer_pending() || self.ext_clock_bits_remaining == 0 {
+            self.reset_ext_clock();
+            self.external_clock_pending.clear();
+            log::debug!("External clock transfer completed");
+        }
+    }
+
+    fn reset_ext_clock(&mut self) {
+        self.ext_clock_active = false;
+        self.ext_clock_bits_remaining = 0;
+        self.ext_clock_cycle_accum = 0;
+        self.ext_clock_dot_cycles_per_bit = 512;
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn run_emulator_thread(
     gb: Arc<Mutex<GameBoy>>,
@@ -549,17 +810,37 @@ fn run_emulator_thread(
     } = channels;
 
     let mut paused = initial_paused;
-    let mut frame_count = 0u64;
     let mut next_frame = Instant::now() + FRAME_TIME;
     let mut breakpoints: HashSet<(u8, u16)> = HashSet::new();
     let mut cumulative_bgb_timestamp: u32 = 0;
+    // False while stopped mid-frame at a breakpoint; states are only taken
+    // and restored between whole frames.
+    let mut at_frame_boundary = true;
+    let mut low_input_latency = false;
+    // Only collected while the performance overlay is shown.
+    let mut perf: Option<FrameStats> = None;
+    // A stuck CPU only pauses emulation while the debugger is open.
+    let mut debugger_open = false;
+    // The last loop reported as stuck, so each one is reported only once.
+    let mut reported_stuck: Option<u16> = None;
 
     loop {
+        // In low-latency mode the frame wait comes first, so input sent while
+        // waiting is applied before the frame's CPU steps rather than after.
+        let mut waited = false;
+        if low_input_latency && !paused {
+            wait_for_frame(&mut next_frame, speed);
+            waited = true;
+        }
+
         while let Ok(cmd) = rx.try_recv() {
             match cmd {
                 EmuCommand::SetPaused(p) => {
                     paused = p;
                     next_frame = Instant::now() + FRAME_TIME;
+                    if let Some(stats) = &mut perf {
+                        stats.restart(Instant::now());
+                    }
                 }
                 EmuCommand::SetSpeed(s) => {
                     speed = s;
@@ -569,6 +850,15 @@ fn run_emulator_thread(
                         gb.mmu.input.set_state(input);
                     }
                 }
+                EmuCommand::SetLowInputLatency(enabled) => {
+                    low_input_latency = enabled;
+                }
+                EmuCommand::SetPerfStats(enabled) => {
+                    perf = enabled.then(|| FrameStats::new(Instant::now()));
+                }
+                EmuCommand::SetDebuggerOpen(open) => {
+                    debugger_open = open;
+                }
                 EmuCommand::UpdateBreakpoints(bps) => {
                     breakpoints.clear();
                     for bp in bps {
@@ -599,6 +889,71 @@ fn run_emulator_thread(
                         }
                     }
                 }
+                EmuCommand::SaveStateSlot { rom, slot } => {
+                    let msg = if !at_frame_boundary {
+                        "Can't save state while stopped at a breakpoint".to_string()
+                    } else if let Ok(gb) = gb.lock() {
+                        match gb.save_state_slot(&rom, slot) {
+                            Ok(()) => format!("Saved to slot {slot}"),
+                            Err(e) => {
+                                log::error!("Failed to save state slot {slot}: {e}");
+                                format!("Failed to save slot {slot}")
+                            }
+                        }
+                    } else {
+                        continue;
+                    };
+                    // Dropped if the UI is behind on frames rather than
+                    // blocking emulation on a toast.
+                    let _ = frame_tx.try_send(EmuEvent::Toast(msg));
+                }
+                EmuCommand::LoadStateSlot { rom, slot } => {
+                    let msg = if let Ok(mut gb) = gb.lock() {
+                        match gb.load_state_slot(&rom, slot) {
+                            Ok(()) => {
+                                at_frame_boundary = true;
+                                format!("Loaded slot {slot}")
+                            }
+                            Err(SlotLoadError::Empty(_)) => format!("Slot {slot} is empty"),
+                            Err(e) => {
+                                log::error!("Failed to load state slot {slot}: {e}");
+                                format!("Failed to load slot {slot}")
+                            }
+                        }
+                    } else {
+                        continue;
+                    };
+                    let _ = frame_tx.try_send(EmuEvent::Toast(msg));
+                }
+                EmuCommand::SetRestartPoint => {
+                    let msg = if !at_frame_boundary {
+                        "Can't set a restart point while stopped at a breakpoint"
+                    } else if let Ok(mut gb) = gb.lock() {
+                        gb.set_restart_point();
+                        "Restart point set"
+                    } else {
+                        continue;
+                    };
+                    let _ = frame_tx.try_send(EmuEvent::Toast(msg.to_string()));
+                }
+                EmuCommand::Restart => {
+                    let msg = if let Ok(mut gb) = gb.lock() {
+                        match gb.restart() {
+                            Ok(true) => {
+                                at_frame_boundary = true;
+                                "Restarted"
+                            }
+                            Ok(false) => "No restart point set",
+                            Err(e) => {
+                                log::error!("Failed to load restart point: {e}");
+                                "Failed to load restart point"
+                            }
+                        }
+                    } else {
+                        continue;
+                    };
+                    let _ = frame_tx.try_send(EmuEvent::Toast(msg.to_string()));
+                }
                 EmuCommand::Shutdown => {
                     return;
                 }
@@ -610,25 +965,8 @@ fn run_emulator_thread(
             continue;
         }
 
-        let frame_duration = Duration::from_secs_f64(1.0 / (GB_FPS * speed.factor as f64));
-
-        if !speed.fast {
-            let now = Instant::now();
-            if now < next_frame {
-                std::thread::sleep(next_frame - now);
-            }
-
-            // Advance next_frame by the frame duration, keeping a fixed schedule.
-            // Allow catching up from small delays (up to ~3 frames behind) naturally,
-            // only reset if we fall too far behind to prevent runaway catch-up.
-            next_frame += frame_duration;
-            let max_behind = frame_duration * 3;
-            if next_frame + max_behind < Instant::now() {
-                next_frame = Instant::now();
-            }
-        } else {
-            // Fast forward: run as fast as possible, reset timing when we exit fast mode
-            next_frame = Instant::now() + frame_duration;
+        if !waited {
+            wait_for_frame(&mut next_frame, speed);
         }
 
         let mut frame_buf = frame_pool_rx
@@ -636,120 +974,91 @@ fn run_emulator_thread(
             .unwrap_or_else(|_| vec![0u32; 160 * 144]);
 
         let mut bp_hit: Option<(u8, u16)> = None;
+        let mut frame_index = 0u64;
+        let frame_start = perf.is_some().then(Instant::now);
+        let mut audio_fill = 0.0;
 
-        if let Ok(mut gb) = gb.lock() {
-            let GameBoy { cpu, mmu, .. } = &mut *gb;
-            mmu.ppu.clear_frame_flag();
-
-            let mut ext_clock_active = false;
-            let mut ext_clock_bits_remaining: u8 = 0;
-            let mut ext_clock_cycle_accum: u32 = 0;
-            let mut ext_clock_dot_cycles_per_bit: u32 = 512;
-
-            while !mmu.ppu.frame_ready() {
-                // Check breakpoints before executing
-                if !breakpoints.is_empty() {
-                    let pc = cpu.pc;
-                    let bank = if (0x4000..=0x7FFF).contains(&pc) {
-                        mmu.cart
-                            .as_ref()
-                            .map(|c| c.current_rom_bank().min(0xFF) as u8)
-                            .unwrap_or(1)
-                    } else if pc < 0x4000 {
-                        0
-                    } else {
-                        0xFF
-                    };
-
-                    if breakpoints.contains(&(bank, pc)) || breakpoints.contains(&(0xFF, pc)) {
-                        bp_hit = Some((bank, pc));
-                        break;
-                    }
-                }
-
-                let prev_dot_div = mmu.dot_div;
-                cpu.step(mmu);
-                let dot_div_delta = mmu.dot_div.wrapping_sub(prev_dot_div) as u32;
-
-                // Update cumulative timestamp for BGB protocol (2 MiHz = dot_div / 2)
-                // BGB expects a 31-bit timestamp that grows continuously, not a wrapped 16-bit value
-                cumulative_bgb_timestamp = cumulative_bgb_timestamp.wrapping_add(dot_div_delta / 2);
-                let bgb_ts = cumulative_bgb_timestamp & 0x7FFF_FFFF;
-                link_timestamp.store(bgb_ts, std::sync::atomic::Ordering::Release);
-                link_doublespeed.store(cpu.double_speed, std::sync::atomic::Ordering::Release);
-
-                // Update slave ready state for the network thread
-                if let Some(byte) = mmu.serial.pending_external_clock_outgoing() {
-                    slave_ready.set_ready(byte);
-                } else {
-                    slave_ready.set_not_ready();
-                }
-
-                // Poll for pending external clock transfers (link cable slave mode)
-                // Check if the network received a byte from master AND the game has set up
-                // an external clock transfer
-                let ext_pending = external_clock_pending.is_pending();
-                let has_transfer = mmu.serial.has_external_clock_transfer_pending();
-                if ext_pending && has_transfer {
-                    if !ext_clock_active {
-                        ext_clock_active = true;
-                        ext_clock_bits_remaining = 8;
-                        ext_clock_cycle_accum = 0;
-                        ext_clock_dot_cycles_per_bit =
-                            external_clock_pending.dot_cycles_per_bit().max(1);
-                        log::debug!(
-                            "External clock transfer armed; pacing clock pulses at {} dot cycles/bit",
-                            ext_clock_dot_cycles_per_bit
-                        );
-                    }
-
-                    ext_clock_cycle_accum = ext_clock_cycle_accum.saturating_add(dot_div_delta);
-
-                    while ext_clock_bits_remaining != 0
-                        && ext_clock_cycle_accum >= ext_clock_dot_cycles_per_bit
-                    {
-                        ext_clock_cycle_accum -= ext_clock_dot_cycles_per_bit;
-                        mmu.serial.external_clock_pulse(1, &mut mmu.if_reg);
-
-                        if !mmu.serial.has_external_clock_transfer_pending() {
-                            break;
-                        }
+        let mut stuck: Option<(u16, bool)> = None;
 
-                        ext_clock_bits_remaining = ext_clock_bits_remaining.saturating_sub(1);
+        if let Ok(mut gb) = gb.lock() {
+            let mut link = LinkPacer::new(
+                &gb,
+                &mut cumulative_bgb_timestamp,
+                &external_clock_pending,
+                &slave_ready,
+                &link_timestamp,
+                &link_doublespeed,
+            );
+            let mut still_stuck = false;
+            let bp = run_frame(
+                &mut gb,
+                |gb| {
+                    link.sync(gb);
+                    !breakpoints.is_empty() && {
+                        let pc = gb.cpu.pc;
+                        let bank = breakpoint_bank(gb, pc);
+                        breakpoints.contains(&(bank, pc)) || breakpoints.contains(&(0xFF, pc))
                     }
-
-                    if !mmu.serial.has_external_clock_transfer_pending()
-                        || ext_clock_bits_remaining == 0
-                    {
-                        ext_clock_active = false;
-                        ext_clock_bits_remaining = 0;
-                        ext_clock_cycle_accum = 0;
-                        ext_clock_dot_cycles_per_bit = 512;
-                        external_clock_pending.clear();
-                        log::debug!("External clock transfer completed");
+                },
+                |pc, locked| {
+                    still_stuck = true;
+                    if reported_stuck == Some(pc) {
+                        return false;
                     }
-                } else {
-                    // Reset pacing state if the pending flag clears or the transfer disappears.
-                    ext_clock_active = false;
-                    ext_clock_bits_remaining = 0;
-                    ext_clock_cycle_accum = 0;
-                    ext_clock_dot_cycles_per_bit = 512;
-                }
+                    reported_stuck = Some(pc);
+                    stuck = Some((pc, locked));
+                    debugger_open
+                },
+            );
+            link.sync(&mut gb);
+            if let Some(pc) = bp {
+                bp_hit = Some((breakpoint_bank(&gb, pc), pc));
+            } else if !still_stuck {
+                reported_stuck = None;
             }
+
+            let mmu = &gb.mmu;
             frame_buf.copy_from_slice(mmu.ppu.framebuffer());
+            frame_index = mmu.ppu.frame_count();
+            if perf.is_some() {
+                audio_fill =
+                    mmu.apu.queued_frames() as f32 / mmu.apu.max_queue_capacity().max(1) as f32;
+            }
         }
 
+        let stuck_pause = stuck.is_some() && debugger_open;
+        at_frame_boundary = bp_hit.is_none() && !stuck_pause;
         if let Some((bank, addr)) = bp_hit {
             paused = true;
             let _ = frame_tx.try_send(EmuEvent::BreakpointHit { bank, addr });
             continue;
         }
+        if let Some((addr, locked)) = stuck {
+            paused = stuck_pause;
+            let _ = frame_tx.try_send(EmuEvent::Stuck {
+                addr,
+                locked,
+                paused,
+            });
+            if paused {
+                continue;
+            }
+        }
 
-        frame_count += 1;
-        let _ = frame_tx.try_send(EmuEvent::Frame {
+        let sent = frame_tx.try_send(EmuEvent::Frame {
             frame: frame_buf,
-            frame_index: frame_count,
+            frame_index,
         });
+
+        if let (Some(stats), Some(start)) = (&mut perf, frame_start) {
+            stats.record_frame(start.elapsed());
+            if sent.is_err() {
+                stats.record_dropped();
+            }
+            if let Some(summary) = stats.report(Instant::now(), audio_fill) {
+                let _ = frame_tx.try_send(EmuEvent::PerfStats(summary));
+            }
+        }
     }
 }
 
@@ -759,6 +1068,11 @@ struct VibeEmuApp {
     frame_rx: cb::Receiver<EmuEvent>,
     frame_pool_tx: cb::Sender<Vec<u32>>,
     _audio_stream: Option<cpal::Stream>,
+    /// Why the last attempt to open audio output failed, shown in the options.
+    audio_error: Option<String>,
+    /// Output device names, listed when "Output devices" is opened in the
+    /// options rather than on every repaint.
+    output_devices: Option<Vec<String>>,
 
     sound_enabled: Arc<AtomicBool>,
 
@@ -780,9 +1094,14 @@ struct VibeEmuApp {
     show_debugger: bool,
     show_vram_viewer: bool,
     show_options: bool,
+    show_perf_overlay: bool,
+    /// Latest summary from the emulator thread while the overlay is shown.
+    perf_stats: Option<PerfStats>,
 
     // Options window state
     emulation_mode: EmulationMode,
+    /// Refuse ROM loads that fail [`strict_mode_check`] (`--strict-mode`).
+    strict_mode: bool,
     dmg_bootrom_path: String,
     cgb_bootrom_path: String,
     selected_window_scale: usize,
@@ -849,6 +1168,9 @@ struct VibeEmuApp {
     open_emulation_submenu: Option<EmulationSubmenu>,
     emulation_submenu_anchor: egui::Pos2,
 
+    /// Short on-screen message (e.g. "Saved to slot 1") and when it was shown.
+    toast: Option<(String, Instant)>,
+
     // Status bar state
     last_fps_update: std::time::Instant,
     frame_count_since_update: u64,
@@ -867,6 +1189,7 @@ impl VibeEmuApp {
         keybinds: KeyBindings,
         keybinds_path: std::path::PathBuf,
         emulation_mode: EmulationMode,
+        strict_mode: bool,
         ui_config_path: std::path::PathBuf,
         ui_config: UiConfig,
         external_clock_pending: Arc<network_link::ExternalClockPending>,
@@ -882,10 +1205,10 @@ impl VibeEmuApp {
 
         let sound_enabled = Arc::new(AtomicBool::new(true));
 
-        let audio_stream = if let Ok(mut gb_lock) = gb.lock() {
-            audio::start_stream(&mut gb_lock.mmu.apu, true, sound_enabled.clone())
+        let (audio_stream, audio_error) = if let Ok(mut gb_lock) = gb.lock() {
+            open_audio(&mut gb_lock.mmu.apu, &sound_enabled)
         } else {
-            None
+            (None, None)
         };
 
         let mut app = Self {
@@ -894,6 +1217,8 @@ impl VibeEmuApp {
             frame_rx,
             frame_pool_tx,
             _audio_stream: audio_stream,
+            audio_error: audio_error.clone(),
+            output_devices: None,
 
             sound_enabled,
 
@@ -913,7 +1238,10 @@ impl VibeEmuApp {
             show_debugger: false,
             show_vram_viewer: false,
             show_options: false,
+            show_perf_overlay: false,
+            perf_stats: None,
             emulation_mode,
+            strict_mode,
             dmg_bootrom_path: String::new(),
             cgb_bootrom_path: String::new(),
             selected_window_scale: (DEFAULT_WINDOW_SCALE - 1) as usize,
@@ -963,12 +1291,16 @@ impl VibeEmuApp {
             link_port: "5000".to_string(),
             open_emulation_submenu: None,
             emulation_submenu_anchor: egui::Pos2::ZERO,
+            toast: audio_error.map(|err| (format!("No audio: {err}"), Instant::now())),
             last_fps_update: std::time::Instant::now(),
             frame_count_since_update: 0,
             current_fps: 0.0,
         };
 
         app.apply_persisted_serial_settings();
+        if app.ui_config.low_input_latency {
+            let _ = app.emu_tx.send(EmuCommand::SetLowInputLatency(true));
+        }
 
         // Load symbols for ROM if one was provided at startup
         if let Some(ref path) = app.current_rom_path {
@@ -1230,6 +1562,7 @@ impl VibeEmuApp {
 
         let mut new_state = 0xFFu8;
         let mut new_fast_forward = false;
+        let mut toggle_perf_overlay = false;
 
         ctx.input(|i| {
             for (action, key) in self.keybinds.iter() {
@@ -1249,6 +1582,31 @@ impl VibeEmuApp {
             }
 
             new_fast_forward = i.key_down(self.keybinds.fast_forward_key());
+            toggle_perf_overlay = i.key_pressed(self.keybinds.perf_overlay_key());
+
+            if let Some(rom) = &self.current_rom_path {
+                for index in 0..STATE_SLOT_COUNT {
+                    if !i.key_pressed(self.keybinds.state_slot_key(index)) {
+                        continue;
+                    }
+                    let rom = rom.clone();
+                    let slot = index as u8 + 1;
+                    let cmd = if i.modifiers.shift {
+                        EmuCommand::LoadStateSlot { rom, slot }
+                    } else {
+                        EmuCommand::SaveStateSlot { rom, slot }
+                    };
+                    let _ = self.emu_tx.send(cmd);
+                }
+            }
+            if i.key_pressed(self.keybinds.restart_key()) {
+                let cmd = if i.modifiers.shift {
+                    EmuCommand::SetRestartPoint
+                } else {
+                    EmuCommand::Restart
+                };
+                let _ = self.emu_tx.send(cmd);
+            }
         });
 
         #[cfg(not(target_os = "android"))]
@@ -1258,6 +1616,10 @@ impl VibeEmuApp {
             new_fast_forward |= pad_ff;
         }
 
+        if toggle_perf_overlay {
+            self.set_perf_overlay(!self.show_perf_overlay);
+        }
+
         if new_state != self.joypad_state {
             self.joypad_state = new_state;
             let _ = self.emu_tx.send(EmuCommand::UpdateInput(new_state));
@@ -1291,6 +1653,35 @@ impl VibeEmuApp {
                     }
                     self.debugger_state.request_scroll_to_pc();
                 }
+                EmuEvent::Stuck {
+                    addr,
+                    locked,
+                    paused,
+                } => {
+                    let msg = if locked {
+                        format!("CPU locked up on an undefined opcode at ${addr:04X}")
+                    } else {
+                        format!("CPU stuck at ${addr:04X} with no interrupt to wake it")
+                    };
+                    warn!("{msg}");
+                    self.toast = Some((msg, Instant::now()));
+                    if paused {
+                        self.paused = true;
+                        if let Ok(mut gb) = self.gb.lock() {
+                            self.debugger_snapshot = Some(UiSnapshot::from_gb(&mut gb, true));
+                        }
+                        self.debugger_state.request_scroll_to_pc();
+                    }
+                }
+                EmuEvent::Toast(msg) => {
+                    info!("{msg}");
+                    self.toast = Some((msg, Instant::now()));
+                }
+                EmuEvent::PerfStats(stats) => {
+                    if self.show_perf_overlay {
+                        self.perf_stats = Some(stats);
+                    }
+                }
             }
         }
 
@@ -1324,7 +1715,7 @@ impl VibeEmuApp {
                                 Arc::clone(&self.link_doublespeed),
                             );
                             if let Ok(mut gb) = self.gb.lock() {
-                                gb.mmu.serial.connect(Box::new(link_port));
+                                gb.attach_serial(Box::new(link_port));
                             }
                         }
                     }
@@ -1332,7 +1723,7 @@ impl VibeEmuApp {
                         info!("Link cable disconnected");
                         self.link_cable_state = LinkCableState::Disconnected;
                         if let Ok(mut gb) = self.gb.lock() {
-                            gb.mmu.serial.connect(Box::new(NullLinkPort::default()));
+                            gb.detach_serial();
                         }
                     }
                     LinkEvent::RemotePaused => {
@@ -1369,7 +1760,7 @@ impl VibeEmuApp {
         self.mobile_adapter = None;
 
         if let Ok(mut gb) = self.gb.lock() {
-            gb.mmu.serial.connect(Box::new(NullLinkPort::default()));
+            gb.detach_serial();
         }
     }
 
@@ -1484,7 +1875,7 @@ impl VibeEmuApp {
                     let link_port = MobileLinkPort::new(Arc::clone(&adapter));
                     self.mobile_adapter = Some(adapter);
                     if let Ok(mut gb) = self.gb.lock() {
-                        gb.mmu.serial.connect(Box::new(link_port));
+                        gb.attach_serial(Box::new(link_port));
                     }
                 }
             }
@@ -1529,12 +1920,24 @@ impl VibeEmuApp {
                     "Loading ROM: {} (CGB header: {}, mode: {:?} → cgb_mode: {})",
                     cart.title, cart.cgb, self.emulation_mode, cgb_mode
                 );
+                if let Err(mismatch) = strict_mode_check(&cart, cgb_mode, self.strict_mode) {
+                    let msg = format!("Refusing to load {}: {mismatch}", cart.title);
+                    error!("{msg}");
+                    self.toast = Some((msg, Instant::now()));
+                    return;
+                }
                 if let Ok(mut gb) = self.gb.lock() {
                     gb.mmu.save_cart_ram();
+                    let autosave = gb.autosave_interval();
+                    let timing_fuzz = gb.timing_fuzz();
+                    let accuracy = gb.accuracy();
                     *gb = GameBoy::new_with_mode(cgb_mode);
+                    gb.set_autosave_interval(autosave);
+                    gb.set_timing_fuzz(timing_fuzz);
+                    gb.set_accuracy(accuracy);
                     gb.mmu.load_cart(cart);
-                    self._audio_stream =
-                        audio::start_stream(&mut gb.mmu.apu, true, self.sound_enabled.clone());
+                    (self._audio_stream, self.audio_error) =
+                        open_audio(&mut gb.mmu.apu, &self.sound_enabled);
                 }
                 self.current_rom_path = Some(path.clone());
                 self.debugger_state.load_symbols_for_rom_path(Some(&path));
@@ -1548,6 +1951,127 @@ impl VibeEmuApp {
         }
     }
 
+    fn save_ram_slot(&mut self, slot: u8) {
+        if let Ok(gb) = self.gb.lock() {
+            match gb.mmu.save_cart_ram_slot(slot) {
+                Ok(()) => info!("Saved battery RAM to slot {slot}"),
+                Err(e) => error!("Failed to save battery slot {slot}: {e}"),
+            }
+        }
+    }
+
+    fn load_ram_slot(&mut self, slot: u8) {
+        if let Ok(mut gb) = self.gb.lock() {
+            match gb.mmu.load_cart_ram_slot(slot) {
+                Ok(()) => info!("Loaded battery RAM from slot {slot}"),
+                Err(e) => error!("Failed to load battery slot {slot}: {e}"),
+            }
+        }
+    }
+
+    fn set_debugger_open(&mut self, show: bool) {
+        self.show_debugger = show;
+        let _ = self.emu_tx.send(EmuCommand::SetDebuggerOpen(show));
+    }
+
+    fn set_perf_overlay(&mut self, show: bool) {
+        self.show_perf_overlay = show;
+        self.perf_stats = None;
+        let _ = self.emu_tx.send(EmuCommand::SetPerfStats(show));
+    }
+
+    /// Draws frame timing, frame rates and audio queue fill in the top-right
+    /// corner of the game image.
+    fn draw_perf_overlay(&self, ui: &egui::Ui, rect: egui::Rect) {
+        if !self.show_perf_overlay {
+            return;
+        }
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let lines = match &self.perf_stats {
+            Some(stats) => vec![
+                (
+                    format!("Emulated {:.1} FPS", stats.emulated_fps),
+                    egui::Color32::WHITE,
+                ),
+                (
+                    format!("Displayed {:.1} FPS", self.current_fps),
+                    egui::Color32::WHITE,
+                ),
+                (
+                    format!(
+                        "Frame {:.2} ms avg, {:.2} max",
+                        ms(stats.avg_frame_time),
+                        ms(stats.max_frame_time)
+                    ),
+                    if stats.max_frame_time > FRAME_TIME {
+                        egui::Color32::LIGHT_RED
+                    } else {
+                        egui::Color32::WHITE
+                    },
+                ),
+                (
+                    format!("Dropped {} frames", stats.dropped_frames),
+                    egui::Color32::WHITE,
+                ),
+                (
+                    format!("Audio queue {:.0}%", stats.audio_fill * 100.0),
+                    egui::Color32::WHITE,
+                ),
+            ],
+            None => vec![("Collecting stats...".to_string(), egui::Color32::WHITE)],
+        };
+
+        let painter = ui.painter_at(rect);
+        let font = egui::FontId::monospace(11.0);
+        let line_height = 13.0;
+        let width = lines
+            .iter()
+            .map(|(text, _)| {
+                painter
+                    .layout_no_wrap(text.clone(), font.clone(), egui::Color32::WHITE)
+                    .size()
+                    .x
+            })
+            .fold(0.0, f32::max);
+        let bg = egui::Rect::from_min_size(
+            rect.right_top() + egui::vec2(-width - 12.0, 0.0),
+            egui::vec2(width + 12.0, lines.len() as f32 * line_height + 8.0),
+        );
+        painter.rect_filled(bg, 0.0, egui::Color32::from_black_alpha(160));
+        for (i, (text, color)) in lines.into_iter().enumerate() {
+            painter.text(
+                bg.left_top() + egui::vec2(6.0, 4.0 + i as f32 * line_height),
+                egui::Align2::LEFT_TOP,
+                text,
+                font.clone(),
+                color,
+            );
+        }
+        ui.ctx().request_repaint_after(perf_stats::REPORT_INTERVAL);
+    }
+
+    fn draw_toast(&mut self, ui: &egui::Ui, rect: egui::Rect) {
+        let Some((msg, shown_at)) = &self.toast else {
+            return;
+        };
+        if shown_at.elapsed() >= TOAST_DURATION {
+            self.toast = None;
+            return;
+        }
+        let painter = ui.painter_at(rect);
+        let pos = rect.left_top() + egui::vec2(6.0, 6.0);
+        let font = egui::FontId::proportional(14.0);
+        painter.text(
+            pos + egui::vec2(1.0, 1.0),
+            egui::Align2::LEFT_TOP,
+            msg,
+            font.clone(),
+            egui::Color32::BLACK,
+        );
+        painter.text(pos, egui::Align2::LEFT_TOP, msg, font, egui::Color32::WHITE);
+        ui.ctx().request_repaint_after(TOAST_DURATION);
+    }
+
     fn handle_file_drop(&mut self, ctx: &egui::Context) {
         let dropped_paths: Vec<std::path::PathBuf> = ctx.input(|i| {
             i.raw
@@ -1591,6 +2115,26 @@ impl eframe::App for VibeEmuApp {
                         ui.close();
                     }
                     ui.separator();
+                    let has_rom_loaded = self.current_rom_path.is_some();
+                    ui.add_enabled_ui(has_rom_loaded, |ui| {
+                        ui.menu_button("Save Battery Slot", |ui| {
+                            for slot in 1..=MAX_RAM_SLOT {
+                                if ui.button(format!("Slot {slot}")).clicked() {
+                                    self.save_ram_slot(slot);
+                                    ui.close();
+                                }
+                            }
+                        });
+                        ui.menu_button("Load Battery Slot", |ui| {
+                            for slot in 1..=MAX_RAM_SLOT {
+                                if ui.button(format!("Slot {slot}")).clicked() {
+                                    self.load_ram_slot(slot);
+                                    ui.close();
+                                }
+                            }
+                        });
+                    });
+                    ui.separator();
                     if ui.button("Exit").clicked() {
                         let _ = self.emu_tx.send(EmuCommand::Shutdown);
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
@@ -1625,11 +2169,8 @@ impl eframe::App for VibeEmuApp {
                     {
                         if let Ok(mut gb) = self.gb.lock() {
                             gb.reset();
-                            self._audio_stream = audio::start_stream(
-                                &mut gb.mmu.apu,
-                                true,
-                                self.sound_enabled.clone(),
-                            );
+                            (self._audio_stream, self.audio_error) =
+                                open_audio(&mut gb.mmu.apu, &self.sound_enabled);
                         }
                         ui.close();
                     }
@@ -1698,7 +2239,7 @@ impl eframe::App for VibeEmuApp {
 
                 ui.menu_button("Debug", |ui| {
                     if ui.button("Debugger").clicked() {
-                        self.show_debugger = !self.show_debugger;
+                        self.set_debugger_open(!self.show_debugger);
                         if self.show_debugger {
                             self.debugger_state.request_scroll_to_pc();
                         }
@@ -1712,6 +2253,11 @@ impl eframe::App for VibeEmuApp {
                         self.show_watchpoints = !self.show_watchpoints;
                         ui.close();
                     }
+                    let mut show_perf = self.show_perf_overlay;
+                    if ui.checkbox(&mut show_perf, "Performance Overlay").changed() {
+                        self.set_perf_overlay(show_perf);
+                        ui.close();
+                    }
                 });
 
                 ui.menu_button("Options", |ui| {
@@ -1831,6 +2377,8 @@ impl eframe::App for VibeEmuApp {
                         size,
                     );
                     ui.put(rect, egui::Image::new(tex).fit_to_exact_size(size));
+                    self.draw_perf_overlay(ui, rect);
+                    self.draw_toast(ui, rect);
                 } else {
                     ui.centered_and_justified(|ui| {
                         ui.label("No ROM loaded. Use File → Open ROM... or drag and drop a ROM file here.");
@@ -1860,8 +2408,10 @@ impl eframe::App for VibeEmuApp {
     }
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        if let Ok(mut gb) = self.gb.lock() {
-            gb.mmu.save_cart_ram();
+        if let Ok(mut gb) = self.gb.lock()
+            && let Err(e) = gb.shutdown()
+        {
+            error!("Failed to save on exit: {e}");
         }
         let _ = self.emu_tx.send(EmuCommand::Shutdown);
     }
@@ -1993,6 +2543,32 @@ impl VibeEmuApp {
                             self.rebinding = Some(RebindTarget::FastForward);
                         }
                         ui.end_row();
+
+                        ui.label("Performance Overlay");
+                        ui.label(format!("{:?}", self.keybinds.perf_overlay_key()));
+                        if ui.button("Rebind").clicked() {
+                            self.rebinding = Some(RebindTarget::PerfOverlay);
+                        }
+                        ui.end_row();
+
+                        ui.separator();
+                        ui.end_row();
+
+                        for index in 0..STATE_SLOT_COUNT {
+                            ui.label(format!("State Slot {} (Shift loads)", index + 1));
+                            ui.label(format!("{:?}", self.keybinds.state_slot_key(index)));
+                            if ui.button("Rebind").clicked() {
+                                self.rebinding = Some(RebindTarget::StateSlot(index));
+                            }
+                            ui.end_row();
+                        }
+
+                        ui.label("Restart Point (Shift sets)");
+                        ui.label(format!("{:?}", self.keybinds.restart_key()));
+                        if ui.button("Rebind").clicked() {
+                            self.rebinding = Some(RebindTarget::Restart);
+                        }
+                        ui.end_row();
                     });
             }
             OptionsTab::Emulation => {
@@ -2015,6 +2591,52 @@ impl VibeEmuApp {
                         self.cgb_bootrom_path = path.to_string_lossy().to_string();
                     }
                 });
+
+                ui.add_space(8.0);
+                let response = ui
+                    .checkbox(&mut self.ui_config.low_input_latency, "Low input latency")
+                    .on_hover_text(
+                        "Apply input that arrives while waiting for a frame before that \
+                         frame runs, instead of one frame later.",
+                    );
+                if response.changed() {
+                    let enabled = self.ui_config.low_input_latency;
+                    let _ = self.emu_tx.send(EmuCommand::SetLowInputLatency(enabled));
+                    if let Err(e) = ui_config::save_to_file(&self.ui_config_path, &self.ui_config) {
+                        log::warn!(
+                            "Failed to save UI config {}: {e}",
+                            self.ui_config_path.display()
+                        );
+                    }
+                }
+
+                ui.add_space(8.0);
+                match &self.audio_error {
+                    Some(err) => {
+                        ui.colored_label(egui::Color32::LIGHT_RED, format!("No audio: {err}"));
+                    }
+                    None => {
+                        ui.label("Audio: playing on the default output device");
+                    }
+                }
+                let devices = ui.collapsing("Output devices", |ui| {
+                    if ui.button("Refresh").clicked() {
+                        self.output_devices = None;
+                    }
+                    let devices = self
+                        .output_devices
+                        .get_or_insert_with(audio::list_output_devices);
+                    if devices.is_empty() {
+                        ui.label("None found");
+                    }
+                    for name in devices.iter() {
+                        ui.label(name.as_str());
+                    }
+                });
+                // Enumerate afresh the next time the section is opened.
+                if devices.body_returned.is_none() {
+                    self.output_devices = None;
+                }
             }
         }
     }
@@ -2032,7 +2654,7 @@ impl VibeEmuApp {
                 .with_inner_size([750.0, 550.0]),
             |ctx, class| {
                 if ctx.input(|i| i.viewport().close_requested()) {
-                    self.show_debugger = false;
+                    self.set_debugger_open(false);
                 }
 
                 match class {
@@ -2249,15 +2871,15 @@ impl VibeEmuApp {
             return;
         };
 
-        // Process step over request
-        if let Ok(mut gb) = self.gb.lock() {
-            let pc = snapshot.cpu.pc;
-            self.debugger_state.handle_step_over_request(
-                self.paused,
-                pc,
-                |addr| gb.mmu.read_byte(addr),
-                &snapshot,
-            );
+        // Process step over request; decoding the next instruction is only
+        // needed once one has been armed.
+        if self.paused
+            && self.debugger_state.step_over_pending()
+            && let Ok(gb) = self.gb.lock()
+        {
+            let next = gb.cpu.preview_next(&gb.mmu);
+            self.debugger_state
+                .handle_step_over_request(self.paused, &next, &snapshot);
         }
 
         // Process run to cursor request
@@ -2854,7 +3476,10 @@ impl VibeEmuApp {
                     value: new_af,
                 });
             }
-            ui.monospace(format!("rom= {:02X}", dbg.active_rom_bank));
+            ui.monospace(format!(
+                "rom= {:02X}/{:02X}",
+                dbg.active_rom_bank, dbg.rom_bank_count
+            ));
         });
 
         ui.separator();
@@ -4931,10 +5556,20 @@ fn main() {
         EmulationMode::Auto => cart.as_ref().is_some_and(|c| c.cgb),
     };
 
+    if let Some(Err(mismatch)) = cart
+        .as_ref()
+        .map(|c| strict_mode_check(c, cgb_mode, args.strict_mode))
+    {
+        error!("Refusing to start: {mismatch}");
+        std::process::exit(1);
+    }
+
     let mut gb = GameBoy::new_with_mode(cgb_mode);
     if let Some(c) = cart {
         gb.mmu.load_cart(c);
     }
+    gb.set_autosave_interval(args.autosave.map(Duration::from_secs));
+    gb.set_timing_fuzz(args.timing_fuzz);
 
     if headless {
         enum Limit {
@@ -4948,26 +5583,62 @@ fn main() {
             Limit::Frames(args.frames.unwrap_or(600))
         };
 
-        match limit {
+        let target_frames = match limit {
             Limit::Frames(n) => {
                 info!("Running headless for {n} frames");
-                for _ in 0..n {
-                    gb.mmu.ppu.clear_frame_flag();
-                    while !gb.mmu.ppu.frame_ready() {
-                        gb.cpu.step(&mut gb.mmu);
-                    }
-                }
+                n
             }
             Limit::Seconds(s) => {
                 let target_frames = (s as f64 * GB_FPS).ceil() as usize;
                 info!("Running headless for {s} seconds (~{target_frames} frames)");
-                for _ in 0..target_frames {
-                    gb.mmu.ppu.clear_frame_flag();
-                    while !gb.mmu.ppu.frame_ready() {
-                        gb.cpu.step(&mut gb.mmu);
-                    }
+                target_frames
+            }
+        };
+
+        let mut recorder = args.record_video.as_ref().map(|path| {
+            match video_capture::VideoRecorder::create(path, target_frames as u32) {
+                Ok(rec) => {
+                    info!("Recording video to {}", path.display());
+                    rec
+                }
+                Err(e) => {
+                    error!("Failed to create {}: {e}", path.display());
+                    std::process::exit(1);
                 }
             }
+        });
+
+        let mut reported_stuck = None;
+        for _ in 0..target_frames {
+            run_frame(
+                &mut gb,
+                |_| false,
+                |pc, locked| {
+                    if reported_stuck != Some(pc) {
+                        reported_stuck = Some(pc);
+                        if locked {
+                            warn!("CPU locked up on an undefined opcode at ${pc:04X}");
+                        } else {
+                            warn!("CPU stuck at ${pc:04X} with no interrupt to wake it");
+                        }
+                    }
+                    false
+                },
+            );
+            if let Some(rec) = recorder.as_mut()
+                && let Err(e) = rec.push_frame(gb.mmu.ppu.framebuffer())
+            {
+                error!("Failed to write video frame: {e}");
+                std::process::exit(1);
+            }
+        }
+
+        if let Some(rec) = recorder {
+            let frames = rec.frames_written();
+            match rec.finish() {
+                Ok(()) => info!("Recorded {frames} frames"),
+                Err(e) => error!("Failed to finish video: {e}"),
+            }
         }
 
         info!("Headless run complete");
@@ -5060,7 +5731,7 @@ fn main() {
                     info!("Mobile Adapter enabled");
                     let adapter = Arc::new(Mutex::new(adapter));
                     let link_port = MobileLinkPort::new(Arc::clone(&adapter));
-                    gb.mmu.serial.connect(Box::new(link_port));
+                    gb.attach_serial(Box::new(link_port));
                 }
             }
             Err(e) => {
@@ -5243,6 +5914,7 @@ fn main() {
     };
 
     let rom_path_clone = rom_path.clone();
+    let strict_mode = args.strict_mode;
 
     let ui_config_path = ui_config::default_ui_config_path();
     let ui_config = ui_config::load_from_file(&ui_config_path);
@@ -5261,6 +5933,7 @@ fn main() {
                 keybinds,
                 keybinds_path,
                 emulation_mode,
+                strict_mode,
                 ui_config_path.clone(),
                 ui_config.clone(),
                 external_clock_pending,
@@ -5274,3 +5947,79 @@ fn main() {
         error!("eframe error: {e}");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Selects both joypad groups and copies P1 to $C000 forever.
+    fn joypad_echo_cart() -> Cartridge {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]); // JP $0150
+        rom[0x0150..0x015A].copy_from_slice(&[
+            0xAF, // XOR A
+            0xE0, 0x00, // LDH (P1),A
+            0xF0, 0x00, // LDH A,(P1)
+            0xEA, 0x00, 0xC0, // LD ($C000),A
+            0x18, 0xF9, // JR -7
+        ]);
+        Cartridge::load(rom)
+    }
+
+    fn next_frame_event(rx: &cb::Receiver<EmuEvent>) {
+        loop {
+            let event = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            if matches!(event, EmuEvent::Frame { .. }) {
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn low_input_latency_applies_input_sent_during_the_wait_to_the_next_frame() {
+        let mut gb = GameBoy::new_with_mode(false);
+        gb.mmu.load_cart(joypad_echo_cart());
+        let gb = Arc::new(Mutex::new(gb));
+
+        let (tx, rx) = mpsc::channel();
+        let (frame_tx, frame_rx) = cb::bounded(3);
+        let (frame_pool_tx, frame_pool_rx) = cb::bounded(4);
+        let emu_gb = Arc::clone(&gb);
+        // Slowed down so the wait before each frame is long.
+        let speed = Speed {
+            factor: 0.25,
+            fast: false,
+        };
+        let emu = thread::spawn(move || {
+            run_emulator_thread(
+                emu_gb,
+                speed,
+                true,
+                EmuThreadChannels {
+                    rx,
+                    frame_tx,
+                    frame_pool_tx,
+                    frame_pool_rx,
+                },
+                Arc::default(),
+                Arc::default(),
+                Arc::default(),
+                Arc::default(),
+            )
+        });
+
+        tx.send(EmuCommand::SetLowInputLatency(true)).unwrap();
+        tx.send(EmuCommand::SetPaused(false)).unwrap();
+        next_frame_event(&frame_rx);
+        assert_eq!(gb.lock().unwrap().mmu.wram[0][0] & 0x0F, 0x0F);
+
+        // The thread is now waiting for the next frame. Input sent meanwhile
+        // is drained after the wait, so that frame already reads it.
+        tx.send(EmuCommand::UpdateInput(0xFE)).unwrap();
+        next_frame_event(&frame_rx);
+        assert_eq!(gb.lock().unwrap().mmu.wram[0][0] & 0x0F, 0x0E);
+
+        tx.send(EmuCommand::Shutdown).unwrap();
+        emu.join().unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+//! Frame timing statistics for the performance overlay.
+//!
+//! The emulator thread only collects these while the overlay is shown. It
+//! then reads the clock twice per frame and sends a [`PerfStats`] summary to
+//! the UI every [`REPORT_INTERVAL`].
+
+use std::time::{Duration, Instant};
+
+/// How often the emulator thread sends a summary.
+pub const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Summary of the frames emulated during one report interval.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PerfStats {
+    /// Mean wall time spent emulating a frame, excluding the frame pacing
+    /// sleep.
+    pub avg_frame_time: Duration,
+    /// Longest time spent emulating a single frame.
+    pub max_frame_time: Duration,
+    /// Frames emulated per second of wall time.
+    pub emulated_fps: f64,
+    /// Frames the UI had no room for since stats collection started.
+    pub dropped_frames: u64,
+    /// Share of the audio queue filled when the interval ended, from 0 to 1.
+    pub audio_fill: f32,
+}
+
+/// Collects frame times on the emulator thread.
+pub struct FrameStats {
+    interval_start: Instant,
+    frames: u32,
+    total: Duration,
+    max: Duration,
+    dropped: u64,
+}
+
+impl FrameStats {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            interval_start: now,
+            frames: 0,
+            total: Duration::ZERO,
+            max: Duration::ZERO,
+            dropped: 0,
+        }
+    }
+
+    /// Records one emulated frame that took `elapsed` to run.
+    pub fn record_frame(&mut self, elapsed: Duration) {
+        self.frames += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+
+    /// Records a finished frame that could not be handed to the UI.
+    pub fn record_dropped(&mut self) {
+        self.dropped += 1;
+    }
+
+    /// Discards the current interval, e.g. after a pause, so idle time does
+    /// not lower the emulated frame rate.
+    pub fn restart(&mut self, now: Instant) {
+        self.interval_start = now;
+        self.frames = 0;
+        self.total = Duration::ZERO;
+        self.max = Duration::ZERO;
+    }
+
+    /// Returns the summary of the current interval and starts the next one
+    /// once [`REPORT_INTERVAL`] has passed; `None` before that.
+    pub fn report(&mut self, now: Instant, audio_fill: f32) -> Option<PerfStats> {
+        let elapsed = now.saturating_duration_since(self.interval_start);
+        if elapsed < REPORT_INTERVAL {
+            return None;
+        }
+        let stats = PerfStats {
+            avg_frame_time: self.total.checked_div(self.frames).unwrap_or_default(),
+            max_frame_time: self.max,
+            emulated_fps: self.frames as f64 / elapsed.as_secs_f64(),
+            dropped_frames: self.dropped,
+            audio_fill,
+        };
+        self.restart(now);
+        Some(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_summarises_each_interval() {
+        let start = Instant::now();
+        let mut stats = FrameStats::new(start);
+        for ms in [2, 4, 6] {
+            stats.record_frame(Duration::from_millis(ms));
+        }
+        stats.record_dropped();
+        assert_eq!(stats.report(start + REPORT_INTERVAL / 2, 0.5), None);
+
+        let end = start + Duration::from_secs(1);
+        let report = stats.report(end, 0.25).unwrap();
+        assert_eq!(report.avg_frame_time, Duration::from_millis(4));
+        assert_eq!(report.max_frame_time, Duration::from_millis(6));
+        assert_eq!(report.emulated_fps, 3.0);
+        assert_eq!(report.dropped_frames, 1);
+        assert_eq!(report.audio_fill, 0.25);
+
+        // Dropped frames keep counting; everything else starts over.
+        let report = stats.report(end + Duration::from_secs(1), 0.0).unwrap();
+        assert_eq!(report.avg_frame_time, Duration::ZERO);
+        assert_eq!(report.max_frame_time, Duration::ZERO);
+        assert_eq!(report.emulated_fps, 0.0);
+        assert_eq!(report.dropped_frames, 1);
+    }
+}
@@ -7,6 +7,7 @@ use std::{
     fs,
     path::{Path, PathBuf},
 };
+use vibe_emu_core::disasm::InstructionPreview;
 use vibe_emu_core::watchpoints::{WatchpointHit, WatchpointTrigger};
 
 const NO_BANK: u8 = 0xFF;
@@ -198,6 +199,12 @@ impl DebuggerState {
         self.pending_scroll_to_pc = true;
     }
 
+    /// Whether a step over is waiting to be resolved against the next
+    /// instruction.
+    pub fn step_over_pending(&self) -> bool {
+        self.pending_step_over
+    }
+
     pub fn request_run_to_cursor(&mut self) {
         if self.waiting_debug_cmd_id.is_some() {
             return;
@@ -542,8 +549,7 @@ impl DebuggerState {
     pub fn handle_step_over_request(
         &mut self,
         paused: bool,
-        pc: u16,
-        mut memory: impl FnMut(u16) -> u8,
+        next: &InstructionPreview,
         snapshot: &UiSnapshot,
     ) {
         if !self.pending_step_over || !paused {
@@ -551,31 +557,12 @@ impl DebuggerState {
         }
         self.pending_step_over = false;
 
-        let opcode = memory(pc);
-
-        let should_step_over = matches!(
-            opcode,
-            0xC4 | 0xCC
-                | 0xCD
-                | 0xD4
-                | 0xDC
-                | 0xC7
-                | 0xCF
-                | 0xD7
-                | 0xDF
-                | 0xE7
-                | 0xEF
-                | 0xF7
-                | 0xFF
-        );
-
-        if should_step_over {
-            let next_pc = pc.wrapping_add(3);
-            let bank = bp_bank_for_addr(next_pc, snapshot);
+        if let Some(return_addr) = next.return_address {
+            let bank = bp_bank_for_addr(return_addr, snapshot);
             self.pending_run_to = Some(DebuggerRunToRequest {
                 target: BreakpointSpec {
                     bank,
-                    addr: next_pc,
+                    addr: return_addr,
                 },
                 ignore_breakpoints: false,
             });
@@ -1,5 +1,5 @@
 pub mod code_data;
 pub mod debugger;
-pub mod disasm;
+pub use vibe_emu_core::disasm;
 pub mod snapshot;
 pub mod watchpoints;
@@ -1,4 +1,5 @@
 use vibe_emu_core::gameboy::GameBoy;
+use vibe_emu_core::snapshot::Snapshot;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct CpuSnapshot {
@@ -70,6 +71,7 @@ pub struct DebuggerSnapshot {
     pub if_reg: u8,
     pub ie_reg: u8,
     pub active_rom_bank: u16,
+    pub rom_bank_count: usize,
     pub disassembly_base: u16,
     pub disassembly_bytes: Vec<u8>,
     pub stack_base: u16,
@@ -92,6 +94,7 @@ impl Default for DebuggerSnapshot {
             if_reg: 0,
             ie_reg: 0,
             active_rom_bank: 1,
+            rom_bank_count: 2,
             disassembly_base: 0,
             disassembly_bytes: vec![0; 0x200],
             stack_base: 0,
@@ -107,20 +110,25 @@ impl Default for DebuggerSnapshot {
 }
 
 impl UiSnapshot {
+    /// Builds on the core's [`GameBoy::publish_snapshot`] for registers and
+    /// the framebuffer, then adds the memory views only the debugger needs.
     pub fn from_gb(gb: &mut GameBoy, paused: bool) -> Self {
+        let mut state = Snapshot::new();
+        gb.publish_snapshot(&mut state);
+
         let cpu = CpuSnapshot {
-            a: gb.cpu.a,
-            f: gb.cpu.f,
-            b: gb.cpu.b,
-            c: gb.cpu.c,
-            d: gb.cpu.d,
-            e: gb.cpu.e,
-            h: gb.cpu.h,
-            l: gb.cpu.l,
-            sp: gb.cpu.sp,
-            pc: gb.cpu.pc,
-            ime: gb.cpu.ime,
-            cycles: gb.cpu.cycles,
+            a: state.a,
+            f: state.f,
+            b: state.b,
+            c: state.c,
+            d: state.d,
+            e: state.e,
+            h: state.h,
+            l: state.l,
+            sp: state.sp,
+            pc: state.pc,
+            ime: state.ime,
+            cycles: state.cycles,
         };
 
         let ppu = &mut gb.mmu.ppu;
@@ -136,30 +144,38 @@ impl UiSnapshot {
         }
 
         let ppu_snap = PpuSnapshot {
-            frame_counter: ppu.frames(),
+            frame_counter: state.frame,
             cgb: ppu.is_cgb(),
-            lcdc: ppu.read_reg(0xFF40),
-            stat: ppu.read_reg(0xFF41),
-            scy: ppu.read_reg(0xFF42),
-            scx: ppu.read_reg(0xFF43),
-            ly: ppu.read_reg(0xFF44),
-            bgp: ppu.read_reg(0xFF47),
-            obp0: ppu.read_reg(0xFF48),
-            obp1: ppu.read_reg(0xFF49),
+            lcdc: state.lcd_regs[0x0],
+            stat: state.lcd_regs[0x1],
+            scy: state.lcd_regs[0x2],
+            scx: state.lcd_regs[0x3],
+            ly: state.lcd_regs[0x4],
+            bgp: state.lcd_regs[0x7],
+            obp0: state.lcd_regs[0x8],
+            obp1: state.lcd_regs[0x9],
             vram0: ppu.vram[0].to_vec(),
             vram1: ppu.vram[1].to_vec(),
             oam: ppu.oam.to_vec(),
-            framebuffer: ppu.framebuffer().to_vec(),
+            framebuffer: state.framebuffer.to_vec(),
             cgb_bg_colors,
             cgb_ob_colors,
         };
 
-        let active_rom_bank = gb
+        // The core reports bank 0 without a cartridge; the debugger expects
+        // the fixed layout's bank 1 there.
+        let active_rom_bank = if gb.mmu.cart.is_some() {
+            state.rom_bank
+        } else {
+            1
+        };
+
+        let rom_bank_count = gb
             .mmu
             .cart
             .as_ref()
-            .map(|c| c.current_rom_bank())
-            .unwrap_or(1);
+            .map(|c| c.rom_bank_count())
+            .unwrap_or(2);
 
         let sram_bank = gb
             .mmu
@@ -203,9 +219,10 @@ impl UiSnapshot {
 
         let dbg = DebuggerSnapshot {
             paused,
-            if_reg: gb.mmu.if_reg,
-            ie_reg: gb.mmu.ie_reg,
+            if_reg: state.if_reg,
+            ie_reg: state.ie_reg,
             active_rom_bank,
+            rom_bank_count,
             disassembly_base,
             disassembly_bytes,
             stack_base,
@@ -95,6 +95,9 @@ pub struct UiConfig {
     pub window_size: WindowSize,
     pub emulation_mode: EmulationMode,
     pub serial: SerialConfig,
+    /// Apply input queued while waiting for the next frame right before it
+    /// runs, instead of a frame later.
+    pub low_input_latency: bool,
 }
 
 pub fn default_ui_config_path() -> PathBuf {
@@ -0,0 +1,199 @@
+//! Frame-by-frame video capture for headless runs.
+//!
+//! Frames are streamed to disk as they are produced, so memory use does not
+//! grow with the length of the capture.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use vibe_emu_core::ppu::Ppu;
+
+const SCREEN_WIDTH: usize = 160;
+const SCREEN_HEIGHT: usize = 144;
+
+/// Frame delay written to APNG frames: 1000/59727 s, i.e. the Game Boy's
+/// ~59.73 Hz refresh rate.
+const APNG_DELAY_NUM: u16 = 1000;
+const APNG_DELAY_DEN: u16 = 59727;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// Animated PNG.
+    Apng,
+    /// Headerless stream of 160x144 RGB24 frames.
+    RawRgb,
+}
+
+impl VideoFormat {
+    /// Picks the format from the file extension (`.rgb`/`.raw` for raw
+    /// frames, anything else for APNG).
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("rgb") || ext.eq_ignore_ascii_case("raw") => {
+                Self::RawRgb
+            }
+            _ => Self::Apng,
+        }
+    }
+}
+
+enum Sink {
+    Apng(png::Writer<BufWriter<File>>),
+    RawRgb(BufWriter<File>),
+}
+
+pub struct VideoRecorder {
+    path: PathBuf,
+    sink: Sink,
+    rgba: Vec<u8>,
+    rgb: Vec<u8>,
+    frames: u32,
+    written: u32,
+}
+
+impl VideoRecorder {
+    /// Creates a recorder for exactly `frames` frames.
+    ///
+    /// APNG stores the frame count in its header, so it has to be known up
+    /// front.
+    pub fn create(path: &Path, frames: u32) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let sink = match VideoFormat::from_path(path) {
+            VideoFormat::Apng => {
+                let mut encoder =
+                    png::Encoder::new(file, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder
+                    .set_animated(frames.max(1), 0)
+                    .map_err(io::Error::other)?;
+                encoder
+                    .set_frame_delay(APNG_DELAY_NUM, APNG_DELAY_DEN)
+                    .map_err(io::Error::other)?;
+                Sink::Apng(encoder.write_header().map_err(io::Error::other)?)
+            }
+            VideoFormat::RawRgb => Sink::RawRgb(file),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            sink,
+            rgba: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4],
+            rgb: Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT * 3),
+            frames,
+            written: 0,
+        })
+    }
+
+    pub fn frames_written(&self) -> u32 {
+        self.written
+    }
+
+    /// Appends one PPU framebuffer (`0x00RRGGBB` pixels).
+    pub fn push_frame(&mut self, framebuffer: &[u32]) -> io::Result<()> {
+        if self.written >= self.frames {
+            return Err(io::Error::other(
+                "more frames than the recorder was created for",
+            ));
+        }
+        Ppu::rgba8888_from(framebuffer, &mut self.rgba);
+        match &mut self.sink {
+            Sink::Apng(writer) => writer
+                .write_image_data(&self.rgba)
+                .map_err(io::Error::other)?,
+            Sink::RawRgb(file) => {
+                self.rgb.clear();
+                for px in self.rgba.chunks_exact(4) {
+                    self.rgb.extend_from_slice(&px[..3]);
+                }
+                file.write_all(&self.rgb)?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Flushes the file. For APNG, every frame announced in
+    /// [`Self::create`] must have been pushed.
+    ///
+    /// If no frame was pushed at all, the file is removed and an error
+    /// returned rather than leaving an empty or unfinished video behind.
+    pub fn finish(self) -> io::Result<()> {
+        if self.written == 0 {
+            drop(self.sink);
+            let _ = std::fs::remove_file(&self.path);
+            return Err(io::Error::other("no frames recorded"));
+        }
+        match self.sink {
+            Sink::Apng(writer) => writer.finish().map_err(io::Error::other),
+            Sink::RawRgb(mut file) => file.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts `fcTL` (frame control) chunks in a PNG byte stream.
+    fn count_apng_frames(data: &[u8]) -> usize {
+        let mut pos = 8; // signature
+        let mut frames = 0;
+        while pos + 8 <= data.len() {
+            let len = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+            if &data[pos + 4..pos + 8] == b"fcTL" {
+                frames += 1;
+            }
+            pos += 12 + len;
+        }
+        frames
+    }
+
+    fn test_frame(shade: u32) -> Vec<u32> {
+        vec![shade * 0x010101; SCREEN_WIDTH * SCREEN_HEIGHT]
+    }
+
+    #[test]
+    fn apng_contains_each_recorded_frame() {
+        let path = std::env::temp_dir().join(format!("vibeemu-rec-{}.apng", std::process::id()));
+        let mut rec = VideoRecorder::create(&path, 3).unwrap();
+        for shade in [0x00, 0x80, 0xFF] {
+            rec.push_frame(&test_frame(shade)).unwrap();
+        }
+        assert!(rec.push_frame(&test_frame(0)).is_err());
+        rec.finish().unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(&data[1..4], b"PNG");
+        assert_eq!(count_apng_frames(&data), 3);
+    }
+
+    #[test]
+    fn finishing_without_frames_is_a_clear_error() {
+        for ext in ["apng", "rgb"] {
+            let path =
+                std::env::temp_dir().join(format!("vibeemu-empty-{}.{ext}", std::process::id()));
+            let rec = VideoRecorder::create(&path, 3).unwrap();
+            let err = rec.finish().unwrap_err();
+            assert_eq!(err.to_string(), "no frames recorded");
+            assert!(!path.exists());
+        }
+    }
+
+    #[test]
+    fn raw_stream_is_rgb24_frames() {
+        let path = std::env::temp_dir().join(format!("vibeemu-rec-{}.rgb", std::process::id()));
+        let mut rec = VideoRecorder::create(&path, 2).unwrap();
+        rec.push_frame(&test_frame(0x10)).unwrap();
+        rec.push_frame(&test_frame(0x20)).unwrap();
+        rec.finish().unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let frame_len = SCREEN_WIDTH * SCREEN_HEIGHT * 3;
+        assert_eq!(data.len(), 2 * frame_len);
+        assert_eq!(data[0], 0x10);
+        assert_eq!(data[frame_len], 0x20);
+    }
+}